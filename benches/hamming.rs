@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate criterion;
+extern crate cryptopals;
+
+use criterion::Criterion;
+
+use cryptopals::utils::{attacks, bytes};
+
+// The original implementation, which compares the arrays a byte at a time.
+fn hamming_dist_bytewise(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+// Mirrors the key size detection in `attacks::get_keysizes` with the given distance
+// function, comparing every pair of chunks in the ciphertext for each key size.
+fn keysize_workload<F>(ciphertext: &[u8], dist: F) -> u32
+    where F: Fn(&[u8], &[u8]) -> u32
+{
+    let mut total = 0;
+    for size in 2..41 {
+        let chunks = ciphertext.chunks(size)
+            .take_while(|chunk| chunk.len() == size)
+            .collect::<Vec<_>>();
+        for pair in chunks.windows(2) {
+            total += dist(pair[0], pair[1]);
+        }
+    }
+    total
+}
+
+fn bench_hamming(c: &mut Criterion) {
+    let input = include_str!("../src/data/6.txt");
+    let ciphertext = bytes::base64_decode(input, &bytes::BASE64_MIME).unwrap();
+
+    c.bench_function("keysize workload (bytewise)", move |b| {
+        b.iter(|| keysize_workload(&ciphertext, hamming_dist_bytewise))
+    });
+
+    let ciphertext = bytes::base64_decode(input, &bytes::BASE64_MIME).unwrap();
+    c.bench_function("keysize workload (u64 words)", move |b| {
+        b.iter(|| keysize_workload(&ciphertext, |x, y| bytes::hamming_dist(x, y).unwrap()))
+    });
+
+    let ciphertext = bytes::base64_decode(input, &bytes::BASE64_MIME).unwrap();
+    c.bench_function("get_keysizes", move |b| {
+        b.iter(|| attacks::get_keysizes(&ciphertext, 2..41, 1))
+    });
+}
+
+criterion_group!(benches, bench_hamming);
+criterion_main!(benches);
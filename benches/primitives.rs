@@ -0,0 +1,140 @@
+#[macro_use]
+extern crate criterion;
+extern crate cryptopals;
+
+use std::iter;
+
+use criterion::{Criterion, Throughput};
+
+use cryptopals::utils::{attacks, bytes, crypto, text};
+use cryptopals::utils::random::{RandomSource, SeededRng};
+use cryptopals::utils::text::Scorer;
+
+// Input sizes, in bytes, for the throughput benchmarks.
+static SIZES: &'static [usize] = &[64, 4096];
+
+fn bench_xor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("xor");
+    for &size in SIZES {
+        let mut rng = SeededRng::new(size as u64);
+        let data = rng.bytes(size);
+        let key = rng.bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_function(format!("fixed/{}", size), |b| {
+            b.iter(|| bytes::xor(&data, key.iter()))
+        });
+        group.bench_function(format!("single byte/{}", size), |b| {
+            b.iter(|| bytes::xor(&data, iter::repeat(&0x5a)))
+        });
+        group.bench_function(format!("repeating key/{}", size), |b| {
+            b.iter(|| bytes::xor(&data, b"ICE".iter().cycle()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_codecs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codecs");
+    for &size in SIZES {
+        let data = SeededRng::new(size as u64).bytes(size);
+        let hex = bytes::hex_from_bytes(&data);
+        let base64 = bytes::base64_from_bytes(&data);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_function(format!("hex encode/{}", size), |b| {
+            b.iter(|| bytes::hex_from_bytes(&data))
+        });
+        group.bench_function(format!("hex decode/{}", size), |b| {
+            b.iter(|| bytes::hex_to_bytes(&hex).unwrap())
+        });
+        group.bench_function(format!("base64 encode/{}", size), |b| {
+            b.iter(|| bytes::base64_from_bytes(&data))
+        });
+        group.bench_function(format!("base64 decode/{}", size), |b| {
+            b.iter(|| bytes::base64_to_bytes(&base64).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_ciphers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aes-128");
+    for &size in SIZES {
+        let mut rng = SeededRng::new(size as u64);
+        let key = rng.bytes(16);
+        let iv = rng.bytes(16);
+        let data = rng.bytes(size);
+        let encrypted = crypto::encrypt_cbc(&key, &iv, &data);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_function(format!("ecb encrypt/{}", size), |b| {
+            b.iter(|| crypto::encrypt_ecb(&key, &data, false))
+        });
+        group.bench_function(format!("cbc encrypt/{}", size), |b| {
+            b.iter(|| crypto::encrypt_cbc(&key, &iv, &data))
+        });
+        group.bench_function(format!("cbc decrypt/{}", size), |b| {
+            b.iter(|| crypto::decrypt_cbc(&key, &iv, &encrypted).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_scorers(c: &mut Criterion) {
+    let plaintext = b"Burning 'em, if you ain't quick and nimble\n\
+                      I go crazy when I hear a cymbal";
+    let ciphertext = bytes::xor(plaintext, b"ICE".iter().cycle());
+
+    let mut group = c.benchmark_group("scorers");
+    group.throughput(Throughput::Bytes(plaintext.len() as u64));
+
+    let cosine = text::CosineScorer::default();
+    group.bench_function("cosine", |b| b.iter(|| cosine.score(plaintext)));
+    let chi_squared = text::ChiSquaredScorer::default();
+    group.bench_function("chi-squared", |b| b.iter(|| chi_squared.score(plaintext)));
+    let ngrams = text::NgramModel::english(3);
+    group.bench_function("trigrams", |b| b.iter(|| ngrams.score(plaintext)));
+    let words = text::WordlistScorer::default();
+    group.bench_function("wordlist", |b| b.iter(|| words.score(plaintext)));
+    let tie_breaker = text::TieBreaker::english();
+    group.bench_function("tie breaker", |b| b.iter(|| tie_breaker.score(plaintext)));
+
+    // Scoring dominates the single-byte brute force, so include it for context.
+    group.bench_function("single byte brute force", |b| {
+        b.iter(|| attacks::single_byte_brute_force(&ciphertext[..]))
+    });
+    group.finish();
+}
+
+// Challenge 4's search for the one line of the dataset that's single-byte XOR'd English,
+// with each of the two frequency scorers.
+fn bench_challenge_4(c: &mut Criterion) {
+    let lines = include_str!("../src/data/4.txt")
+        .lines()
+        .map(|line| bytes::hex_to_bytes(line).unwrap())
+        .collect::<Vec<_>>();
+    let scorers: Vec<(&str, Box<dyn Scorer>)> =
+        vec![("cosine", Box::new(text::CosineScorer::default())),
+             ("chi-squared", Box::new(text::ChiSquaredScorer::default()))];
+
+    let mut group = c.benchmark_group("challenge 4");
+    for (name, scorer) in &scorers {
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                lines.iter()
+                    .map(|line| attacks::single_byte_brute_force_with(line, &**scorer))
+                    .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches,
+                 bench_xor,
+                 bench_codecs,
+                 bench_ciphers,
+                 bench_scorers,
+                 bench_challenge_4);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use std::str;
+
+use cryptopals::utils::bytes;
+
+// Whatever decodes encodes back to a string that decodes the same way.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(base64) = str::from_utf8(data) {
+        if let Ok(decoded) = bytes::base64_to_bytes(base64) {
+            let encoded = bytes::base64_from_bytes(&decoded);
+            assert_eq!(bytes::base64_to_bytes(&encoded), Ok(decoded));
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use cryptopals::utils::bytes;
+use cryptopals::utils::oracles::ProfileCookieOracle;
+
+// Cookies are parsed from whatever an attacker's ciphertext decrypts to, which is decoded
+// as lossily as `decrypt_cookie` does. Every key in the result is from the input.
+fuzz_target!(|data: &[u8]| {
+    let cookie = bytes::to_string(data);
+    let parsed = ProfileCookieOracle::parse_cookie(&cookie);
+    for key in parsed.keys() {
+        assert!(cookie.contains(key.as_str()));
+    }
+});
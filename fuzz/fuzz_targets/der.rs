@@ -0,0 +1,33 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use cryptopals::utils::rsa::der::{self, Reader};
+
+// Walk the values in the input, reading into sequences, and skipping anything else with
+// the tag in the first byte. DER has one encoding for each integer, so an integer that
+// reads successfully encodes back to the contents it was read from.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let mut readers = vec![Reader::new(&data[1..])];
+    while let Some(mut reader) = readers.pop() {
+        if reader.is_empty() {
+            continue;
+        }
+        let mut integer = reader.clone();
+        let mut sequence = reader.clone();
+        if let Ok(n) = integer.read_integer() {
+            let contents = reader.read(0x02).unwrap();
+            assert_eq!(der::encode_integer(&n), der::encode(0x02, contents));
+            readers.push(reader);
+        } else if let Ok(inner) = sequence.read_sequence() {
+            readers.push(sequence);
+            readers.push(inner);
+        } else if reader.read(data[0]).is_ok() {
+            readers.push(reader);
+        }
+    }
+});
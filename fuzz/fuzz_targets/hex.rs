@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use std::str;
+
+use cryptopals::utils::bytes;
+
+// Whatever decodes encodes back to digits that decode the same way.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(hex) = str::from_utf8(data) {
+        if let Ok(decoded) = bytes::hex_to_bytes(hex) {
+            assert_eq!(bytes::hex_to_bytes(&bytes::hex_from_bytes(&decoded)), Ok(decoded));
+        }
+    }
+});
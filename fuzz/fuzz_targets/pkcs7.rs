@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use cryptopals::utils::crypto;
+
+// Padding comes straight from decrypted ciphertext, so anything goes. When it's accepted,
+// the stripped data is a proper prefix of the input, and padding it back to the input's
+// length gives the input.
+fuzz_target!(|data: &[u8]| {
+    if let Some(stripped) = crypto::strip_pkcs7(data) {
+        assert!(stripped.len() < data.len());
+        assert_eq!(crypto::pad_pkcs7(&stripped, data.len()), Ok(data.to_vec()));
+    }
+});
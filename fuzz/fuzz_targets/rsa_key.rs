@@ -0,0 +1,24 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cryptopals;
+
+use std::str;
+
+use cryptopals::utils::rsa::{PrivateKey, PublicKey};
+
+// Parse the input as PKCS#1 keys, in DER and, when it's text, in PEM. Public keys have
+// nothing the encoder leaves out, so one that parses encodes back to the input. Private
+// keys are encoded with recomputed CRT parameters, which only have to parse the same way.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = PublicKey::from_der(data) {
+        assert_eq!(key.to_der(), data);
+    }
+    if let Ok(key) = PrivateKey::from_der(data) {
+        assert_eq!(PrivateKey::from_der(&key.to_der()), Ok(key));
+    }
+    if let Ok(pem) = str::from_utf8(data) {
+        let _ = PublicKey::from_pem(pem);
+        let _ = PrivateKey::from_pem(pem);
+    }
+});
@@ -0,0 +1,328 @@
+use std::fs;
+
+use cryptopals::utils::{attacks, bytes, classical, text};
+use cryptopals::utils::attacks::Candidate;
+use cryptopals::utils::report::Report;
+
+use tools::{fail, read_input};
+use usage_error;
+
+// Options shared by the crack and detect commands. Each command only accepts some of them.
+struct Options {
+    lines: bool,
+    raw: bool,
+    top: usize,
+    block_size: usize,
+    crib: Option<Vec<u8>>,
+    html: Option<String>,
+    path: Option<String>,
+}
+
+fn parse_options<I>(mut args: I, allowed: &[&str]) -> Options
+    where I: Iterator<Item = String>
+{
+    let mut options = Options {
+        lines: false,
+        raw: false,
+        top: 5,
+        block_size: 16,
+        crib: None,
+        html: None,
+        path: None,
+    };
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") && !allowed.contains(&arg.as_str()) {
+            usage_error(&format!("unrecognized option '{}'", arg));
+        }
+        match arg.as_str() {
+            "--lines" => options.lines = true,
+            "--raw" => options.raw = true,
+            "--top" | "--block-size" => {
+                let value = match args.next().map(|value| value.parse()) {
+                    Some(Ok(value)) if value > 0 => value,
+                    _ => usage_error(&format!("{} requires a positive number", arg)),
+                };
+                if arg == "--top" {
+                    options.top = value;
+                } else {
+                    options.block_size = value;
+                }
+            }
+            "--crib" => {
+                options.crib = match args.next() {
+                    Some(ref crib) if !crib.is_empty() => Some(crib.clone().into_bytes()),
+                    _ => usage_error("--crib requires some text"),
+                }
+            }
+            "--html" => {
+                options.html = match args.next() {
+                    Some(path) => Some(path),
+                    None => usage_error("--html requires a file"),
+                }
+            }
+            _ if options.path.is_none() => options.path = Some(arg),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+    options
+}
+
+// Read the ciphertexts to attack: the whole input, or each nonempty line of it with
+// --lines. Unless --raw is given, each one is decoded from whichever of hex, base32 or
+// base64 it looks like.
+fn read_ciphertexts(options: &Options) -> Vec<Vec<u8>> {
+    let input = read_input(options.path.as_ref().map(|path| path.as_str()));
+    let pieces = if options.lines {
+        input.split(|&byte| byte == b'\n')
+            .filter(|line| !line.iter().all(|byte| byte.is_ascii_whitespace()))
+            .map(|line| line.to_vec())
+            .collect()
+    } else {
+        vec![input]
+    };
+
+    if options.raw {
+        return pieces;
+    }
+    pieces.into_iter()
+        .map(|piece| match String::from_utf8(piece) {
+            Ok(text) => bytes::decode_any(&text),
+            Err(_) => fail("input is not valid UTF-8; pass --raw for binary input"),
+        })
+        .collect()
+}
+
+// Escape and truncate a plaintext for display on a single line.
+fn preview(plaintext: &[u8]) -> String {
+    let text = format!("{:?}", bytes::to_string(plaintext));
+    if text.chars().count() <= 72 {
+        return text;
+    }
+    text.chars().take(69).collect::<String>() + "..."
+}
+
+// Label a result with the line it came from, when the input has multiple ciphertexts.
+fn location(line: usize, show_line: bool) -> String {
+    if show_line {
+        format!("line {:<4} ", line + 1)
+    } else {
+        String::new()
+    }
+}
+
+fn describe_xor_key(key: &[u8]) -> String {
+    format!("key {:?} (hex {})", bytes::to_string(key), bytes::hex_from_bytes(key))
+}
+
+fn describe_shift(key: &[u8]) -> String {
+    format!("shift {:>2}", key[0])
+}
+
+// Print ranked candidates, each labeled with its key and, for multiple ciphertexts, the
+// line it came from.
+fn print_candidates<F>(candidates: &[(usize, Candidate)], top: usize, show_line: bool, key: F)
+    where F: Fn(&[u8]) -> String
+{
+    for (rank, &(line, ref candidate)) in candidates.iter().take(top).enumerate() {
+        println!("{:>3}. {}score {:>10.4}  {}  {}",
+                 rank + 1,
+                 location(line, show_line),
+                 candidate.score,
+                 key(&candidate.key),
+                 preview(&candidate.plaintext));
+    }
+}
+
+fn write_report(path: &str, report: &Report) {
+    if let Err(err) = fs::write(path, report.to_html()) {
+        fail(&format!("couldn't write {}: {}", path, err));
+    }
+}
+
+// Write a report of the ranked candidates with --html: a table of the best ones, and the
+// best plaintext's letter frequencies next to English's, which is what the scorers weigh.
+fn report_candidates<F>(options: &Options,
+                        title: &str,
+                        candidates: &[(usize, Candidate)],
+                        key: F)
+    where F: Fn(&[u8]) -> String
+{
+    let path = match options.html {
+        Some(ref path) => path,
+        None => return,
+    };
+    let mut report = Report::new(title);
+    let rows = candidates.iter()
+        .take(options.top)
+        .enumerate()
+        .map(|(rank, &(line, ref candidate))| {
+            vec![(rank + 1).to_string(),
+                 (line + 1).to_string(),
+                 format!("{:.4}", candidate.score),
+                 key(&candidate.key),
+                 preview(&candidate.plaintext)]
+        })
+        .collect::<Vec<_>>();
+    report.table("Candidates", &["rank", "line", "score", "key", "plaintext"], &rows);
+    if let Some(&(_, ref best)) = candidates.first() {
+        report.frequencies("Letter frequencies of the best candidate",
+                           &text::Frequencies::from_corpus(&best.plaintext),
+                           &text::Frequencies::english());
+        report.text("Best candidate", &bytes::to_string(&best.plaintext));
+    }
+    write_report(path, &report);
+}
+
+// Rank the candidates from every ciphertext together.
+fn rank_all<F>(ciphertexts: &[Vec<u8>], attack: F) -> Vec<(usize, Candidate)>
+    where F: Fn(&[u8]) -> Vec<Candidate>
+{
+    let mut candidates = ciphertexts.iter()
+        .enumerate()
+        .flat_map(|(line, ciphertext)| {
+            attack(ciphertext).into_iter().map(move |candidate| (line, candidate))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.1.score.partial_cmp(&a.1.score).unwrap());
+    candidates
+}
+
+fn crack_single_xor<I: Iterator<Item = String>>(args: I) {
+    let options = parse_options(args, &["--lines", "--raw", "--top", "--html"]);
+    let ciphertexts = read_ciphertexts(&options);
+    let scorer = text::TieBreaker::english();
+    let candidates = rank_all(&ciphertexts,
+                              |ciphertext| attacks::rank_single_byte_xor(ciphertext, &scorer));
+    print_candidates(&candidates, options.top, options.lines, describe_xor_key);
+    report_candidates(&options, "Single-byte XOR", &candidates, describe_xor_key);
+}
+
+fn crack_repeating_xor<I: Iterator<Item = String>>(args: I) {
+    let options = parse_options(args, &["--raw", "--top", "--html"]);
+    let ciphertext = read_ciphertexts(&options).remove(0);
+
+    // Key size detection compares pairs of chunks for key sizes up to 40 bytes.
+    if ciphertext.len() < 80 {
+        fail("ciphertext must be at least 80 bytes long");
+    }
+
+    let model = text::NgramModel::english(4);
+    let candidates = attacks::rank_repeating_key_xor(&ciphertext, options.top, &model)
+        .into_iter()
+        .map(|candidate| (0, candidate))
+        .collect::<Vec<_>>();
+    print_candidates(&candidates, options.top, false, describe_xor_key);
+    report_candidates(&options, "Repeating-key XOR", &candidates, describe_xor_key);
+}
+
+fn crack_caesar<I: Iterator<Item = String>>(args: I) {
+    // Caesar ciphertexts are text, so they're never decoded.
+    let mut options = parse_options(args, &["--lines", "--top", "--html"]);
+    options.raw = true;
+    let ciphertexts = read_ciphertexts(&options);
+    let model = text::NgramModel::english(4);
+    let candidates = rank_all(&ciphertexts,
+                              |ciphertext| attacks::rank_caesar(ciphertext, &model));
+    print_candidates(&candidates, options.top, options.lines, describe_shift);
+    report_candidates(&options, "Caesar cipher", &candidates, describe_shift);
+}
+
+// Solve a monoalphabetic substitution cipher, printing the key and the whole plaintext,
+// since a preview isn't enough to judge the few letters the solver may still have wrong.
+fn crack_substitution<I: Iterator<Item = String>>(args: I) {
+    let mut options = parse_options(args, &[]);
+    options.raw = true;
+    let ciphertext = read_ciphertexts(&options).remove(0);
+    let solution = classical::solve_substitution(&ciphertext);
+    println!("key {}  score {:.4}", bytes::to_string(&solution.key), solution.score);
+    println!();
+    println!("{}", bytes::to_string(&solution.plaintext).trim_end());
+}
+
+// Crib-drag ciphertexts that share a keystream, one per line of the input.
+fn crack_crib_drag<I: Iterator<Item = String>>(args: I) {
+    let mut options = parse_options(args, &["--crib", "--raw", "--top"]);
+    options.lines = true;
+    let crib = match options.crib.take() {
+        Some(crib) => crib,
+        None => usage_error("crib-drag requires --crib"),
+    };
+    let ciphertexts = read_ciphertexts(&options);
+    if ciphertexts.len() < 2 {
+        fail("crib dragging needs at least two ciphertexts");
+    }
+
+    for (rank, m) in attacks::crib_drag(&ciphertexts, &crib).iter().take(options.top).enumerate() {
+        println!("{:>3}. {:<18} offset {:<4} score {:>10.4}  {}",
+                 rank + 1,
+                 format!("lines {} and {}", m.pair.0 + 1, m.pair.1 + 1),
+                 m.offset,
+                 m.score,
+                 preview(&m.guess));
+    }
+}
+
+/// Run one of the cracking attacks on user-supplied ciphertext, printing the most likely
+/// decryptions.
+pub fn crack<I: Iterator<Item = String>>(mut args: I) {
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("single-xor") => crack_single_xor(args),
+        Some("repeating-xor") => crack_repeating_xor(args),
+        Some("caesar") => crack_caesar(args),
+        Some("substitution") => crack_substitution(args),
+        Some("crib-drag") => crack_crib_drag(args),
+        Some(other) => usage_error(&format!("unknown attack '{}'", other)),
+        None => usage_error("crack requires an attack"),
+    }
+}
+
+/// Score ciphertexts for signs of ECB mode, printing the most likely first.
+pub fn detect<I: Iterator<Item = String>>(mut args: I) {
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("ecb") => {}
+        Some(other) => usage_error(&format!("unknown detector '{}'", other)),
+        None => usage_error("detect requires a detector"),
+    }
+
+    let options = parse_options(args, &["--lines", "--raw", "--top", "--block-size", "--html"]);
+    let ciphertexts = read_ciphertexts(&options);
+    let matches = attacks::detect_ecb_batch(&ciphertexts, options.block_size);
+
+    for (rank, result) in matches.iter().take(options.top).enumerate() {
+        let score = &result.score;
+        println!("{:>3}. {}{}  {} repeated blocks at alignment {}, confidence {:.4}",
+                 rank + 1,
+                 location(result.line, options.lines),
+                 if score.is_ecb() { "ECB    " } else { "not ECB" },
+                 score.repeats,
+                 score.alignment,
+                 score.confidence);
+    }
+
+    // With --html, show where the repeats are in each of the top ciphertexts.
+    if let Some(ref path) = options.html {
+        let mut report = Report::new("ECB detection");
+        let rows = matches.iter()
+            .take(options.top)
+            .map(|result| {
+                vec![(result.line + 1).to_string(),
+                     result.score.is_ecb().to_string(),
+                     result.score.repeats.to_string(),
+                     result.score.alignment.to_string(),
+                     format!("{:.4}", result.score.confidence)]
+            })
+            .collect::<Vec<_>>();
+        report.table("Ciphertexts",
+                     &["line", "ECB", "repeats", "alignment", "confidence"],
+                     &rows);
+        for result in matches.iter().take(options.top) {
+            let ciphertext = ciphertexts[result.line].get(result.score.alignment..).unwrap_or(&[]);
+            report.block_map(&format!("Line {}, from offset {}",
+                                      result.line + 1,
+                                      result.score.alignment),
+                             ciphertext,
+                             options.block_size);
+        }
+        write_report(path, &report);
+    }
+}
@@ -0,0 +1,293 @@
+extern crate cryptopals;
+extern crate log;
+#[macro_use]
+extern crate serde_json;
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::time::Instant;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::Value;
+
+use cryptopals::challenges::{self, Challenge};
+use cryptopals::utils::cache;
+use cryptopals::utils::progress::{self, Progress};
+
+mod crack;
+mod tools;
+
+static USAGE: &'static str = "\
+Usage:
+    cryptopals list
+    cryptopals run --all [--json] [--progress] [--no-cache] [-v...]
+    cryptopals run [--set <set>] [--challenge <number>] [--json] [--progress] [--no-cache] [-v...]
+    cryptopals run (--all | --set <set> | --challenge <number>) --trials <n> [--json]
+    cryptopals encode <encoding> [<file>]
+    cryptopals decode <encoding> [<file>]
+    cryptopals xor (--key <key> | --hex-key <hex>) [<file>]
+    cryptopals hamming <file> <file>
+    cryptopals hexdump [--block-size <size>] [<file>]
+    cryptopals penguin [--mode <mode>] [<file>]
+    cryptopals crack single-xor [--lines] [--raw] [--top <n>] [--html <file>] [<file>]
+    cryptopals crack repeating-xor [--raw] [--top <n>] [--html <file>] [<file>]
+    cryptopals crack caesar [--lines] [--top <n>] [--html <file>] [<file>]
+    cryptopals crack substitution [<file>]
+    cryptopals crack crib-drag --crib <text> [--raw] [--top <n>] [<file>]
+    cryptopals detect ecb [--lines] [--raw] [--top <n>] [--block-size <size>] [--html <file>]
+                          [<file>]
+
+Input is read from the given file, or from stdin if it's omitted or '-'. Encodings are
+hex, base64, base64url and base32; decoding ignores whitespace. The crack and detect
+commands decode their input from whichever of these it looks like, unless --raw is given.
+
+Options:
+    --all                   Run every challenge.
+    --set <set>             Run the challenges in the given set.
+    --challenge <number>    Run the challenge with the given number.
+    --json                  Print the results as a JSON array.
+    --trials <n>            Run each randomized challenge n times and report how often it
+                            succeeds, with a 95% confidence interval.
+    --progress              Show the progress of long-running attacks on stderr.
+    --no-cache              Recompute the tables attacks precompute, rather than loading
+                            them from the cache directory.
+    -v, --verbose           Log to stderr. Repeat for more detail.
+    --key <key>             XOR with the given text, repeated as necessary.
+    --hex-key <hex>         XOR with the given hex-encoded bytes.
+    --block-size <size>     Bytes per hexdump row, or cipher block size [default: 16].
+    --mode <mode>           Encrypt the pixels of a PPM or BMP image with 'ecb' or 'ctr'
+                            [default: ecb]. The image is written in the same format.
+    --lines                 Treat each line of the input as a separate ciphertext.
+    --raw                   Use the input as is, rather than decoding it.
+    --top <n>               Print the n best candidates [default: 5].
+    --crib <text>           Text guessed to be in one of the plaintexts. crib-drag reads one
+                            ciphertext per line, all encrypted under the same keystream.
+    --html <file>           Also write an HTML report of the analysis to the given file, with
+                            charts of the letter frequencies or repeated blocks it found.
+    --list                  Same as the list command.";
+
+// Which challenges to run and how to print them, as given on the command line.
+struct Selection {
+    set: Option<u32>,
+    challenge: Option<u32>,
+    json: bool,
+    progress: bool,
+    cache: bool,
+    verbosity: usize,
+    trials: Option<u32>,
+}
+
+impl Selection {
+    fn matches(&self, challenge: &Challenge) -> bool {
+        self.set.map_or(true, |set| set == challenge.set) &&
+        self.challenge.map_or(true, |number| number == challenge.number)
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+// Parse the value following a flag as a number.
+fn parse_number<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> u32 {
+    match args.next().map(|value| value.parse()) {
+        Some(Ok(number)) => number,
+        _ => usage_error(&format!("{} requires a number", flag)),
+    }
+}
+
+fn parse_run_args<I: Iterator<Item = String>>(mut args: I) -> Selection {
+    let mut all = false;
+    let mut selection = Selection {
+        set: None,
+        challenge: None,
+        json: false,
+        progress: false,
+        cache: true,
+        verbosity: 0,
+        trials: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--json" => selection.json = true,
+            "--progress" => selection.progress = true,
+            "--no-cache" => selection.cache = false,
+            "-v" | "--verbose" => selection.verbosity += 1,
+            "-vv" => selection.verbosity += 2,
+            "--set" => selection.set = Some(parse_number(&mut args, "--set")),
+            "--challenge" => selection.challenge = Some(parse_number(&mut args, "--challenge")),
+            "--trials" => selection.trials = Some(parse_number(&mut args, "--trials")),
+            _ => usage_error(&format!("unrecognized argument '{}'", arg)),
+        }
+    }
+
+    let selected = selection.set.is_some() || selection.challenge.is_some();
+    if all == selected {
+        usage_error("specify either --all, or --set and/or --challenge");
+    }
+    if selection.trials == Some(0) {
+        usage_error("--trials requires a positive number");
+    }
+    selection
+}
+
+// Writes log records to stderr.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn init_logging(verbosity: usize) {
+    let level = match verbosity {
+        0 => return,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(level);
+}
+
+// Redraw a single status line on stderr for the attack in progress.
+fn show_progress(progress: &Progress) {
+    let mut guess = progress.best_guess.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(40)
+        .collect::<String>();
+    if progress.best_guess.chars().count() > 40 {
+        guess.push_str("...");
+    }
+
+    let steps = match progress.total {
+        Some(total) => format!("{}/{}", progress.done, total),
+        None => progress.done.to_string(),
+    };
+    let eta = progress.eta().map_or(String::new(), |eta| format!(", ETA {}s", eta.as_secs()));
+
+    eprint!("\r\x1b[K{}: {}{} {:?}", progress.attack, steps, eta, guess);
+    if progress.total == Some(progress.done) {
+        eprintln!();
+    }
+    let _ = io::stderr().flush();
+}
+
+fn list(challenges: &[Challenge]) {
+    for challenge in challenges {
+        println!("Set {}, challenge {:2}: {}", challenge.set, challenge.number, challenge.title);
+    }
+}
+
+// Format a fraction as a percentage for display.
+fn percent(fraction: f64) -> String {
+    format!("{:.1}%", fraction * 100.0)
+}
+
+// Run a randomized challenge repeatedly, summarizing how often it succeeded.
+fn run_trials(challenge: &Challenge, runs: usize) -> (String, Value) {
+    let trials = challenge.run_trials(runs).unwrap();
+    let (low, high) = trials.confidence_interval();
+    let text = format!("{}/{} runs succeeded ({}, 95% CI {} to {})",
+                       trials.successes,
+                       trials.runs,
+                       percent(trials.success_rate()),
+                       percent(low),
+                       percent(high));
+    let json = json!({
+        "runs": trials.runs,
+        "successes": trials.successes,
+        "success_rate": trials.success_rate(),
+        "confidence_interval": [low, high],
+    });
+    (text, json)
+}
+
+fn run(challenges: &[Challenge], selection: &Selection) {
+    let selected = challenges.iter()
+        .filter(|c| selection.matches(c))
+        .filter(|c| selection.trials.is_none() || c.is_probabilistic())
+        .collect::<Vec<_>>();
+    if selected.is_empty() {
+        if selection.trials.is_some() {
+            eprintln!("error: no matching randomized challenges");
+        } else {
+            eprintln!("error: no matching challenges");
+        }
+        process::exit(1);
+    }
+
+    init_logging(selection.verbosity);
+    cache::set_enabled(selection.cache);
+    if selection.progress {
+        progress::set_callback(show_progress);
+    }
+
+    let mut records = Vec::new();
+    for challenge in selected {
+        let start = Instant::now();
+        let (text, result) = match selection.trials {
+            Some(runs) => run_trials(challenge, runs as usize),
+            None => {
+                let output = challenge.run();
+                (output.text, output.json)
+            }
+        };
+        let elapsed = start.elapsed();
+        let millis = elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1_000_000;
+
+        if selection.json {
+            records.push(json!({
+                "set": challenge.set,
+                "challenge": challenge.number,
+                "title": challenge.title,
+                "millis": millis,
+                "result": result,
+            }));
+        } else {
+            println!("=== Set {}, challenge {}: {} ({} ms)",
+                     challenge.set,
+                     challenge.number,
+                     challenge.title,
+                     millis);
+            println!("{}\n", text.trim_end());
+        }
+    }
+
+    if selection.json {
+        println!("{}", serde_json::to_string_pretty(&records).unwrap());
+    }
+}
+
+fn main() {
+    let challenges = challenges::all();
+    let mut args = env::args().skip(1);
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("list") | Some("--list") => list(&challenges),
+        Some("run") => run(&challenges, &parse_run_args(args)),
+        Some("encode") => tools::encode(args),
+        Some("decode") => tools::decode(args),
+        Some("xor") => tools::xor(args),
+        Some("hamming") => tools::hamming(args),
+        Some("hexdump") => tools::hexdump(args),
+        Some("penguin") => tools::penguin(args),
+        Some("crack") => crack::crack(args),
+        Some("detect") => crack::detect(args),
+        Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+        Some(command) => usage_error(&format!("unrecognized command '{}'", command)),
+        None => usage_error("no command given"),
+    }
+}
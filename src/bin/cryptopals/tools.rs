@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+use cryptopals::utils::{bytes, hexdump, image};
+use cryptopals::utils::image::Image;
+
+use usage_error;
+
+// Text encodings supported by the encode and decode commands.
+enum Codec {
+    Hex,
+    Base64,
+    Base64Url,
+    Base32,
+}
+
+fn parse_codec(arg: Option<String>) -> Codec {
+    match arg.as_ref().map(|arg| arg.as_str()) {
+        Some("hex") => Codec::Hex,
+        Some("base64") => Codec::Base64,
+        Some("base64url") => Codec::Base64Url,
+        Some("base32") => Codec::Base32,
+        Some(other) => usage_error(&format!("unknown encoding '{}'", other)),
+        None => usage_error("an encoding is required"),
+    }
+}
+
+// Report an error that isn't the user's fault, and exit.
+pub fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+// Take the optional input file argument, rejecting anything after it.
+fn input_arg<I: Iterator<Item = String>>(args: &mut I) -> Option<String> {
+    let path = args.next();
+    if let Some(arg) = args.next() {
+        usage_error(&format!("unexpected argument '{}'", arg));
+    }
+    path
+}
+
+// Read the whole input from the given file, or from stdin if it's missing or "-".
+pub fn read_input(path: Option<&str>) -> Vec<u8> {
+    let mut input = Vec::new();
+    let result = match path {
+        None | Some("-") => io::stdin().read_to_end(&mut input),
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_end(&mut input)),
+    };
+    if let Err(err) = result {
+        fail(&format!("couldn't read {}: {}", path.unwrap_or("stdin"), err));
+    }
+    input
+}
+
+// Read the input as text with all whitespace removed, as expected by the decoders.
+fn read_encoded(path: Option<&str>) -> String {
+    match String::from_utf8(read_input(path)) {
+        Ok(text) => text.chars().filter(|c| !c.is_whitespace()).collect(),
+        Err(_) => fail("input is not valid UTF-8"),
+    }
+}
+
+fn write_output(data: &[u8]) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(err) = stdout.write_all(data).and_then(|_| stdout.flush()) {
+        fail(&format!("couldn't write output: {}", err));
+    }
+}
+
+/// Encode the input as hex, base64 or base32.
+pub fn encode<I: Iterator<Item = String>>(mut args: I) {
+    let codec = parse_codec(args.next());
+    let input = read_input(input_arg(&mut args).as_ref().map(|path| path.as_str()));
+    let encoded = match codec {
+        Codec::Hex => bytes::hex_from_bytes(&input),
+        Codec::Base64 => bytes::base64_from_bytes(&input),
+        Codec::Base64Url => bytes::base64_encode(&input, &bytes::BASE64_URL_SAFE),
+        Codec::Base32 => bytes::base32_from_bytes(&input),
+    };
+    println!("{}", encoded);
+}
+
+/// Decode hex, base64 or base32 input, ignoring whitespace, and write the raw bytes.
+pub fn decode<I: Iterator<Item = String>>(mut args: I) {
+    let codec = parse_codec(args.next());
+    let input = read_encoded(input_arg(&mut args).as_ref().map(|path| path.as_str()));
+    let decoded = match codec {
+        Codec::Hex => bytes::hex_to_bytes(&input),
+        Codec::Base64 => bytes::base64_decode(&input, &bytes::BASE64_STANDARD),
+        Codec::Base64Url => bytes::base64_decode(&input, &bytes::BASE64_URL_SAFE),
+        Codec::Base32 => bytes::base32_to_bytes(&input),
+    };
+    match decoded {
+        Ok(decoded) => write_output(&decoded),
+        Err(err) => fail(err),
+    }
+}
+
+/// XOR the input with a repeating key, given as text or hex.
+pub fn xor<I: Iterator<Item = String>>(mut args: I) {
+    let mut key = None;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key = args.next().map(|key| key.into_bytes()),
+            "--hex-key" => {
+                key = match args.next().map(|hex| bytes::hex_to_bytes(&hex)) {
+                    Some(Ok(key)) => Some(key),
+                    _ => usage_error("--hex-key requires a hex string"),
+                }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let key = match key {
+        Some(ref key) if !key.is_empty() => key,
+        _ => usage_error("xor requires a nonempty --key or --hex-key"),
+    };
+    let input = read_input(path.as_ref().map(|path| path.as_str()));
+    write_output(&bytes::xor(&input, key.iter().cycle()));
+}
+
+/// Print the bitwise Hamming distance between two files of equal length.
+pub fn hamming<I: Iterator<Item = String>>(mut args: I) {
+    let (a, b) = match (args.next(), args.next(), args.next()) {
+        (Some(a), Some(b), None) => (read_input(Some(&a)), read_input(Some(&b))),
+        _ => usage_error("hamming requires exactly two files"),
+    };
+    match bytes::hamming_dist(&a, &b) {
+        Ok(dist) => println!("{}", dist),
+        Err(err) => fail(err),
+    }
+}
+
+/// Print a hexdump of the input, marking repeated blocks.
+pub fn hexdump<I: Iterator<Item = String>>(mut args: I) {
+    let mut block_size = 16;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--block-size" => {
+                block_size = match args.next().map(|size| size.parse()) {
+                    Some(Ok(size)) if size > 0 => size,
+                    _ => usage_error("--block-size requires a positive number"),
+                }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let input = read_input(path.as_ref().map(|path| path.as_str()));
+    print!("{}", hexdump::dump(&input, block_size));
+}
+
+/// Encrypt the pixels of a PPM or BMP image under a random key, keeping its header, and
+/// write the image in the same format, to see what ECB mode leaves visible.
+pub fn penguin<I: Iterator<Item = String>>(mut args: I) {
+    let mut ctr = false;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mode" => {
+                ctr = match args.next().as_ref().map(|mode| mode.as_str()) {
+                    Some("ecb") => false,
+                    Some("ctr") => true,
+                    _ => usage_error("--mode requires 'ecb' or 'ctr'"),
+                }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let input = read_input(path.as_ref().map(|path| path.as_str()));
+    let original = match Image::decode(&input) {
+        Ok(original) => original,
+        Err(err) => fail(err),
+    };
+    let key = bytes::random(16);
+    let encrypted = if ctr {
+        image::encrypt_ctr(&original, &key, 0)
+    } else {
+        image::encrypt_ecb(&original, &key)
+    };
+    if input.starts_with(b"BM") {
+        write_output(&encrypted.to_bmp());
+    } else {
+        write_output(&encrypted.to_ppm());
+    }
+}
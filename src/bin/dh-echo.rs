@@ -0,0 +1,213 @@
+extern crate cryptopals;
+
+use std::env;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
+
+use cryptopals::utils::bytes;
+use cryptopals::utils::dh::{EchoClient, EchoServer, Group, Injection, ParameterInjection};
+use cryptopals::utils::protocol::{self, TcpTransport};
+
+static USAGE: &'static str = "\
+Usage:
+    dh-echo bob [--listen <address>]
+    dh-echo alice [--connect <address>] [--x25519] <message>
+    dh-echo mitm [--listen <address>] [--connect <address>] [--inject <parameter>]
+
+Runs the Diffie-Hellman echo protocol from challenge 34 between separate processes. Bob
+echoes whatever Alice sends him, and Mallory's MITM proxy injects p as both public keys,
+or a malicious g as in challenge 35, then prints every message she decrypts. Messages are
+length-prefixed frames, readable in a packet capture.
+
+To watch the attack, run each of these in its own terminal:
+    dh-echo bob
+    dh-echo mitm
+    dh-echo alice --connect 127.0.0.1:9035 'hello bob'
+
+Options:
+    --listen <address>      Address to listen on [default: 127.0.0.1:9034 for bob,
+                            127.0.0.1:9035 for mitm].
+    --connect <address>     Address of bob, or of the proxy [default: 127.0.0.1:9034].
+    --inject <parameter>    What Mallory swaps in: 'keys' for p as both public keys, or
+                            'g=1', 'g=p' or 'g=p-1' for the generator Bob is sent
+                            [default: keys].
+    --x25519                Agree on a key with X25519 instead of the NIST group. Mallory's
+                            attack doesn't apply, and she only breaks the session.";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+// Addresses and the message, as given on the command line.
+struct Options {
+    listen: Option<String>,
+    connect: String,
+    message: Option<String>,
+    x25519: bool,
+    injection: Option<Injection>,
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Options {
+    let mut options = Options {
+        listen: None,
+        connect: "127.0.0.1:9034".to_string(),
+        message: None,
+        x25519: false,
+        injection: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" | "--connect" => {
+                let address = match args.next() {
+                    Some(address) => address,
+                    None => usage_error(&format!("{} requires an address", arg)),
+                };
+                if arg == "--listen" {
+                    options.listen = Some(address);
+                } else {
+                    options.connect = address;
+                }
+            }
+            "--x25519" => options.x25519 = true,
+            "--inject" => {
+                options.injection = match args.next().as_ref().map(|arg| arg.as_str()) {
+                    Some("keys") => Some(Injection::PublicKeys),
+                    Some("g=1") => Some(Injection::GeneratorOne),
+                    Some("g=p") => Some(Injection::GeneratorP),
+                    Some("g=p-1") => Some(Injection::GeneratorPMinusOne),
+                    Some(other) => usage_error(&format!("unknown injection '{}'", other)),
+                    None => usage_error("--inject requires a parameter"),
+                }
+            }
+            _ if options.message.is_none() && !arg.starts_with("--") => {
+                options.message = Some(arg)
+            }
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+    options
+}
+
+fn listen(address: &str) -> TcpListener {
+    match TcpListener::bind(address) {
+        Ok(listener) => {
+            eprintln!("listening on {}", address);
+            listener
+        }
+        Err(err) => fail(&format!("couldn't listen on {}: {}", address, err)),
+    }
+}
+
+fn connect(address: &str) -> TcpStream {
+    match TcpStream::connect(address) {
+        Ok(stream) => stream,
+        Err(err) => fail(&format!("couldn't connect to {}: {}", address, err)),
+    }
+}
+
+// Serve the echo protocol, one session per connection.
+fn bob(options: Options) {
+    if options.message.is_some() {
+        usage_error("only alice takes a message");
+    }
+    if options.x25519 {
+        usage_error("only alice chooses the key exchange");
+    }
+    if options.injection.is_some() {
+        usage_error("only mitm injects parameters");
+    }
+    let listener = listen(options.listen.as_ref().map_or("127.0.0.1:9034", |a| a.as_str()));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => fail(&format!("couldn't accept a connection: {}", err)),
+        };
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+            let mut bob = EchoServer::new();
+            if let Err(err) = protocol::drive(&mut bob, &mut TcpTransport::new(stream)) {
+                eprintln!("{}: {}", peer, err);
+            }
+            for message in &bob.received {
+                println!("{}: received {:?}", peer, bytes::to_string(message));
+            }
+        });
+    }
+}
+
+fn alice(options: Options) {
+    let message = match options.message {
+        Some(message) => message,
+        None => usage_error("alice requires a message"),
+    };
+    if options.listen.is_some() {
+        usage_error("alice doesn't listen");
+    }
+    if options.injection.is_some() {
+        usage_error("only mitm injects parameters");
+    }
+    let mut alice = if options.x25519 {
+        EchoClient::x25519(message.as_bytes())
+    } else {
+        EchoClient::new(Group::nist(), message.as_bytes())
+    };
+    let mut transport = TcpTransport::new(connect(&options.connect));
+    if let Err(err) = protocol::drive(&mut alice, &mut transport) {
+        fail(&err.to_string());
+    }
+    match alice.echo {
+        Some(ref echo) if *echo == message.as_bytes() => {
+            println!("echo: {:?}", bytes::to_string(echo))
+        }
+        Some(ref echo) => fail(&format!("echo didn't match: {:?}", bytes::to_string(echo))),
+        None => fail("connection closed before the echo arrived"),
+    }
+}
+
+// Relay each client connection to bob, running the parameter injection attack on it.
+fn mitm(options: Options) {
+    if options.message.is_some() {
+        usage_error("only alice takes a message");
+    }
+    if options.x25519 {
+        usage_error("only alice chooses the key exchange");
+    }
+    let injection = options.injection.unwrap_or(Injection::PublicKeys);
+    let listener = listen(options.listen.as_ref().map_or("127.0.0.1:9035", |a| a.as_str()));
+    for client in listener.incoming() {
+        let client = match client {
+            Ok(client) => client,
+            Err(err) => fail(&format!("couldn't accept a connection: {}", err)),
+        };
+        let server = connect(&options.connect);
+        thread::spawn(move || {
+            let peer = client.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+            let mut mallory = ParameterInjection::new().with_injection(injection);
+            if let Err(err) = protocol::relay(&mut mallory, client, server) {
+                eprintln!("{}: {}", peer, err);
+            }
+            for message in &mallory.recovered {
+                println!("{}: recovered {:?}", peer, bytes::to_string(message));
+            }
+        });
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("bob") => bob(parse_options(args)),
+        Some("alice") => alice(parse_options(args)),
+        Some("mitm") => mitm(parse_options(args)),
+        Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+        Some(role) => usage_error(&format!("unrecognized role '{}'", role)),
+        None => usage_error("no role given"),
+    }
+}
@@ -0,0 +1,251 @@
+extern crate cryptopals;
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cryptopals::utils::{attacks, bytes};
+use cryptopals::utils::oracles::PaddingOracle;
+use cryptopals::utils::random::{OsSource, RandomSource};
+
+static USAGE: &'static str = "\
+Usage:
+    padding-oracle serve [--listen <address>] [--secret <text>] [--delay <ms>] [--flaky <percent>]
+    padding-oracle attack [--connect <address>] [--retries <n>]
+
+Serves the CBC padding oracle from challenge 17 over TCP, and attacks it from another
+process. The protocol is one line per request:
+    TOKEN                   Answered with the IV and ciphertext of the secret, in hex.
+    CHECK <iv> <ciphertext> Answered with OK or BAD PADDING, and nothing more.
+
+Options:
+    --listen <address>      Address to serve on [default: 127.0.0.1:9017].
+    --connect <address>     Address of the server [default: 127.0.0.1:9017].
+    --secret <text>         The secret to encrypt [default: a line from challenge 17].
+    --delay <ms>            Delay each answer, to simulate a slow network [default: 0].
+    --flaky <percent>       Hang up instead of answering this often [default: 0].
+    --retries <n>           Times to retry a failed request before giving up [default: 5].";
+
+static DEFAULT_SECRET: &'static str = "000003Cooking MC's like a pound of bacon";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+// Options for both commands, as given on the command line.
+struct Options {
+    address: String,
+    secret: String,
+    delay: u64,
+    flaky: u64,
+    retries: u32,
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I, allowed: &[&str]) -> Options {
+    let mut options = Options {
+        address: "127.0.0.1:9017".to_string(),
+        secret: DEFAULT_SECRET.to_string(),
+        delay: 0,
+        flaky: 0,
+        retries: 5,
+    };
+    while let Some(arg) = args.next() {
+        if !allowed.contains(&arg.as_str()) {
+            usage_error(&format!("unexpected argument '{}'", arg));
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => usage_error(&format!("{} requires a value", arg)),
+        };
+        let number = || match value.parse() {
+            Ok(number) => number,
+            Err(_) => usage_error(&format!("{} requires a number", arg)),
+        };
+        match arg.as_str() {
+            "--listen" | "--connect" => options.address = value.clone(),
+            "--secret" => options.secret = value.clone(),
+            "--delay" => options.delay = number(),
+            "--flaky" => options.flaky = number(),
+            _ => options.retries = number() as u32,
+        }
+    }
+    options
+}
+
+// Answer one request line, or return None for a malformed one.
+fn answer(oracle: &PaddingOracle, line: &str) -> Option<String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["TOKEN"] => {
+            let (iv, ciphertext) = oracle.token();
+            Some(format!("{} {}", bytes::hex_from_bytes(&iv), bytes::hex_from_bytes(&ciphertext)))
+        }
+        ["CHECK", iv, ciphertext] => {
+            let iv = bytes::hex_to_bytes(iv).ok()?;
+            let ciphertext = bytes::hex_to_bytes(ciphertext).ok()?;
+            let valid = oracle.check(&iv, &ciphertext);
+            Some(if valid { "OK" } else { "BAD PADDING" }.to_string())
+        }
+        _ => None,
+    }
+}
+
+// Write a line in a single call. With Nagle's algorithm off, that's a single packet, so
+// the attack's thousands of tiny round trips aren't each held up by delayed ACKs.
+fn write_line(stream: &mut TcpStream, line: &str) -> io::Result<()> {
+    stream.write_all(format!("{}\n", line).as_bytes())
+}
+
+fn serve_connection(oracle: &PaddingOracle, stream: TcpStream, options: &Options) {
+    let mut rng = OsSource::new();
+    let mut writer = match stream.set_nodelay(true).and_then(|_| stream.try_clone()) {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        thread::sleep(Duration::from_millis(options.delay));
+        if (rng.range(0, 100) as u64) < options.flaky {
+            // Simulate a transient failure by hanging up mid-request.
+            return;
+        }
+        let reply = answer(oracle, &line).unwrap_or_else(|| "ERROR".to_string());
+        if write_line(&mut writer, &reply).is_err() {
+            return;
+        }
+    }
+}
+
+fn serve(options: Options) {
+    let listener = match TcpListener::bind(&options.address) {
+        Ok(listener) => listener,
+        Err(err) => fail(&format!("couldn't listen on {}: {}", options.address, err)),
+    };
+    eprintln!("listening on {}", options.address);
+
+    let oracle = Arc::new(PaddingOracle::new(options.secret.as_bytes()));
+    let options = Arc::new(options);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => fail(&format!("couldn't accept a connection: {}", err)),
+        };
+        let oracle = oracle.clone();
+        let options = options.clone();
+        thread::spawn(move || serve_connection(&oracle, stream, &options));
+    }
+}
+
+// A connection to the oracle server that reconnects and retries, with exponential backoff,
+// when a request fails.
+struct RemoteOracle {
+    address: String,
+    retries: u32,
+    connection: Option<(BufReader<TcpStream>, TcpStream)>,
+    queries: usize,
+    failures: usize,
+}
+
+impl RemoteOracle {
+    fn new(address: &str, retries: u32) -> Self {
+        RemoteOracle {
+            address: address.to_string(),
+            retries: retries,
+            connection: None,
+            queries: 0,
+            failures: 0,
+        }
+    }
+
+    fn try_request(&mut self, line: &str) -> io::Result<String> {
+        if self.connection.is_none() {
+            let stream = TcpStream::connect(&self.address)?;
+            stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+            stream.set_nodelay(true)?;
+            self.connection = Some((BufReader::new(stream.try_clone()?), stream));
+        }
+        let (ref mut reader, ref mut writer) = *self.connection.as_mut().unwrap();
+        write_line(writer, line)?;
+        let mut reply = String::new();
+        if reader.read_line(&mut reply)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "server hung up"));
+        }
+        Ok(reply.trim_end().to_string())
+    }
+
+    fn request(&mut self, line: &str) -> String {
+        self.queries += 1;
+        let mut attempt = 0;
+        loop {
+            match self.try_request(line) {
+                Ok(reply) => return reply,
+                Err(err) => {
+                    self.connection = None;
+                    self.failures += 1;
+                    if attempt == self.retries {
+                        fail(&format!("giving up after {} attempts: {}", attempt + 1, err));
+                    }
+                    thread::sleep(Duration::from_millis(50 << attempt.min(6)));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn check(&mut self, iv: &[u8], ciphertext: &[u8]) -> bool {
+        let line = format!("CHECK {} {}",
+                           bytes::hex_from_bytes(iv),
+                           bytes::hex_from_bytes(ciphertext));
+        match self.request(&line).as_str() {
+            "OK" => true,
+            "BAD PADDING" => false,
+            reply => fail(&format!("unexpected reply {:?}", reply)),
+        }
+    }
+}
+
+fn attack(options: Options) {
+    let mut oracle = RemoteOracle::new(&options.address, options.retries);
+    let token = oracle.request("TOKEN");
+    let (iv, ciphertext) = match token.split_once(' ') {
+        Some((iv, ciphertext)) => (bytes::hex_to_bytes(iv), bytes::hex_to_bytes(ciphertext)),
+        None => fail(&format!("unexpected reply {:?}", token)),
+    };
+    let (iv, ciphertext) = match (iv, ciphertext) {
+        (Ok(iv), Ok(ciphertext)) => (iv, ciphertext),
+        _ => fail(&format!("unexpected reply {:?}", token)),
+    };
+
+    let mut check = |iv: &[u8], ciphertext: &[u8]| oracle.check(iv, ciphertext);
+    match attacks::decrypt_cbc_padding_oracle(&iv, &ciphertext, &mut check) {
+        Ok(plaintext) => println!("{:?}", bytes::to_string(&plaintext)),
+        Err(err) => fail(err),
+    }
+    eprintln!("{} queries, {} failed requests retried", oracle.queries, oracle.failures);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("serve") => {
+            serve(parse_options(args, &["--listen", "--secret", "--delay", "--flaky"]))
+        }
+        Some("attack") => attack(parse_options(args, &["--connect", "--retries"])),
+        Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+        Some(command) => usage_error(&format!("unrecognized command '{}'", command)),
+        None => usage_error("no command given"),
+    }
+}
@@ -0,0 +1,93 @@
+extern crate cryptopals;
+
+use std::env;
+use std::process;
+
+use cryptopals::server;
+
+static USAGE: &'static str = "\
+Usage:
+    srp-http serve [--listen <address>]
+    srp-http register [--connect <address>] <email> <password>
+    srp-http login [--connect <address>] <email> <password>
+
+Runs the SRP server from challenge 36 behind a small JSON-over-HTTP API, and a client for
+it. The server keeps its users in memory.
+
+Options:
+    --listen <address>      Address to serve on [default: 127.0.0.1:9036].
+    --connect <address>     Address of the server [default: 127.0.0.1:9036].";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+// Parse the optional address flag and the positional arguments that follow it.
+fn parse_args<I: Iterator<Item = String>>(mut args: I, flag: &str) -> (String, Vec<String>) {
+    let mut address = "127.0.0.1:9036".to_string();
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            address = match args.next() {
+                Some(address) => address,
+                None => usage_error(&format!("{} requires an address", flag)),
+            };
+        } else if arg.starts_with("--") {
+            usage_error(&format!("unrecognized option '{}'", arg));
+        } else {
+            positional.push(arg);
+        }
+    }
+    (address, positional)
+}
+
+// Parse the address and the email and password for the client commands.
+fn parse_credentials<I: Iterator<Item = String>>(args: I) -> (String, String, String) {
+    let (address, mut positional) = parse_args(args, "--connect");
+    if positional.len() != 2 {
+        usage_error("expected an email and a password");
+    }
+    let password = positional.pop().unwrap();
+    let email = positional.pop().unwrap();
+    (address, email, password)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    match args.next().as_ref().map(|arg| arg.as_str()) {
+        Some("serve") => {
+            let (address, positional) = parse_args(args, "--listen");
+            if !positional.is_empty() {
+                usage_error("serve takes no arguments");
+            }
+            eprintln!("listening on {}", address);
+            if let Err(err) = server::serve(&address) {
+                fail(&err);
+            }
+        }
+        Some("register") => {
+            let (address, email, password) = parse_credentials(args);
+            match server::register(&address, &email, &password) {
+                Ok(()) => println!("registered {}", email),
+                Err(err) => fail(&err),
+            }
+        }
+        Some("login") => {
+            let (address, email, password) = parse_credentials(args);
+            match server::login(&address, &email, &password) {
+                Ok(true) => println!("logged in as {}", email),
+                Ok(false) => fail("wrong password"),
+                Err(err) => fail(&err),
+            }
+        }
+        Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+        Some(command) => usage_error(&format!("unrecognized command '{}'", command)),
+        None => usage_error("no command given"),
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use set_1;
+use set_2;
+use utils::{bytes, oracles};
+use utils::trials::{self, Trials};
+
+/// The result of running a challenge, both formatted for display and as structured data.
+pub struct Output {
+    pub text: String,
+    pub json: Value,
+}
+
+impl Output {
+    fn new<T: Serialize>(text: String, value: &T) -> Self {
+        Output {
+            text: text,
+            json: serde_json::to_value(value).unwrap(),
+        }
+    }
+}
+
+// Results that are already fit for display.
+fn display<T: Serialize + Display>(value: T) -> Output {
+    Output::new(value.to_string(), &value)
+}
+
+// Results that may contain unprintable characters, or that have no natural display format.
+fn debug<T: Serialize + Debug>(value: T) -> Output {
+    Output::new(format!("{:?}", value), &value)
+}
+
+/// A runnable challenge, along with enough metadata to list it.
+pub struct Challenge {
+    pub set: u32,
+    pub number: u32,
+    pub title: &'static str,
+    run: Box<dyn Fn() -> Output>,
+    trial: Option<Box<dyn Fn() -> bool>>,
+}
+
+impl Challenge {
+    fn new<F>(set: u32, number: u32, title: &'static str, run: F) -> Self
+        where F: Fn() -> Output + 'static
+    {
+        Challenge {
+            set: set,
+            number: number,
+            title: title,
+            run: Box::new(run),
+            trial: None,
+        }
+    }
+
+    // Mark a challenge whose outcome depends on randomness, giving a check of whether a
+    // single run succeeded.
+    fn with_trial<F>(mut self, trial: F) -> Self
+        where F: Fn() -> bool + 'static
+    {
+        self.trial = Some(Box::new(trial));
+        self
+    }
+
+    /// Run the challenge and return its result.
+    pub fn run(&self) -> Output {
+        (self.run)()
+    }
+
+    /// Whether the challenge can fail or behave differently from run to run, because it
+    /// attacks a randomized oracle.
+    pub fn is_probabilistic(&self) -> bool {
+        self.trial.is_some()
+    }
+
+    /// Run the challenge the given number of times and count how often it succeeds, or
+    /// return None if it's deterministic.
+    pub fn run_trials(&self, runs: usize) -> Option<Trials> {
+        self.trial.as_ref().map(|trial| trials::run(runs, trial))
+    }
+}
+
+/// All implemented challenges, in order.
+pub fn all() -> Vec<Challenge> {
+    vec![
+        Challenge::new(1, 1, "Convert hex to base64", || display(set_1::challenge_1())),
+        Challenge::new(1, 2, "Fixed XOR", || display(set_1::challenge_2())),
+        Challenge::new(1, 3, "Single-byte XOR cipher", || display(set_1::challenge_3())),
+        Challenge::new(1, 4, "Detect single-character XOR", || display(set_1::challenge_4())),
+        Challenge::new(1, 5, "Implement repeating-key XOR", || display(set_1::challenge_5())),
+        Challenge::new(1, 6, "Break repeating-key XOR", || display(set_1::challenge_6())),
+        Challenge::new(1, 7, "AES in ECB mode", || display(set_1::challenge_7())),
+        Challenge::new(1, 8, "Detect AES in ECB mode", || display(set_1::challenge_8())),
+        Challenge::new(2, 9, "Implement PKCS#7 padding", || debug(set_2::challenge_9())),
+        Challenge::new(2, 10, "Implement CBC mode", || display(set_2::challenge_10())),
+        Challenge::new(2, 11, "An ECB/CBC detection oracle", || {
+            let correct = set_2::challenge_11();
+            Output::new(format!("Detected mode correctly: {}", correct), &correct)
+        }).with_trial(set_2::challenge_11),
+        Challenge::new(2, 12, "Byte-at-a-time ECB decryption (Simple)", || {
+            display(set_2::challenge_12())
+        }),
+        Challenge::new(2, 13, "ECB cut-and-paste", || {
+            // Sort the profile so the output is the same from run to run.
+            let profile = set_2::challenge_13().into_iter().collect::<BTreeMap<_, _>>();
+            debug(profile)
+        }),
+        Challenge::new(2, 14, "Byte-at-a-time ECB decryption (Harder)", || {
+            display(set_2::challenge_14())
+        }).with_trial(|| {
+            // The oracle picks a new random prefix each time.
+            let expected = bytes::to_string(&oracles::UnknownStringOracle::unknown_string());
+            set_2::challenge_14() == expected
+        }),
+        Challenge::new(2, 15, "PKCS#7 padding validation", || {
+            let results = set_2::challenge_15();
+            let lines = results.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            Output::new(lines.join("\n"), &results)
+        }),
+        Challenge::new(2, 16, "CBC bitflipping attacks", || debug(set_2::challenge_16())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all() {
+        let challenges = all();
+        for (i, challenge) in challenges.iter().enumerate() {
+            assert_eq!(challenge.number as usize, i + 1);
+            assert_eq!(challenge.set, (challenge.number - 1) / 8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_run_trials() {
+        let challenges = all();
+        let trials = challenges[10].run_trials(10).unwrap();
+        assert_eq!(trials.successes, 10);
+        assert!(challenges[0].run_trials(10).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+#[cfg(feature = "backend-openssl")]
+use openssl::sha;
+
+#[cfg(feature = "backend-openssl")]
+use utils::bytes;
+use utils::cache;
+
+#[cfg(feature = "fetch")]
+static BASE_URL: &'static str = "https://cryptopals.com/static/challenge-data/";
+
+/// A challenge data file, identified by its name on cryptopals.com. The checksum is the
+/// SHA-256 of the copy vendored in src/data, which downloaded copies must match.
+pub struct Dataset {
+    pub name: &'static str,
+    pub sha256: &'static str,
+    embedded: Option<&'static str>,
+}
+
+static DATASETS: [Dataset; 5] = [
+    Dataset {
+        name: "4.txt",
+        sha256: "c87c921c561bf2a69cf4847dd6649f6d05430fcabae80fe5e78b56d78978a436",
+        embedded: Some(include_str!("data/4.txt")),
+    },
+    Dataset {
+        name: "6.txt",
+        sha256: "9cce7ff2a0ade90b54c0e20ee8283c0cd8caa7663f995eff2d7b9ace3bd53d8d",
+        embedded: Some(include_str!("data/6.txt")),
+    },
+    Dataset {
+        name: "7.txt",
+        sha256: "c50fd4291beb52b9fbac8c4bddc5454c7757d0988359e5625e430bc7cdd709c3",
+        embedded: Some(include_str!("data/7.txt")),
+    },
+    Dataset {
+        name: "8.txt",
+        sha256: "d61d668f428e48b70c4148ba6a3201afb6d6bd8f630686f23162400683a066b7",
+        embedded: Some(include_str!("data/8.txt")),
+    },
+    Dataset {
+        name: "10.txt",
+        sha256: "81fbeb6c3194bac8191cfcc8dbb63b705f1d499d3d43ab7795627501dbe34925",
+        embedded: Some(include_str!("data/10.txt")),
+    },
+];
+
+/// All known datasets.
+pub fn all() -> &'static [Dataset] {
+    &DATASETS
+}
+
+/// Look up a dataset by name.
+pub fn find(name: &str) -> Option<&'static Dataset> {
+    DATASETS.iter().find(|dataset| dataset.name == name)
+}
+
+/// Load the dataset with the given name. See `Dataset::load`.
+pub fn load(name: &str) -> Result<String, &'static str> {
+    find(name).ok_or("Unknown dataset.")?.load()
+}
+
+/// Directory where downloaded datasets are cached. See `cache::base_dir`.
+pub fn cache_dir() -> PathBuf {
+    cache::base_dir()
+}
+
+impl Dataset {
+    /// Load the contents of the dataset. With the `fetch` feature enabled, the file is read
+    /// from the cache directory, or downloaded there if it's missing, and its checksum is
+    /// verified. If that fails (e.g., when offline), or without the feature, the copy
+    /// embedded in the binary is used instead.
+    pub fn load(&self) -> Result<String, &'static str> {
+        #[cfg(feature = "fetch")]
+        {
+            match self.fetch() {
+                Ok(contents) => return Ok(contents),
+                Err(err) => {
+                    if self.embedded.is_none() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        self.embedded
+            .map(|contents| contents.to_string())
+            .ok_or("Dataset isn't embedded; enable the fetch feature to download it.")
+    }
+
+    /// Check whether the given contents match the dataset's checksum. Uses OpenSSL's
+    /// SHA-256, so requires the `backend-openssl` feature.
+    #[cfg(feature = "backend-openssl")]
+    pub fn verify(&self, contents: &[u8]) -> bool {
+        bytes::hex_from_bytes(&sha::sha256(contents)) == self.sha256
+    }
+
+    #[cfg(feature = "fetch")]
+    fn fetch(&self) -> Result<String, &'static str> {
+        use std::fs;
+
+        let path = cache_dir().join(self.name);
+        let contents = match fs::read(&path) {
+            Ok(ref cached) if self.verify(cached) => cached.clone(),
+            _ => {
+                let downloaded = download(&format!("{}{}", BASE_URL, self.name))?;
+                if !self.verify(&downloaded) {
+                    return Err("Downloaded dataset failed checksum verification.");
+                }
+
+                // Failing to cache the file isn't fatal; it'll just be downloaded again.
+                let _ = fs::create_dir_all(cache_dir())
+                    .and_then(|_| fs::write(&path, &downloaded));
+                downloaded
+            }
+        };
+
+        String::from_utf8(contents).map_err(|_| "Dataset isn't valid UTF-8.")
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn download(url: &str) -> Result<Vec<u8>, &'static str> {
+    use curl::easy::Easy;
+
+    let mut contents = Vec::new();
+    let mut handle = Easy::new();
+    handle.url(url).map_err(|_| "Invalid dataset URL.")?;
+    handle.fail_on_error(true).map_err(|_| "Failed to configure download.")?;
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|chunk| {
+                contents.extend_from_slice(chunk);
+                Ok(chunk.len())
+            })
+            .map_err(|_| "Failed to configure download.")?;
+        transfer.perform().map_err(|_| "Failed to download dataset.")?;
+    }
+    Ok(contents)
+}
+
+#[cfg(all(test, feature = "backend-openssl"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_checksums() {
+        for dataset in all() {
+            let embedded = dataset.embedded.unwrap();
+            assert!(dataset.verify(embedded.as_bytes()), "{}", dataset.name);
+        }
+        assert!(!find("6.txt").unwrap().verify(b"tampered"));
+        assert!(load("nonexistent.txt").is_err());
+    }
+}
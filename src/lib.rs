@@ -1,9 +1,59 @@
+// Without the default `std` feature only the codecs in `utils::bytes` are built, and the
+// crate needs nothing beyond `alloc`. Aliasing `core` as `std` keeps their imports the same.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+
+#[cfg(feature = "fetch")]
+extern crate curl;
+#[cfg(feature = "std")]
 extern crate itertools;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "std")]
+extern crate num_bigint;
+#[cfg(feature = "std")]
+extern crate num_integer;
+#[cfg(feature = "std")]
+extern crate num_traits;
+#[cfg(feature = "backend-openssl")]
 extern crate openssl;
+#[cfg(feature = "backend-openssl")]
+extern crate openssl_sys;
+#[cfg(feature = "std")]
 extern crate rand;
-extern crate rustc_serialize;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "std")]
+extern crate serde;
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "aes", macro_use)]
+extern crate serde_derive;
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "server", macro_use)]
+extern crate serde_json;
+#[cfg(feature = "server")]
+extern crate tiny_http;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod challenges;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod datasets;
+pub mod prelude;
+#[cfg(all(feature = "server", feature = "aes"))]
+pub mod server;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(all(feature = "std", feature = "aes"))]
 pub mod set_1;
+#[cfg(all(feature = "std", feature = "aes"))]
 pub mod set_2;
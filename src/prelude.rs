@@ -0,0 +1,65 @@
+//! The parts of the crate meant for use as a library, in one place:
+//!
+//! ```ignore
+//! extern crate cryptopals;
+//! use cryptopals::prelude::*;
+//! ```
+//!
+//! Everything here is re-exported from `utils`, and keeps its name and signature across
+//! releases. Anything reachable only through `utils::` is the challenges' own plumbing and
+//! may change. Items appear only when the features they need are enabled: the codecs and
+//! hashes are always available, most of the rest needs `std`, and the ciphers and oracles
+//! need `aes`.
+
+pub use utils::bytes::{base64_from_bytes, base64_to_bytes, hamming_dist, hex_from_bytes,
+                       hex_to_bytes, xor};
+pub use utils::digest::{hkdf, hmac, Digest};
+pub use utils::sha1::{sha1, Sha1};
+pub use utils::sha256::{hmac_sha256, sha256, Sha256};
+
+#[cfg(feature = "std")]
+pub use utils::attacks::{brute_force, decrypt_cbc_padding_oracle, decrypt_ecb_suffix,
+                         decrypt_ecb_suffix_fast, detect_block_size, detect_ecb,
+                         detect_ecb_batch, find_repeating_key, rank_repeating_key_xor,
+                         rank_single_byte_xor, scan_corpus, single_byte_brute_force,
+                         single_byte_brute_force_with, BruteForce, Candidate, CorpusHit,
+                         Search};
+#[cfg(feature = "std")]
+pub use utils::bytes::decode_any;
+#[cfg(feature = "std")]
+pub use utils::crypto::{pad_pkcs7, strip_pkcs7, PaddingError};
+#[cfg(feature = "std")]
+pub use utils::mac::{verify, Hmac, Mac, Poly1305};
+#[cfg(feature = "std")]
+pub use utils::random::{OsSource, RandomSource, SeededRng};
+#[cfg(feature = "std")]
+pub use utils::text::{CharacterClassScorer, CosineScorer, PrintableScorer, Scorer, Weighted};
+
+#[cfg(all(feature = "std", feature = "aes"))]
+pub use utils::aead::{Aead, ChaCha20Poly1305, Ccm, CtrHmac, Gcm};
+#[cfg(feature = "aes")]
+pub use utils::cipher::{aes_128, BlockCipher};
+#[cfg(all(feature = "std", feature = "aes"))]
+pub use utils::crypto::{ctr, decrypt_cbc, decrypt_cbc_checked, decrypt_ecb, encrypt_cbc,
+                        encrypt_ecb, Ctr};
+#[cfg(all(feature = "std", feature = "aes"))]
+pub use utils::mac::{CbcMac, Cmac, Gmac};
+#[cfg(all(feature = "std", feature = "aes"))]
+pub use utils::oracles::{encryption_oracle, CBCCookieOracle, CookieError, CountingOracle,
+                         PaddingOracle, PlaintextPolicy, PredictableIvOracle, ProfileCookieOracle,
+                         UnknownStringOracle};
+
+#[cfg(all(test, feature = "std", feature = "aes"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prelude() {
+        // Challenge 17 through nothing but the prelude.
+        let oracle = PaddingOracle::new(b"attack at dawn");
+        let (iv, ciphertext) = oracle.token();
+        let mut check = |iv: &[u8], ciphertext: &[u8]| oracle.check(iv, ciphertext);
+        let plaintext = decrypt_cbc_padding_oracle(&iv, &ciphertext, &mut check).unwrap();
+        assert_eq!(hex_from_bytes(&plaintext), hex_from_bytes(b"attack at dawn"));
+    }
+}
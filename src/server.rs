@@ -0,0 +1,200 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+use num_bigint::BigInt;
+use num_traits::Num;
+use serde_json::{self, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use utils::bytes;
+use utils::dh::Group;
+use utils::srp;
+
+// Big integers travel as hex strings, and salts and proofs as hex-encoded bytes.
+fn int_to_hex(n: &BigInt) -> String {
+    n.to_str_radix(16)
+}
+
+fn int_from_hex(hex: &str) -> Option<BigInt> {
+    BigInt::from_str_radix(hex, 16).ok()
+}
+
+fn field<'a>(body: &'a Value, name: &str) -> Result<&'a str, String> {
+    body.get(name).and_then(|value| value.as_str()).ok_or(format!("missing field '{}'", name))
+}
+
+// Handle one API request, returning the status code and JSON body of the response.
+fn handle(srp: &mut srp::Server, request: &mut Request) -> (u16, Value) {
+    if *request.method() != Method::Post {
+        return (405, json!({ "error": "use POST" }));
+    }
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        return (400, json!({ "error": "body is not UTF-8" }));
+    }
+    let body = match serde_json::from_str::<Value>(&body) {
+        Ok(body) => body,
+        Err(_) => return (400, json!({ "error": "body is not JSON" })),
+    };
+    let email = match field(&body, "email") {
+        Ok(email) => email,
+        Err(err) => return (400, json!({ "error": err })),
+    };
+
+    match (request.url(), body.get("A"), body.get("proof")) {
+        ("/register", _, _) => {
+            let password = match field(&body, "password") {
+                Ok(password) => password,
+                Err(err) => return (400, json!({ "error": err })),
+            };
+            match srp.register(email, password) {
+                Ok(()) => (200, json!({})),
+                Err(err) => (409, json!({ "error": err })),
+            }
+        }
+        ("/login", Some(public_a), None) => {
+            let public_a = match public_a.as_str().and_then(int_from_hex) {
+                Some(public_a) => public_a,
+                None => return (400, json!({ "error": "A must be a hex integer" })),
+            };
+            match srp.start_login(email, &public_a) {
+                Ok((salt, public_b)) => {
+                    let salt = bytes::hex_from_bytes(&salt);
+                    (200, json!({ "salt": salt, "B": int_to_hex(&public_b) }))
+                }
+                Err(err) => (403, json!({ "error": err })),
+            }
+        }
+        ("/login", None, Some(proof)) => {
+            let proof = proof.as_str().and_then(|proof| bytes::hex_to_bytes(proof).ok());
+            match proof {
+                Some(ref proof) if srp.finish_login(email, proof) => (200, json!({})),
+                _ => (403, json!({ "error": "login failed" })),
+            }
+        }
+        ("/login", _, _) => (400, json!({ "error": "send either A or proof" })),
+        _ => (404, json!({ "error": "no such endpoint" })),
+    }
+}
+
+/// Serve challenge 36's SRP server over HTTP on the given address, until the process is
+/// killed. Requests and responses are JSON:
+///
+/// * `POST /register` with `email` and `password` registers a user.
+/// * `POST /login` with `email` and the client's public key `A` starts a login, and
+///   responds with the `salt` and the server's public key `B`.
+/// * `POST /login` with `email` and `proof` finishes it, responding with status 200 if the
+///   proof is right and 403 otherwise.
+pub fn serve(address: &str) -> Result<(), String> {
+    run(Server::http(address).map_err(|err| err.to_string())?);
+    Ok(())
+}
+
+/// Start the server from `serve` on a new thread, returning once it's listening. The
+/// address it returns is the one actually bound, so asking for port 0 gets a free port.
+pub fn start(address: &str) -> Result<SocketAddr, String> {
+    let server = Server::http(address).map_err(|err| err.to_string())?;
+    let bound = server.server_addr().to_ip().ok_or("not listening on an IP address")?;
+    thread::spawn(move || run(server));
+    Ok(bound)
+}
+
+// Answer requests until the server shuts down.
+fn run(server: Server) {
+    let mut srp = srp::Server::new(Group::nist());
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .unwrap();
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = handle(&mut srp, &mut request);
+        info!("{} {} -> {}", request.method(), request.url(), status);
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        if let Err(err) = request.respond(response) {
+            warn!("Couldn't send response: {}", err);
+        }
+    }
+}
+
+// POST a JSON body to the server, returning the status code and the JSON response. Speaks
+// just enough HTTP/1.1 to talk to `serve`.
+fn post(address: &str, path: &str, body: &Value) -> Result<(u16, Value), String> {
+    let mut stream = TcpStream::connect(address).map_err(|err| err.to_string())?;
+    let body = body.to_string();
+    let request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+                           Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                          path,
+                          address,
+                          body.len(),
+                          body);
+    let mut response = String::new();
+    stream.write_all(request.as_bytes())
+        .and_then(|_| stream.read_to_string(&mut response))
+        .map_err(|err| err.to_string())?;
+
+    let malformed = || "malformed response".to_string();
+    let (head, body) = response.split_at(response.find("\r\n\r\n").ok_or_else(malformed)?);
+    let status = head.split(' ').nth(1).and_then(|status| status.parse().ok());
+    let body = serde_json::from_str(body.trim()).map_err(|_| malformed())?;
+    Ok((status.ok_or_else(malformed)?, body))
+}
+
+// Turn an error response into a message.
+fn error(status: u16, body: &Value) -> String {
+    let message = body.get("error").and_then(|error| error.as_str()).unwrap_or("unknown error");
+    format!("{} ({})", message, status)
+}
+
+/// Register a user with an SRP server started by `serve`.
+pub fn register(address: &str, email: &str, password: &str) -> Result<(), String> {
+    match post(address, "/register", &json!({ "email": email, "password": password }))? {
+        (200, _) => Ok(()),
+        (status, body) => Err(error(status, &body)),
+    }
+}
+
+/// Log in to an SRP server started by `serve`, returning whether the server accepted the
+/// password. The password itself never leaves the client.
+pub fn login(address: &str, email: &str, password: &str) -> Result<bool, String> {
+    let client = srp::Client::new(Group::nist(), email, password);
+    let start = json!({ "email": email, "A": int_to_hex(client.public_key()) });
+    let (salt, public_b) = match post(address, "/login", &start)? {
+        (200, body) => {
+            let salt = field(&body, "salt")
+                .ok()
+                .and_then(|salt| bytes::hex_to_bytes(salt).ok());
+            let public_b = field(&body, "B").ok().and_then(int_from_hex);
+            match (salt, public_b) {
+                (Some(salt), Some(public_b)) => (salt, public_b),
+                _ => return Err("malformed response".to_string()),
+            }
+        }
+        (status, body) => return Err(error(status, &body)),
+    };
+
+    let proof = bytes::hex_from_bytes(&client.proof(&salt, &public_b));
+    match post(address, "/login", &json!({ "email": email, "proof": proof }))? {
+        (200, _) => Ok(true),
+        (403, _) => Ok(false),
+        (status, body) => Err(error(status, &body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_login() {
+        let address = start("127.0.0.1:0").unwrap().to_string();
+        let address = address.as_str();
+
+        register(address, "alice@example.com", "hunter2").unwrap();
+        assert!(register(address, "alice@example.com", "hunter2").is_err());
+        assert!(login(address, "alice@example.com", "hunter2").unwrap());
+        assert!(!login(address, "alice@example.com", "hunter3").unwrap());
+        assert!(login(address, "bob@example.com", "hunter2").is_err());
+    }
+}
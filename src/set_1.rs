@@ -1,9 +1,7 @@
-use std::f64;
+use std::fmt;
 
-use rustc_serialize::base64::*;
-use rustc_serialize::hex::*;
-
-use utils::{attacks, bytes, crypto};
+use datasets;
+use utils::{attacks, bytes, crypto, text};
 
 /// Convert hex to base64.
 pub fn challenge_1() -> String {
@@ -11,44 +9,48 @@ pub fn challenge_1() -> String {
                  696b65206120706f69736f6e6f7573206d757368726f6f6d";
 
     // For funsies, the bytes module includes homemade functions for encoding and decoding
-    // hex and base64 encoded strings. For the remainder of the challenges, we'll use the
-    // standard rustc_serialize crate to do this conversion instead, since it is likely
-    // more rebust and has a nicer API.
-    bytes::hex_to_base64(input)
+    // hex and base64 encoded strings, which are used for the remainder of the challenges.
+    bytes::hex_to_base64(input).unwrap()
 }
 
 /// Fixed XOR.
 pub fn challenge_2() -> String {
-    let a = "1c0111001f010100061a024b53535009181c".from_hex().unwrap();
-    let b = "686974207468652062756c6c277320657965".from_hex().unwrap();
-    bytes::xor(&a, &b).to_hex()
+    let a = bytes::hex_to_bytes("1c0111001f010100061a024b53535009181c").unwrap();
+    let b = bytes::hex_to_bytes("686974207468652062756c6c277320657965").unwrap();
+    bytes::hex_from_bytes(&bytes::xor(&a, &b))
 }
 
 /// Single-byte XOR cipher.
 pub fn challenge_3() -> String {
     let input = "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736";
-    let input_bytes = input.from_hex().unwrap();
-    let (_, decoded, _) = attacks::single_byte_brute_force(&input_bytes);
+    break_single_byte_xor(&bytes::hex_to_bytes(input).unwrap())
+}
+
+/// Decrypt an English plaintext that was XOR'd against a single byte.
+pub fn break_single_byte_xor(ciphertext: &[u8]) -> String {
+    // The plaintext is short, so use a wordlist to break ties between close frequency scores.
+    let scorer = text::TieBreaker::english();
+    let (_, decoded, _) = attacks::single_byte_brute_force_with(ciphertext, &scorer);
     decoded
 }
 
 /// Detect single-character XOR.
 pub fn challenge_4() -> String {
-    let input = include_str!("data/4.txt");
-
-    let mut result = String::new();
-    let mut best_score = f64::MIN;
-
-    for line in input.lines() {
-        let line_bytes = line.from_hex().unwrap();
-        let (score, decoded, _) = attacks::single_byte_brute_force(&line_bytes);
-        if score > best_score {
-            best_score = score;
-            result = decoded;
-        }
-    }
+    let input = datasets::load("4.txt").unwrap();
+    let lines = input.lines()
+        .map(|line| bytes::hex_to_bytes(line).unwrap())
+        .collect::<Vec<_>>();
+    detect_single_byte_xor(&lines)
+}
 
-    result
+/// Find the candidate most likely to be English XOR'd against a single byte, and return
+/// its decryption.
+pub fn detect_single_byte_xor(candidates: &[Vec<u8>]) -> String {
+    // The plaintext is short, so use a wordlist to break ties between close frequency scores.
+    let scorer = text::TieBreaker::english();
+    attacks::scan_corpus(candidates, &scorer, 1)
+        .first()
+        .map_or(String::new(), |hit| bytes::to_string(&hit.candidate.plaintext))
 }
 
 /// Implement repeating-key XOR.
@@ -56,76 +58,93 @@ pub fn challenge_5() -> String {
     let pad = b"ICE".iter().cycle();
     let text = &b"Burning 'em, if you ain't quick and nimble\n\
                   I go crazy when I hear a cymbal"[..];
-    bytes::xor(text, pad).to_hex()
+    bytes::hex_from_bytes(&bytes::xor(text, pad))
+}
+
+/// The key and plaintext recovered by breaking repeating-key XOR.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RepeatingKeyXor {
+    pub key: String,
+    pub plaintext: String,
+}
+
+impl fmt::Display for RepeatingKeyXor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key: {:?}\n{}", self.key, self.plaintext)
+    }
 }
 
 /// Break repeating-key XOR.
-pub fn challenge_6() -> (String, String) {
-    let input = include_str!("data/6.txt").to_string().replace("\n", "");
-    let ciphertext = input.from_base64().unwrap();
-
-    // Get most likely key size.
-    let keysizes = attacks::get_keysizes(&ciphertext, 2..41, 1);
-
-    // Use the same brute force technique for breaking single-byte XOR encryption
-    // to determine the most likely key for each given key size.
-    let keys = keysizes.iter()
-        .map(|size| {
-            // Break cipher text into key sized chunks and transpose them into a vector of
-            // vectors of bytes, where the nth vector contains all bytes in the ciphertext
-            // that were XOR'd with the nth byte of the key. Allows us to generalize
-            // the single-byte brute force technique to a multi-byte repeating key.
-            let transposed = bytes::transpose(ciphertext.chunks(*size));
-
-            // Find the most likely key byte for each group of transposed bytes.
-            transposed.iter()
-                .map(|bytes| {
-                    let (_, _, key_byte) = attacks::single_byte_brute_force(bytes);
-                    key_byte
-                })
-                .collect::<Vec<u8>>()
-        })
-        .collect::<Vec<_>>();
+pub fn challenge_6() -> RepeatingKeyXor {
+    let input = datasets::load("6.txt").unwrap();
+    break_repeating_key_xor(&bytes::base64_decode(&input, &bytes::BASE64_MIME).unwrap())
+}
+
+/// Recover the key and plaintext of an English text encrypted with repeating-key XOR,
+/// using a key of between 2 and 40 bytes.
+pub fn break_repeating_key_xor(ciphertext: &[u8]) -> RepeatingKeyXor {
+    let key = attacks::find_repeating_key(ciphertext);
 
     // XOR the ciphertext with the found key, and convert the result into a string.
-    let pad = keys[0].iter().cycle();
-    let decoded = bytes::xor(&ciphertext, pad);
+    let pad = key.iter().cycle();
+    let decoded = bytes::xor(ciphertext, pad);
 
-    (bytes::to_string(&keys[0]), bytes::to_string(&decoded))
+    RepeatingKeyXor {
+        key: bytes::to_string(&key),
+        plaintext: bytes::to_string(&decoded),
+    }
 }
 
 /// AES in ECB mode.
 pub fn challenge_7() -> String {
-    let input = include_str!("data/7.txt").to_string().replace("\n", "");
-    let ciphertext = input.from_base64().unwrap();
-    let key = &b"YELLOW SUBMARINE"[..];
+    let input = datasets::load("7.txt").unwrap();
+    let ciphertext = bytes::base64_decode(&input, &bytes::BASE64_MIME).unwrap();
+    decrypt_aes_128_ecb(&ciphertext, b"YELLOW SUBMARINE")
+}
 
-    let decoded = crypto::decrypt_ecb(key, None, &ciphertext, true);
-    bytes::to_string(&decoded)
+/// Decrypt an AES-128-ECB ciphertext with PKCS#7 padding.
+pub fn decrypt_aes_128_ecb(ciphertext: &[u8], key: &[u8]) -> String {
+    bytes::to_string(&crypto::decrypt_ecb(key, ciphertext, true))
 }
 
-/// Detect AES in ECB mode.
-pub fn challenge_8() -> (usize, String) {
-    let input = include_str!("data/8.txt");
-
-    let mut index = 0;
-    let mut result = String::new();
-    let mut max = 0;
-
-    // Find the line that has the most repeated 16-byte chunks. This is likely
-    // indicative of an ECB-encoded plaintext, assuming the plaintext itself has
-    // some repeated 16-byte chunks. Will not work for arbitrary plaintexts.
-    for (i, line) in input.lines().enumerate() {
-        let bytes = line.from_hex().unwrap();
-        let count = attacks::max_repeated_blocks(&bytes, 16);
-        if count > max {
-            max = count;
-            index = i;
-            result = line.to_string();
-        }
+/// A line of input that appears to have been encrypted with ECB mode.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EcbLine {
+    pub index: usize,
+    pub ciphertext: String,
+}
+
+impl fmt::Display for EcbLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.index, self.ciphertext)
     }
+}
 
-    (index, result)
+/// Detect AES in ECB mode.
+pub fn challenge_8() -> EcbLine {
+    let input = datasets::load("8.txt").unwrap();
+    let lines = input.lines()
+        .map(|line| bytes::hex_to_bytes(line).unwrap())
+        .collect::<Vec<_>>();
+    detect_ecb_line(&lines).unwrap()
+}
+
+/// Find the candidate ciphertext most likely to have been encrypted with a 16-byte block
+/// cipher in ECB mode, or None if none of them look like ECB.
+pub fn detect_ecb_line(candidates: &[Vec<u8>]) -> Option<EcbLine> {
+    // The line with the most repeated 16-byte chunks is likely to be ECB-encoded, assuming
+    // the plaintext itself has some repeated 16-byte chunks. Will not work for arbitrary
+    // plaintexts.
+    attacks::detect_ecb_batch(candidates, 16)
+        .into_iter()
+        .next()
+        .filter(|result| result.score.is_ecb())
+        .map(|result| {
+            EcbLine {
+                index: result.line,
+                ciphertext: bytes::hex_from_bytes(&candidates[result.line]),
+            }
+        })
 }
 
 #[cfg(test)]
@@ -160,6 +179,28 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_challenge_4_scorers() {
+        use utils::text::{ChiSquaredScorer, CosineScorer, Scorer};
+
+        // Either scorer should pick out the real plaintext from the other lines of the
+        // dataset. benches/primitives.rs times them on the same search.
+        let scorers: Vec<Box<dyn Scorer>> = vec![Box::new(CosineScorer::default()),
+                                                 Box::new(ChiSquaredScorer::default())];
+        for scorer in &scorers {
+            let best = include_str!("data/4.txt")
+                .lines()
+                .map(|line| {
+                    let line_bytes = bytes::hex_to_bytes(line).unwrap();
+                    attacks::single_byte_brute_force_with(&line_bytes, &**scorer)
+                })
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                .unwrap();
+            assert_eq!(best.1, "Now that the party is jumping\n");
+            assert_eq!(best.2, 0x35);
+        }
+    }
+
     #[test]
     fn test_challenge_5() {
         let result = challenge_5();
@@ -171,10 +212,24 @@ mod tests {
 
     #[test]
     fn test_challenge_6() {
-        let (key, result) = challenge_6();
+        let result = challenge_6();
         let expected = include_str!("data/play_that_funky_music.txt");
-        assert_eq!(result, expected);
-        assert_eq!(key, "Terminator X: Bring the noise");
+        assert_eq!(result.plaintext, expected);
+        assert_eq!(result.key, "Terminator X: Bring the noise");
+    }
+
+    #[test]
+    fn test_break_repeating_key_xor() {
+        let plaintext = include_str!("data/play_that_funky_music.txt");
+        // Key size detection can't tell a key from a repetition of itself, so use a key
+        // that is too long to be repeated within the range of sizes tested.
+        let key = "Stop collaborate listen";
+        let ciphertext = bytes::xor(plaintext.as_bytes(), key.as_bytes().iter().cycle());
+
+        let result = break_repeating_key_xor(&ciphertext);
+        assert_eq!(result.key, key);
+        assert_eq!(result.plaintext, plaintext);
+        assert_eq!(detect_ecb_line(&[ciphertext]), None);
     }
 
     #[test]
@@ -186,7 +241,7 @@ mod tests {
 
     #[test]
     fn text_challenge_8() {
-        let (index, result) = challenge_8();
+        let result = challenge_8();
 
         // Note that the second 16-byte chunk is repeated 4 times.
         let expected = "d880619740a8a19b7840a8a31c810a3d\
@@ -200,7 +255,7 @@ mod tests {
                         d403180c98c8f6db1f2a3f9c4040deb0\
                         ab51b29933f2c123c58386b06fba186a";
 
-        assert_eq!(result, expected);
-        assert_eq!(index, 132);
+        assert_eq!(result.ciphertext, expected);
+        assert_eq!(result.index, 132);
     }
 }
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use rustc_serialize::base64::*;
-
+use datasets;
 use utils::{attacks, bytes, crypto, oracles};
 
 /// Implement PKCS#7 padding.
@@ -12,21 +12,21 @@ pub fn challenge_9() -> String {
 
 /// Implement CBC mode.
 pub fn challenge_10() -> String {
-    let input = include_str!("data/10.txt").to_string().replace("\n", "");
-    let ciphertext = input.from_base64().unwrap();
-
-    let key = &b"YELLOW SUBMARINE"[..];
-    let iv = [0u8; 16];
+    let input = datasets::load("10.txt").unwrap();
+    let ciphertext = bytes::base64_decode(&input, &bytes::BASE64_MIME).unwrap();
+    decrypt_aes_128_cbc(&ciphertext, b"YELLOW SUBMARINE", &[0u8; 16])
+}
 
-    let decrypted = crypto::decrypt_cbc(key, &iv, &ciphertext);
-    bytes::to_string(&decrypted)
+/// Decrypt an AES-128-CBC ciphertext with PKCS#7 padding.
+pub fn decrypt_aes_128_cbc(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> String {
+    bytes::to_string(&crypto::decrypt_cbc(key, iv, ciphertext).unwrap())
 }
 
 /// An ECB/CBC detection oracle.
 pub fn challenge_11() -> bool {
     let data = [0u8; 64];
     let (encrypted, cbc) = oracles::encryption_oracle(&data);
-    attacks::detect_ecb(&encrypted, 16) != cbc
+    attacks::detect_ecb(&encrypted, 16).is_ecb() != cbc
 }
 
 /// Byte-at-a-time ECB decryption (Simple).
@@ -36,10 +36,11 @@ pub fn challenge_12() -> String {
     let mut encrypt = |bytes: &[u8]| -> Vec<u8> { oracle.encrypt(bytes) };
 
     // Detect block size.
-    let block_size = attacks::detect_block_size(&mut encrypt).unwrap();
+    let (block_size, prefix_len) = attacks::detect_block_size(&mut encrypt).unwrap();
 
-    // We know that the block size is actually 128 bits.
+    // We know that the block size is actually 128 bits, and that there is no prefix.
     assert_eq!(block_size, 16);
+    assert_eq!(prefix_len, 0);
 
     // Multiply block size by 4 to ensure that even if an arbitrary amount of random
     // padding is preprended by the oracle, we will still have two full blocks of zeros.
@@ -50,10 +51,10 @@ pub fn challenge_12() -> String {
     let num_blocks = encrypted_zeros.len() / block_size - 4;
 
     // Detect that ECB is being used. We know that this is the case.
-    assert!(attacks::detect_ecb(&encrypted_zeros, block_size));
+    assert!(attacks::detect_ecb(&encrypted_zeros, block_size).is_ecb());
 
     // Decrypt the unknown string.
-    attacks::decrypt_ecb_suffix(&mut encrypt, block_size, num_blocks)
+    attacks::decrypt_ecb_suffix(&mut encrypt, block_size, prefix_len, num_blocks).unwrap()
 }
 
 /// ECB cut-and-paste.
@@ -70,23 +71,47 @@ pub fn challenge_13() -> HashMap<String, String> {
     // |email=..........|adminBBBBBBBBBBB|...&uid=10&role=|userCCCCCCCCCCCC|
     // |0123456789ABCDEF|0123456789ABCDEF|0123456789ABCDEF|0123456789ABCDEF|
 
+    let block_size = 16;
+    let prefix = "email=";
+    let suffix = "&uid=10&role=";
+
+    // Align a padded "admin" block to a block boundary.
+    let admin = crypto::pad_pkcs7(b"admin", block_size).unwrap();
+    let (mut email, _) = attacks::align_input(prefix.len(), block_size, b'.', &admin);
+
+    // Extend the email so that the role value starts a new block.
+    let used = prefix.len() + email.len() + suffix.len();
+    email.extend(vec![b'.'; (block_size - used % block_size) % block_size]);
+    let email = bytes::to_string(&email);
+
     let oracle = oracles::ProfileCookieOracle::new();
-    let email = "..........admin\x0b\x0b\x0b\x0b\x0b\x0b\x0b\x0b\x0b\x0b\x0b...";
-    let cookie = oracle.encrypt_cookie(email);
-    let mut tampered = Vec::new();
-    tampered.extend(&cookie[0..48]);
-    tampered.extend(&cookie[16..32]);
-    oracle.decrypt_cookie(&tampered)
+    let cookie = oracle.encrypt_cookie(&email);
+
+    // Reconstruct the plaintext of the cookie so we can locate the blocks we need.
+    let plaintext = bytes::from_string(&(prefix.to_string() + &email + suffix + "user"));
+    let blocks = attacks::label_blocks(&cookie, &plaintext, block_size);
+    let find = |target: &[u8]| {
+        blocks.iter().position(|block| block.plaintext == Some(target)).unwrap()
+    };
+    let admin_index = find(&admin);
+    let user_index = find(b"user");
+
+    // Keep everything up to the role value, then paste in the admin block.
+    let mut indices = (0..user_index).collect::<Vec<_>>();
+    indices.push(admin_index);
+    let tampered = attacks::select_blocks(&cookie, block_size, &indices);
+    oracle.decrypt_cookie(&tampered).unwrap()
 }
 
 /// Byte-at-a-time ECB decryption (Harder).
-pub fn challenge_14() {
+pub fn challenge_14() -> String {
     // Initialize the oracle and wrap it in a closure so it can be easily passed around.
-    let oracle = oracles::UnknownStringOracle::new(false);
+    // This time, the oracle prepends a random number of random bytes to our input.
+    let oracle = oracles::UnknownStringOracle::new(true);
     let mut encrypt = |bytes: &[u8]| -> Vec<u8> { oracle.encrypt(bytes) };
 
-    // Detect block size.
-    let block_size = attacks::detect_block_size(&mut encrypt).unwrap();
+    // Detect block size and the length of the random prefix.
+    let (block_size, prefix_len) = attacks::detect_block_size(&mut encrypt).unwrap();
 
     // We know that the block size is actually 128 bits.
     assert_eq!(block_size, 16);
@@ -96,28 +121,56 @@ pub fn challenge_14() {
     let zeros = vec![0u8; block_size * 4];
     let encrypted_zeros = encrypt(&zeros);
 
-    // Determine how many additional blocks of suffix are being added to the input.
-    let num_blocks = encrypted_zeros.len() / block_size - 4;
-
     // Detect that ECB is being used. We know that this is the case.
-    assert!(attacks::detect_ecb(&encrypted_zeros, block_size));
+    assert!(attacks::detect_ecb(&encrypted_zeros, block_size).is_ecb());
+
+    // Determine how many blocks of suffix follow our input once the prefix is padded out
+    // to a block boundary.
+    let fill = (block_size - prefix_len % block_size) % block_size;
+    let num_blocks = (encrypt(&vec![0u8; fill]).len() - prefix_len - fill) / block_size;
 
     // Decrypt the unknown string.
-    attacks::decrypt_ecb_suffix(&mut encrypt, block_size, num_blocks)
+    attacks::decrypt_ecb_suffix(&mut encrypt, block_size, prefix_len, num_blocks).unwrap()
+}
+
+/// A padded input, along with the result of stripping its padding (or None if the padding
+/// was invalid).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PaddingValidation {
+    pub input: Vec<u8>,
+    pub stripped: Option<Vec<u8>>,
+}
+
+impl fmt::Display for PaddingValidation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stripped = self.stripped.as_ref().map(|bytes| bytes::to_string(bytes));
+        write!(f, "{:?} => {:?}", bytes::to_string(&self.input), stripped)
+    }
 }
 
 /// PKCS#7 padding validation.
-pub fn challenge_15() -> (Option<Vec<u8>>, Option<Vec<u8>>, Option<Vec<u8>>) {
-    (
-        crypto::strip_pkcs7(&b"ICE ICE BABY\x04\x04\x04\x04"[..]),
-        crypto::strip_pkcs7(&b"ICE ICE BABY\x05\x05\x05\x05"[..]),
-        crypto::strip_pkcs7(&b"ICE ICE BABY\x01\x02\x03\x04"[..]),
-    )
+pub fn challenge_15() -> Vec<PaddingValidation> {
+    let inputs = [&b"ICE ICE BABY\x04\x04\x04\x04"[..],
+                  &b"ICE ICE BABY\x05\x05\x05\x05"[..],
+                  &b"ICE ICE BABY\x01\x02\x03\x04"[..]];
+    inputs.iter()
+        .map(|input| {
+            PaddingValidation {
+                input: input.to_vec(),
+                stripped: crypto::strip_pkcs7(input),
+            }
+        })
+        .collect()
 }
 
 /// CBC bitflipping attacks.
 pub fn challenge_16() -> String {
+    flip_admin(&oracles::CBCCookieOracle::new()).unwrap()
+}
 
+/// Make the given cookie oracle decrypt a cookie containing ";admin=true;", returning the
+/// decrypted cookie, or the oracle's error if its plaintext policy rejects it.
+pub fn flip_admin(oracle: &oracles::CBCCookieOracle) -> Result<String, oracles::CookieError> {
     // ';' has ASCII code 59 and '=' has ASCII code 61. By replacing them with
     // ':' (ASCII code 58) and '<' (ASCII code 60), we can make CBC decryption
     // transform those bytes into the correct characters by flipping the lowest order
@@ -126,13 +179,15 @@ pub fn challenge_16() -> String {
     // |comment1=cooking|%20MCs;userdata=|:admin<true:....|
     // |0123456789ABCDEF|0123456789ABCDEF|0123456789ABCDEF|
 
-    let oracle = oracles::CBCCookieOracle::new();
     let userdata = ":admin<true:....";
     let mut ciphertext = oracle.encrypt(userdata);
 
-    ciphertext[16] ^= 1;
-    ciphertext[22] ^= 1;
-    ciphertext[27] ^= 1;
+    // Flip bits in the second block to edit the corresponding bytes of the third. This
+    // scrambles the second block, which only a lenient enough server will accept.
+    let block = bytes::nth_block_mut(&mut ciphertext, 16, 1).unwrap();
+    block[0] ^= 1;
+    block[6] ^= 1;
+    block[11] ^= 1;
 
     oracle.decrypt(&ciphertext)
 }
@@ -140,7 +195,7 @@ pub fn challenge_16() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use utils::{bytes, oracles};
+    use utils::{bytes, oracles, trials};
 
     #[test]
     fn test_challenge_9() {
@@ -159,9 +214,8 @@ mod tests {
     #[test]
     fn test_challenge_11() {
         // Since encryption oracle output is non-deterministic, do several trials to be sure.
-        for _ in 0..30 {
-            assert!(challenge_11());
-        }
+        let trials = trials::run(30, challenge_11);
+        assert_eq!(trials.successes, trials.runs);
     }
 
     #[test]
@@ -187,8 +241,8 @@ mod tests {
     #[test]
     fn test_challenge_15() {
         let result = challenge_15();
-        let expected = (Some(b"ICE ICE BABY"[..].to_vec()), None, None);
-        assert_eq!(result, expected);
+        let stripped = result.into_iter().map(|r| r.stripped).collect::<Vec<_>>();
+        assert_eq!(stripped, vec![Some(b"ICE ICE BABY".to_vec()), None, None]);
     }
 
     #[test]
@@ -196,4 +250,27 @@ mod tests {
         let result = challenge_16();
         assert!(result.contains(";admin=true;"));
     }
+
+    #[test]
+    fn test_flip_admin_policies() {
+        use utils::oracles::{CBCCookieOracle, CookieError, PlaintextPolicy};
+        use utils::random::SeededRng;
+
+        let oracle = |policy| {
+            CBCCookieOracle::with_source(&mut SeededRng::new(16)).with_policy(policy)
+        };
+        let quoted = flip_admin(&oracle(PlaintextPolicy::QuoteNonPrintable)).unwrap();
+        assert!(quoted.contains(";admin=true;") && quoted.contains('%'));
+        assert!(quoted.is_ascii());
+
+        // The scrambled block is random, so it's almost certainly not ASCII, or UTF-8.
+        for &policy in &[PlaintextPolicy::RejectHighAscii, PlaintextPolicy::StrictUtf8] {
+            match flip_admin(&oracle(policy)) {
+                Err(CookieError::Rejected(plaintext)) => {
+                    assert_eq!(&plaintext[32..44], b";admin=true;");
+                }
+                other => panic!("Expected a rejection, got {:?}", other),
+            }
+        }
+    }
 }
@@ -0,0 +1,451 @@
+use utils::{bytes, chacha20};
+use utils::cipher::{self, BlockCipher};
+use utils::crypto::Ctr;
+use utils::mac::{ghash, verify};
+use utils::poly1305::{aead_input, poly1305};
+use utils::sha256::hmac_sha256;
+
+/// Authenticated encryption with associated data. The key is fixed when the construction
+/// is created, and each message is sealed under a nonce, which must never repeat for the
+/// same key, along with associated data that is authenticated but not encrypted.
+pub trait Aead {
+    /// The length of the nonces the construction takes, in bytes.
+    fn nonce_len(&self) -> usize;
+
+    /// The length of the tags `seal` produces, in bytes.
+    fn tag_len(&self) -> usize;
+
+    /// Encrypt the data in place, and return a tag covering it and the associated data.
+    /// Panics if the nonce is the wrong length.
+    fn seal(&mut self, nonce: &[u8], associated_data: &[u8], data: &mut [u8]) -> Vec<u8>;
+
+    /// Check the tag, and only if it's valid, decrypt the data in place. On failure the
+    /// data is left encrypted.
+    fn open(&mut self,
+            nonce: &[u8],
+            associated_data: &[u8],
+            data: &mut [u8],
+            tag: &[u8])
+            -> Result<(), &'static str>;
+}
+
+/// AES-128-CTR for encryption, then HMAC-SHA256 over the nonce, associated data and
+/// ciphertext. The 32-byte key is split into an encryption key and a MAC key, and the
+/// 8-byte nonce is the nonce half of the CTR counter block.
+pub struct CtrHmac {
+    encryption_key: Vec<u8>,
+    mac_key: Vec<u8>,
+}
+
+impl CtrHmac {
+    /// Create the construction with a 32-byte key. Panics if the key is the wrong length.
+    pub fn new(key: &[u8]) -> Self {
+        assert_eq!(key.len(), 32, "CTR-then-HMAC keys must be 32 bytes long.");
+        CtrHmac {
+            encryption_key: key[..16].to_vec(),
+            mac_key: key[16..].to_vec(),
+        }
+    }
+
+    // The lengths are included so that bytes can't be moved between the associated data
+    // and the ciphertext without changing the tag.
+    fn tag(&self, nonce: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+        let mut message = nonce.to_vec();
+        message.extend_from_slice(associated_data);
+        message.extend_from_slice(ciphertext);
+        message.extend_from_slice(&(associated_data.len() as u64).to_be_bytes());
+        message.extend_from_slice(&(ciphertext.len() as u64).to_be_bytes());
+        hmac_sha256(&self.mac_key, &message).to_vec()
+    }
+
+    fn apply_keystream(&self, nonce: &[u8], data: &mut [u8]) {
+        let mut counter_nonce = [0u8; 8];
+        counter_nonce.copy_from_slice(nonce);
+        Ctr::new(&self.encryption_key, u64::from_le_bytes(counter_nonce)).apply_keystream(data);
+    }
+}
+
+impl Aead for CtrHmac {
+    fn nonce_len(&self) -> usize {
+        8
+    }
+
+    fn tag_len(&self) -> usize {
+        32
+    }
+
+    fn seal(&mut self, nonce: &[u8], associated_data: &[u8], data: &mut [u8]) -> Vec<u8> {
+        assert_eq!(nonce.len(), self.nonce_len(), "Nonce is the wrong length.");
+        self.apply_keystream(nonce, data);
+        self.tag(nonce, associated_data, data)
+    }
+
+    fn open(&mut self,
+            nonce: &[u8],
+            associated_data: &[u8],
+            data: &mut [u8],
+            tag: &[u8])
+            -> Result<(), &'static str> {
+        if nonce.len() != self.nonce_len() {
+            return Err("Nonce is the wrong length.");
+        }
+        if !verify(&self.tag(nonce, associated_data, data), tag) {
+            return Err("Invalid tag.");
+        }
+        self.apply_keystream(nonce, data);
+        Ok(())
+    }
+}
+
+/// AES-128 in CCM mode (RFC 3610): a CBC-MAC over the formatted nonce, associated data and
+/// plaintext, then CTR mode over the plaintext and the tag.
+pub struct Ccm {
+    cipher: Box<dyn BlockCipher>,
+    tag_len: usize,
+    nonce_len: usize,
+}
+
+impl Ccm {
+    /// Create CCM with an AES-128 key, a tag length that is an even number of bytes from 4
+    /// to 16, and a nonce length from 7 to 13 bytes. Every byte of nonce is one fewer for
+    /// the message length: with an n-byte nonce, messages are limited to 2^(8 * (15 - n))
+    /// bytes. Panics if any of the parameters is invalid.
+    pub fn new(key: &[u8], tag_len: usize, nonce_len: usize) -> Self {
+        assert!((4..=16).contains(&tag_len) && tag_len % 2 == 0, "Invalid CCM tag length.");
+        assert!((7..=13).contains(&nonce_len), "Invalid CCM nonce length.");
+        Ccm {
+            cipher: cipher::aes_128(key),
+            tag_len: tag_len,
+            nonce_len: nonce_len,
+        }
+    }
+
+    // The size in bytes of the message length and counter fields, called L in RFC 3610.
+    fn length_size(&self) -> usize {
+        15 - self.nonce_len
+    }
+
+    // Format a number as a big-endian field of the given size.
+    fn field(value: u64, size: usize) -> Vec<u8> {
+        value.to_be_bytes()[8 - size..].to_vec()
+    }
+
+    fn cbc_mac(&mut self, nonce: &[u8], associated_data: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        // The first block packs flags for the parameters in with the nonce and length.
+        let adata = if associated_data.is_empty() { 0 } else { 0x40 };
+        let flags = adata | ((self.tag_len - 2) / 2) << 3 | (self.length_size() - 1);
+        let mut data = vec![flags as u8];
+        data.extend_from_slice(nonce);
+        data.extend(Self::field(plaintext.len() as u64, self.length_size()));
+
+        // Associated data is prefixed with its length, in as few bytes as the length needs.
+        if !associated_data.is_empty() {
+            let len = associated_data.len() as u64;
+            if len < 0xff00 {
+                data.extend(Self::field(len, 2));
+            } else if len <= 0xffff_ffff {
+                data.extend_from_slice(&[0xff, 0xfe]);
+                data.extend(Self::field(len, 4));
+            } else {
+                data.extend_from_slice(&[0xff, 0xff]);
+                data.extend(Self::field(len, 8));
+            }
+            data.extend_from_slice(associated_data);
+            data.resize((data.len() + 15) / 16 * 16, 0);
+        }
+        data.extend_from_slice(plaintext);
+        data.resize((data.len() + 15) / 16 * 16, 0);
+
+        let mut mac = vec![0u8; 16];
+        for block in data.chunks(16) {
+            mac = bytes::xor(&mac, block);
+            self.cipher.encrypt_block(&mut mac);
+        }
+        mac.truncate(self.tag_len);
+        mac
+    }
+
+    // XOR the keystream into the data, starting from the given counter. Counter 0 encrypts
+    // the tag, and the message starts at counter 1.
+    fn apply_keystream(&mut self, nonce: &[u8], start: u64, data: &mut [u8]) {
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            let mut block = vec![(self.length_size() - 1) as u8];
+            block.extend_from_slice(nonce);
+            block.extend(Self::field(start + i as u64, self.length_size()));
+            self.cipher.encrypt_block(&mut block);
+            for (byte, key) in chunk.iter_mut().zip(block) {
+                *byte ^= key;
+            }
+        }
+    }
+}
+
+impl Aead for Ccm {
+    fn nonce_len(&self) -> usize {
+        self.nonce_len
+    }
+
+    fn tag_len(&self) -> usize {
+        self.tag_len
+    }
+
+    fn seal(&mut self, nonce: &[u8], associated_data: &[u8], data: &mut [u8]) -> Vec<u8> {
+        assert_eq!(nonce.len(), self.nonce_len, "Nonce is the wrong length.");
+        let max_len = (data.len() as u64).checked_shr(8 * self.length_size() as u32);
+        assert!(max_len.unwrap_or(0) == 0, "Message is too long for the nonce length.");
+
+        let mut tag = self.cbc_mac(nonce, associated_data, data);
+        self.apply_keystream(nonce, 1, data);
+        self.apply_keystream(nonce, 0, &mut tag);
+        tag
+    }
+
+    fn open(&mut self,
+            nonce: &[u8],
+            associated_data: &[u8],
+            data: &mut [u8],
+            tag: &[u8])
+            -> Result<(), &'static str> {
+        if nonce.len() != self.nonce_len {
+            return Err("Nonce is the wrong length.");
+        }
+        // The tag covers the plaintext, so decrypt a copy to check it against.
+        let mut plaintext = data.to_vec();
+        self.apply_keystream(nonce, 1, &mut plaintext);
+        let mut expected = self.cbc_mac(nonce, associated_data, &plaintext);
+        self.apply_keystream(nonce, 0, &mut expected);
+        if !verify(&expected, tag) {
+            return Err("Invalid tag.");
+        }
+        data.copy_from_slice(&plaintext);
+        Ok(())
+    }
+}
+
+/// AES in GCM mode (NIST SP 800-38D) with 12-byte nonces and 16-byte tags: CTR mode
+/// with a 32-bit big-endian counter after the nonce, then GHASH over the associated data
+/// and ciphertext, masked with the encryption of the first counter block.
+pub struct Gcm {
+    cipher: Box<dyn BlockCipher>,
+    h: Vec<u8>,
+}
+
+impl Gcm {
+    /// Create the AEAD with a 16, 24 or 32-byte key, for AES-128, AES-192 or AES-256, and
+    /// derive the hash key H = E(0) from it. Panics if the key is any other length.
+    pub fn new(key: &[u8]) -> Self {
+        assert!(key.len() == 16 || key.len() == 24 || key.len() == 32,
+                "GCM keys must be 16, 24 or 32 bytes long.");
+        let mut cipher = cipher::aes(key);
+        let mut h = vec![0u8; 16];
+        cipher.encrypt_block(&mut h);
+        Gcm {
+            cipher: cipher,
+            h: h,
+        }
+    }
+
+    // XOR the keystream into the data, starting from the given counter. Counter 1 masks
+    // the tag, and the message starts at counter 2.
+    fn apply_keystream(&mut self, nonce: &[u8], start: u32, data: &mut [u8]) {
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            let mut block = nonce.to_vec();
+            block.extend_from_slice(&start.wrapping_add(i as u32).to_be_bytes());
+            self.cipher.encrypt_block(&mut block);
+            for (byte, key) in chunk.iter_mut().zip(block) {
+                *byte ^= key;
+            }
+        }
+    }
+
+    fn tag(&mut self, nonce: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+        let mut tag = ghash(&self.h, associated_data, ciphertext);
+        self.apply_keystream(nonce, 1, &mut tag);
+        tag
+    }
+}
+
+impl Aead for Gcm {
+    fn nonce_len(&self) -> usize {
+        12
+    }
+
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn seal(&mut self, nonce: &[u8], associated_data: &[u8], data: &mut [u8]) -> Vec<u8> {
+        assert_eq!(nonce.len(), self.nonce_len(), "Nonce is the wrong length.");
+        self.apply_keystream(nonce, 2, data);
+        self.tag(nonce, associated_data, data)
+    }
+
+    fn open(&mut self,
+            nonce: &[u8],
+            associated_data: &[u8],
+            data: &mut [u8],
+            tag: &[u8])
+            -> Result<(), &'static str> {
+        if nonce.len() != self.nonce_len() {
+            return Err("Nonce is the wrong length.");
+        }
+        if !verify(&self.tag(nonce, associated_data, data), tag) {
+            return Err("Invalid tag.");
+        }
+        self.apply_keystream(nonce, 2, data);
+        Ok(())
+    }
+}
+
+/// The ChaCha20-Poly1305 AEAD from RFC 8439, with a 32-byte key and 12-byte nonces.
+pub struct ChaCha20Poly1305 {
+    key: Vec<u8>,
+}
+
+impl ChaCha20Poly1305 {
+    /// Create the AEAD with a 32-byte key. Panics if the key is the wrong length.
+    pub fn new(key: &[u8]) -> Self {
+        assert_eq!(key.len(), 32, "ChaCha20-Poly1305 keys must be 32 bytes long.");
+        ChaCha20Poly1305 { key: key.to_vec() }
+    }
+
+    /// The one-time Poly1305 key for a nonce: the first half of keystream block 0. Anyone
+    /// who learns it can forge tags for any message under the same nonce.
+    pub fn one_time_key(&self, nonce: &[u8]) -> Vec<u8> {
+        chacha20::block(&self.key, nonce, 0)[..32].to_vec()
+    }
+}
+
+impl Aead for ChaCha20Poly1305 {
+    fn nonce_len(&self) -> usize {
+        12
+    }
+
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn seal(&mut self, nonce: &[u8], associated_data: &[u8], data: &mut [u8]) -> Vec<u8> {
+        assert_eq!(nonce.len(), self.nonce_len(), "Nonce is the wrong length.");
+        chacha20::apply_keystream(&self.key, nonce, 1, data);
+        poly1305(&self.one_time_key(nonce), &aead_input(associated_data, data)).to_vec()
+    }
+
+    fn open(&mut self,
+            nonce: &[u8],
+            associated_data: &[u8],
+            data: &mut [u8],
+            tag: &[u8])
+            -> Result<(), &'static str> {
+        if nonce.len() != self.nonce_len() {
+            return Err("Nonce is the wrong length.");
+        }
+        let expected = poly1305(&self.one_time_key(nonce), &aead_input(associated_data, data));
+        if !verify(&expected, tag) {
+            return Err("Invalid tag.");
+        }
+        chacha20::apply_keystream(&self.key, nonce, 1, data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ctr_hmac() {
+        let mut aead = CtrHmac::new(&[7u8; 32]);
+        let mut data = b"attack at dawn".to_vec();
+        let tag = aead.seal(&[1; 8], b"to: alice", &mut data);
+        assert_eq!(tag.len(), aead.tag_len());
+        assert!(data != b"attack at dawn");
+
+        // Tampering with any input makes the tag invalid, and leaves the data encrypted.
+        let mut tampered = data.clone();
+        tampered[0] ^= 1;
+        assert!(aead.open(&[1; 8], b"to: alice", &mut tampered, &tag).is_err());
+        assert!(aead.open(&[2; 8], b"to: alice", &mut data.clone(), &tag).is_err());
+        assert!(aead.open(&[1; 8], b"to: mallory", &mut data.clone(), &tag).is_err());
+        assert!(aead.open(&[1; 8], b"to: alice", &mut data.clone(), &tag[..31]).is_err());
+
+        aead.open(&[1; 8], b"to: alice", &mut data, &tag).unwrap();
+        assert_eq!(data, b"attack at dawn");
+    }
+
+    #[test]
+    fn test_gcm() {
+        // Test case 2 from the GCM specification.
+        let mut aead = Gcm::new(&[0; 16]);
+        let mut data = vec![0u8; 16];
+        let tag = aead.seal(&[0; 12], b"", &mut data);
+        assert_eq!(bytes::hex_from_bytes(&data), "0388dace60b6a392f328c2b971b2fe78");
+        assert_eq!(bytes::hex_from_bytes(&tag), "ab6e47d42cec13bdf53a67b21257bddf");
+
+        let mut tampered = data.clone();
+        tampered[15] ^= 1;
+        assert!(aead.open(&[0; 12], b"", &mut tampered, &tag).is_err());
+        assert!(aead.open(&[0; 12], b"extra", &mut data.clone(), &tag).is_err());
+        aead.open(&[0; 12], b"", &mut data, &tag).unwrap();
+        assert_eq!(data, vec![0; 16]);
+
+        // Test cases 8 and 14, the same message under all-zero 192 and 256-bit keys.
+        let longer_keys = [
+            (24, "98e7247c07f0fe411c267e4384b0f600", "2ff58d80033927ab8ef4d4587514f0fb"),
+            (32, "cea7403d4d606b6e074ec5d3baf39d18", "d0d1c8a799996bf0265b98b5d48ab919"),
+        ];
+        for &(key_len, ciphertext, expected_tag) in longer_keys.iter() {
+            let mut data = vec![0u8; 16];
+            let tag = Gcm::new(&vec![0; key_len]).seal(&[0; 12], b"", &mut data);
+            assert_eq!(bytes::hex_from_bytes(&data), ciphertext);
+            assert_eq!(bytes::hex_from_bytes(&tag), expected_tag);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gcm_key_length() {
+        Gcm::new(&[0; 20]);
+    }
+
+    #[test]
+    fn test_chacha20_poly1305() {
+        // Test vector from RFC 8439, section 2.8.2.
+        let key = (0x80..0xa0).collect::<Vec<u8>>();
+        let nonce = bytes::hex_to_bytes("070000004041424344454647").unwrap();
+        let aad = bytes::hex_to_bytes("50515253c0c1c2c3c4c5c6c7").unwrap();
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+                          one tip for the future, sunscreen would be it.";
+
+        let mut aead = ChaCha20Poly1305::new(&key);
+        let mut data = plaintext.to_vec();
+        let tag = aead.seal(&nonce, &aad, &mut data);
+        assert_eq!(bytes::hex_from_bytes(&data[..16]), "d31a8d34648e60db7b86afbc53ef7ec2");
+        assert_eq!(bytes::hex_from_bytes(&tag), "1ae10b594f09e26a7e902ecbd0600691");
+
+        assert!(aead.open(&nonce, &aad[1..], &mut data, &tag).is_err());
+        aead.open(&nonce, &aad, &mut data, &tag).unwrap();
+        assert_eq!(data, &plaintext[..]);
+    }
+
+    #[test]
+    fn test_ccm() {
+        // Packet vector #1 from RFC 3610, section 8.
+        let key = bytes::hex_to_bytes("c0c1c2c3c4c5c6c7c8c9cacbcccdcecf").unwrap();
+        let nonce = bytes::hex_to_bytes("00000003020100a0a1a2a3a4a5").unwrap();
+        let header = bytes::hex_to_bytes("0001020304050607").unwrap();
+        let plaintext = bytes::hex_to_bytes("08090a0b0c0d0e0f101112131415161718191a1b1c1d1e")
+            .unwrap();
+
+        let mut ccm = Ccm::new(&key, 8, 13);
+        let mut data = plaintext.clone();
+        let tag = ccm.seal(&nonce, &header, &mut data);
+        assert_eq!(bytes::hex_from_bytes(&data),
+                   "588c979a61c663d2f066d0c2c0f989806d5f6b61dac384");
+        assert_eq!(bytes::hex_from_bytes(&tag), "17e8d12cfdf926e0");
+
+        assert!(ccm.open(&nonce, &header[1..], &mut data, &tag).is_err());
+        ccm.open(&nonce, &header, &mut data, &tag).unwrap();
+        assert_eq!(data, plaintext);
+    }
+}
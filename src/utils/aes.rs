@@ -0,0 +1,202 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use utils::cipher::BlockCipher;
+use utils::math::gf256;
+
+// Generated at compile time, so creating a cipher doesn't have to redo the field arithmetic.
+static SBOX: [u8; 256] = gf256::sbox();
+static INV_SBOX: [u8; 256] = gf256::inv_sbox();
+
+// Round constants for the key schedule: successive powers of x in GF(2^8). AES-128 uses all
+// ten, and the longer keys need fewer.
+static RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Pure-Rust AES with 128, 192 or 256-bit keys, following FIPS-197 directly. The S-boxes
+/// are generated from the field arithmetic in `math::gf256` rather than hardcoded. Written
+/// for clarity rather than speed, and not constant time.
+pub struct Aes {
+    round_keys: Vec<[u8; 16]>,
+}
+
+impl Aes {
+    /// Expand the given 16, 24 or 32-byte key. Panics if the key is any other length.
+    pub fn new(key: &[u8]) -> Self {
+        assert!(key.len() == 16 || key.len() == 24 || key.len() == 32,
+                "AES keys must be 16, 24 or 32 bytes long.");
+
+        // The key schedule works on 4-byte words; each round key is four consecutive words.
+        // A key of nk words gets nk + 6 rounds, plus the initial round key.
+        let nk = key.len() / 4;
+        let mut words = vec![[0u8; 4]; 4 * (nk + 7)];
+        for (word, chunk) in words.iter_mut().zip(key.chunks(4)) {
+            word.copy_from_slice(chunk);
+        }
+        for i in nk..words.len() {
+            let mut word = words[i - 1];
+            if i % nk == 0 {
+                word.rotate_left(1);
+            }
+            if i % nk == 0 || (nk > 6 && i % nk == 4) {
+                for byte in &mut word {
+                    *byte = SBOX[*byte as usize];
+                }
+            }
+            if i % nk == 0 {
+                word[0] ^= RCON[i / nk - 1];
+            }
+            for j in 0..4 {
+                word[j] ^= words[i - nk][j];
+            }
+            words[i] = word;
+        }
+
+        let round_keys = words.chunks(4)
+            .map(|chunk| {
+                let mut round_key = [0u8; 16];
+                for (i, word) in chunk.iter().enumerate() {
+                    round_key[i * 4..i * 4 + 4].copy_from_slice(word);
+                }
+                round_key
+            })
+            .collect();
+
+        Aes { round_keys: round_keys }
+    }
+
+    fn rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    fn add_round_key(&self, state: &mut [u8], round: usize) {
+        for (byte, key) in state.iter_mut().zip(self.round_keys[round].iter()) {
+            *byte ^= key;
+        }
+    }
+}
+
+impl BlockCipher for Aes {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&mut self, block: &mut [u8]) {
+        assert_eq!(block.len(), 16, "AES blocks must be 16 bytes long.");
+        let rounds = self.rounds();
+        self.add_round_key(block, 0);
+        for round in 1..=rounds {
+            for byte in block.iter_mut() {
+                *byte = SBOX[*byte as usize];
+            }
+            shift_rows(block);
+            if round != rounds {
+                mix_columns(block);
+            }
+            self.add_round_key(block, round);
+        }
+    }
+
+    fn decrypt_block(&mut self, block: &mut [u8]) {
+        assert_eq!(block.len(), 16, "AES blocks must be 16 bytes long.");
+        let rounds = self.rounds();
+        for round in (1..=rounds).rev() {
+            self.add_round_key(block, round);
+            if round != rounds {
+                inv_mix_columns(block);
+            }
+            inv_shift_rows(block);
+            for byte in block.iter_mut() {
+                *byte = INV_SBOX[*byte as usize];
+            }
+        }
+        self.add_round_key(block, 0);
+    }
+}
+
+// The state is stored column by column, so byte `r + 4 * c` is in row r and column c.
+// ShiftRows rotates row r left by r places.
+fn shift_rows(state: &mut [u8]) {
+    let mut old = [0u8; 16];
+    old.copy_from_slice(state);
+    for r in 0..4 {
+        for c in 0..4 {
+            state[r + 4 * c] = old[r + 4 * ((c + r) % 4)];
+        }
+    }
+}
+
+fn inv_shift_rows(state: &mut [u8]) {
+    let mut old = [0u8; 16];
+    old.copy_from_slice(state);
+    for r in 0..4 {
+        for c in 0..4 {
+            state[r + 4 * ((c + r) % 4)] = old[r + 4 * c];
+        }
+    }
+}
+
+// Multiply each column by the fixed polynomial 3x^3 + x^2 + x + 2, using the shortcut
+// from FIPS-197 section 5.1.3: each output byte is 2a_r + 3a_{r+1} + a_{r+2} + a_{r+3}.
+fn mix_columns(state: &mut [u8]) {
+    for column in state.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        let sum = a[0] ^ a[1] ^ a[2] ^ a[3];
+        for r in 0..4 {
+            column[r] = a[r] ^ sum ^ gf256::xtime(a[r] ^ a[(r + 1) % 4]);
+        }
+    }
+}
+
+// Multiply each column by the inverse polynomial 11x^3 + 13x^2 + 9x + 14.
+fn inv_mix_columns(state: &mut [u8]) {
+    for column in state.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for r in 0..4 {
+            column[r] = gf256::mul(a[r], 14) ^ gf256::mul(a[(r + 1) % 4], 11) ^
+                        gf256::mul(a[(r + 2) % 4], 13) ^ gf256::mul(a[(r + 3) % 4], 9);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    #[test]
+    fn test_fips_197() {
+        // Example vectors from FIPS-197 appendix C, one for each key length.
+        let plaintext = bytes::hex_to_bytes("00112233445566778899aabbccddeeff").unwrap();
+        let keys_and_ciphertexts = [
+            ("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            ("000102030405060708090a0b0c0d0e0f1011121314151617",
+             "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+             "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+        for &(key, ciphertext) in keys_and_ciphertexts.iter() {
+            let mut block = plaintext.clone();
+            let mut aes = Aes::new(&bytes::hex_to_bytes(key).unwrap());
+            aes.encrypt_block(&mut block);
+            assert_eq!(bytes::hex_from_bytes(&block), ciphertext);
+            aes.decrypt_block(&mut block);
+            assert_eq!(block, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_key_schedule() {
+        // Last round key for each of the keys in FIPS-197 appendix A.
+        let keys_and_round_keys = [
+            ("2b7e151628aed2a6abf7158809cf4f3c", "d014f9a8c9ee2589e13f0cc8b6630ca6"),
+            ("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b",
+             "e98ba06f448c773c8ecc720401002202"),
+            ("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4",
+             "fe4890d1e6188d0b046df344706c631e"),
+        ];
+        for &(key, round_key) in keys_and_round_keys.iter() {
+            let aes = Aes::new(&bytes::hex_to_bytes(key).unwrap());
+            assert_eq!(bytes::hex_from_bytes(aes.round_keys.last().unwrap()), round_key);
+        }
+    }
+}
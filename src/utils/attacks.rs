@@ -1,50 +1,572 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::ops::Range;
 use std::f64;
 use std::iter;
 
 use itertools::Itertools;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
 
-use utils::{bytes, crypto, text};
+use utils::{bytes, crypto, math, parallel, poly1305, text};
+use utils::digest::Digest;
+use utils::math::lattice;
+use utils::progress::Tracker;
+use utils::rsa::PublicKey;
+pub use utils::classical::caesar_shift;
+use utils::text::Scorer;
 
 /// Brute force an English string that has been XOR'd with a single byte.
 pub fn single_byte_brute_force(ciphertext: &[u8]) -> (f64, String, u8) {
-    let mut result = String::new();
+    single_byte_brute_force_with(ciphertext, &text::CosineScorer::default())
+}
+
+/// Brute force an English string that has been XOR'd with a single byte, using the given
+/// scorer to rank the candidate plaintexts.
+pub fn single_byte_brute_force_with<S>(ciphertext: &[u8], scorer: &S) -> (f64, String, u8)
+    where S: text::Scorer + ?Sized
+{
+    let mut result = Vec::new();
     let mut best_score = f64::MIN;
     let mut key = 0u8;
 
-    for byte in 0..255u8 {
-        let decoded_bytes = bytes::xor(ciphertext, iter::repeat(&byte));
-        if let Ok(decoded) = String::from_utf8(decoded_bytes) {
-            let score = text::score(&decoded);
-            if score > best_score {
-                best_score = score;
-                result = decoded;
-                key = byte
+    for byte in 0..=255u8 {
+        let decoded = bytes::xor(ciphertext, iter::repeat(&byte));
+        let score = scorer.score(&decoded);
+        if score > best_score {
+            best_score = score;
+            result = decoded;
+            key = byte
+        }
+    }
+
+    (best_score, bytes::to_string(&result), key)
+}
+
+/// Find the most likely repeating XOR key of the given size by breaking each column of the
+/// ciphertext, i.e. the bytes XOR'd with the same key byte, as single-byte XOR. The columns
+/// are broken in parallel when the `parallel` feature is enabled.
+pub fn repeating_key_brute_force(ciphertext: &[u8], keysize: usize) -> Vec<u8> {
+    repeating_key_brute_force_with(ciphertext, keysize, &text::CosineScorer::default())
+}
+
+/// Find the most likely repeating XOR key of the given size, using the given scorer to break
+/// each column. A column is every keysize-th byte of the plaintext, so only scorers that
+/// look at the frequencies of single bytes, rather than at words or n-grams, make sense here.
+pub fn repeating_key_brute_force_with<S>(ciphertext: &[u8],
+                                         keysize: usize,
+                                         scorer: &S)
+                                         -> Vec<u8>
+    where S: text::Scorer + Sync + ?Sized
+{
+    // Each column is only copied out when it's about to be broken.
+    parallel::map_iter(bytes::columns(ciphertext, keysize), |column| {
+        single_byte_brute_force_with(&column.cloned().collect::<Vec<u8>>(), scorer).2
+    })
+}
+
+/// Find the most likely key for a ciphertext encrypted with repeating-key XOR, trying key
+/// sizes from 2 to 40 bytes.
+pub fn find_repeating_key(ciphertext: &[u8]) -> Vec<u8> {
+    // Get most likely key size.
+    let keysize = get_keysizes(ciphertext, 2..41, 1)[0];
+
+    // Use the same brute force technique for breaking single-byte XOR encryption
+    // to determine the most likely key, one column at a time.
+    let key = repeating_key_brute_force(ciphertext, keysize);
+
+    // Frequency analysis looks at each column in isolation, so polish the key using a
+    // quadgram model of English, which takes neighboring letters into account.
+    refine_repeating_key(ciphertext, &key, &text::NgramModel::english(4))
+}
+
+/// Find the most likely key for a ciphertext encrypted with repeating-key XOR, using the
+/// given scorer to break each column, for plaintexts that don't look like English prose.
+/// See `repeating_key_brute_force_with` for which scorers suit. Unlike `find_repeating_key`,
+/// the key isn't refined afterwards, since that needs a scorer that looks at neighboring
+/// bytes; `refine_repeating_key` does it with whichever one fits the plaintext.
+pub fn find_repeating_key_with<S>(ciphertext: &[u8], scorer: &S) -> Vec<u8>
+    where S: text::Scorer + Sync + ?Sized
+{
+    let keysize = get_keysizes(ciphertext, 2..41, 1)[0];
+    repeating_key_brute_force_with(ciphertext, keysize, scorer)
+}
+
+/// A candidate decryption found by a brute-force attack, along with the key that produced it
+/// and its score under the scorer used to rank the candidates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub score: f64,
+    pub key: Vec<u8>,
+    pub plaintext: Vec<u8>,
+}
+
+// Sort candidates from best to worst score. Scorers can return NaN for text they have
+// nothing to say about (e.g. cosine similarity with no letters), so rank those last.
+fn rank(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+    for candidate in &mut candidates {
+        if candidate.score.is_nan() {
+            candidate.score = f64::NEG_INFINITY;
+        }
+    }
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    candidates
+}
+
+/// Try every single-byte XOR key on the ciphertext, returning all 256 candidates ranked from
+/// most to least English-like.
+pub fn rank_single_byte_xor<S>(ciphertext: &[u8], scorer: &S) -> Vec<Candidate>
+    where S: text::Scorer + ?Sized
+{
+    rank((0..=255u8)
+        .map(|byte| {
+            let plaintext = bytes::xor(ciphertext, iter::repeat(&byte));
+            Candidate {
+                score: scorer.score(&plaintext),
+                key: vec![byte],
+                plaintext: plaintext,
+            }
+        })
+        .collect())
+}
+
+/// Try the given number of most likely key sizes for a repeating-key XOR ciphertext,
+/// breaking and refining a key of each size, and rank the resulting decryptions using
+/// the scorer.
+pub fn rank_repeating_key_xor<S>(ciphertext: &[u8], limit: usize, scorer: &S) -> Vec<Candidate>
+    where S: text::Scorer + ?Sized
+{
+    rank(get_keysizes(ciphertext, 2..41, limit)
+        .into_iter()
+        .map(|size| {
+            let key = repeating_key_brute_force(ciphertext, size);
+            let key = refine_repeating_key(ciphertext, &key, scorer);
+            let plaintext = bytes::xor(ciphertext, key.iter().cycle());
+            Candidate {
+                score: scorer.score(&plaintext),
+                key: key,
+                plaintext: plaintext,
+            }
+        })
+        .collect())
+}
+
+/// Try all 26 shifts of a Caesar cipher, returning the candidates ranked from most to least
+/// English-like. The key of each candidate is the shift that was used to encrypt.
+pub fn rank_caesar<S>(ciphertext: &[u8], scorer: &S) -> Vec<Candidate>
+    where S: text::Scorer + ?Sized
+{
+    rank((0..26u8)
+        .map(|shift| {
+            let plaintext = caesar_shift(ciphertext, 26 - shift);
+            Candidate {
+                score: scorer.score(&plaintext),
+                key: vec![shift],
+                plaintext: plaintext,
+            }
+        })
+        .collect())
+}
+
+/// A line of a corpus found by `scan_corpus`, with its best single-byte XOR decryption.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusHit {
+    /// Index of the line in the corpus.
+    pub line: usize,
+    pub candidate: Candidate,
+}
+
+/// Find the lines of a corpus most likely to be English XOR'd against a single byte, like
+/// the needle in challenge 4's haystack. Every line is broken by brute force, in parallel
+/// when the `parallel` feature is enabled, and its best candidate is streamed back to be
+/// ranked, so only the `limit` best hits are ever held, however big the corpus. Hits with
+/// equal scores are ranked by line, so the result doesn't depend on which thread finished
+/// first.
+pub fn scan_corpus<S>(lines: &[Vec<u8>], scorer: &S, limit: usize) -> Vec<CorpusHit>
+    where S: text::Scorer + Sync + ?Sized
+{
+    let tracker = Tracker::new("corpus scan", Some(lines.len()));
+    let mut hits: Vec<CorpusHit> = Vec::with_capacity(limit + 1);
+    let mut done = 0;
+    let consume = |line, candidate| {
+        let hit = CorpusHit {
+            line: line,
+            candidate: candidate,
+        };
+        let position = hits.iter()
+            .position(|other| {
+                hit.candidate.score > other.candidate.score ||
+                hit.candidate.score == other.candidate.score && hit.line < other.line
+            })
+            .unwrap_or(hits.len());
+        if position < limit {
+            hits.insert(position, hit);
+            hits.truncate(limit);
+        }
+        done += 1;
+        let best = hits.first().map(|hit| bytes::to_string(&hit.candidate.plaintext));
+        tracker.update(done, best.as_ref().map_or("", |best| best.as_str()));
+    };
+    parallel::for_each(lines, |line| rank_single_byte_xor(line, scorer).swap_remove(0), consume);
+
+    let best = hits.first().map(|hit| bytes::to_string(&hit.candidate.plaintext));
+    tracker.finish(best.as_ref().map_or("", |best| best.as_str()));
+    hits
+}
+
+/// How far a paused brute-force search got: the number of keys at the start of the keyspace
+/// it has already ruled out. Resume it with the same keyspace to carry on from there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub tried: u64,
+}
+
+/// How a brute-force search ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Search<K> {
+    /// The first key in the keyspace that passed the test.
+    Found(K),
+    /// No key passed.
+    Exhausted,
+    /// The search used up its budget before finding a key, and can be resumed.
+    Paused(Checkpoint),
+}
+
+impl<K> Search<K> {
+    /// The key, if one was found.
+    pub fn found(self) -> Option<K> {
+        match self {
+            Search::Found(key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// A search through a keyspace for a key that passes a test, such as a seed that reproduces
+/// a token or a password that matches a hash. Keys are taken from the keyspace in chunks,
+/// and with the `parallel` feature each chunk is tested on rayon's thread pool. The search
+/// stops after the first chunk with a match in it, and returns the first match in keyspace
+/// order, however many threads took part. Progress is reported once per chunk, and a budget
+/// pauses a long search with a `Checkpoint` to resume from later.
+pub struct BruteForce {
+    attack: &'static str,
+    chunk_size: usize,
+    budget: Option<u64>,
+    start: u64,
+}
+
+impl BruteForce {
+    /// A search that reports its progress under the given name.
+    pub fn new(attack: &'static str) -> Self {
+        BruteForce {
+            attack: attack,
+            chunk_size: 4096,
+            budget: None,
+            start: 0,
+        }
+    }
+
+    /// Test keys in chunks of the given size. Bigger chunks keep more threads busy, and
+    /// smaller ones stop sooner after a match.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size must be nonzero.");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Pause after testing this many keys, if no key has passed by then.
+    pub fn with_budget(mut self, keys: u64) -> Self {
+        self.budget = Some(keys);
+        self
+    }
+
+    /// Skip the keys a paused search already ruled out.
+    pub fn resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.start = checkpoint.tried;
+        self
+    }
+
+    /// Search the keyspace for the first key that passes the test.
+    pub fn run<K, I, F>(&self, keyspace: I, test: F) -> Search<K>
+        where I: IntoIterator<Item = K>,
+              K: Send + Sync,
+              F: Fn(&K) -> bool + Sync + Send
+    {
+        let mut keys = keyspace.into_iter().skip(self.start as usize);
+        let total = match keys.size_hint() {
+            (low, Some(high)) if low == high => Some(self.start as usize + high),
+            _ => None,
+        };
+        let tracker = Tracker::new(self.attack, total);
+        let end = self.budget.map(|budget| self.start + budget);
+
+        let mut tried = self.start;
+        loop {
+            let size = end.map_or(self.chunk_size as u64,
+                                  |end| (end - tried).min(self.chunk_size as u64));
+            if size == 0 {
+                tracker.finish("paused");
+                return Search::Paused(Checkpoint { tried: tried });
+            }
+            let mut chunk = keys.by_ref().take(size as usize).collect::<Vec<_>>();
+            if chunk.is_empty() {
+                tracker.finish("exhausted");
+                return Search::Exhausted;
+            }
+            if let Some(i) = parallel::position(&chunk, &test) {
+                tracker.finish("found");
+                return Search::Found(chunk.swap_remove(i));
+            }
+            tried += chunk.len() as u64;
+            tracker.update(tried as usize, "");
+        }
+    }
+}
+
+/// Search a keyspace for the first key that passes the test, with the default settings of
+/// `BruteForce`.
+pub fn brute_force<K, I, F>(keyspace: I, test: F) -> Option<K>
+    where I: IntoIterator<Item = K>,
+          K: Send + Sync,
+          F: Fn(&K) -> bool + Sync + Send
+{
+    BruteForce::new("brute force").run(keyspace, test).found()
+}
+
+/// One placement of a crib in a crib-dragging attack: if either of the two ciphertexts in
+/// `pair` has the crib at `offset`, then the other one has `guess` there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CribMatch {
+    pub score: f64,
+    pub pair: (usize, usize),
+    pub offset: usize,
+    pub guess: Vec<u8>,
+}
+
+/// Drag a crib, a word or phrase guessed to be in one of the plaintexts, across ciphertexts
+/// encrypted under the same keystream, as with the fixed-nonce CTR of challenge 19. The
+/// keystream cancels out in the XOR of two ciphertexts, so XORing in the crib at the right
+/// offset reveals the other plaintext there. Every offset of every pair is tried, and the
+/// placements are ranked by how English-like the revealed text is, best first.
+pub fn crib_drag(ciphertexts: &[Vec<u8>], crib: &[u8]) -> Vec<CribMatch> {
+    crib_drag_with(ciphertexts, crib, &text::ChiSquaredScorer::default())
+}
+
+/// Crib-drag with the given scorer. Guesses all have the same length as the crib, so
+/// scorers that only compare texts of equal length can be used.
+pub fn crib_drag_with<S>(ciphertexts: &[Vec<u8>], crib: &[u8], scorer: &S) -> Vec<CribMatch>
+    where S: text::Scorer + ?Sized
+{
+    let mut matches = Vec::new();
+    if crib.is_empty() {
+        return matches;
+    }
+    for (i, j) in (0..ciphertexts.len()).tuple_combinations() {
+        let xored = bytes::xor(&ciphertexts[i], &ciphertexts[j]);
+        for (offset, window) in xored.windows(crib.len()).enumerate() {
+            let guess = bytes::xor(window, crib);
+            matches.push(CribMatch {
+                score: scorer.score(&guess),
+                pair: (i, j),
+                offset: offset,
+                guess: guess,
+            });
+        }
+    }
+    for candidate in &mut matches {
+        if candidate.score.is_nan() {
+            candidate.score = f64::NEG_INFINITY;
+        }
+    }
+    matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    matches
+}
+
+/// How strongly the XOR of two ciphertexts suggests they share a keystream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeystreamReuse {
+    pub pair: (usize, usize),
+    /// The number of bytes the two ciphertexts have in common.
+    pub overlap: usize,
+    /// Standard deviations above what unrelated keystreams would give. Below about 4, the
+    /// pair is probably unrelated.
+    pub z_score: f64,
+}
+
+// Whether a byte of the XOR of two ciphertexts looks like the XOR of two bytes of text:
+// below 0x20, as for two letters of the same case or equal bytes, or from 0x40 to 0x5f, as
+// for a letter and a space or punctuation. A quarter of random bytes look like this.
+fn looks_like_text_xor(byte: u8) -> bool {
+    byte < 0x20 || (byte >= 0x40 && byte < 0x60)
+}
+
+/// Score every pair of ciphertexts for signs of a shared keystream, as in a two-time pad or
+/// CTR with a fixed nonce. The keystream cancels in their XOR, leaving the XOR of the
+/// plaintexts, which for text is dominated by a few ranges of byte values. Returns the
+/// pairs from most to least likely to share a keystream.
+pub fn keystream_reuse_scores(ciphertexts: &[Vec<u8>]) -> Vec<KeystreamReuse> {
+    let expected = 0.25;
+    let mut scores = (0..ciphertexts.len())
+        .tuple_combinations()
+        .map(|(i, j)| {
+            let xored = bytes::xor(&ciphertexts[i], &ciphertexts[j]);
+            let n = xored.len() as f64;
+            let hits = xored.iter().filter(|&&byte| looks_like_text_xor(byte)).count() as f64;
+            let z_score = if xored.is_empty() {
+                0.0
+            } else {
+                (hits - n * expected) / (n * expected * (1.0 - expected)).sqrt()
+            };
+            KeystreamReuse {
+                pair: (i, j),
+                overlap: xored.len(),
+                z_score: z_score,
+            }
+        })
+        .collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.z_score.partial_cmp(&a.z_score).unwrap());
+    scores
+}
+
+/// Group ciphertexts by the keystream they appear to share, linking every pair whose
+/// z-score is at least the threshold. Each group lists indices into `ciphertexts` in order,
+/// and ciphertexts with no partner get a group of their own. A good first step before
+/// running the fixed-nonce CTR attacks, which assume a single keystream.
+pub fn group_by_keystream(ciphertexts: &[Vec<u8>], threshold: f64) -> Vec<Vec<usize>> {
+    // Label each ciphertext with the lowest index in its group, merging labels as links
+    // are found.
+    let mut labels = (0..ciphertexts.len()).collect::<Vec<_>>();
+    for score in keystream_reuse_scores(ciphertexts) {
+        if score.z_score < threshold {
+            break;
+        }
+        let (a, b) = (labels[score.pair.0], labels[score.pair.1]);
+        let (keep, replace) = (a.min(b), a.max(b));
+        for label in &mut labels {
+            if *label == replace {
+                *label = keep;
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, &label) in labels.iter().enumerate() {
+        if label == i {
+            groups.push(vec![i]);
+        } else {
+            groups.iter_mut().find(|group| group[0] == label).unwrap().push(i);
+        }
+    }
+    groups
+}
+
+/// Refine a candidate key for repeating-key XOR by hill climbing. Each byte of the key is
+/// replaced with whichever value makes the full decrypted text score best, repeating until
+/// no single-byte change improves the score. Intended for use with a scorer that considers
+/// context, such as an n-gram model, to fix key bytes that frequency analysis got wrong
+/// because the ciphertext was too short.
+pub fn refine_repeating_key<S>(ciphertext: &[u8], key: &[u8], scorer: &S) -> Vec<u8>
+    where S: text::Scorer + ?Sized
+{
+    let mut key = key.to_vec();
+    let mut best_score = scorer.score(&bytes::xor(ciphertext, key.iter().cycle()));
+    let mut improved = true;
+
+    // The number of passes isn't known in advance, so each key byte tried is one step.
+    let tracker = Tracker::new("repeating-key XOR refinement", None);
+    let mut steps = 0;
+
+    while improved {
+        improved = false;
+        for i in 0..key.len() {
+            let original = key[i];
+            let mut best_byte = original;
+            for byte in 0..=255u8 {
+                key[i] = byte;
+                let score = scorer.score(&bytes::xor(ciphertext, key.iter().cycle()));
+                if score > best_score {
+                    best_score = score;
+                    best_byte = byte;
+                }
             }
+            key[i] = best_byte;
+            improved |= best_byte != original;
+            steps += 1;
+            tracker.update(steps, &bytes::to_string(&key));
         }
     }
 
-    (best_score, result, key)
+    tracker.finish(&bytes::to_string(&key));
+    key
+}
+
+/// Which chunks of the ciphertext `get_keysizes_with` compares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// The chunks at the start of the ciphertext.
+    Head,
+    /// Chunks spread evenly across the whole ciphertext, which is less easily fooled by a
+    /// repetitive opening.
+    Spread,
+}
+
+/// Options for key size detection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeysizeConfig {
+    /// The number of chunks of each size to compare pairwise. Time grows with its square.
+    pub chunks: usize,
+    pub sampling: Sampling,
+    /// If set, this many of the sizes with the lowest Hamming distances are broken and
+    /// re-ranked by the fraction of real words in their decryptions. Unlike letter
+    /// frequencies, that doesn't reward the overfitting that longer keys allow. Sizes that
+    /// decrypt equally well, like multiples of the real key size, are ranked smallest first.
+    pub trial_decrypt: Option<usize>,
 }
 
+/// The classic challenge 6 settings: the first four chunks, ranked by distance alone.
+pub const KEYSIZE_DEFAULT: KeysizeConfig = KeysizeConfig {
+    chunks: 4,
+    sampling: Sampling::Head,
+    trial_decrypt: None,
+};
+
 /// Determine the most likely key sizes for a repeating-key XOR encoded ciphertext.
 /// Returns a vector of potential key sizes, sorted in ascending order by the
 /// mean normalized Hamming distance between chunks of that size in the ciphertext.
 pub fn get_keysizes(ciphertext: &[u8], range: Range<usize>, limit: usize) -> Vec<usize> {
+    get_keysizes_with(ciphertext, range, limit, &KEYSIZE_DEFAULT)
+}
+
+/// Determine the most likely key sizes for a repeating-key XOR encoded ciphertext, with the
+/// given options. Sizes with fewer than two whole chunks in the ciphertext can't be judged,
+/// and are left out.
+pub fn get_keysizes_with(ciphertext: &[u8],
+                         range: Range<usize>,
+                         limit: usize,
+                         config: &KeysizeConfig)
+                         -> Vec<usize> {
     let mut sizes = Vec::new();
 
     // Check key sizes in given size range.
     for size in range {
-        // Maximum number of chunks to test. Overall space/time usage varies factorially
-        // with this parameter, so large values may cause this function to take a long time.
-        let num_chunks = 4;
+        // A short final chunk can't be compared with the others, so only full chunks are
+        // used.
+        let full = ciphertext.len() / size;
+        let count = config.chunks.min(full);
+        if count < 2 {
+            continue;
+        }
+        let indices = match config.sampling {
+            Sampling::Head => (0..count).collect::<Vec<_>>(),
+            Sampling::Spread => (0..count).map(|i| i * full / count).collect(),
+        };
 
         // Get Hamming distances of pairs of chunks of the given size.
-        let dists = ciphertext.chunks(size)
-            .take(num_chunks)
+        let dists = indices.iter()
+            .map(|&i| &ciphertext[i * size..(i + 1) * size])
             .combinations(2)
-            .map(|pair| bytes::hamming_dist(pair[0], pair[1]) as f64 / size as f64)
+            .map(|pair| bytes::hamming_dist(pair[0], pair[1]).unwrap() as f64 / size as f64)
             .collect::<Vec<f64>>();
 
         let avg = dists.iter().sum::<f64>() / dists.len() as f64;
@@ -54,7 +576,131 @@ pub fn get_keysizes(ciphertext: &[u8], range: Range<usize>, limit: usize) -> Vec
     // Floats are not (in general) totally ordered, so we need to use a partially ordered sort,
     // but assume that the actual values we get are totally ordered since we don't expect NaNs.
     sizes.sort_by(|&(a, _), &(b, _)| a.partial_cmp(&b).unwrap());
-    sizes.into_iter().map(|(_, size)| size).take(limit).collect()
+    debug!("key size scores: {:?}", &sizes[..limit.min(sizes.len())]);
+    let mut sizes = sizes.into_iter().map(|(_, size)| size).collect::<Vec<_>>();
+
+    if let Some(trials) = config.trial_decrypt {
+        let trials = trials.min(sizes.len());
+        let scorer = text::WordlistScorer::default();
+        let mut scored = sizes[..trials]
+            .iter()
+            .map(|&size| {
+                let key = repeating_key_brute_force(ciphertext, size);
+                let score = scorer.score(&bytes::xor(ciphertext, key.iter().cycle()));
+                (if score.is_nan() { f64::NEG_INFINITY } else { score }, size)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|&(a, x), &(b, y)| b.partial_cmp(&a).unwrap().then(x.cmp(&y)));
+        debug!("key size trial decryption scores: {:?}", scored);
+        for (slot, (_, size)) in sizes.iter_mut().zip(scored) {
+            *slot = size;
+        }
+    }
+
+    sizes.truncate(limit);
+    sizes
+}
+
+/// The index of coincidence of English text as bytes, with spaces and mixed case, roughly.
+pub const ENGLISH_IC: f64 = 0.067;
+
+// The mean index of coincidence of the columns of the ciphertext for the given key size.
+fn column_coincidence(ciphertext: &[u8], size: usize) -> f64 {
+    bytes::columns(ciphertext, size)
+        .map(|column| math::index_of_coincidence(&column.cloned().collect::<Vec<u8>>()))
+        .sum::<f64>() / size as f64
+}
+
+/// Rank key sizes for a repeating-key XOR ciphertext by the mean index of coincidence of
+/// its columns. Each column of the right size is the plaintext XOR'd with a single byte,
+/// which leaves the index as high as the plaintext's, whatever language or format that is,
+/// while wrong sizes mix key bytes and flatten it. Multiples of the right size do just as
+/// well, so sizes within 25% of the best index come first, smallest first, and the rest
+/// follow from highest index to lowest.
+pub fn keysizes_by_coincidence(ciphertext: &[u8],
+                               range: Range<usize>,
+                               limit: usize)
+                               -> Vec<usize> {
+    let mut sizes = range.filter(|&size| ciphertext.len() >= 2 * size)
+        .map(|size| (column_coincidence(ciphertext, size), size))
+        .collect::<Vec<_>>();
+    sizes.sort_by(|&(a, _), &(b, _)| b.partial_cmp(&a).unwrap());
+    debug!("key size coincidence: {:?}", &sizes[..limit.min(sizes.len())]);
+
+    let best = sizes.first().map_or(0.0, |&(ic, _)| ic);
+    let (mut top, rest): (Vec<_>, Vec<_>) =
+        sizes.into_iter().partition(|&(ic, _)| ic >= 0.75 * best);
+    top.sort_by_key(|&(_, size)| size);
+    top.into_iter().chain(rest).map(|(_, size)| size).take(limit).collect()
+}
+
+/// Friedman's estimate of the key length of a repeating-key XOR ciphertext. A key of
+/// length L makes the ciphertext's index of coincidence about 1/L of the plaintext's, such
+/// as `ENGLISH_IC`, plus (L - 1)/L of the index between bytes encrypted with different key
+/// bytes, so that's solved for L. That last index is 1/256 for random keys, but printable
+/// keys map letters into overlapping ranges and push it well above that. It's only an
+/// estimate, but unlike the other methods it isn't drawn to multiples of the key length.
+pub fn friedman_estimate(ciphertext: &[u8], plaintext_ic: f64, cross_ic: f64) -> f64 {
+    (plaintext_ic - cross_ic) / (math::index_of_coincidence(ciphertext) - cross_ic)
+}
+
+// The fraction of positions where the ciphertext matches itself the given distance later.
+fn coincidence_at(ciphertext: &[u8], distance: usize) -> f64 {
+    let matches = ciphertext.iter().zip(&ciphertext[distance..]).filter(|&(a, b)| a == b).count();
+    matches as f64 / (ciphertext.len() - distance) as f64
+}
+
+/// Rank key sizes by combining Hamming distance, column coincidence and the Friedman
+/// estimate. Each method ranks every size, and sizes are ordered by their median rank, so
+/// one method going badly wrong, as the Friedman estimate does on short inputs, can't
+/// outvote the other two. Ties go to the best total rank, then the smaller size. The
+/// indices the Friedman estimate needs are measured on the ciphertext, using the best size
+/// by coincidence: the plaintext's from its columns, and the one between key bytes from
+/// bytes less than that size apart. So nothing here assumes the plaintext is English or
+/// the key is random.
+pub fn vote_keysizes(ciphertext: &[u8], range: Range<usize>, limit: usize) -> Vec<usize> {
+    let sizes = range.filter(|&size| ciphertext.len() >= 2 * size).collect::<Vec<_>>();
+    if sizes.is_empty() {
+        return Vec::new();
+    }
+    let (low, high) = (sizes[0], sizes[sizes.len() - 1] + 1);
+
+    let by_coincidence = keysizes_by_coincidence(ciphertext, low..high, sizes.len());
+    let best = by_coincidence[0];
+    let plaintext_ic = column_coincidence(ciphertext, best);
+    let cross_ic = if best > 1 {
+        (1..best).map(|distance| coincidence_at(ciphertext, distance)).sum::<f64>() /
+        (best - 1) as f64
+    } else {
+        1.0 / 256.0
+    };
+    let estimate = friedman_estimate(ciphertext, plaintext_ic, cross_ic);
+    debug!("friedman estimate: {}", estimate);
+    let mut by_friedman = sizes.clone();
+    // The estimate is NaN if the ciphertext is no more repetitive than the key bytes are
+    // with each other, in which case it has no opinion.
+    by_friedman.sort_by(|&a, &b| {
+        let (a, b) = ((a as f64 - estimate).abs(), (b as f64 - estimate).abs());
+        a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    });
+    let by_hamming = get_keysizes(ciphertext, low..high, sizes.len());
+
+    // Each size's ranks under the three methods, sorted.
+    let mut ranks = HashMap::new();
+    for ranking in &[by_hamming, by_coincidence, by_friedman] {
+        for (rank, &size) in ranking.iter().enumerate() {
+            ranks.entry(size).or_insert_with(Vec::new).push(rank);
+        }
+    }
+    for size_ranks in ranks.values_mut() {
+        size_ranks.sort();
+    }
+    let mut ranked = sizes;
+    ranked.sort_by_key(|size| (ranks[size][1], ranks[size].iter().sum::<usize>(), *size));
+    debug!("key size ranks: {:?}",
+           ranked.iter().take(limit).map(|size| (size, &ranks[size])).collect::<Vec<_>>());
+    ranked.truncate(limit);
+    ranked
 }
 
 /// Attempt to detect the use of an ECB mode block cipher by looking for repeated blocks
@@ -70,62 +716,1146 @@ pub fn max_repeated_blocks(bytes: &[u8], block_size: usize) -> i32 {
     counts.values().cloned().max().unwrap_or(0)
 }
 
-/// Given a ciphertext and a block size, returns true if the ciphertext appears to be
-/// encrypted using ECB mode. This is just a simple heuristic that looks for repeated
-/// blocks, and as such doesn't guarantee that ECB mode was used. The check will
-/// fail if the plaintext didn't have any repeated blocks to begin with.
-pub fn detect_ecb(bytes: &[u8], block_size: usize) -> bool {
-    max_repeated_blocks(bytes, block_size) > 1
+/// The result of scanning a ciphertext for repeated blocks, as a sign of ECB mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EcbScore {
+    /// Byte offset of the block alignment with the most repeated blocks.
+    pub alignment: usize,
+    /// Number of blocks at that alignment that are repeats of an earlier block.
+    pub repeats: usize,
+    /// Byte offsets of every block at that alignment whose value occurs more than once.
+    pub offsets: Vec<usize>,
+    /// Probability that the observed repetition is not just a chance collision between
+    /// blocks of random-looking ciphertext.
+    pub confidence: f64,
+}
+
+impl EcbScore {
+    /// Returns true if the ciphertext appears to be encrypted using ECB mode.
+    pub fn is_ecb(&self) -> bool {
+        self.confidence > 0.99
+    }
+}
+
+/// Given a ciphertext and a block size, score how likely the ciphertext is to have been
+/// encrypted using ECB mode. This is still a heuristic that looks for repeated blocks, and
+/// as such will fail if the plaintext didn't have any repeated blocks to begin with, but
+/// every alignment is checked, so ciphertexts with a header or IV of arbitrary length
+/// prepended to them will still be detected.
+pub fn detect_ecb(bytes: &[u8], block_size: usize) -> EcbScore {
+    let mut best = (0, 0, Vec::new(), 0);
+
+    for alignment in 0..block_size {
+        let blocks = bytes.get(alignment..)
+            .unwrap_or(&[])
+            .chunks(block_size)
+            .filter(|block| block.len() == block_size)
+            .collect::<Vec<_>>();
+
+        let mut counts = HashMap::new();
+        for block in &blocks {
+            *counts.entry(*block).or_insert(0) += 1;
+        }
+
+        let repeats = counts.values().map(|count| count - 1).sum::<usize>();
+        if repeats > best.1 {
+            let offsets = blocks.iter()
+                .enumerate()
+                .filter(|&(_, block)| counts[block] > 1)
+                .map(|(i, _)| alignment + i * block_size)
+                .collect();
+            best = (alignment, repeats, offsets, blocks.len());
+        }
+    }
+
+    let (alignment, repeats, offsets, num_blocks) = best;
+
+    // By the birthday bound, the expected number of colliding pairs among n random blocks
+    // of b bits is about n(n - 1) / 2^(b + 1). Since we checked every alignment, multiply
+    // by the number of alignments. Treating the number of chance collisions as Poisson
+    // distributed, the confidence is the probability of seeing fewer repeats than we did.
+    let pairs = (num_blocks * num_blocks.saturating_sub(1)) as f64 / 2.0;
+    let expected = block_size as f64 * pairs / 2f64.powi(8 * block_size as i32);
+    let confidence = if repeats == 0 {
+        0.0
+    } else {
+        1.0 - poisson_tail(expected, repeats)
+    };
+
+    EcbScore {
+        alignment: alignment,
+        repeats: repeats,
+        offsets: offsets,
+        confidence: confidence,
+    }
+}
+
+/// One ciphertext from a batch scanned for ECB mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EcbMatch {
+    /// Index of the ciphertext in the batch.
+    pub line: usize,
+    pub score: EcbScore,
+    /// The distinct block values that repeat at the best alignment, in order of first
+    /// occurrence.
+    pub repeated: Vec<Vec<u8>>,
+}
+
+/// Score every ciphertext in a batch, such as the lines of a dataset, for signs of ECB mode,
+/// in parallel when the `parallel` feature is enabled. Returns all of them ranked from most
+/// to least likely to be ECB, by confidence and then by number of repeats, with ties left
+/// in their original order.
+pub fn detect_ecb_batch(lines: &[Vec<u8>], block_size: usize) -> Vec<EcbMatch> {
+    let indexed = lines.iter().enumerate().collect::<Vec<_>>();
+    let mut matches = parallel::map(&indexed, |&(line, ciphertext)| {
+        let score = detect_ecb(ciphertext, block_size);
+        let mut repeated: Vec<Vec<u8>> = Vec::new();
+        for &offset in &score.offsets {
+            let block = &ciphertext[offset..offset + block_size];
+            if !repeated.iter().any(|seen| seen.as_slice() == block) {
+                repeated.push(block.to_vec());
+            }
+        }
+        EcbMatch {
+            line: line,
+            score: score,
+            repeated: repeated,
+        }
+    });
+    matches.sort_by(|a, b| {
+        b.score
+            .confidence
+            .partial_cmp(&a.score.confidence)
+            .unwrap_or(Ordering::Equal)
+            .then(b.score.repeats.cmp(&a.score.repeats))
+    });
+    matches
+}
+
+// Probability that a Poisson random variable with the given mean is at least k.
+fn poisson_tail(mean: f64, k: usize) -> f64 {
+    // Sum the upper tail directly to avoid cancellation when the mean is tiny.
+    let mut term = (-mean).exp();
+    for i in 1..(k + 1) {
+        term *= mean / i as f64;
+    }
+    let mut total = 0.0;
+    for i in (k + 1)..(k + 100) {
+        total += term;
+        term *= mean / i as f64;
+    }
+    total.min(1.0)
+}
+
+/// What a byte string looks like it is, as guessed by `classify`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+    /// A block cipher in ECB mode, which leaves repeated blocks.
+    Ecb,
+    /// Random-looking and a whole number of blocks, as from CBC or another padded block mode.
+    BlockMode,
+    /// Random-looking but not a whole number of blocks, as from CTR, a stream cipher, or
+    /// compression.
+    StreamMode,
+    /// Printable text drawn from the alphabet of hex, base32 or base64. Decode it first.
+    Encoded,
+    /// Neither random-looking nor encoded, such as plaintext or structured binary data.
+    Plain,
+}
+
+/// The guess made by `classify`, along with the statistics it was based on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Classification {
+    pub class: Class,
+    /// Shannon entropy in bits per byte.
+    pub entropy: f64,
+    /// Correlation between each byte and the next, near zero for random data.
+    pub serial_correlation: f64,
+    /// Fraction of bytes that are printable ASCII or whitespace.
+    pub printable: f64,
+    pub ecb: EcbScore,
 }
 
-/// Given a block cipher encryption function, detect the block size of the cipher.
-pub fn detect_block_size<F>(encrypt: &mut F) -> Option<usize>
+/// Guess what produced a byte string, generalizing `detect_ecb`. Text is classified as
+/// encoded or plain by its alphabet. Otherwise, repeated blocks mean ECB, and data that is
+/// close to the maximum entropy for its length with no serial correlation is taken to be
+/// ciphertext, from a block mode if its length is a multiple of the block size, which must
+/// be nonzero.
+pub fn classify(data: &[u8], block_size: usize) -> Result<Classification, &'static str> {
+    if block_size == 0 {
+        return Err("Block size must be nonzero.");
+    }
+    let is_printable = |byte: u8| (byte >= 0x20 && byte < 0x7f) || byte.is_ascii_whitespace();
+    let is_encoded = |byte: u8| {
+        byte.is_ascii_alphanumeric() || b"+/=-_".contains(&byte) || byte.is_ascii_whitespace()
+    };
+    let printable = if data.is_empty() {
+        1.0
+    } else {
+        data.iter().filter(|&&byte| is_printable(byte)).count() as f64 / data.len() as f64
+    };
+    let entropy = math::entropy(data);
+    let serial_correlation = math::serial_correlation(data);
+    let ecb = detect_ecb(data, block_size);
+
+    // Random bytes have an entropy a little under log2 of the number of distinct values
+    // they could have taken, and a serial correlation within a few multiples of 1/sqrt(n).
+    let n = data.len() as f64;
+    let max_entropy = n.min(256.0).log2();
+    let random = entropy >= 0.9 * max_entropy && serial_correlation.abs() < 4.0 / n.sqrt();
+
+    let class = if data.is_empty() {
+        Class::Plain
+    } else if printable == 1.0 && data.iter().all(|&byte| is_encoded(byte)) &&
+              !data.contains(&b' ') {
+        Class::Encoded
+    } else if printable > 0.95 {
+        Class::Plain
+    } else if ecb.is_ecb() {
+        Class::Ecb
+    } else if !random {
+        Class::Plain
+    } else if data.len() % block_size == 0 {
+        Class::BlockMode
+    } else {
+        Class::StreamMode
+    };
+
+    Ok(Classification {
+        class: class,
+        entropy: entropy,
+        serial_correlation: serial_correlation,
+        printable: printable,
+        ecb: ecb,
+    })
+}
+
+/// A single block of ciphertext, labeled with its position in the ciphertext and the
+/// plaintext block that produced it (if the plaintext is known at that position).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabeledBlock<'a> {
+    pub index: usize,
+    pub ciphertext: &'a [u8],
+    pub plaintext: Option<&'a [u8]>,
+}
+
+/// Split a ciphertext into blocks, pairing each block with the corresponding block of
+/// a known (or reconstructed) plaintext. Blocks past the end of the given plaintext,
+/// such as a trailing block of padding, are labeled with None.
+pub fn label_blocks<'a>(ciphertext: &'a [u8],
+                        plaintext: &'a [u8],
+                        block_size: usize)
+                        -> Vec<LabeledBlock<'a>> {
+    let mut plaintext_blocks = plaintext.chunks(block_size);
+    ciphertext.chunks(block_size)
+        .enumerate()
+        .map(|(index, block)| {
+            LabeledBlock {
+                index: index,
+                ciphertext: block,
+                plaintext: plaintext_blocks.next(),
+            }
+        })
+        .collect()
+}
+
+/// Craft an input that places the given attacker-controlled data at the start of a block,
+/// assuming the oracle prepends `prefix_len` bytes to the input before encryption. The data
+/// is preceded by the minimum number of filler bytes needed to complete the prefix's last
+/// block. Returns the crafted input and the index of the block where the data begins.
+pub fn align_input(prefix_len: usize,
+                   block_size: usize,
+                   filler: u8,
+                   data: &[u8])
+                   -> (Vec<u8>, usize) {
+    let fill = (block_size - prefix_len % block_size) % block_size;
+    let mut input = vec![filler; fill];
+    input.extend(data);
+    (input, (prefix_len + fill) / block_size)
+}
+
+/// Reassemble a ciphertext from the blocks at the given indices, in the given order.
+/// Indices may repeat, which allows a single block to be pasted in multiple places.
+pub fn select_blocks(ciphertext: &[u8], block_size: usize, indices: &[usize]) -> Vec<u8> {
+    indices.iter()
+        .flat_map(|&i| bytes::nth_block(ciphertext, block_size, i).unwrap().iter().cloned())
+        .collect()
+}
+
+/// Given an ECB encryption function that may prepend an unknown, fixed prefix to its input,
+/// detect the block size of the cipher and the length of the prefix. Returns a tuple of
+/// (block size, prefix length), or None if no plausible block size was found.
+pub fn detect_block_size<F>(encrypt: &mut F) -> Option<(usize, usize)>
+    where F: FnMut(&[u8]) -> Vec<u8>
+{
+    // If the prefix happens to end with copies of the filler byte, those bytes will be
+    // mistaken for part of our input, so the prefix will appear to be shorter than it
+    // actually is. The last byte of the prefix can't match both fillers, so one of the
+    // two results is guaranteed to be correct.
+    let first = detect_block_layout(encrypt, 0x00);
+    let second = detect_block_layout(encrypt, 0xFF);
+    match (first, second) {
+        (Some((size_a, prefix_a)), Some((size_b, prefix_b))) if size_a == size_b => {
+            Some((size_a, prefix_a.max(prefix_b)))
+        }
+        _ => None,
+    }
+}
+
+// Feed the encryption function increasingly long runs of the filler byte. As soon as the
+// run is long enough to complete the last block of the prefix and then fill two whole
+// blocks, the ciphertext will contain a pair of identical consecutive blocks, whose
+// position reveals both the block size and the length of the prefix.
+fn detect_block_layout<F>(encrypt: &mut F, filler: u8) -> Option<(usize, usize)>
     where F: FnMut(&[u8]) -> Vec<u8>
 {
-    let mut input = vec![0u8];
-    let mut encrypted = encrypt(&input);
+    // Very small block sizes are excluded, since pairs of identical consecutive bytes are
+    // likely to occur in the ciphertext by chance.
+    let (min_size, max_size) = (4, 64);
 
-    // Find an input size such that the prefix of the ciphertext remains fixed. This indicates
-    // that we've filled the first block on the previous iteration.
-    for i in 1..40 {
-        let old = encrypted;
-        input.push(0);
-        encrypted = encrypt(&input);
-        if encrypted[0..i] == old[0..i] {
-            return Some(i);
+    for len in (2 * min_size)..(3 * max_size) {
+        let encrypted = encrypt(&vec![filler; len]);
+        for size in (min_size..(max_size + 1)).take_while(|size| 2 * size <= len) {
+            let blocks = encrypted.chunks(size).collect::<Vec<_>>();
+            if let Some(i) = (1..blocks.len()).position(|i| blocks[i - 1] == blocks[i]) {
+                // Number of filler bytes that went towards completing the prefix's last block.
+                let fill = len - 2 * size;
+                return (i * size).checked_sub(fill).map(|prefix_len| (size, prefix_len));
+            }
         }
     }
 
     None
 }
 
-/// Given an ECB encryption function with known block size that appends an unknown suffix
-/// to its input prior to encryption, use knowledge of the block size and brute force to
-/// decrypt the suffix one byte at a time without knowlege of the key.
-pub fn decrypt_ecb_suffix<F>(encrypt: &mut F, block_size: usize, num_blocks: usize) -> String
+/// Given an ECB encryption function with known block size that prepends a prefix of known
+/// length and appends an unknown suffix to its input prior to encryption, use knowledge of
+/// the block size and brute force to decrypt the suffix one byte at a time without knowlege
+/// of the key. Fails if there are no blocks of suffix to decrypt.
+pub fn decrypt_ecb_suffix<F>(encrypt: &mut F,
+                             block_size: usize,
+                             prefix_len: usize,
+                             num_blocks: usize)
+                             -> Result<String, &'static str>
     where F: FnMut(&[u8]) -> Vec<u8>
 {
     let mut decrypted = Vec::new();
     let len = block_size * num_blocks;
+    let last = len.checked_sub(1).ok_or("No suffix to decrypt.")?;
+
+    // Pad out the last block of the prefix so that our input starts on a block boundary.
+    let fill = (block_size - prefix_len % block_size) % block_size;
+    let start = prefix_len + fill;
+    let end = start + len;
+
+    let tracker = Tracker::new("byte-at-a-time ECB decryption", Some(last));
 
     for i in 1..len {
-        let mut pad = vec![0u8; len - i];
+        let mut pad = vec![0u8; fill + len - i];
         let encrypted = encrypt(&pad);
         pad.extend(&decrypted);
 
         // Try all possibilities for the last byte in the first block.
-        for byte in 0..255u8 {
+        for byte in 0..=255u8 {
             pad.push(byte);
             let test = encrypt(&pad);
-            if test[0..len] == encrypted[0..len] {
+            if test[start..end] == encrypted[start..end] {
                 // Found matching byte!
                 decrypted.push(byte);
                 break;
             }
             pad.pop();
         }
+        tracker.update(i, &bytes::to_string(&decrypted));
     }
 
     // Potentially strip PKCS#7 padding if any is present.
-    bytes::to_string(&crypto::strip_pkcs7(&decrypted).unwrap_or(decrypted))
+    let result = bytes::to_string(&crypto::strip_pkcs7(&decrypted).unwrap_or(decrypted));
+    tracker.finish(&result);
+    Ok(result)
+}
+
+/// The same attack as `decrypt_ecb_suffix`, with far fewer queries, for oracles that are
+/// slow to ask. Rather than one query per guess, each byte takes at most one query, which
+/// encrypts all 256 guesses for it side by side, and the answers go into a single
+/// dictionary of encrypted blocks that later bytes look in before asking again. The block
+/// holding each unknown byte comes from one of `block_size` ciphertexts, one per amount of
+/// filler, which are only requested once; they also give away the length of the suffix, so
+/// unlike `decrypt_ecb_suffix` this needs no block count, and doesn't guess at padding.
+/// Fails if the ciphertexts don't behave like padded ECB, for example if they never grow.
+pub fn decrypt_ecb_suffix_fast<F>(encrypt: &mut F,
+                                  block_size: usize,
+                                  prefix_len: usize)
+                                  -> Result<String, &'static str>
+    where F: FnMut(&[u8]) -> Vec<u8>
+{
+    // Pad out the last block of the prefix so that our input starts on a block boundary.
+    let fill = (block_size - prefix_len % block_size) % block_size;
+    let start = (prefix_len + fill) / block_size;
+
+    // The ciphertext with each amount of filler after the prefix's. The suffix is as long
+    // as the shortest ciphertext, less everything before it, less the filler that made the
+    // ciphertext a block longer, which always happens by `block_size` bytes of filler.
+    let shifted = (0..block_size + 1)
+        .map(|extra| encrypt(&vec![0u8; fill + extra]))
+        .collect::<Vec<_>>();
+    let grows = (1..block_size + 1)
+        .find(|&extra| shifted[extra].len() > shifted[0].len())
+        .ok_or("Ciphertext never grew a block.")?;
+    let len = shifted[0]
+        .len()
+        .checked_sub(start * block_size + grows)
+        .ok_or("Ciphertext is too short to hold a suffix.")?;
+
+    let tracker = Tracker::new("byte-at-a-time ECB decryption", Some(len));
+    let mut dictionary = HashMap::new();
+    let mut decrypted = vec![0u8; block_size - 1];
+    for i in 0..len {
+        // With this much filler, byte i is the last of its block, and the rest of the block
+        // is the bytes before it.
+        let extra = block_size - 1 - i % block_size;
+        let index = start + (extra + i) / block_size;
+        let target = bytes::nth_block(&shifted[extra], block_size, index)
+            .ok_or("Ciphertext is shorter than the suffix.")?;
+
+        // The same block means the same plaintext, so if the dictionary has the block from
+        // an earlier guess, the byte is already known.
+        if !dictionary.contains_key(target) {
+            let mut guesses = vec![0u8; fill];
+            for byte in 0..=255u8 {
+                guesses.extend(&decrypted[i..]);
+                guesses.push(byte);
+            }
+            let encrypted = encrypt(&guesses);
+            let blocks = encrypted.chunks(block_size).skip(start);
+            for (byte, block) in blocks.take(256).enumerate() {
+                dictionary.insert(block.to_vec(), byte as u8);
+            }
+        }
+        let byte = *dictionary.get(target).ok_or("No guess matched the target block.")?;
+        decrypted.push(byte);
+        tracker.update(i + 1, &bytes::to_string(&decrypted[block_size - 1..]));
+    }
+
+    let result = bytes::to_string(&decrypted[block_size - 1..]);
+    tracker.finish(&result);
+    Ok(result)
+}
+
+/// Decrypt a CBC ciphertext with a padding oracle, which reports whether a given IV and
+/// ciphertext decrypt to validly padded plaintext (challenge 17). Each block is attacked on
+/// its own: forging the block before it one byte at a time reveals the block's decryption,
+/// at 256 queries per byte. Exactly one value of each byte should give valid padding, so
+/// this fails if the oracle accepts none of them or more than one. Either way it isn't
+/// answering honestly, or its answers don't depend on the padding at all, as with a server
+/// that checks a MAC first.
+pub fn decrypt_cbc_padding_oracle<F>(iv: &[u8],
+                                     ciphertext: &[u8],
+                                     oracle: &mut F)
+                                     -> Result<Vec<u8>, &'static str>
+    where F: FnMut(&[u8], &[u8]) -> bool
+{
+    let block_size = iv.len();
+    if ciphertext.is_empty() || ciphertext.len() % block_size != 0 {
+        return Err("Ciphertext is not a whole number of blocks.");
+    }
+
+    let tracker = Tracker::new("CBC padding oracle", Some(ciphertext.len()));
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    let previous_blocks = iter::once(iv).chain(bytes::blocks(ciphertext, block_size));
+
+    for (previous, block) in previous_blocks.zip(bytes::blocks(ciphertext, block_size)) {
+        // The block's raw decryption, before it's XORed with the previous block.
+        let mut intermediate = vec![0u8; block_size];
+        for pos in (0..block_size).rev() {
+            // Forge a previous block that makes the bytes after `pos` decrypt to padding.
+            let pad = (block_size - pos) as u8;
+            let mut forged = vec![0u8; block_size];
+            for i in pos + 1..block_size {
+                forged[i] = intermediate[i] ^ pad;
+            }
+
+            let mut found = None;
+            for guess in 0..=255u8 {
+                forged[pos] = guess;
+                if !oracle(&forged, block) {
+                    continue;
+                }
+                // For the last byte, valid padding might be a longer run like 02 02 that we
+                // hit by accident. Changing the byte before rules that out.
+                if pos == block_size - 1 && pos > 0 {
+                    forged[pos - 1] ^= 1;
+                    let still_valid = oracle(&forged, block);
+                    forged[pos - 1] ^= 1;
+                    if !still_valid {
+                        continue;
+                    }
+                }
+                if found.is_some() {
+                    return Err("Oracle accepted more than one guess.");
+                }
+                found = Some(guess);
+            }
+            intermediate[pos] = found.ok_or("Oracle rejected every guess.")? ^ pad;
+        }
+
+        plaintext.extend(bytes::xor(&intermediate, previous));
+        tracker.update(plaintext.len(), &bytes::to_string(&plaintext));
+    }
+
+    let result = crypto::strip_pkcs7(&plaintext).ok_or("Recovered plaintext is badly padded.")?;
+    tracker.finish(&bytes::to_string(&result));
+    Ok(result)
+}
+
+/// Find which of the candidates is the plaintext of a block of CBC ciphertext, given an
+/// oracle that encrypts chosen messages with predictable IVs, as in the BEAST attack on
+/// TLS 1.0. Each message's IV is the last ciphertext block the oracle sent, starting with
+/// the last block of `ciphertext`, which must be the most recent thing it sent.
+///
+/// Block `index` of the ciphertext is E(P ^ C), where C is the block before it, or the IV.
+/// Sending a message that starts with G ^ C ^ IV makes its first block E(G ^ C), which
+/// matches if and only if the guess G is P. That takes one query per candidate, so it only
+/// works for low-entropy blocks, like a PIN in a known place; BEAST lines the secret up so
+/// each block has only one unknown byte.
+pub fn verify_cbc_guesses<F, I>(iv: &[u8],
+                                ciphertext: &[u8],
+                                index: usize,
+                                candidates: I,
+                                encrypt: &mut F)
+                                -> Option<Vec<u8>>
+    where F: FnMut(&[u8]) -> Vec<u8>,
+          I: IntoIterator<Item = Vec<u8>>
+{
+    let block_size = iv.len();
+    let target = bytes::nth_block(ciphertext, block_size, index)?;
+    let previous = match index {
+        0 => iv,
+        _ => bytes::nth_block(ciphertext, block_size, index - 1)?,
+    };
+
+    let mut next_iv = bytes::blocks(ciphertext, block_size).last()?.to_vec();
+    for guess in candidates {
+        if guess.len() != block_size {
+            continue;
+        }
+        let response = encrypt(&bytes::xor(&bytes::xor(&guess, previous), &next_iv));
+        if &response[..block_size] == target {
+            return Some(guess);
+        }
+        next_iv = response[response.len() - block_size..].to_vec();
+    }
+    None
+}
+
+/// Recover the one-time Poly1305 key from two ChaCha20-Poly1305 messages sealed under the
+/// same key and nonce. Each message is given as its ciphertext and tag. The ciphertexts
+/// must differ, be the same length and no longer than 16 bytes, and share the associated
+/// data, so that everything in the two tags cancels except for one coefficient of r^2.
+///
+/// The tags are only known modulo 2^128, so this tries each of the few possible carries,
+/// takes square roots modulo 2^130 - 5, and keeps any root that is a properly clamped r and
+/// explains both tags. Returns the 32-byte key, with r already clamped, which forges tags
+/// for any message under that nonce.
+pub fn recover_poly1305_key(associated_data: &[u8],
+                            first: (&[u8], &[u8]),
+                            second: (&[u8], &[u8]))
+                            -> Result<Vec<u8>, &'static str> {
+    let ((first, first_tag), (second, second_tag)) = (first, second);
+    if first.len() != second.len() || first.len() > 16 || first == second {
+        return Err("Ciphertexts must differ, match in length, and fit in one block.");
+    }
+
+    let p = poly1305::prime();
+    let modulus = BigInt::one() << 128;
+    let to_int = |bytes: &[u8]| BigInt::from_bytes_le(Sign::Plus, bytes);
+    let first_input = poly1305::aead_input(associated_data, first);
+    let second_input = poly1305::aead_input(associated_data, second);
+
+    // The coefficient of r^2 in the difference of the two polynomials.
+    let difference = to_int(first) - to_int(second);
+    let inverse = math::invmod(&difference, &p).ok_or("Ciphertexts must differ.")?;
+    let tag_difference = to_int(first_tag) - to_int(second_tag);
+
+    for carry in -4i32..=4 {
+        let tags = &tag_difference + &modulus * BigInt::from(carry);
+        let square = (tags * &inverse).mod_floor(&p);
+        let root = match math::mod_sqrt(&square, &p) {
+            Some(root) => root,
+            None => continue,
+        };
+        for r in vec![&p - &root, root] {
+            if !poly1305::is_clamped(&r) {
+                continue;
+            }
+            let s = (to_int(first_tag) - poly1305::evaluate(&r, &first_input)).mod_floor(&modulus);
+            let second_s = to_int(second_tag) - poly1305::evaluate(&r, &second_input);
+            if !(second_s - &s).mod_floor(&modulus).is_zero() {
+                continue;
+            }
+
+            let mut key = vec![0u8; 32];
+            let (r, s) = (r.to_bytes_le().1, s.to_bytes_le().1);
+            key[..r.len()].copy_from_slice(&r);
+            key[16..16 + s.len()].copy_from_slice(&s);
+            return Ok(key);
+        }
+    }
+    Err("No key explains both tags.")
+}
+
+/// Build a GCM ciphertext that opens under two different AES-128 keys, as in the
+/// "invisible salamanders" attack on message franking: the tag authenticates the ciphertext
+/// but doesn't commit to the key, so a recipient can be shown one plaintext and a moderator
+/// another. `ciphertext` is whatever the two plaintexts need it to be, except for the 16-byte
+/// block at index `free`, which is overwritten. Returns the finished ciphertext and the tag
+/// that both keys accept for it under the given nonce and associated data.
+///
+/// Each tag is E(J0) + GHASH, and GHASH is linear in every ciphertext block, so with the
+/// free block as the unknown x, the two tags agree when x (H1^k + H2^k) equals the sum of
+/// the tags with x zeroed, where k is how far the block is from the end of the polynomial.
+/// That's one division in GF(2^128).
+#[cfg(feature = "aes")]
+pub fn gcm_key_collision(keys: (&[u8], &[u8]),
+                         nonce: &[u8],
+                         associated_data: &[u8],
+                         ciphertext: &[u8],
+                         free: usize)
+                         -> Result<(Vec<u8>, Vec<u8>), &'static str> {
+    use utils::cipher;
+    use utils::mac::{self, ghash};
+
+    if nonce.len() != 12 {
+        return Err("Nonce is the wrong length.");
+    }
+    if (free + 1) * 16 > ciphertext.len() {
+        return Err("The free block must be a whole block of the ciphertext.");
+    }
+    let mut ciphertext = ciphertext.to_vec();
+    for byte in &mut ciphertext[free * 16..(free + 1) * 16] {
+        *byte = 0;
+    }
+
+    // The hash key raised to the power for the free block, and the tag with it zeroed.
+    let exponent = ((ciphertext.len() + 15) / 16 - free + 1) as u64;
+    let terms = |key: &[u8]| {
+        let mut cipher = cipher::aes_128(key);
+        let mut h = [0u8; 16];
+        cipher.encrypt_block(&mut h);
+        let mut mask = [0u8; 16];
+        mask[..12].copy_from_slice(nonce);
+        mask[15] = 1;
+        cipher.encrypt_block(&mut mask);
+        let mut tag = [0u8; 16];
+        tag.copy_from_slice(&ghash(&h, associated_data, &ciphertext));
+        (mac::gf128_pow(u128::from_be_bytes(h), exponent),
+         u128::from_be_bytes(tag) ^ u128::from_be_bytes(mask))
+    };
+    let ((first_h, first_tag), (second_h, second_tag)) = (terms(keys.0), terms(keys.1));
+
+    let coefficient = first_h ^ second_h;
+    if coefficient == 0 {
+        return Err("The keys must differ.");
+    }
+    let x = mac::gf128_mul(first_tag ^ second_tag, mac::gf128_inverse(coefficient));
+    ciphertext[free * 16..(free + 1) * 16].copy_from_slice(&x.to_be_bytes());
+    let tag = (first_tag ^ mac::gf128_mul(x, first_h)).to_be_bytes().to_vec();
+    Ok((ciphertext, tag))
+}
+
+/// Extend a Merkle-Damgard hash of an unknown message of `message_len` bytes, such as
+/// H(key || message) used as a MAC, with the given suffix. Returns the glue padding, which
+/// goes between the original message and the suffix, and the digest of the whole thing,
+/// computed by picking up from the original digest.
+pub fn length_extension<D>(digest: &[u8],
+                           message_len: u64,
+                           suffix: &[u8])
+                           -> Result<(Vec<u8>, Vec<u8>), &'static str>
+    where D: Digest
+{
+    let glue = bytes::md_padding_be(message_len);
+    let mut hash = D::from_raw_state(digest, message_len + glue.len() as u64)?;
+    Ok((glue, hash.digest(suffix)))
+}
+
+/// Recover an RSA plaintext when all but its lowest `unknown_bits` bits are known, as with
+/// a fixed message ending in a short secret or in a little random padding, for a small
+/// public exponent. `known` is the plaintext with the unknown bits zeroed. The unknown part
+/// x is a small root of (known + x)^e - c modulo n, which Coppersmith's method finds as
+/// long as it's a bit less than 1/e of the size of n. Returns None if it's too big, or if
+/// no root turns up.
+pub fn coppersmith_stereotyped(key: &PublicKey,
+                               ciphertext: &BigInt,
+                               known: &BigInt,
+                               unknown_bits: usize)
+                               -> Option<BigInt> {
+    let e = key.e.to_usize().filter(|&e| e >= 2 && e <= 16)?;
+
+    // Pick the smallest multiplicity that should work, allowing for the dimension lost to
+    // the approximation in LLL: X^(n - 1) 2^(n(n - 1)/2) < N^h, for n = (h + 1)e.
+    let bits = key.n.bits();
+    let multiplicity = (1..6).find(|&h| {
+        let n = (h + 1) * e;
+        unknown_bits * (n - 1) + n * (n - 1) / 2 < h * bits
+    })?;
+
+    // The coefficients of (known + x)^e - c, by the binomial theorem.
+    let mut f = Vec::with_capacity(e + 1);
+    let mut binomial = BigInt::one();
+    for k in 0..e + 1 {
+        f.push(&binomial * num_traits::pow(known.clone(), e - k));
+        binomial = binomial * BigInt::from(e - k) / BigInt::from(k + 1);
+    }
+    f[0] -= ciphertext;
+
+    let bound = BigInt::one() << unknown_bits;
+    lattice::small_roots(&f, &key.n, &bound, multiplicity)
+        .into_iter()
+        .find(|x| !x.is_negative())
+        .map(|x| known + x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+    use utils::random::{RandomSource, SeededRng};
+    use utils::rsa::{self, PrivateKey};
+    use utils::sha1::Sha1;
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_padding_oracle_attack() {
+        use utils::crypto::Stealing;
+        use utils::oracles::PaddingOracle;
+
+        let secret = b"000001With the bass kicked in and the Vega's are pumpin'";
+        let oracle = PaddingOracle::new(secret);
+        let (iv, ciphertext) = oracle.token();
+        let mut check = |iv: &[u8], ciphertext: &[u8]| oracle.check(iv, ciphertext);
+        let result = decrypt_cbc_padding_oracle(&iv, &ciphertext, &mut check).unwrap();
+        assert_eq!(result, &secret[..]);
+
+        // With ciphertext stealing there's no padding, so a server that reports decryption
+        // errors accepts every forgery, and its answers say nothing about the plaintext.
+        let key = [7u8; 16];
+        let encrypted = crypto::encrypt_cbc_cs(&key, &iv, secret, Stealing::Cs3).unwrap();
+        let mut rejected = 0;
+        let mut check = |iv: &[u8], ciphertext: &[u8]| {
+            let valid = crypto::decrypt_cbc_cs(&key, iv, ciphertext, Stealing::Cs3).is_ok();
+            rejected += !valid as usize;
+            valid
+        };
+        let result = decrypt_cbc_padding_oracle(&iv, &encrypted[..48], &mut check);
+        assert_eq!(result, Err("Oracle accepted more than one guess."));
+        assert_eq!(rejected, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_verify_cbc_guesses() {
+        use utils::oracles::PredictableIvOracle;
+
+        let mut oracle = PredictableIvOracle::with_source(b"account=12345678pin=4721;secure;",
+                                                          &mut SeededRng::new(0));
+        let (iv, ciphertext) = oracle.send_secret();
+        let pins = (0..10000).map(|pin| format!("pin={:04};secure;", pin).into_bytes());
+        let mut encrypt = |data: &[u8]| oracle.encrypt(data);
+        let found = verify_cbc_guesses(&iv, &ciphertext, 1, pins, &mut encrypt);
+        assert_eq!(found, Some(b"pin=4721;secure;".to_vec()));
+
+        let accounts = vec![b"account=00000000".to_vec(), b"account=12345678".to_vec()];
+        let (iv, ciphertext) = oracle.send_secret();
+        let mut encrypt = |data: &[u8]| oracle.encrypt(data);
+        let found = verify_cbc_guesses(&iv, &ciphertext, 0, accounts, &mut encrypt);
+        assert_eq!(found, Some(b"account=12345678".to_vec()));
+    }
+
+    #[test]
+    fn test_length_extension() {
+        let key = b"YELLOW SUBMARINE";
+        let message = b"comment1=cooking%20MCs;userdata=foo";
+        let mut secret = key.to_vec();
+        secret.extend_from_slice(message);
+        let mac = Sha1::new().digest(&secret);
+
+        let suffix = b";admin=true";
+        let (glue, forged) = length_extension::<Sha1>(&mac, secret.len() as u64, suffix).unwrap();
+        secret.extend(glue);
+        secret.extend_from_slice(suffix);
+        assert_eq!(forged, Sha1::new().digest(&secret));
+    }
+
+    #[test]
+    fn test_coppersmith_stereotyped() {
+        let mut rng = SeededRng::new(0);
+        let key = PrivateKey::generate_with_source(512, &BigInt::from(3), &mut rng);
+        let message = b"the password for today is: swordfish";
+        let m = rsa::int_from_bytes(message);
+        let c = key.public_key().encrypt(&m);
+
+        let unknown_bits = 8 * b"swordfish".len();
+        let known = (&m >> unknown_bits) << unknown_bits;
+        let recovered = coppersmith_stereotyped(&key.public_key(), &c, &known, unknown_bits);
+        assert_eq!(recovered, Some(m));
+        assert_eq!(coppersmith_stereotyped(&key.public_key(), &c, &known, 200), None);
+    }
+
+    #[test]
+    fn test_crib_drag() {
+        let keystream = bytes::random(40);
+        let ciphertexts = [&b"we will attack the castle at dawn"[..],
+                           &b"bring the siege ladders to the gate"[..],
+                           &b"i have a cunning plan, my lord"[..]]
+            .iter()
+            .map(|plaintext| bytes::xor(*plaintext, &keystream))
+            .collect::<Vec<_>>();
+
+        // " the " appears at 14 in the first plaintext and at 5 and 26 in the second.
+        let matches = crib_drag(&ciphertexts, b" the ");
+        let top = matches.iter()
+            .take(4)
+            .map(|m| (m.pair, m.offset, &m.guess[..]))
+            .collect::<Vec<_>>();
+        assert!(top.contains(&((0, 1), 14, &b"e lad"[..])));
+        assert!(top.contains(&((1, 2), 5, &b"e a c"[..])));
+        assert!(top.contains(&((0, 1), 26, &b"at da"[..])));
+    }
+
+    #[test]
+    fn test_group_by_keystream() {
+        let plaintexts = [&b"we will attack the castle at dawn"[..],
+                          &b"bring the siege ladders to the gate"[..],
+                          &b"i have a cunning plan, my lord"[..],
+                          &b"Now is the winter of our discontent"[..],
+                          &b"Made glorious summer by this sun of York"[..]];
+        let keystreams = [bytes::random(48), bytes::random(48), bytes::random(48)];
+        let ciphertexts = plaintexts.iter()
+            .zip(&[0, 1, 2, 1, 0])
+            .map(|(plaintext, &key)| bytes::xor(*plaintext, &keystreams[key]))
+            .collect::<Vec<_>>();
+
+        let scores = keystream_reuse_scores(&ciphertexts);
+        assert!(scores[0].z_score > 4.0);
+        assert!(scores.iter().any(|score| score.pair == (0, 4) && score.z_score > 4.0));
+        assert_eq!(group_by_keystream(&ciphertexts, 4.0), vec![vec![0, 4], vec![1, 3], vec![2]]);
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_classify() {
+        let key = bytes::random(16);
+        let plaintext = include_bytes!("../data/play_that_funky_music.txt");
+        let cbc = crypto::encrypt_cbc(&key, &[0; 16], plaintext);
+        let ctr = crypto::ctr(&key, 0, plaintext);
+        let ecb = crypto::encrypt_ecb(&key, &vec![b'A'; 64], true);
+
+        assert_eq!(classify(&cbc, 16).unwrap().class, Class::BlockMode);
+        assert_eq!(classify(&ctr, 16).unwrap().class, Class::StreamMode);
+        assert_eq!(classify(&ecb, 16).unwrap().class, Class::Ecb);
+        assert_eq!(classify(bytes::base64_from_bytes(&cbc).as_bytes(), 16).unwrap().class,
+                   Class::Encoded);
+        assert_eq!(classify(plaintext, 16).unwrap().class, Class::Plain);
+
+        let counter = (0..200u8).collect::<Vec<u8>>();
+        let classification = classify(&counter, 16).unwrap();
+        assert_eq!(classification.class, Class::Plain);
+        assert!(classification.serial_correlation > 0.9);
+        assert!(classify(&counter, 0).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_poly1305_nonce_reuse() {
+        use utils::aead::{Aead, ChaCha20Poly1305};
+
+        let mut aead = ChaCha20Poly1305::new(&bytes::random(32));
+        let nonce = [0u8; 12];
+        let mut first = b"transfer $100".to_vec();
+        let first_tag = aead.seal(&nonce, b"bank", &mut first);
+        let mut second = b"transfer $500".to_vec();
+        let second_tag = aead.seal(&nonce, b"bank", &mut second);
+
+        let key = recover_poly1305_key(b"bank", (&first, &first_tag), (&second, &second_tag))
+            .unwrap();
+        // r comes back clamped, which is all Poly1305 ever uses of it.
+        assert_eq!(key[16..], aead.one_time_key(&nonce)[16..]);
+
+        // With the keystream and the Poly1305 key both reused, any message can be forged.
+        let mut forged = bytes::xor(&first, &bytes::xor(b"transfer $100", b"transfer $999"));
+        let tag = poly1305::poly1305(&key, &poly1305::aead_input(b"bank", &forged));
+        aead.open(&nonce, b"bank", &mut forged, &tag).unwrap();
+        assert_eq!(forged, b"transfer $999");
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_detect_ecb_misaligned() {
+        let key = bytes::random(16);
+        let encrypted = crypto::encrypt_ecb(&key, &[0u8; 48], true);
+
+        // If the prefix ended with the last byte of the repeated block, the blocks would
+        // repeat just as often one byte earlier, and alignment 4 would tie with 5.
+        let mut ciphertext = vec![!encrypted[15]; 5];
+        ciphertext.extend(encrypted);
+
+        let score = detect_ecb(&ciphertext, 16);
+        assert!(score.is_ecb());
+        assert_eq!(score.alignment, 5);
+        assert_eq!(score.repeats, 2);
+        assert_eq!(score.offsets, vec![5, 21, 37]);
+    }
+
+    #[test]
+    fn test_detect_ecb_batch() {
+        let key = bytes::random(16);
+        let mut plaintext = vec![b'A'; 32];
+        plaintext.extend(vec![b'B'; 32]);
+        let lines = vec![bytes::random(64), crypto::encrypt_ecb(&key, &plaintext, false)];
+
+        let matches = detect_ecb_batch(&lines, 16);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line, 1);
+        assert!(matches[0].score.is_ecb());
+        assert_eq!(matches[0].repeated, vec![lines[1][..16].to_vec(), lines[1][32..48].to_vec()]);
+        assert!(matches[1].repeated.is_empty());
+    }
+
+    #[test]
+    fn test_rank_caesar() {
+        let plaintext = &b"The Quick Brown Fox Jumps Over The Lazy Dog!"[..];
+        let ciphertext = caesar_shift(plaintext, 3);
+        assert_eq!(&ciphertext[..10], b"Wkh Txlfn ");
+
+        let model = text::NgramModel::english(4);
+        let candidates = rank_caesar(&ciphertext, &model);
+        assert_eq!(candidates.len(), 26);
+        assert_eq!(candidates[0].key, vec![3]);
+        assert_eq!(candidates[0].plaintext, plaintext);
+        assert!(candidates[0].score > candidates[1].score);
+    }
+
+    #[test]
+    fn test_scan_corpus() {
+        let mut lines = (0..100).map(|_| bytes::random(30)).collect::<Vec<_>>();
+        let plaintext = &b"Now that the party is jumping\n"[..];
+        lines[42] = bytes::xor(plaintext, iter::repeat(&0x35));
+        // A duplicate of the needle, which ties with it and ranks after it.
+        lines.push(lines[42].clone());
+
+        let hits = scan_corpus(&lines, &text::TieBreaker::english(), 3);
+        assert_eq!(hits.len(), 3);
+        assert_eq!((hits[0].line, hits[1].line), (42, 100));
+        assert_eq!(hits[0].candidate.key, vec![0x35]);
+        assert_eq!(hits[0].candidate.plaintext, plaintext);
+        assert!(hits[1].candidate.score > hits[2].candidate.score);
+        assert!(scan_corpus(&lines, &text::TieBreaker::english(), 0).is_empty());
+    }
+
+    #[test]
+    fn test_rank_single_byte_xor() {
+        let plaintext = &b"Cooking MC's like a pound of bacon"[..];
+        let ciphertext = bytes::xor(plaintext, iter::repeat(&0xff));
+        let candidates = rank_single_byte_xor(&ciphertext, &text::TieBreaker::english());
+        assert_eq!(candidates.len(), 256);
+        assert_eq!(candidates[0].key, vec![0xff]);
+        assert_eq!(candidates[0].plaintext, plaintext);
+    }
+
+    #[test]
+    fn test_get_keysizes_with() {
+        let mut plaintext = b"-=".iter().cycle().take(80).cloned().collect::<Vec<u8>>();
+        plaintext.extend_from_slice(b"Now that the party is jumping, with the bass kicked in and \
+                                      the Vega's are pumping, quick to the point, to the point, \
+                                      no faking, cooking MCs like a pound of bacon.");
+        let key = b"SPICE";
+        let ciphertext = bytes::xor(&plaintext[..], key.iter().cycle());
+
+        // The repetitive header makes multiples of 10 look best at the start, and sampling
+        // more widely isn't enough on its own, but trial decryption sorts it out.
+        assert_eq!(get_keysizes(&ciphertext, 2..41, 1), vec![10]);
+        let config = KeysizeConfig {
+            chunks: 8,
+            sampling: Sampling::Spread,
+            trial_decrypt: Some(10),
+        };
+        assert_eq!(get_keysizes_with(&ciphertext, 2..41, 3, &config), vec![5, 20, 10]);
+
+        // Sizes without two whole chunks to compare are skipped rather than ranked as NaN.
+        assert!(get_keysizes(&ciphertext[..30], 20..41, 5).is_empty());
+    }
+
+    #[test]
+    fn test_vote_keysizes() {
+        // Hamming distance settles on 5 for this text and key, but the other methods
+        // outvote it.
+        let plaintext = include_bytes!("../data/play_that_funky_music.txt");
+        let key = b"Vanilla Ice";
+        for &len in &[120, 300, 2000] {
+            let ciphertext = bytes::xor(&plaintext[..len], key.iter().cycle());
+            assert_eq!(get_keysizes(&ciphertext, 2..41, 1), vec![5]);
+            assert_eq!(keysizes_by_coincidence(&ciphertext, 2..41, 1), vec![11]);
+            assert_eq!(vote_keysizes(&ciphertext, 2..41, 1), vec![11]);
+        }
+
+        // Skewed binary data, where English scoring would be no help at all.
+        let mut rng = SeededRng::new(0);
+        let binary = (0..600)
+            .map(|_| {
+                let r = rng.range(0, 64);
+                (r * r / 64) as u8
+            })
+            .collect::<Vec<u8>>();
+        let key = b"\x01\x80\xff\x10\x33\x77\xee";
+        let ciphertext = bytes::xor(&binary, key.iter().cycle());
+        assert_eq!(get_keysizes(&ciphertext, 2..41, 1), vec![35]);
+        assert_eq!(vote_keysizes(&ciphertext, 2..41, 1), vec![7]);
+
+        // With a random key, the textbook estimate gets close on a long enough ciphertext.
+        let key = rng.bytes(11);
+        let ciphertext = bytes::xor(&plaintext[..], key.iter().cycle());
+        assert!((friedman_estimate(&ciphertext, ENGLISH_IC, 1.0 / 256.0) - 11.0).abs() < 3.0);
+    }
+
+    #[test]
+    fn test_refine_repeating_key() {
+        let plaintext = &b"It was a bright cold day in April, \
+                           and the clocks were striking thirteen."[..];
+        let key = &b"SUBMARINES"[..];
+        let ciphertext = bytes::xor(plaintext, key.iter().cycle());
+
+        // With only seven or eight bytes per column, frequency analysis gets some of the key
+        // bytes wrong, but the quadgram model can fix them using the surrounding context.
+        let guess = repeating_key_brute_force(&ciphertext, key.len());
+        assert!(guess != key);
+
+        let model = text::NgramModel::english(4);
+        assert_eq!(refine_repeating_key(&ciphertext, &guess, &model), key);
+    }
+
+    #[test]
+    fn test_find_repeating_key_with() {
+        let plaintext = &b"Als Gregor Samsa eines Morgens aus unruhigen Traeumen erwachte, \
+                           fand er sich in seinem Bett zu einem ungeheueren Ungeziefer \
+                           verwandelt. Er lag auf seinem panzerartig harten Ruecken und sah, \
+                           wenn er den Kopf ein wenig hob, seinen gewoelbten, braunen, von \
+                           bogenfoermigen Versteifungen geteilten Bauch."[..];
+        let key = &b"YELLOWSUB"[..];
+        let ciphertext = bytes::xor(plaintext, key.iter().cycle());
+
+        // German, with its umlauts spelled out in ASCII.
+        let scorer = text::Weighted::new()
+            .with(1.0, text::ChiSquaredScorer::new(text::Language::German.frequencies()))
+            .with(100.0, text::PrintableScorer);
+        assert_eq!(find_repeating_key_with(&ciphertext, &scorer), key);
+        assert_eq!(repeating_key_brute_force_with(&ciphertext, key.len(), &scorer), key);
+    }
+
+    #[test]
+    fn test_decrypt_ecb_suffix_empty() {
+        let mut encrypt = |_: &[u8]| Vec::new();
+        assert_eq!(decrypt_ecb_suffix(&mut encrypt, 16, 0, 0), Err("No suffix to decrypt."));
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_decrypt_ecb_suffix_fast() {
+        use utils::oracles::{CountingOracle, UnknownStringOracle};
+
+        let oracle = UnknownStringOracle::with_source(true, &mut SeededRng::new(3));
+        let mut counting = CountingOracle::new(|data: &[u8]| oracle.encrypt(data));
+        let (block_size, prefix_len) = {
+            let mut encrypt = |data: &[u8]| counting.encrypt(data);
+            detect_block_size(&mut encrypt).unwrap()
+        };
+        let before = counting.queries();
+        let result = {
+            let mut encrypt = |data: &[u8]| counting.encrypt(data);
+            decrypt_ecb_suffix_fast(&mut encrypt, block_size, prefix_len).unwrap()
+        };
+        let expected = UnknownStringOracle::unknown_string();
+        assert_eq!(result, bytes::to_string(&expected));
+
+        // One query per byte at most, and fewer when blocks repeat, where the original
+        // attack makes as many as 256.
+        assert!(counting.queries() - before <= expected.len() + block_size + 1);
+
+        // Oracles that don't pad to whole blocks: one whose output never grows, and a
+        // stream cipher's, which grows a byte at a time.
+        let mut encrypt = |_: &[u8]| vec![0u8; 32];
+        assert_eq!(decrypt_ecb_suffix_fast(&mut encrypt, 16, 0),
+                   Err("Ciphertext never grew a block."));
+        let mut encrypt = |data: &[u8]| data.to_vec();
+        assert!(decrypt_ecb_suffix_fast(&mut encrypt, 16, 0).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_gcm_key_collision() {
+        use utils::aead::{Aead, Gcm};
+
+        // One plaintext at the start under the first key, another at the end under the
+        // second, and a block in between to make the tags agree.
+        let (first_key, second_key) = (b"YELLOW SUBMARINE", b"PURPLE SALAMANDR");
+        let (nonce, associated_data) = ([7u8; 12], b"franking");
+        let seal = |key: &[u8], plaintext: &[u8; 48]| {
+            let mut data = plaintext.to_vec();
+            Gcm::new(key).seal(&nonce, associated_data, &mut data);
+            data
+        };
+        let first = seal(first_key, b"Meet me at the docks at noon, bring the money!!!");
+        let second = seal(second_key, b"Just a friendly hello, nothing to see here...!!!");
+        let mut ciphertext = first[..16].to_vec();
+        ciphertext.extend_from_slice(&[0; 16]);
+        ciphertext.extend_from_slice(&second[32..]);
+
+        let (ciphertext, tag) =
+            gcm_key_collision((first_key, second_key), &nonce, associated_data, &ciphertext, 1)
+                .unwrap();
+        let mut data = ciphertext.clone();
+        Gcm::new(first_key).open(&nonce, associated_data, &mut data, &tag).unwrap();
+        assert_eq!(&data[..16], b"Meet me at the d");
+        let mut data = ciphertext.clone();
+        Gcm::new(second_key).open(&nonce, associated_data, &mut data, &tag).unwrap();
+        assert_eq!(&data[32..], b"o see here...!!!");
+
+        assert!(gcm_key_collision((first_key, first_key), &nonce, b"", &ciphertext, 1).is_err());
+        assert!(gcm_key_collision((first_key, second_key), &nonce, b"", &ciphertext, 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_brute_force() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use utils::progress;
+
+        let test = |&x: &u64| x * x % 100_003 == 4;
+        assert_eq!(brute_force(0..100_000, test), Some(2));
+        assert_eq!(brute_force(3..200_000, test), Some(100_001));
+        assert_eq!(brute_force(0..10, |&x: &u64| x > 10), None);
+
+        // A budget pauses the search on a chunk boundary, and resuming picks up from there.
+        let chunks = Rc::new(RefCell::new(0));
+        let counter = chunks.clone();
+        progress::set_callback(move |_| *counter.borrow_mut() += 1);
+        let search = BruteForce::new("test").with_chunk_size(1000).with_budget(2500);
+        let checkpoint = match search.run(3..200_000, test) {
+            Search::Paused(checkpoint) => checkpoint,
+            other => panic!("Expected a pause, got {:?}", other),
+        };
+        progress::clear_callback();
+        assert_eq!(checkpoint, Checkpoint { tried: 2500 });
+        assert_eq!(*chunks.borrow(), 3);
+        let search = BruteForce::new("test").with_chunk_size(1000).resume(checkpoint);
+        assert_eq!(search.run(3..200_000, test), Search::Found(100_001));
+        assert_eq!(search.run(3..1000, test), Search::Exhausted);
+    }
+
+    #[test]
+    fn test_detect_ecb_random() {
+        let score = detect_ecb(&bytes::random(160), 16);
+        assert!(!score.is_ecb());
+        assert_eq!(score.confidence, 0.0);
+    }
 }
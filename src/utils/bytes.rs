@@ -1,10 +1,77 @@
-use std::collections::HashMap;
-use std::iter::{self, FromIterator};
+use std::iter::{self, StepBy};
+use std::ops::Range;
+use std::slice::{self, ChunksExact};
 
-use rand::{Rng, OsRng};
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
 
-static BASE64_CHARS: &'static str =
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use utils::random::{OsSource, RandomSource};
+#[cfg(feature = "std")]
+use utils::text;
+
+pub(crate) static HEX_LOWER: &'static str = "0123456789abcdef";
+static HEX_UPPER: &'static str = "0123456789ABCDEF";
+
+static BASE64_STANDARD_CHARS: &'static str =
     "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+static BASE64_URL_SAFE_CHARS: &'static str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+static BASE32_CHARS: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+static BASE58_CHARS: &'static str =
+    "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The alphabet used to encode the last two of the 64 base64 digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base64Alphabet {
+    /// RFC 4648 standard alphabet, using '+' and '/'.
+    Standard,
+    /// RFC 4648 URL and filename safe alphabet, using '-' and '_'.
+    UrlSafe,
+}
+
+impl Base64Alphabet {
+    pub(crate) fn chars(&self) -> &'static [u8] {
+        match *self {
+            Base64Alphabet::Standard => BASE64_STANDARD_CHARS.as_bytes(),
+            Base64Alphabet::UrlSafe => BASE64_URL_SAFE_CHARS.as_bytes(),
+        }
+    }
+}
+
+/// Options controlling how base64 is encoded and decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Base64Config {
+    pub alphabet: Base64Alphabet,
+    /// Whether encoded output is padded with '=' to a multiple of 4 characters. When
+    /// decoding, padding is required if this is set and optional otherwise.
+    pub pad: bool,
+    /// If set, encoded output is broken into lines of at most this many characters
+    /// separated by CRLF, and line breaks are skipped when decoding.
+    pub line_length: Option<usize>,
+}
+
+/// Standard base64, as used by the challenge data files once line breaks are removed.
+pub const BASE64_STANDARD: Base64Config = Base64Config {
+    alphabet: Base64Alphabet::Standard,
+    pad: true,
+    line_length: None,
+};
+
+/// Unpadded base64 using the URL-safe alphabet.
+pub const BASE64_URL_SAFE: Base64Config = Base64Config {
+    alphabet: Base64Alphabet::UrlSafe,
+    pad: false,
+    line_length: None,
+};
+
+/// Standard base64 wrapped at 76 characters per line, as in MIME (RFC 2045).
+pub const BASE64_MIME: Base64Config = Base64Config {
+    alphabet: Base64Alphabet::Standard,
+    pad: true,
+    line_length: Some(76),
+};
 
 /// XOR two byte strings, truncating the longer one if the sizes are different.
 pub fn xor<'a, 'b, A, B>(a: A, b: B) -> Vec<u8>
@@ -28,89 +95,461 @@ pub fn from_string(string: &str) -> Vec<u8> {
     string.chars().map(|c| c as u8).collect()
 }
 
-/// Convert a hexadecimal string to a vector of bytes.
-pub fn hex_to_bytes(hex: &str) -> Vec<u8> {
-    let chars = hex.chars().collect::<Vec<char>>();
-    chars.chunks(2).map(|chunk| {
-        let byte = chunk.iter().cloned().collect::<String>();
-        u8::from_str_radix(&byte, 16).unwrap()
-    }).collect()
+/// Convert a hexadecimal string to a vector of bytes. Digits may be in upper or lower case,
+/// and whitespace (e.g., line breaks in a data file) is ignored. Returns an error if the
+/// string contains any other characters or an odd number of digits.
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, &'static str> {
+    let digits = hex.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or("Invalid hexadecimal digit."))
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    if digits.len() % 2 != 0 {
+        return Err("Odd number of hexadecimal digits.");
+    }
+
+    Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
 }
 
-/// Convert an array of bytes into a hexadecimal string.
+/// Convert an array of bytes into a lowercase hexadecimal string.
 pub fn hex_from_bytes(bytes: &[u8]) -> String {
-    let hex_bytes = bytes.iter()
-        .map(|b| format!("{:2x}", b))
-        .collect::<Vec<String>>();
-    hex_bytes.concat()
+    hex_encode(bytes, false)
+}
+
+/// Convert an array of bytes into an uppercase hexadecimal string.
+pub fn hex_from_bytes_upper(bytes: &[u8]) -> String {
+    hex_encode(bytes, true)
+}
+
+// Encode each byte as exactly two hex digits in the given case.
+fn hex_encode(bytes: &[u8], uppercase: bool) -> String {
+    let digits = if uppercase { HEX_UPPER } else { HEX_LOWER }.as_bytes();
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(digits[(byte >> 4) as usize] as char);
+        hex.push(digits[(byte & 0xF) as usize] as char);
+    }
+    hex
 }
 
-/// Convert a base64 string into a vector of bytes.
-pub fn base64_to_bytes(base64: &str) -> Vec<u8> {
-    // Build mapping from base64 characters to their indices in the string.
-    let codes : HashMap<char, u32> = HashMap::from_iter(
-        BASE64_CHARS.chars().enumerate().map(|(i, c)| (c, i as u32))
-    );
+/// Convert a standard, padded base64 string into a vector of bytes.
+pub fn base64_to_bytes(base64: &str) -> Result<Vec<u8>, &'static str> {
+    base64_decode(base64, &BASE64_STANDARD)
+}
+
+/// Convert an array of bytes into a standard, padded base64 string.
+pub fn base64_from_bytes(bytes: &[u8]) -> String {
+    base64_encode(bytes, &BASE64_STANDARD)
+}
+
+/// Decode a base64 string using the given configuration. Returns an error if the string
+/// contains characters outside of the configured alphabet, if the padding is missing
+/// (when required) or malformed, or if the length is not a valid base64 length.
+pub fn base64_decode(base64: &str, config: &Base64Config) -> Result<Vec<u8>, &'static str> {
+    // Build mapping from base64 characters to their indices in the alphabet.
+    let mut codes = [None; 256];
+    for (i, c) in config.alphabet.chars().iter().enumerate() {
+        codes[*c as usize] = Some(i as u32);
+    }
+
+    let chars = base64.bytes()
+        .filter(|c| config.line_length.is_none() || (*c != b'\r' && *c != b'\n'))
+        .collect::<Vec<u8>>();
 
-    // Iterate over base64 string 4 chars at a time.
-    let chars = base64.chars().collect::<Vec<char>>();
-    chars.chunks(4).flat_map(|chunk| {
-        // Strip padding.
-        let stripped = chunk.iter().filter(|c| **c != '=').collect::<Vec<_>>();
+    // Padding may only appear at the very end, and only in a complete final group.
+    let data_len = chars.iter().position(|c| *c == b'=').unwrap_or(chars.len());
+    let padding = chars.len() - data_len;
+    if chars[data_len..].iter().any(|c| *c != b'=') {
+        return Err("Invalid base64 padding.");
+    }
+    if padding > 2 || (padding > 0 && chars.len() % 4 != 0) {
+        return Err("Invalid base64 padding.");
+    }
+    if config.pad && chars.len() % 4 != 0 {
+        return Err("Missing base64 padding.");
+    }
+    if data_len % 4 == 1 {
+        return Err("Invalid base64 length.");
+    }
 
+    let mut decoded = Vec::with_capacity(data_len * 3 / 4);
+    for chunk in chars[..data_len].chunks(4) {
         // Insert the bits corresponding to the indices of the given characters
         // into the lower 24 bits of a u32.
         let mut bits = 0;
-        for i in 0..stripped.len() {
-            bits |= (codes[&chunk[i]]) << ((3 - i) * 6);
+        for (i, c) in chunk.iter().enumerate() {
+            let code = codes[*c as usize].ok_or("Invalid base64 character.")?;
+            bits |= code << ((3 - i) * 6);
         }
 
         // Decode 8 bits at a time.
-        (0..(stripped.len() - 1)).map(|i| {
-            let shift = (2 - i) * 8;
-            ((bits & (0xFF << shift)) >> shift) as u8
-        }).collect::<Vec<u8>>()
-    }).collect()
+        for i in 0..(chunk.len() - 1) {
+            decoded.push((bits >> ((2 - i) * 8)) as u8);
+        }
+    }
+
+    Ok(decoded)
 }
 
-/// Convert an array of bytes into a base64 string.
-pub fn base64_from_bytes(bytes: &[u8]) -> String {
-    let codes = BASE64_CHARS.chars().collect::<Vec<char>>();
+/// Encode an array of bytes as base64 using the given configuration.
+pub fn base64_encode(bytes: &[u8], config: &Base64Config) -> String {
+    let codes = config.alphabet.chars();
+    let mut chars = Vec::with_capacity((bytes.len() + 2) / 3 * 4);
 
     // Iterate over input 3 bytes at a time.
-    bytes.chunks(3).map(|chunk| {
+    for chunk in bytes.chunks(3) {
         // Insert bytes into the lower 24 bits of a u32.
         let mut bits = 0;
-        for i in 0..chunk.len() {
-            bits |= (chunk[i] as u32) << ((2 - i) * 8);
+        for (i, byte) in chunk.iter().enumerate() {
+            bits |= (*byte as u32) << ((2 - i) * 8);
         }
 
         // Encode 6 bits at a time.
-        let chars = (0..(chunk.len() + 1)).map(|i| {
-            let shift = (3 - i) * 6;
-            let index = ((bits & (0x3F << shift)) >> shift) as usize;
-            codes[index]
-        }).collect::<String>();
+        for i in 0..(chunk.len() + 1) {
+            chars.push(codes[((bits >> ((3 - i) * 6)) & 0x3F) as usize]);
+        }
 
         // Append padding if there were fewer than 3 bytes.
-        let padding = iter::repeat("=").take(3 - chunk.len()).collect::<String>();
-        chars + &padding
-    }).collect::<Vec<String>>().concat()
+        if config.pad {
+            for _ in chunk.len()..3 {
+                chars.push(b'=');
+            }
+        }
+    }
+
+    match config.line_length {
+        Some(length) if length > 0 => chars.chunks(length)
+            .map(|line| line.iter().map(|c| *c as char).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\r\n"),
+        _ => chars.iter().map(|c| *c as char).collect(),
+    }
 }
 
 /// Convert a hexadecimal string into a base64 string.
-pub fn hex_to_base64(hex: &str) -> String {
-    base64_from_bytes(&hex_to_bytes(&hex))
+pub fn hex_to_base64(hex: &str) -> Result<String, &'static str> {
+    Ok(base64_from_bytes(&hex_to_bytes(&hex)?))
 }
 
 /// Convert a base64 string into a hexadecimal string.
-pub fn base64_to_hex(base64: &str) -> String {
-    hex_from_bytes(&base64_to_bytes(&base64))
+pub fn base64_to_hex(base64: &str) -> Result<String, &'static str> {
+    Ok(hex_from_bytes(&base64_to_bytes(&base64)?))
+}
+
+/// Convert an RFC 4648 base32 string into a vector of bytes. Lowercase digits are
+/// accepted and padding is optional, but if present it must complete the final group.
+pub fn base32_to_bytes(base32: &str) -> Result<Vec<u8>, &'static str> {
+    let chars = base32.as_bytes();
+    let data_len = chars.iter().position(|c| *c == b'=').unwrap_or(chars.len());
+    if chars[data_len..].iter().any(|c| *c != b'=') {
+        return Err("Invalid base32 padding.");
+    }
+    if data_len < chars.len() && chars.len() % 8 != 0 {
+        return Err("Invalid base32 padding.");
+    }
+
+    // A final group of k bytes is encoded as ceil(8k / 5) characters, so a trailing group
+    // of 1, 3, or 6 characters can't have come from any input.
+    let remainder = data_len % 8;
+    if remainder == 1 || remainder == 3 || remainder == 6 {
+        return Err("Invalid base32 length.");
+    }
+
+    let mut decoded = Vec::with_capacity(data_len * 5 / 8);
+    for chunk in chars[..data_len].chunks(8) {
+        // Insert 5 bits per character into the lower 40 bits of a u64.
+        let mut bits = 0u64;
+        for (i, c) in chunk.iter().enumerate() {
+            let code = BASE32_CHARS.bytes()
+                .position(|d| d == c.to_ascii_uppercase())
+                .ok_or("Invalid base32 character.")?;
+            bits |= (code as u64) << ((7 - i) * 5);
+        }
+
+        for i in 0..(chunk.len() * 5 / 8) {
+            decoded.push((bits >> ((4 - i) * 8)) as u8);
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Convert an array of bytes into a padded RFC 4648 base32 string.
+pub fn base32_from_bytes(bytes: &[u8]) -> String {
+    let codes = BASE32_CHARS.as_bytes();
+    let mut base32 = String::with_capacity((bytes.len() + 4) / 5 * 8);
+
+    // Iterate over input 5 bytes at a time.
+    for chunk in bytes.chunks(5) {
+        let mut bits = 0u64;
+        for (i, byte) in chunk.iter().enumerate() {
+            bits |= (*byte as u64) << ((4 - i) * 8);
+        }
+
+        // Encode 5 bits at a time, then pad the group out to 8 characters.
+        let num_chars = (chunk.len() * 8 + 4) / 5;
+        for i in 0..num_chars {
+            base32.push(codes[((bits >> ((7 - i) * 5)) & 0x1F) as usize] as char);
+        }
+        for _ in num_chars..8 {
+            base32.push('=');
+        }
+    }
+
+    base32
+}
+
+/// Convert a base58 string (using the Bitcoin alphabet) into a vector of bytes. Each
+/// leading '1' encodes a leading zero byte.
+pub fn base58_to_bytes(base58: &str) -> Result<Vec<u8>, &'static str> {
+    let zeros = base58.bytes().take_while(|c| *c == b'1').count();
+
+    // Accumulate the value as a little-endian big integer in base 256.
+    let mut value: Vec<u8> = Vec::new();
+    for c in base58.bytes().skip(zeros) {
+        let mut carry = BASE58_CHARS.bytes()
+            .position(|d| d == c)
+            .ok_or("Invalid base58 character.")? as u32;
+        for byte in &mut value {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            value.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut decoded = vec![0; zeros];
+    decoded.extend(value.iter().rev());
+    Ok(decoded)
+}
+
+/// Convert an array of bytes into a base58 string (using the Bitcoin alphabet).
+pub fn base58_from_bytes(bytes: &[u8]) -> String {
+    let codes = BASE58_CHARS.as_bytes();
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+
+    // Repeatedly multiply the little-endian base 58 digits by 256 and add the next byte.
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in &mut digits {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut base58 = "1".repeat(zeros);
+    base58.extend(digits.iter().rev().map(|d| codes[*d as usize] as char));
+    base58
+}
+
+/// Convert an Ascii85 string into a vector of bytes. The Adobe "<~" and "~>" delimiters
+/// are optional, whitespace is ignored, and 'z' may be used as shorthand for a group of
+/// four zero bytes.
+pub fn ascii85_to_bytes(ascii85: &str) -> Result<Vec<u8>, &'static str> {
+    let mut body = ascii85.trim();
+    if body.starts_with("<~") {
+        body = &body[2..];
+    }
+    if body.ends_with("~>") {
+        body = &body[..body.len() - 2];
+    }
+
+    let mut decoded = Vec::with_capacity(body.len() * 4 / 5);
+    let mut group = Vec::with_capacity(5);
+    for c in body.bytes().filter(|c| !(*c as char).is_whitespace()) {
+        match c {
+            b'z' if group.is_empty() => decoded.extend(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(c - b'!');
+                if group.len() == 5 {
+                    decoded.extend(&ascii85_decode_group(&group)?);
+                    group.clear();
+                }
+            }
+            _ => return Err("Invalid Ascii85 character."),
+        }
+    }
+
+    // A final partial group of n characters is padded with 'u' and yields n - 1 bytes.
+    match group.len() {
+        0 => {}
+        1 => return Err("Invalid Ascii85 length."),
+        n => {
+            group.resize(5, 84);
+            decoded.extend(&ascii85_decode_group(&group)?[..n - 1]);
+        }
+    }
+
+    Ok(decoded)
+}
+
+// Decode five base 85 digits into four bytes.
+fn ascii85_decode_group(digits: &[u8]) -> Result<[u8; 4], &'static str> {
+    let value = digits.iter().fold(0u64, |acc, d| acc * 85 + *d as u64);
+    if value > u32::max_value() as u64 {
+        return Err("Ascii85 group out of range.");
+    }
+    let value = value as u32;
+    Ok([(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+/// Convert an array of bytes into an Ascii85 string, without delimiters. Complete groups
+/// of four zero bytes are abbreviated as 'z'.
+pub fn ascii85_from_bytes(bytes: &[u8]) -> String {
+    let mut ascii85 = String::with_capacity((bytes.len() + 3) / 4 * 5);
+    for chunk in bytes.chunks(4) {
+        if chunk == [0; 4] {
+            ascii85.push('z');
+            continue;
+        }
+
+        // Pad a short final group with zeros and emit only the first n + 1 digits.
+        let mut value = 0u32;
+        for i in 0..4 {
+            value = value << 8 | *chunk.get(i).unwrap_or(&0) as u32;
+        }
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        ascii85.extend(digits[..chunk.len() + 1].iter().map(|d| *d as char));
+    }
+    ascii85
+}
+
+/// Encodings recognized by `detect_encoding`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Hex,
+    Base32,
+    Base64,
+    Text,
+}
+
+/// The most likely format of a string, along with the estimated probability that it
+/// is correct.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding {
+    pub format: Format,
+    pub confidence: f64,
+}
+
+/// Guess whether the given string is hex, base32, base64 (standard or URL-safe, with or
+/// without line breaks), or raw text. Each encoding that decodes the input without error
+/// is scored by the likelihood of drawing its digits uniformly at random, and text is
+/// scored with a simple model of English character frequencies. The confidence is the
+/// posterior probability of the most likely format, treating all formats as equally
+/// likely up front; short inputs like "facade" are genuinely ambiguous.
+#[cfg(feature = "std")]
+pub fn detect_encoding(input: &str) -> Encoding {
+    classify(input).0
+}
+
+/// Decode the given string using the format chosen by `detect_encoding`. Text is
+/// returned as its raw bytes.
+#[cfg(feature = "std")]
+pub fn decode_any(input: &str) -> Vec<u8> {
+    classify(input).1
+}
+
+// Find the most likely format of the input, returning it along with the decoded bytes.
+#[cfg(feature = "std")]
+fn classify(input: &str) -> (Encoding, Vec<u8>) {
+    let compact = input.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    if compact.is_empty() {
+        return (Encoding { format: Format::Text, confidence: 1.0 }, input.as_bytes().to_vec());
+    }
+
+    let digits = compact.len() as f64;
+    let raw = input.as_bytes().to_vec();
+    let mut candidates = vec![(Format::Text, text_log_likelihood(input), raw)];
+    if let Ok(decoded) = hex_to_bytes(&compact) {
+        candidates.push((Format::Hex, -digits * 16f64.ln(), decoded));
+    }
+    if let Ok(decoded) = base32_to_bytes(&compact) {
+        candidates.push((Format::Base32, -digits * 32f64.ln(), decoded));
+    }
+    let base64 = base64_decode(&compact, &BASE64_STANDARD)
+        .or_else(|_| base64_decode(&compact, &BASE64_URL_SAFE));
+    if let Ok(decoded) = base64 {
+        candidates.push((Format::Base64, -digits * 64f64.ln(), decoded));
+    }
+
+    // Normalize the likelihoods relative to the best candidate to avoid underflow.
+    let best = candidates.iter()
+        .map(|&(_, log_likelihood, _)| log_likelihood)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let total = candidates.iter()
+        .map(|&(_, log_likelihood, _)| (log_likelihood - best).exp())
+        .sum::<f64>();
+
+    let index = candidates.iter()
+        .position(|&(_, log_likelihood, _)| log_likelihood == best)
+        .unwrap();
+    let (format, _, decoded) = candidates.swap_remove(index);
+    (Encoding { format: format, confidence: 1.0 / total }, decoded)
+}
+
+// Natural log likelihood of the string under a crude model of English text.
+#[cfg(feature = "std")]
+fn text_log_likelihood(input: &str) -> f64 {
+    let letter_freqs = text::Language::English.letter_freqs();
+    let total = letter_freqs.iter().sum::<f64>();
+    input.chars()
+        .map(|c| {
+            let p = match c {
+                ' ' => 0.15,
+                '\n' => 0.01,
+                'a'..='z' => 0.70 * letter_freqs[c as usize - 'a' as usize] / total,
+                'A'..='Z' => 0.04 * letter_freqs[c as usize - 'A' as usize] / total,
+                '0'..='9' => 0.01 / 10.0,
+                _ if c.is_ascii_punctuation() => 0.09 / 32.0,
+                _ => 0.0001,
+            };
+            p.ln()
+        })
+        .sum()
 }
 
-/// Compute the bitwise Hamming distance between two byte arrays.
-pub fn hamming_dist(a: &[u8], b: &[u8]) -> u32 {
-    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+/// Compute the bitwise Hamming distance between two byte arrays, which must be the same
+/// length. The bulk of the arrays is compared 8 bytes at a time.
+pub fn hamming_dist(a: &[u8], b: &[u8]) -> Result<u32, &'static str> {
+    if a.len() != b.len() {
+        return Err("Byte arrays must be the same length.");
+    }
+
+    let words = a.len() / 8 * 8;
+    let mut dist = a[..words].chunks(8)
+        .zip(b[..words].chunks(8))
+        .map(|(x, y)| (read_u64(x) ^ read_u64(y)).count_ones())
+        .sum::<u32>();
+
+    // Handle whatever is left over after the last full word a byte at a time.
+    dist += a[words..].iter()
+        .zip(&b[words..])
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum::<u32>();
+
+    Ok(dist)
+}
+
+// Reinterpret 8 bytes as a u64. Byte order is irrelevant since only bits are counted.
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(bytes);
+    u64::from_ne_bytes(word)
 }
 
 /// Transpose a collection of byte strings. Given M byte strings of length N, returns
@@ -151,8 +590,403 @@ pub fn transpose<'a, T>(input: T) -> Vec<Vec<u8>>
     transposed
 }
 
+/// A single column of `columns`: every `width`th byte, starting from the column's offset.
+pub type Column<'a> = StepBy<slice::Iter<'a, u8>>;
+
+/// Iterator over the columns of a byte array laid out in rows of `width` bytes. Created by
+/// `columns`.
+#[derive(Clone, Debug)]
+pub struct Columns<'a> {
+    data: &'a [u8],
+    width: usize,
+    index: usize,
+}
+
+impl<'a> Iterator for Columns<'a> {
+    type Item = Column<'a>;
+
+    fn next(&mut self) -> Option<Column<'a>> {
+        if self.index >= self.width.min(self.data.len()) {
+            return None;
+        }
+        let column = self.data[self.index..].iter().step_by(self.width);
+        self.index += 1;
+        Some(column)
+    }
+}
+
+/// Lazily transpose a byte array. Viewing the data as rows of `width` bytes (the last of
+/// which may be short), yields an iterator over each column in turn, so the nth column
+/// contains every byte whose index is congruent to n modulo `width`. Equivalent to
+/// `transpose(data.chunks(width))`, but nothing is copied. Panics if `width` is zero.
+pub fn columns(data: &[u8], width: usize) -> Columns<'_> {
+    assert!(width > 0, "Column width must be nonzero.");
+    Columns {
+        data: data,
+        width: width,
+        index: 0,
+    }
+}
+
+/// Iterator over the full blocks of a byte array, created by `blocks`. Any trailing bytes
+/// that don't make up a full block are available through `remainder`.
+#[derive(Clone, Debug)]
+pub struct Blocks<'a> {
+    inner: ChunksExact<'a, u8>,
+}
+
+impl<'a> Blocks<'a> {
+    /// The bytes after the last full block, which may be empty.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.inner.remainder()
+    }
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Blocks<'a> {}
+
+/// Split a byte array into blocks of the given size. Unlike `chunks`, every block yielded
+/// is exactly `size` bytes long; a short final block is kept separate as the remainder.
+/// Panics if `size` is zero.
+pub fn blocks(data: &[u8], size: usize) -> Blocks<'_> {
+    Blocks { inner: data.chunks_exact(size) }
+}
+
+/// Get the nth full block of the given size, or None if the data ends before it.
+pub fn nth_block(data: &[u8], size: usize, n: usize) -> Option<&[u8]> {
+    block_range(data, size, n..(n + 1))
+}
+
+/// Get a mutable reference to the nth full block of the given size, or None if the data
+/// ends before it.
+pub fn nth_block_mut(data: &mut [u8], size: usize, n: usize) -> Option<&mut [u8]> {
+    let end = (n + 1).checked_mul(size)?;
+    data.get_mut((n * size)..end)
+}
+
+/// Get the contiguous run of full blocks with indices in the given range, or None if the
+/// data ends before the last of them.
+pub fn block_range(data: &[u8], size: usize, range: Range<usize>) -> Option<&[u8]> {
+    let end = range.end.checked_mul(size)?;
+    data.get((range.start * size)..end)
+}
+
+/// Serialize 32-bit words as big-endian bytes, as used by SHA-1 and SHA-256.
+pub fn pack_u32_be(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect()
+}
+
+/// Serialize 32-bit words as little-endian bytes, as used by MD4 and MD5.
+pub fn pack_u32_le(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect()
+}
+
+/// Parse big-endian bytes into 32-bit words. The length must be a multiple of 4.
+pub fn unpack_u32_be(bytes: &[u8]) -> Result<Vec<u32>, &'static str> {
+    unpack_u32(bytes, u32::from_be_bytes)
+}
+
+/// Parse little-endian bytes into 32-bit words. The length must be a multiple of 4.
+pub fn unpack_u32_le(bytes: &[u8]) -> Result<Vec<u32>, &'static str> {
+    unpack_u32(bytes, u32::from_le_bytes)
+}
+
+fn unpack_u32(bytes: &[u8], from_bytes: fn([u8; 4]) -> u32) -> Result<Vec<u32>, &'static str> {
+    if bytes.len() % 4 != 0 {
+        return Err("Length is not a multiple of 4 bytes.");
+    }
+    Ok(bytes.chunks(4)
+        .map(|chunk| from_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+/// Rotate a 32-bit word left by `n` bits.
+pub fn rotl32(word: u32, n: u32) -> u32 {
+    word.rotate_left(n)
+}
+
+/// Rotate a 32-bit word right by `n` bits.
+pub fn rotr32(word: u32, n: u32) -> u32 {
+    word.rotate_right(n)
+}
+
+/// Rotate a 64-bit word left by `n` bits.
+pub fn rotl64(word: u64, n: u32) -> u64 {
+    word.rotate_left(n)
+}
+
+/// Rotate a 64-bit word right by `n` bits.
+pub fn rotr64(word: u64, n: u32) -> u64 {
+    word.rotate_right(n)
+}
+
+/// Merkle-Damgard padding for a message of the given length in bytes, with the message
+/// length in bits encoded as a big-endian u64 (SHA-1, SHA-256). Appending this to the
+/// message yields a multiple of 64 bytes. Handy for length extension attacks, which need
+/// the "glue" padding of a message they can't see.
+pub fn md_padding_be(message_len: u64) -> Vec<u8> {
+    md_padding(message_len, message_len.wrapping_mul(8).to_be_bytes())
+}
+
+/// Merkle-Damgard padding for a message of the given length in bytes, with the message
+/// length in bits encoded as a little-endian u64 (MD4, MD5).
+pub fn md_padding_le(message_len: u64) -> Vec<u8> {
+    md_padding(message_len, message_len.wrapping_mul(8).to_le_bytes())
+}
+
+// A single 1 bit, then zeros up to 8 bytes short of a 64-byte boundary, then the length.
+fn md_padding(message_len: u64, length: [u8; 8]) -> Vec<u8> {
+    let zeros = (119 - (message_len % 64) as usize) % 64;
+    let mut padding = Vec::with_capacity(zeros + 9);
+    padding.push(0x80);
+    padding.extend(iter::repeat(0).take(zeros));
+    padding.extend(&length);
+    padding
+}
+
 /// Convenience function to generate a vector of random bytes.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub fn random(size: usize) -> Vec<u8> {
-    let mut rng = OsRng::new().unwrap();
-    rng.gen_iter().take(size).collect()
+    OsSource::new().bytes(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        let bytes = [0x00, 0x0f, 0xa0, 0xff, 0x12];
+        assert_eq!(hex_from_bytes(&bytes), "000fa0ff12");
+        assert_eq!(hex_from_bytes_upper(&bytes), "000FA0FF12");
+        assert_eq!(hex_to_bytes("000fa0ff12"), Ok(bytes.to_vec()));
+        assert_eq!(hex_to_bytes("000FA0\nff 12\n"), Ok(bytes.to_vec()));
+        assert_eq!(hex_to_bytes(""), Ok(vec![]));
+        assert!(hex_to_bytes("abc").is_err());
+        assert!(hex_to_bytes("zz").is_err());
+    }
+
+    #[test]
+    fn test_hamming_dist() {
+        assert_eq!(hamming_dist(b"this is a test", b"wokka wokka!!!"), Ok(37));
+        assert_eq!(hamming_dist(b"", b""), Ok(0));
+        assert!(hamming_dist(b"abc", b"ab").is_err());
+    }
+
+    #[test]
+    fn test_columns() {
+        let data = (0..23).collect::<Vec<u8>>();
+        for width in 1..30 {
+            let lazy = columns(&data, width)
+                .map(|column| column.cloned().collect::<Vec<u8>>())
+                .collect::<Vec<_>>();
+            assert_eq!(lazy, transpose(data.chunks(width)));
+        }
+        assert_eq!(columns(&[], 4).count(), 0);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let data = (0..40).collect::<Vec<u8>>();
+        let mut iter = blocks(&data, 16);
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(&data[0..16]));
+        assert_eq!(iter.next(), Some(&data[16..32]));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.remainder(), &data[32..]);
+        assert!(blocks(&data[..32], 16).remainder().is_empty());
+
+        assert_eq!(nth_block(&data, 16, 1), Some(&data[16..32]));
+        assert_eq!(nth_block(&data, 16, 2), None);
+        assert_eq!(nth_block(&data, 8, 4), Some(&data[32..40]));
+        assert_eq!(block_range(&data, 8, 1..3), Some(&data[8..24]));
+        assert_eq!(block_range(&data, 8, 3..6), None);
+        assert_eq!(block_range(&data, 8, 2..2), Some(&[][..]));
+
+        let mut data = data;
+        nth_block_mut(&mut data, 16, 1).unwrap()[0] = 0xff;
+        assert_eq!(data[16], 0xff);
+        assert!(nth_block_mut(&mut data, 16, 2).is_none());
+    }
+
+    #[test]
+    fn test_word_packing() {
+        let words = [0x01020304, 0xdeadbeef];
+        let be = [0x01, 0x02, 0x03, 0x04, 0xde, 0xad, 0xbe, 0xef];
+        let le = [0x04, 0x03, 0x02, 0x01, 0xef, 0xbe, 0xad, 0xde];
+        assert_eq!(pack_u32_be(&words), be);
+        assert_eq!(pack_u32_le(&words), le);
+        assert_eq!(unpack_u32_be(&be), Ok(words.to_vec()));
+        assert_eq!(unpack_u32_le(&le), Ok(words.to_vec()));
+        assert!(unpack_u32_be(&be[..7]).is_err());
+
+        assert_eq!(rotl32(0x80000001, 1), 0x00000003);
+        assert_eq!(rotr32(0x80000001, 1), 0xc0000000);
+        assert_eq!(rotl64(0x8000000000000001, 4), 0x18);
+        assert_eq!(rotr64(0x18, 4), 0x8000000000000001);
+    }
+
+    #[test]
+    fn test_md_padding() {
+        // "abc" is 24 bits long.
+        let padding = md_padding_be(3);
+        assert_eq!(padding.len(), 61);
+        assert_eq!(padding[0], 0x80);
+        assert_eq!(&padding[53..], &[0, 0, 0, 0, 0, 0, 0, 24]);
+        assert_eq!(&md_padding_le(3)[53..], &[24, 0, 0, 0, 0, 0, 0, 0]);
+
+        // There must always be room for the 0x80 byte and the length.
+        for len in 0..200 {
+            let padding = md_padding_be(len);
+            assert_eq!((len as usize + padding.len()) % 64, 0);
+            assert!(padding.len() >= 9 && padding.len() <= 72);
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        let cases = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                     ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for &(plain, encoded) in &cases {
+            assert_eq!(base64_from_bytes(plain.as_bytes()), encoded);
+            assert_eq!(base64_to_bytes(encoded), Ok(plain.as_bytes().to_vec()));
+        }
+
+        assert!(base64_to_bytes("Zm9vYg").is_err());
+        assert!(base64_to_bytes("Zm9v\nYmFy").is_err());
+        assert!(base64_to_bytes("Zm=vYg==").is_err());
+        assert!(base64_to_bytes("Zg===").is_err());
+        assert!(base64_to_bytes("Zm9vY===").is_err());
+        assert!(base64_to_bytes("Zm9v-_==").is_err());
+    }
+
+    #[test]
+    fn test_base64_configs() {
+        let bytes = [0xfb, 0xff, 0xbf, 0xfe];
+        assert_eq!(base64_encode(&bytes, &BASE64_STANDARD), "+/+//g==");
+        assert_eq!(base64_encode(&bytes, &BASE64_URL_SAFE), "-_-__g");
+        assert_eq!(base64_decode("-_-__g", &BASE64_URL_SAFE), Ok(bytes.to_vec()));
+        assert_eq!(base64_decode("-_-__g==", &BASE64_URL_SAFE), Ok(bytes.to_vec()));
+        assert!(base64_decode("+/+//g", &BASE64_URL_SAFE).is_err());
+        assert!(base64_decode("-_-__", &BASE64_URL_SAFE).is_err());
+
+        let long = (0..100).collect::<Vec<u8>>();
+        let mime = base64_encode(&long, &BASE64_MIME);
+        let lines = mime.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.iter().map(|l| l.len()).collect::<Vec<_>>(), vec![76, 60]);
+        assert_eq!(lines.concat(), base64_from_bytes(&long));
+        assert_eq!(base64_decode(&mime, &BASE64_MIME), Ok(long.clone()));
+        assert_eq!(base64_decode(&mime.replace("\r", ""), &BASE64_MIME), Ok(long));
+    }
+
+    #[test]
+    fn test_base32() {
+        let cases = [("", ""), ("f", "MY======"), ("fo", "MZXQ===="), ("foo", "MZXW6==="),
+                     ("foob", "MZXW6YQ="), ("fooba", "MZXW6YTB"), ("foobar", "MZXW6YTBOI======")];
+        for &(plain, encoded) in &cases {
+            assert_eq!(base32_from_bytes(plain.as_bytes()), encoded);
+            assert_eq!(base32_to_bytes(encoded), Ok(plain.as_bytes().to_vec()));
+        }
+
+        assert_eq!(base32_to_bytes("mzxw6yq"), Ok(b"foob".to_vec()));
+        assert!(base32_to_bytes("MZXW6YQ1").is_err());
+        assert!(base32_to_bytes("MZX=====").is_err());
+        assert!(base32_to_bytes("MY=====").is_err());
+    }
+
+    #[test]
+    fn test_base58() {
+        assert_eq!(base58_from_bytes(b"Hello World!"), "2NEpo7TZRRrLZSi2U");
+        assert_eq!(base58_from_bytes(&[0, 0, 0x28, 0x7f, 0xb4, 0xcd]), "11233QC4");
+        assert_eq!(base58_from_bytes(&[]), "");
+        assert_eq!(base58_to_bytes("2NEpo7TZRRrLZSi2U"), Ok(b"Hello World!".to_vec()));
+        assert_eq!(base58_to_bytes("11233QC4"), Ok(vec![0, 0, 0x28, 0x7f, 0xb4, 0xcd]));
+        assert!(base58_to_bytes("0OIl").is_err());
+    }
+
+    #[test]
+    fn test_ascii85() {
+        let text = &b"Man is distinguished"[..];
+        let encoded = "9jqo^BlbD-BleB1DJ+*+F(f,q";
+        assert_eq!(ascii85_from_bytes(text), encoded);
+        assert_eq!(ascii85_to_bytes(encoded), Ok(text.to_vec()));
+        assert_eq!(ascii85_to_bytes("<~9jqo^BlbD-Bl\neB1DJ+*+F(f,q~>"), Ok(text.to_vec()));
+
+        assert_eq!(ascii85_from_bytes(&[0, 0, 0, 0, 1]), "z!<");
+        assert_eq!(ascii85_to_bytes("z!<"), Ok(vec![0, 0, 0, 0, 1]));
+        assert!(ascii85_to_bytes("!!z!!").is_err());
+        assert!(ascii85_to_bytes("s8W-\"").is_err());
+        assert!(ascii85_to_bytes("9jqo^B").is_err());
+    }
+
+    // `random` needs std, so the checks against random data are kept apart from the rest.
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_random_inputs() {
+        // Compare against a bytewise count at lengths around the word size.
+        let a = random(100);
+        let b = random(100);
+        for len in 0..100 {
+            let expected = a[..len].iter()
+                .zip(&b[..len])
+                .map(|(x, y)| (x ^ y).count_ones())
+                .sum::<u32>();
+            assert_eq!(hamming_dist(&a[..len], &b[..len]), Ok(expected));
+        }
+
+        let random_bytes = random(97);
+        assert_eq!(base32_to_bytes(&base32_from_bytes(&random_bytes)), Ok(random_bytes));
+
+        let mut random_bytes = random(64);
+        random_bytes[0] = 0;
+        assert_eq!(base58_to_bytes(&base58_from_bytes(&random_bytes)), Ok(random_bytes));
+
+        let random_bytes = random(103);
+        assert_eq!(ascii85_to_bytes(&ascii85_from_bytes(&random_bytes)), Ok(random_bytes));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_detect_encoding() {
+        let hex = include_str!("../data/4.txt");
+        let detected = detect_encoding(hex);
+        assert_eq!(detected.format, Format::Hex);
+        assert!(detected.confidence > 0.99);
+        assert_eq!(decode_any(hex), hex_to_bytes(hex).unwrap());
+
+        let base64 = include_str!("../data/6.txt");
+        let detected = detect_encoding(base64);
+        assert_eq!(detected.format, Format::Base64);
+        assert!(detected.confidence > 0.99);
+        assert_eq!(decode_any(base64), base64_decode(base64, &BASE64_MIME).unwrap());
+
+        let text = "Hello, world! This is just some plain text.";
+        let detected = detect_encoding(text);
+        assert_eq!(detected.format, Format::Text);
+        assert!(detected.confidence > 0.99);
+        assert_eq!(decode_any(text), text.as_bytes());
+
+        let base32 = base32_from_bytes(text.as_bytes());
+        assert_eq!(detect_encoding(&base32).format, Format::Base32);
+        assert_eq!(decode_any(&base32), text.as_bytes());
+
+        let url_safe = base64_encode(&[0xfb; 30], &BASE64_URL_SAFE);
+        assert_eq!(detect_encoding(&url_safe).format, Format::Base64);
+
+        // Could plausibly be either a word or a hex string.
+        let detected = detect_encoding("facade");
+        assert_eq!(detected.format, Format::Hex);
+        assert!(detected.confidence < 0.99);
+    }
 }
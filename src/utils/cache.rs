@@ -0,0 +1,163 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+use utils::bytes;
+use utils::sha256::sha256;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn the cache on or off for the whole process. While it's off, `Cache::get_or_compute`
+/// always computes, and neither reads nor writes anything.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// The directory the crate keeps things it can download or recompute in. Taken from the
+/// CRYPTOPALS_CACHE_DIR environment variable if set, and otherwise ~/.cache/cryptopals.
+pub fn base_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CRYPTOPALS_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".cache").join("cryptopals"),
+        None => env::temp_dir().join("cryptopals"),
+    }
+}
+
+// A temporary file to write the given one through, whose name no other writer will pick:
+// the process ID tells processes apart, and a random suffix tells threads apart.
+fn temp_path(path: &Path) -> PathBuf {
+    let suffix = RandomState::new().build_hasher().finish();
+    let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    path.with_file_name(format!("{}.{}-{:016x}.tmp", name, process::id(), suffix))
+}
+
+/// A directory of precomputed values, like the tables an attack builds before it makes any
+/// queries, which take too long to rebuild on every run. Each value is stored as JSON under
+/// its name and a hash of the parameters it was computed from, so changing the parameters
+/// never returns a stale value.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// The cache in the "precomputed" directory under `base_dir()`.
+    pub fn new() -> Self {
+        Self::in_dir(base_dir().join("precomputed"))
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Cache { dir: dir }
+    }
+
+    /// The file a value with the given name and parameters is stored in.
+    pub fn path(&self, name: &str, params: &[u8]) -> PathBuf {
+        let hash = bytes::hex_from_bytes(&sha256(params));
+        self.dir.join(format!("{}-{}.json", name, &hash[..32]))
+    }
+
+    /// Load the value with the given name and parameters, or compute and store it if it
+    /// isn't there, or can't be read. Failing to store it, whether the file can't be written
+    /// or the value can't be serialized, only costs the next run the time to compute it
+    /// again, so it's logged rather than returned.
+    pub fn get_or_compute<T, F>(&self, name: &str, params: &[u8], compute: F) -> T
+        where T: Serialize + DeserializeOwned,
+              F: FnOnce() -> T
+    {
+        if !is_enabled() {
+            return compute();
+        }
+
+        let path = self.path(name, params);
+        let loaded = fs::read(&path).ok().and_then(|data| serde_json::from_slice(&data).ok());
+        if let Some(value) = loaded {
+            debug!("cache: loaded {} from {}", name, path.display());
+            return value;
+        }
+
+        let value = compute();
+        // Write to a temporary file first, so a run that's interrupted, or another one
+        // reading at the same time, never sees half a value.
+        let temp = temp_path(&path);
+        let stored = serde_json::to_vec(&value)
+            .map_err(io::Error::from)
+            .and_then(|data| {
+                fs::create_dir_all(&self.dir)?;
+                fs::write(&temp, data)?;
+                fs::rename(&temp, &path)
+            });
+        match stored {
+            Ok(()) => debug!("cache: stored {} in {}", name, path.display()),
+            Err(err) => {
+                warn!("cache: couldn't store {}: {}", name, err);
+                let _ = fs::remove_file(&temp);
+            }
+        }
+        value
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_cache() {
+        let dir = env::temp_dir().join(format!("cryptopals-cache-test-{}", std::process::id()));
+        let cache = Cache::in_dir(dir.clone());
+        let computed = Cell::new(0);
+        let compute = |n: u32| {
+            computed.set(computed.get() + 1);
+            (0..n).map(|i| i * i).collect::<Vec<u32>>()
+        };
+
+        let table: Vec<u32> = cache.get_or_compute("squares", b"4", || compute(4));
+        assert_eq!(cache.get_or_compute("squares", b"4", || compute(4)), table);
+        assert_eq!(computed.get(), 1);
+
+        // Different parameters are a different value.
+        assert_eq!(cache.get_or_compute("squares", b"5", || compute(5)).len(), 5);
+        assert_eq!(computed.get(), 2);
+
+        // A corrupted file is recomputed.
+        fs::write(cache.path("squares", b"4"), b"not json").unwrap();
+        assert_eq!(cache.get_or_compute("squares", b"4", || compute(4)), table);
+        assert_eq!(computed.get(), 3);
+
+        // With the cache off, everything is recomputed.
+        set_enabled(false);
+        assert_eq!(cache.get_or_compute("squares", b"4", || compute(4)), table);
+        set_enabled(true);
+        assert_eq!(computed.get(), 4);
+
+        // Values that can't be stored as JSON, like maps with non-string keys, are still
+        // returned, just not cached.
+        let map = || (0..4u8).map(|i| (vec![i], i)).collect::<HashMap<Vec<u8>, u8>>();
+        assert_eq!(cache.get_or_compute("blocks", b"", map), map());
+        assert!(!cache.path("blocks", b"").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+use utils::bytes;
+
+// "expand 32-byte k", the first row of the state.
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = bytes::rotl32(state[d] ^ state[a], 16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = bytes::rotl32(state[b] ^ state[c], 12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = bytes::rotl32(state[d] ^ state[a], 8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = bytes::rotl32(state[b] ^ state[c], 7);
+}
+
+/// Compute one 64-byte block of ChaCha20 keystream (RFC 8439) from a 32-byte key, a 12-byte
+/// nonce and a block counter. Panics if the key or nonce is the wrong length.
+pub fn block(key: &[u8], nonce: &[u8], counter: u32) -> [u8; 64] {
+    assert_eq!(key.len(), 32, "ChaCha20 keys must be 32 bytes long.");
+    assert_eq!(nonce.len(), 12, "ChaCha20 nonces must be 12 bytes long.");
+
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&CONSTANTS);
+    initial[4..12].copy_from_slice(&bytes::unpack_u32_le(key).unwrap());
+    initial[12] = counter;
+    initial[13..].copy_from_slice(&bytes::unpack_u32_le(nonce).unwrap());
+
+    // Ten double rounds, each a column round then a diagonal round.
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    for (word, initial) in state.iter_mut().zip(initial.iter()) {
+        *word = word.wrapping_add(*initial);
+    }
+
+    let mut output = [0u8; 64];
+    output.copy_from_slice(&bytes::pack_u32_le(&state));
+    output
+}
+
+/// XOR the ChaCha20 keystream into the data in place, starting from the given block
+/// counter. Encryption and decryption are the same operation.
+pub fn apply_keystream(key: &[u8], nonce: &[u8], counter: u32, data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let keystream = block(key, nonce, counter.wrapping_add(i as u32));
+        for (byte, key) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= key;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chacha20() {
+        // Test vector from RFC 8439, section 2.4.2.
+        let key = (0..32).collect::<Vec<u8>>();
+        let nonce = bytes::hex_to_bytes("000000000000004a00000000").unwrap();
+        let mut data = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+                         one tip for the future, sunscreen would be it."
+            .to_vec();
+        apply_keystream(&key, &nonce, 1, &mut data);
+        assert_eq!(bytes::hex_from_bytes(&data[..32]),
+                   "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b");
+        assert_eq!(bytes::hex_from_bytes(&data[data.len() - 4..]), "5e42874d");
+    }
+}
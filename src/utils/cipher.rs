@@ -0,0 +1,99 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
+#[cfg(feature = "backend-openssl")]
+use openssl::symm::{Cipher, Crypter, Mode};
+#[cfg(feature = "backend-openssl")]
+use openssl_sys::EVP_aes_192_ecb;
+
+#[cfg(not(feature = "backend-openssl"))]
+use utils::aes;
+
+/// A block cipher with a fixed key, operating on one block at a time. Each backend provides
+/// its AES implementation through this interface, and the modes of operation in
+/// `utils::crypto` are built on top of it.
+pub trait BlockCipher {
+    /// The block size in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Encrypt a single block in place.
+    fn encrypt_block(&mut self, block: &mut [u8]);
+
+    /// Decrypt a single block in place.
+    fn decrypt_block(&mut self, block: &mut [u8]);
+}
+
+/// Create an AES-128 cipher with the given key, using the backend selected at build time:
+/// OpenSSL if the `backend-openssl` feature is enabled, and the native implementation in
+/// `utils::aes` otherwise. Panics if the key isn't 16 bytes long.
+pub fn aes_128(key: &[u8]) -> Box<dyn BlockCipher> {
+    assert_eq!(key.len(), 16, "AES-128 keys must be 16 bytes long.");
+    aes(key)
+}
+
+/// Create an AES cipher from the same backend as `aes_128`, with a 16, 24 or 32-byte key for
+/// AES-128, AES-192 or AES-256. Panics if the key is any other length.
+pub fn aes(key: &[u8]) -> Box<dyn BlockCipher> {
+    #[cfg(feature = "backend-openssl")]
+    let cipher = Box::new(OpensslAes::new(key));
+    #[cfg(not(feature = "backend-openssl"))]
+    let cipher = Box::new(aes::Aes::new(key));
+    cipher
+}
+
+/// AES backed by OpenSSL's ECB mode, with padding disabled.
+#[cfg(feature = "backend-openssl")]
+pub struct OpensslAes {
+    encrypter: Crypter,
+    decrypter: Crypter,
+}
+
+#[cfg(feature = "backend-openssl")]
+impl OpensslAes {
+    /// Create a cipher with the given 16, 24 or 32-byte key. Panics if the key is any other
+    /// length.
+    pub fn new(key: &[u8]) -> Self {
+        let cipher = match key.len() {
+            16 => Cipher::aes_128_ecb(),
+            // The bindings only wrap AES-128 and AES-256, so AES-192 comes from libcrypto.
+            24 => unsafe { Cipher::from_ptr(EVP_aes_192_ecb()) },
+            32 => Cipher::aes_256_ecb(),
+            _ => panic!("AES keys must be 16, 24 or 32 bytes long."),
+        };
+        let crypter = |mode| {
+            let mut crypter = Crypter::new(cipher, mode, key, None).unwrap();
+            crypter.pad(false);
+            crypter
+        };
+        OpensslAes {
+            encrypter: crypter(Mode::Encrypt),
+            decrypter: crypter(Mode::Decrypt),
+        }
+    }
+}
+
+// Run a single block through a crypter. OpenSSL insists on room for an extra block of
+// output, even though ECB without padding never produces one.
+#[cfg(feature = "backend-openssl")]
+fn crypt_block(crypter: &mut Crypter, block: &mut [u8]) {
+    assert_eq!(block.len(), 16, "AES blocks must be 16 bytes long.");
+    let mut output = [0u8; 32];
+    let count = crypter.update(block, &mut output).unwrap();
+    assert_eq!(count, 16);
+    block.copy_from_slice(&output[..16]);
+}
+
+#[cfg(feature = "backend-openssl")]
+impl BlockCipher for OpensslAes {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&mut self, block: &mut [u8]) {
+        crypt_block(&mut self.encrypter, block);
+    }
+
+    fn decrypt_block(&mut self, block: &mut [u8]) {
+        crypt_block(&mut self.decrypter, block);
+    }
+}
@@ -0,0 +1,266 @@
+use utils::attacks::Candidate;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::text::{self, Scorer};
+
+/// English letters from most to least common, for a first guess at a substitution key.
+const ENGLISH_ORDER: &'static [u8; 26] = b"ETAOINSHRDLCUMWFGYPBVKJXQZ";
+
+// The alphabet index of a letter, ignoring case, or None for anything else.
+fn letter_index(byte: u8) -> Option<usize> {
+    match byte {
+        b'a'..=b'z' => Some((byte - b'a') as usize),
+        b'A'..=b'Z' => Some((byte - b'A') as usize),
+        _ => None,
+    }
+}
+
+// Replace each letter with the letter at the given index, keeping its case.
+fn with_letter(byte: u8, index: u8) -> u8 {
+    if byte.is_ascii_lowercase() {
+        b'a' + index
+    } else {
+        b'A' + index
+    }
+}
+
+/// Shift each ASCII letter in the text forward through the alphabet by the given amount,
+/// preserving case and leaving all other bytes alone.
+pub fn caesar_shift(text: &[u8], shift: u8) -> Vec<u8> {
+    let shift = shift % 26;
+    text.iter()
+        .map(|&c| match c {
+            b'a'..=b'z' => b'a' + (c - b'a' + shift) % 26,
+            b'A'..=b'Z' => b'A' + (c - b'A' + shift) % 26,
+            _ => c,
+        })
+        .collect()
+}
+
+// Shift each letter by the next letter of the key, forwards or backwards. Only letters use
+// up key letters, as in the classic pen-and-paper cipher.
+fn vigenere(text: &[u8], key: &[u8], forward: bool) -> Result<Vec<u8>, &'static str> {
+    let shifts = key.iter().filter_map(|&byte| letter_index(byte)).collect::<Vec<_>>();
+    if shifts.is_empty() || shifts.len() != key.len() {
+        return Err("Vigenere keys must be nonempty and all letters.");
+    }
+
+    let mut shifts = shifts.iter().cycle();
+    Ok(text.iter()
+        .map(|&byte| match letter_index(byte) {
+            Some(index) => {
+                let shift = *shifts.next().unwrap();
+                let shifted = if forward { index + shift } else { index + 26 - shift };
+                with_letter(byte, (shifted % 26) as u8)
+            }
+            None => byte,
+        })
+        .collect())
+}
+
+/// Encrypt with the Vigenere cipher: each letter is shifted by the corresponding letter of
+/// the repeating key, with A meaning no shift. Case is kept and other bytes are left alone.
+pub fn vigenere_encrypt(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>, &'static str> {
+    vigenere(plaintext, key, true)
+}
+
+pub fn vigenere_decrypt(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>, &'static str> {
+    vigenere(ciphertext, key, false)
+}
+
+/// A monoalphabetic substitution cipher, given by the ciphertext letter for each plaintext
+/// letter from A to Z.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Substitution {
+    key: [u8; 26],
+}
+
+impl Substitution {
+    /// The substitution with the given ciphertext alphabet, which must be a permutation of
+    /// the 26 letters in either case.
+    pub fn new(alphabet: &[u8]) -> Result<Self, &'static str> {
+        let mut key = [0u8; 26];
+        let mut seen = [false; 26];
+        if alphabet.len() != 26 {
+            return Err("Substitution alphabets must have 26 letters.");
+        }
+        for (i, &byte) in alphabet.iter().enumerate() {
+            let index = letter_index(byte).ok_or("Substitution alphabets must be letters.")?;
+            if seen[index] {
+                return Err("Substitution alphabets can't repeat letters.");
+            }
+            seen[index] = true;
+            key[i] = index as u8;
+        }
+        Ok(Substitution { key: key })
+    }
+
+    /// A random substitution.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn random() -> Self {
+        Self::random_with_source(&mut OsSource::new())
+    }
+
+    /// A random substitution, shuffled with the given source.
+    pub fn random_with_source<R>(rng: &mut R) -> Self
+        where R: RandomSource + ?Sized
+    {
+        let mut key = [0u8; 26];
+        for (i, letter) in key.iter_mut().enumerate() {
+            *letter = i as u8;
+        }
+        for i in (1..26).rev() {
+            key.swap(i, rng.range(0, i + 1));
+        }
+        Substitution { key: key }
+    }
+
+    /// The ciphertext alphabet, in upper case.
+    pub fn alphabet(&self) -> Vec<u8> {
+        self.key.iter().map(|&index| b'A' + index).collect()
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        Self::apply(&self.key, plaintext)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Vec<u8> {
+        Self::apply(&self.inverse(), ciphertext)
+    }
+
+    fn inverse(&self) -> [u8; 26] {
+        let mut inverse = [0u8; 26];
+        for (i, &index) in self.key.iter().enumerate() {
+            inverse[index as usize] = i as u8;
+        }
+        inverse
+    }
+
+    fn apply(key: &[u8; 26], text: &[u8]) -> Vec<u8> {
+        text.iter()
+            .map(|&byte| match letter_index(byte) {
+                Some(index) => with_letter(byte, key[index]),
+                None => byte,
+            })
+            .collect()
+    }
+}
+
+// The first guess at a key: match the ciphertext's letters to English ones by frequency.
+fn frequency_guess(ciphertext: &[u8]) -> Substitution {
+    let mut counts = [0usize; 26];
+    for index in ciphertext.iter().filter_map(|&byte| letter_index(byte)) {
+        counts[index] += 1;
+    }
+    let mut by_frequency = (0..26).collect::<Vec<usize>>();
+    by_frequency.sort_by_key(|&index| (usize::max_value() - counts[index], index));
+
+    let mut key = [0u8; 26];
+    for (rank, &index) in by_frequency.iter().enumerate() {
+        key[(ENGLISH_ORDER[rank] - b'A') as usize] = index as u8;
+    }
+    Substitution { key: key }
+}
+
+/// Break a substitution cipher using the quadgram model of English, with a few restarts.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn solve_substitution(ciphertext: &[u8]) -> Candidate {
+    solve_substitution_with(ciphertext, &text::NgramModel::english(4), 4, &mut OsSource::new())
+}
+
+/// Break a substitution cipher by simulated annealing: starting from a key matched to
+/// English letter frequencies, and then from random keys for each restart, repeatedly swap
+/// two letters of the key, keeping swaps that improve the score and, while the temperature
+/// is high, sometimes ones that don't, to get out of local maxima. The result is the best
+/// decryption seen, with the ciphertext alphabet as the key. The scorer should be one that
+/// looks at neighboring letters, like an n-gram model; letter frequencies alone can't tell
+/// the swaps apart.
+pub fn solve_substitution_with<S, R>(ciphertext: &[u8],
+                                     scorer: &S,
+                                     restarts: usize,
+                                     rng: &mut R)
+                                     -> Candidate
+    where S: Scorer + ?Sized,
+          R: RandomSource + ?Sized
+{
+    const STEPS: usize = 10000;
+    const INITIAL_TEMPERATURE: f64 = 10.0;
+
+    let mut best = frequency_guess(ciphertext);
+    let mut best_score = scorer.score(&best.decrypt(ciphertext));
+    for restart in 0..restarts + 1 {
+        let mut key = if restart == 0 {
+            best.clone()
+        } else {
+            Substitution::random_with_source(rng)
+        };
+        let mut score = scorer.score(&key.decrypt(ciphertext));
+
+        for step in 0..STEPS {
+            let temperature = INITIAL_TEMPERATURE * (1.0 - step as f64 / STEPS as f64);
+            let (i, j) = (rng.range(0, 26), rng.range(0, 25));
+            let j = if j >= i { j + 1 } else { j };
+            key.key.swap(i, j);
+            let candidate = scorer.score(&key.decrypt(ciphertext));
+
+            // Accept worse keys with probability e^(difference / temperature).
+            let uniform = rng.next_u64() as f64 / u64::max_value() as f64;
+            if candidate >= score || uniform < ((candidate - score) / temperature).exp() {
+                score = candidate;
+                if score > best_score {
+                    best_score = score;
+                    best = key.clone();
+                }
+            } else {
+                key.key.swap(i, j);
+            }
+        }
+    }
+
+    Candidate {
+        score: best_score,
+        key: best.alphabet(),
+        plaintext: best.decrypt(ciphertext),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_ciphers() {
+        assert_eq!(caesar_shift(b"Hello, World!", 3), b"Khoor, Zruog!".to_vec());
+        assert_eq!(vigenere_encrypt(b"Attack at dawn!", b"LEMON"),
+                   Ok(b"Lxfopv ef rnhr!".to_vec()));
+        assert_eq!(vigenere_decrypt(b"Lxfopv ef rnhr!", b"lemon"),
+                   Ok(b"Attack at dawn!".to_vec()));
+        assert!(vigenere_encrypt(b"hi", b"L3MON").is_err());
+
+        let key = Substitution::new(b"QWERTYUIOPASDFGHJKLZXCVBNM").unwrap();
+        assert_eq!(key.encrypt(b"Hello, World!"), b"Itssg, Vgksr!".to_vec());
+        assert_eq!(key.decrypt(b"Itssg, Vgksr!"), b"Hello, World!".to_vec());
+        assert!(Substitution::new(b"QWERTYUIOPASDFGHJKLZXCVBNQ").is_err());
+    }
+
+    #[test]
+    fn test_solve_substitution() {
+        // Rare letters like Z and Q only get floor scores from the model, so the text avoids
+        // words where they could trade places without changing the score.
+        let plaintext = &b"Call me Ishmael. Some years ago, never mind how long precisely, having \
+                           little or no money in my purse, and nothing particular to interest me \
+                           on shore, I thought I would sail about a little and see the watery \
+                           part of the world. It is a way I have of driving off the spleen and \
+                           regulating the circulation. Whenever I find myself growing grim about \
+                           the mouth, whenever it is a damp, dreary November in my soul, then I \
+                           account it high time to get to sea as soon as I can."[..];
+        let mut rng = SeededRng::new(0);
+        let key = Substitution::random_with_source(&mut rng);
+        let ciphertext = key.encrypt(plaintext);
+        let model = text::NgramModel::english(4);
+        let solution = solve_substitution_with(&ciphertext, &model, 4, &mut rng);
+        assert_eq!(solution.plaintext, plaintext.to_vec());
+    }
+}
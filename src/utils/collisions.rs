@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use utils::parallel;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+
+/// Two different messages whose hashes agree in their first `bits` bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collision {
+    pub first: Vec<u8>,
+    pub second: Vec<u8>,
+    /// The shared truncated hash.
+    pub digest: u64,
+    /// The number of hash evaluations the search took.
+    pub evaluations: u64,
+}
+
+/// The first `bits` bits of a digest, as a big-endian integer. Panics if `bits` is more
+/// than 64 or more than the digest has.
+pub fn truncate(digest: &[u8], bits: u32) -> u64 {
+    assert!(bits <= 64 && bits as usize <= 8 * digest.len(),
+            "Can't truncate to more bits than the digest or a u64 has.");
+    if bits == 0 {
+        return 0;
+    }
+    let mut word = [0u8; 8];
+    let len = digest.len().min(8);
+    word[..len].copy_from_slice(&digest[..len]);
+    u64::from_be_bytes(word) >> (64 - bits)
+}
+
+// The message the walk hashes at a point. Distinct points give distinct messages.
+fn message(point: u64) -> Vec<u8> {
+    point.to_be_bytes().to_vec()
+}
+
+// One walk from a starting point to the first distinguished point on its path.
+struct Trail {
+    start: u64,
+    end: u64,
+    length: u64,
+}
+
+// Walk from `start` until reaching a point whose low `distinguished_bits` bits are zero.
+// Gives up after twenty times the expected length, since the walk may have fallen into a
+// cycle with no distinguished points on it.
+fn walk<F>(step: &F, start: u64, distinguished_bits: u32) -> Option<Trail>
+    where F: Fn(u64) -> u64
+{
+    let mask = (1u64 << distinguished_bits) - 1;
+    let limit = 20u64 << distinguished_bits;
+    let mut point = start;
+    for length in 1..limit + 1 {
+        point = step(point);
+        if point & mask == 0 {
+            return Some(Trail {
+                start: start,
+                end: point,
+                length: length,
+            });
+        }
+    }
+    None
+}
+
+// Two trails end at the same distinguished point, so they merge somewhere. Walk them in
+// step from the same distance to the end, and return the two points just before they meet,
+// along with the number of steps taken. If one trail started on the other, there's no
+// collision to find.
+fn locate<F>(step: &F, a: &Trail, b: &Trail) -> (Option<(u64, u64)>, u64)
+    where F: Fn(u64) -> u64
+{
+    let (long, short) = if a.length >= b.length { (a, b) } else { (b, a) };
+    let mut x = long.start;
+    let mut y = short.start;
+    let mut steps = 0;
+    for _ in 0..long.length - short.length {
+        x = step(x);
+        steps += 1;
+    }
+    if x == y {
+        return (None, steps);
+    }
+    loop {
+        let (next_x, next_y) = (step(x), step(y));
+        steps += 2;
+        if next_x == next_y {
+            return (Some((x, y)), steps);
+        }
+        x = next_x;
+        y = next_y;
+    }
+}
+
+/// Find a collision in the first `bits` bits of a hash function, for widths up to 64 bits.
+/// See find_collision_with().
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find_collision<F>(hash: F, bits: u32) -> Collision
+    where F: Fn(&[u8]) -> Vec<u8> + Sync + Send
+{
+    find_collision_with(hash, bits, bits / 4, &mut OsSource::new())
+}
+
+/// Find a collision in the first `bits` bits of a hash function by parallel collision
+/// search, as described by van Oorschot and Wiener. The truncated hash becomes a random
+/// walk over `bits`-bit points, and each trail is walked until it reaches a distinguished
+/// point, one whose low `distinguished_bits` bits are zero. Only the start, end and length
+/// of each trail are kept, so memory stays small; when two trails end at the same point,
+/// they must have merged, and retracing them finds the collision. Trails are independent,
+/// so each round of them is walked in parallel when the `parallel` feature is enabled.
+///
+/// The search takes about 2^(bits / 2) hash evaluations. More distinguished bits mean less
+/// memory but longer trails, and more work wasted retracing them.
+pub fn find_collision_with<F, R>(hash: F,
+                                 bits: u32,
+                                 distinguished_bits: u32,
+                                 rng: &mut R)
+                                 -> Collision
+    where F: Fn(&[u8]) -> Vec<u8> + Sync + Send,
+          R: RandomSource + ?Sized
+{
+    const TRAILS_PER_ROUND: usize = 64;
+    assert!(bits >= 1 && bits <= 64, "Truncated hashes must be between 1 and 64 bits.");
+    assert!(distinguished_bits < bits, "Distinguished points must be possible.");
+
+    let mask = if bits == 64 { !0 } else { (1 << bits) - 1 };
+    let step = |point: u64| truncate(&hash(&message(point)), bits);
+    let mut ends: HashMap<u64, Trail> = HashMap::new();
+    let mut evaluations = 0;
+    loop {
+        let starts = (0..TRAILS_PER_ROUND).map(|_| rng.next_u64() & mask).collect::<Vec<_>>();
+        let trails = parallel::map(&starts, |&start| walk(&step, start, distinguished_bits));
+        for trail in trails {
+            let trail = match trail {
+                Some(trail) => trail,
+                None => {
+                    evaluations += 20 << distinguished_bits;
+                    continue;
+                }
+            };
+            evaluations += trail.length;
+            let (points, steps) = match ends.entry(trail.end) {
+                Entry::Occupied(other) => locate(&step, other.get(), &trail),
+                Entry::Vacant(entry) => {
+                    entry.insert(trail);
+                    continue;
+                }
+            };
+            evaluations += steps;
+            if let Some((x, y)) = points {
+                return Collision {
+                    first: message(x),
+                    second: message(y),
+                    digest: step(x),
+                    evaluations: evaluations,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+    use utils::sha1::sha1;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate(&[0xde, 0xad, 0xbe, 0xef], 12), 0xdea);
+        assert_eq!(truncate(&[0xde, 0xad, 0xbe, 0xef], 32), 0xdeadbeef);
+        assert_eq!(truncate(&[0xde, 0xad, 0xbe, 0xef], 0), 0);
+    }
+
+    #[test]
+    fn test_find_collision() {
+        let hash = |data: &[u8]| sha1(data).to_vec();
+        let collision = find_collision_with(hash, 32, 8, &mut SeededRng::new(0));
+        assert!(collision.first != collision.second);
+        assert_eq!(truncate(&sha1(&collision.first), 32), collision.digest);
+        assert_eq!(truncate(&sha1(&collision.second), 32), collision.digest);
+    }
+}
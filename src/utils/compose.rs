@@ -0,0 +1,222 @@
+use utils::{attacks, bytes, crypto};
+use utils::mac::verify;
+use utils::sha256::hmac_sha256;
+
+/// One of the three generic ways of combining AES-128-CBC with HMAC-SHA256. Each is created
+/// with a 32-byte key, split into an encryption key and a MAC key, and seals messages under
+/// a fresh random IV. Sealed messages start with the IV and the CBC ciphertext, and may be
+/// followed by a tag in the clear.
+pub trait Composition {
+    /// The length of the tag that follows the ciphertext in the clear. Zero for
+    /// MAC-then-encrypt, whose tag is encrypted along with the message.
+    fn tag_len(&self) -> usize;
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Check and decrypt a sealed message. The errors are distinct, which is realistic and
+    /// is exactly what makes the compositions that decrypt first attackable.
+    fn open(&self, message: &[u8]) -> Result<Vec<u8>, &'static str>;
+}
+
+// The key halves shared by all three compositions.
+struct Keys {
+    encryption: Vec<u8>,
+    mac: Vec<u8>,
+}
+
+impl Keys {
+    fn new(key: &[u8]) -> Self {
+        assert_eq!(key.len(), 32, "Composition keys must be 32 bytes long.");
+        Keys {
+            encryption: key[..16].to_vec(),
+            mac: key[16..].to_vec(),
+        }
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut message = bytes::random(16);
+        let ciphertext = crypto::encrypt_cbc(&self.encryption, &message, plaintext);
+        message.extend(ciphertext);
+        message
+    }
+
+    fn decrypt(&self, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if message.len() < 16 {
+            return Err("Message too short.");
+        }
+        let (iv, ciphertext) = message.split_at(16);
+        crypto::decrypt_cbc_checked(&self.encryption, iv, ciphertext).map_err(|_| "Bad padding.")
+    }
+
+    fn mac(&self, data: &[u8]) -> Vec<u8> {
+        hmac_sha256(&self.mac, data).to_vec()
+    }
+}
+
+// Split a tag of the given length off the end of a message.
+fn split_tag(message: &[u8], tag_len: usize) -> Result<(&[u8], &[u8]), &'static str> {
+    if message.len() < tag_len {
+        return Err("Message too short.");
+    }
+    Ok(message.split_at(message.len() - tag_len))
+}
+
+/// Encrypt, then MAC the IV and ciphertext. Tampered messages are rejected before anything
+/// is decrypted, so they reveal nothing about the plaintext.
+pub struct EncryptThenMac {
+    keys: Keys,
+}
+
+impl EncryptThenMac {
+    pub fn new(key: &[u8]) -> Self {
+        EncryptThenMac { keys: Keys::new(key) }
+    }
+}
+
+impl Composition for EncryptThenMac {
+    fn tag_len(&self) -> usize {
+        32
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut message = self.keys.encrypt(plaintext);
+        let tag = self.keys.mac(&message);
+        message.extend(tag);
+        message
+    }
+
+    fn open(&self, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+        let (message, tag) = split_tag(message, self.tag_len())?;
+        if !verify(&self.keys.mac(message), tag) {
+            return Err("Bad MAC.");
+        }
+        self.keys.decrypt(message)
+    }
+}
+
+/// MAC the plaintext, then encrypt the plaintext and tag together, as SSL and early TLS
+/// did. The padding has to be checked before the MAC can be, so it can be attacked.
+pub struct MacThenEncrypt {
+    keys: Keys,
+}
+
+impl MacThenEncrypt {
+    pub fn new(key: &[u8]) -> Self {
+        MacThenEncrypt { keys: Keys::new(key) }
+    }
+}
+
+impl Composition for MacThenEncrypt {
+    fn tag_len(&self) -> usize {
+        0
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut data = plaintext.to_vec();
+        data.extend(self.keys.mac(plaintext));
+        self.keys.encrypt(&data)
+    }
+
+    fn open(&self, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+        let data = self.keys.decrypt(message)?;
+        let (plaintext, tag) = split_tag(&data, 32)?;
+        if !verify(&self.keys.mac(plaintext), tag) {
+            return Err("Bad MAC.");
+        }
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// Encrypt the plaintext and MAC it separately, as SSH does. Decryption comes before the
+/// MAC check here too, and since the tag is deterministic, equal plaintexts have equal
+/// tags.
+pub struct EncryptAndMac {
+    keys: Keys,
+}
+
+impl EncryptAndMac {
+    pub fn new(key: &[u8]) -> Self {
+        EncryptAndMac { keys: Keys::new(key) }
+    }
+}
+
+impl Composition for EncryptAndMac {
+    fn tag_len(&self) -> usize {
+        32
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut message = self.keys.encrypt(plaintext);
+        message.extend(self.keys.mac(plaintext));
+        message
+    }
+
+    fn open(&self, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+        let (message, tag) = split_tag(message, self.tag_len())?;
+        let plaintext = self.keys.decrypt(message)?;
+        if !verify(&self.keys.mac(&plaintext), tag) {
+            return Err("Bad MAC.");
+        }
+        Ok(plaintext)
+    }
+}
+
+/// Decrypt a sealed message using nothing but whether `open` complains about the padding.
+/// Forged ciphertexts keep the original tag, if there is one. For MAC-then-encrypt the
+/// recovered plaintext includes the encrypted tag.
+///
+/// Succeeds against MAC-then-encrypt and encrypt-and-MAC, and fails against
+/// encrypt-then-MAC, which rejects every forgery before looking at its padding, so that no
+/// forgery is ever reported as badly padded and the oracle says nothing.
+pub fn padding_oracle_attack(composition: &dyn Composition,
+                             message: &[u8])
+                             -> Result<Vec<u8>, &'static str> {
+    let (message, tag) = split_tag(message, composition.tag_len())?;
+    if message.len() < 16 {
+        return Err("Message too short.");
+    }
+    let (iv, ciphertext) = message.split_at(16);
+
+    let mut oracle = |iv: &[u8], ciphertext: &[u8]| {
+        let forged = [iv, ciphertext, tag].concat();
+        composition.open(&forged) != Err("Bad padding.")
+    };
+    attacks::decrypt_cbc_padding_oracle(iv, ciphertext, &mut oracle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compositions() {
+        let key = bytes::random(32);
+        let compositions: Vec<Box<dyn Composition>> = vec![Box::new(EncryptThenMac::new(&key)),
+                                                            Box::new(MacThenEncrypt::new(&key)),
+                                                            Box::new(EncryptAndMac::new(&key))];
+        for composition in &compositions {
+            let mut message = composition.seal(b"attack at dawn");
+            assert_eq!(composition.open(&message), Ok(b"attack at dawn".to_vec()));
+            message[0] ^= 1;
+            assert!(composition.open(&message).is_err());
+        }
+    }
+
+    #[test]
+    fn test_padding_oracle_attack() {
+        let key = bytes::random(32);
+        let secret = b"Cooking MC's like a pound of bacon";
+
+        let composition = MacThenEncrypt::new(&key);
+        let recovered = padding_oracle_attack(&composition, &composition.seal(secret)).unwrap();
+        assert_eq!(&recovered[..secret.len()], &secret[..]);
+
+        let composition = EncryptAndMac::new(&key);
+        let recovered = padding_oracle_attack(&composition, &composition.seal(secret)).unwrap();
+        assert_eq!(recovered, &secret[..]);
+
+        let composition = EncryptThenMac::new(&key);
+        assert_eq!(padding_oracle_attack(&composition, &composition.seal(secret)),
+                   Err("Oracle accepted more than one guess."));
+    }
+}
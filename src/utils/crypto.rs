@@ -1,8 +1,8 @@
+use std::fmt;
 use std::iter;
 
-use openssl::symm::{Cipher, Crypter, Mode};
-
-use utils::bytes;
+#[cfg(feature = "aes")]
+use utils::{bytes, cipher};
 
 /// Pad the given bytes array to the given length using PKCS#7 padding.
 /// Padded length cannot be less than the original length, and can be at most
@@ -24,95 +24,329 @@ pub fn pad_pkcs7(bytes: &[u8], length: usize) -> Result<Vec<u8>, &'static str> {
 /// Remove PKCS#7 padding from the given byte array.
 /// Returns None if the padding is invalid.
 pub fn strip_pkcs7(bytes: &[u8]) -> Option<Vec<u8>> {
-    if let Some(pad) = bytes.last() {
+    if let Some(&pad) = bytes.last() {
+        // There's always at least one byte of padding, and never more than the whole input.
+        if pad == 0 || pad as usize > bytes.len() {
+            return None;
+        }
         // Check if the last `pad` bytes all have a value equal to `pad`.
-        if bytes.iter().rev().take(*pad as usize).all(|byte| *byte == *pad) {
-            return Some(bytes[0..bytes.len() - *pad as usize].to_vec());
+        if bytes.iter().rev().take(pad as usize).all(|byte| *byte == pad) {
+            return Some(bytes[0..bytes.len() - pad as usize].to_vec());
         }
     }
     None
 }
 
-// Clone of the openssl::symm::cipher() function, with the additional option to enable
-// or disable padding of the output.
-fn run_crypter(cipher: Cipher,
-               mode: Mode,
-               key: &[u8],
-               iv: Option<&[u8]>,
-               data: &[u8],
-               pad: bool)
-               -> Vec<u8> {
-    let mut crypter = Crypter::new(cipher, mode, key, iv).unwrap();
-    crypter.pad(pad);
-    let mut output = vec![0; data.len() + cipher.block_size()];
-    let count = crypter.update(data, &mut output).unwrap();
-    let rest = crypter.finalize(&mut output[count..]).unwrap();
-    output.truncate(count + rest);
-    output
-}
+/// Encrypt the given data with AES-128-ECB encryption. Without padding, the data must be
+/// a multiple of the block size.
+#[cfg(feature = "aes")]
+pub fn encrypt_ecb(key: &[u8], data: &[u8], pad: bool) -> Vec<u8> {
+    let mut cipher = cipher::aes_128(key);
+    let block_size = cipher.block_size();
+    let mut data = if pad {
+        pad_pkcs7(data, (data.len() / block_size + 1) * block_size).unwrap()
+    } else {
+        data.to_vec()
+    };
 
-/// Encrypt the given data with AES-128-ECB encryption.
-pub fn encrypt_ecb(key: &[u8], iv: Option<&[u8]>, data: &[u8], pad: bool) -> Vec<u8> {
-    run_crypter(Cipher::aes_128_ecb(), Mode::Encrypt, key, iv, data, pad)
+    assert!(data.len() % block_size == 0, "Data is not a multiple of the block size.");
+    for block in data.chunks_mut(block_size) {
+        cipher.encrypt_block(block);
+    }
+    data
 }
 
-/// Decrypt data encrypted with AES-128-ECB encryption.
-pub fn decrypt_ecb(key: &[u8], iv: Option<&[u8]>, data: &[u8], pad: bool) -> Vec<u8> {
-    run_crypter(Cipher::aes_128_ecb(), Mode::Decrypt, key, iv, data, pad)
+/// Decrypt data encrypted with AES-128-ECB encryption. Panics if the data is not a multiple
+/// of the block size, or if padding is expected but invalid.
+#[cfg(feature = "aes")]
+pub fn decrypt_ecb(key: &[u8], data: &[u8], pad: bool) -> Vec<u8> {
+    let mut cipher = cipher::aes_128(key);
+    let block_size = cipher.block_size();
+    let mut data = data.to_vec();
+
+    assert!(data.len() % block_size == 0, "Ciphertext is not a multiple of the block size.");
+    for block in data.chunks_mut(block_size) {
+        cipher.decrypt_block(block);
+    }
+
+    if pad {
+        strip_pkcs7(&data).expect("Invalid PKCS#7 padding.")
+    } else {
+        data
+    }
 }
 
-/// Basic implementation of a CBC-mode encryption, using OpenSSL's AES-128-ECB function
+/// Basic implementation of a CBC-mode encryption, using AES-128 from the selected backend
 /// as the underlying block cipher.
+#[cfg(feature = "aes")]
 pub fn encrypt_cbc(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
-    // Hardcode this to 16 bytes since we're using 128-bit AES.
-    let block_size = 16;
-
-    // Number of 128-bit blocks in the output. If the input length is a perfect multiple of
-    // the block size, add an extra block due to PKCS#7 padding.
-    let num_blocks = data.len() / block_size + 1;
+    // If the input length is a perfect multiple of the block size, PKCS#7 padding adds an
+    // extra block.
+    let padded_len = (data.len() / 16 + 1) * 16;
+    encrypt_cbc_raw(key, iv, &pad_pkcs7(data, padded_len).unwrap())
+}
 
-    // Pad input to be a perfect multiple of the block size.
-    let padded = pad_pkcs7(data, num_blocks * block_size).unwrap();
+/// Encrypt data with AES-128-CBC without padding it. Panics if the data isn't a whole
+/// number of blocks.
+#[cfg(feature = "aes")]
+pub fn encrypt_cbc_raw(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut cipher = cipher::aes_128(key);
+    let block_size = cipher.block_size();
+    assert!(data.len() % block_size == 0, "Data is not a multiple of the block size.");
 
     // Output vector of encrypted blocks.
-    let mut blocks: Vec<Vec<u8>> = Vec::with_capacity(num_blocks);
+    let mut blocks: Vec<Vec<u8>> = Vec::with_capacity(data.len() / block_size);
 
-    // Break input into 128-bit blocks.
-    for block in padded.chunks(block_size) {
+    // Break input into blocks.
+    for block in bytes::blocks(data, block_size) {
         // XOR with previous ciphertext block (or IV for the first block).
-        let chained = bytes::xor(block, blocks.last().unwrap_or(&iv.to_vec()));
+        let mut chained = bytes::xor(block, blocks.last().unwrap_or(&iv.to_vec()));
 
-        // Encrypt XOR'd block with AES-128-ECB.
-        blocks.push(encrypt_ecb(key, None, &chained, false));
+        // Encrypt the XOR'd block.
+        cipher.encrypt_block(&mut chained);
+        blocks.push(chained);
     }
 
     // Concatenate blocks into final ciphertext.
     blocks.into_iter().flat_map(|block| block.into_iter()).collect()
 }
 
-/// Decrypt data encrypted AES-128-CBC, as implemented by the encrypt_cbc function.
-pub fn decrypt_cbc(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+/// Decrypt data encrypted AES-128-CBC, as implemented by the encrypt_cbc function. Fails if
+/// the ciphertext isn't a whole number of blocks or the padding is invalid.
+#[cfg(feature = "aes")]
+pub fn decrypt_cbc(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    strip_pkcs7(&decrypt_cbc_raw(key, iv, data)?).ok_or("Invalid PKCS#7 padding.")
+}
+
+/// Why a CBC ciphertext couldn't be decrypted. Keeping the two apart is exactly what makes
+/// a padding oracle, so code that answers to an attacker should report them the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingError {
+    /// The IV isn't one block, or the ciphertext isn't a nonzero number of whole blocks.
+    Length,
+    /// The decrypted plaintext doesn't end in valid PKCS#7 padding.
+    Padding,
+}
+
+impl fmt::Display for PaddingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaddingError::Length => write!(f, "Ciphertext is the wrong length."),
+            PaddingError::Padding => write!(f, "Invalid PKCS#7 padding."),
+        }
+    }
+}
+
+/// Decrypt data encrypted with AES-128-CBC, returning an error rather than panicking if the
+/// IV or ciphertext is the wrong length or the padding is invalid.
+#[cfg(feature = "aes")]
+pub fn decrypt_cbc_checked(key: &[u8],
+                           iv: &[u8],
+                           data: &[u8])
+                           -> Result<Vec<u8>, PaddingError> {
+    if iv.len() != 16 || data.is_empty() || data.len() % 16 != 0 {
+        return Err(PaddingError::Length);
+    }
+    let decrypted = decrypt_cbc_raw(key, iv, data).map_err(|_| PaddingError::Length)?;
+    strip_pkcs7(&decrypted).ok_or(PaddingError::Padding)
+}
+
+/// Decrypt data encrypted with AES-128-CBC without removing the padding, or checking that
+/// there is any. Fails if the ciphertext isn't a whole number of blocks.
+#[cfg(feature = "aes")]
+pub fn decrypt_cbc_raw(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut cipher = cipher::aes_128(key);
+
     // Cached ciphertext block for chaining.
     let mut last = None;
 
-    // Break input into 128-bit blocks.
-    let padded = data.chunks(16)
-        .flat_map(|block| {
+    // Break input into blocks. Valid ciphertext never ends with a partial block.
+    let blocks = bytes::blocks(data, cipher.block_size());
+    if !blocks.remainder().is_empty() {
+        return Err("Ciphertext isn't a whole number of blocks.");
+    }
+    Ok(blocks.flat_map(|block| {
             // Decrypt block level encryption.
-            let decrypted = decrypt_ecb(key, None, block, false);
+            let mut decrypted = block.to_vec();
+            cipher.decrypt_block(&mut decrypted);
 
             // XOR against previous ciphertext block (or IV for the first block).
             let chained = bytes::xor(&decrypted, last.unwrap_or(iv));
             last = Some(block);
             chained.into_iter()
         })
-        .collect::<Vec<u8>>();
+        .collect())
+}
+
+/// The three variants of CBC with ciphertext stealing from the addendum to NIST SP 800-38A.
+/// They only differ in the order of the last two ciphertext blocks, the second to last of
+/// which is cut short to the length of the last plaintext block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stealing {
+    /// The short block comes first, as it would in plain CBC.
+    Cs1,
+    /// Like CS3 when the last block is partial, and like plain CBC when it isn't.
+    Cs2,
+    /// The short block always comes last, as in Kerberos.
+    Cs3,
+}
+
+// The length of the last block of a message of the given length, which is never empty.
+#[cfg(feature = "aes")]
+fn last_block_len(len: usize) -> usize {
+    (len + 15) % 16 + 1
+}
+
+// Whether the last two ciphertext blocks are swapped, for a message of the given length.
+#[cfg(feature = "aes")]
+fn swapped(stealing: Stealing, len: usize) -> bool {
+    len > 16 &&
+    match stealing {
+        Stealing::Cs1 => false,
+        Stealing::Cs2 => len % 16 != 0,
+        Stealing::Cs3 => true,
+    }
+}
+
+/// Encrypt data of any length from one block up with AES-128-CBC and ciphertext stealing,
+/// so the ciphertext is exactly as long as the plaintext. The last block is padded with
+/// zeros and encrypted as usual, and then the end of the block before it, which the
+/// decryption of the last block gives back, is left out.
+#[cfg(feature = "aes")]
+pub fn encrypt_cbc_cs(key: &[u8],
+                      iv: &[u8],
+                      data: &[u8],
+                      stealing: Stealing)
+                      -> Result<Vec<u8>, &'static str> {
+    if data.len() < 16 {
+        return Err("Ciphertext stealing needs at least one block.");
+    }
+
+    let partial = last_block_len(data.len());
+    let mut padded = data.to_vec();
+    padded.resize(data.len() - partial + 16, 0);
+    let mut ciphertext = encrypt_cbc_raw(key, iv, &padded);
+    let last = ciphertext.split_off(padded.len() - 16);
+    ciphertext.truncate(data.len() - 16);
+    if swapped(stealing, data.len()) {
+        let short = ciphertext.split_off(data.len() - 16 - partial);
+        ciphertext.extend(last);
+        ciphertext.extend(short);
+    } else {
+        ciphertext.extend(last);
+    }
+    Ok(ciphertext)
+}
+
+/// Decrypt data encrypted with AES-128-CBC and ciphertext stealing. Every ciphertext of at
+/// least one block decrypts to something, since there's no padding to check.
+#[cfg(feature = "aes")]
+pub fn decrypt_cbc_cs(key: &[u8],
+                      iv: &[u8],
+                      data: &[u8],
+                      stealing: Stealing)
+                      -> Result<Vec<u8>, &'static str> {
+    if data.len() < 16 {
+        return Err("Ciphertext stealing needs at least one block.");
+    }
+
+    // Put the blocks in CS1 order: whole blocks, the short block, then the last block.
+    let partial = last_block_len(data.len());
+    let mut ciphertext = data.to_vec();
+    if swapped(stealing, data.len()) {
+        let short = ciphertext.split_off(data.len() - partial);
+        let last = ciphertext.split_off(data.len() - partial - 16);
+        ciphertext.extend(short);
+        ciphertext.extend(last);
+    }
+    if data.len() == 16 {
+        return decrypt_cbc_raw(key, iv, &ciphertext);
+    }
+
+    // The last block decrypts to the zero padded last plaintext block XORed with the whole
+    // second to last ciphertext block, so its tail is the part of that block that was left
+    // out.
+    let last = ciphertext.split_off(data.len() - 16);
+    let decrypted = decrypt_ecb(key, &last, false);
+    let tail = bytes::xor(&decrypted[..partial], &ciphertext[data.len() - 16 - partial..]);
+    ciphertext.extend_from_slice(&decrypted[partial..]);
+    let mut plaintext = decrypt_cbc_raw(key, iv, &ciphertext)?;
+    plaintext.extend(tail);
+    Ok(plaintext)
+}
+
+/// AES-128 in CTR mode, as in challenge 18: the keystream is the encryption of a 64-bit
+/// little-endian nonce followed by a 64-bit little-endian block counter. The keystream can
+/// be sought to any byte offset, so a range of a large ciphertext can be decrypted or
+/// rewritten without generating the keystream that comes before it.
+#[cfg(feature = "aes")]
+pub struct Ctr {
+    cipher: Box<dyn cipher::BlockCipher>,
+    nonce: u64,
+    counter: u64,
+    keystream: Vec<u8>,
+    position: usize,
+}
+
+#[cfg(feature = "aes")]
+impl Ctr {
+    pub fn new(key: &[u8], nonce: u64) -> Self {
+        let mut ctr = Ctr {
+            cipher: cipher::aes_128(key),
+            nonce: nonce,
+            counter: 0,
+            keystream: Vec::new(),
+            position: 0,
+        };
+        ctr.seek(0);
+        ctr
+    }
+
+    /// Move to the given byte offset in the keystream.
+    pub fn seek(&mut self, offset: u64) {
+        let block_size = self.cipher.block_size() as u64;
+        self.counter = offset / block_size;
+        self.position = (offset % block_size) as usize;
+        self.generate_block();
+    }
+
+    /// The current byte offset in the keystream.
+    pub fn offset(&self) -> u64 {
+        self.counter * self.keystream.len() as u64 + self.position as u64
+    }
+
+    /// XOR the keystream into the given data, starting at the current offset and advancing
+    /// past it. Encryption and decryption are the same operation.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for byte in data {
+            if self.position == self.keystream.len() {
+                self.counter += 1;
+                self.position = 0;
+                self.generate_block();
+            }
+            *byte ^= self.keystream[self.position];
+            self.position += 1;
+        }
+    }
+
+    // Encrypt the nonce and current counter into the keystream block.
+    fn generate_block(&mut self) {
+        let mut block = self.nonce.to_le_bytes().to_vec();
+        block.extend_from_slice(&self.counter.to_le_bytes());
+        self.cipher.encrypt_block(&mut block);
+        self.keystream = block;
+    }
+}
 
-    // Strip padding before returning data.
-    strip_pkcs7(&padded).unwrap()
+/// Encrypt or decrypt data with AES-128-CTR, starting from the beginning of the keystream.
+#[cfg(feature = "aes")]
+pub fn ctr(key: &[u8], nonce: u64, data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    Ctr::new(key, nonce).apply_keystream(&mut data);
+    data
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "aes"))]
 mod tests {
     use super::*;
 
@@ -122,7 +356,120 @@ mod tests {
         let key = &b"YELLOW SUBMARINE"[..];
         let iv = &b"abcdefghijklmnop"[..];
         let encrypted = encrypt_cbc(key, iv, input);
-        let decrypted = decrypt_cbc(key, iv, &encrypted);
+        let decrypted = decrypt_cbc(key, iv, &encrypted).unwrap();
         assert_eq!(input, &decrypted[..]);
+        assert_eq!(decrypt_cbc(key, iv, &encrypted[..40]),
+                   Err("Ciphertext isn't a whole number of blocks."));
+
+        assert_eq!(decrypt_cbc_checked(key, iv, &encrypted), Ok(input.to_vec()));
+        let raw = decrypt_cbc_raw(key, iv, &encrypted).unwrap();
+        assert_eq!(&raw[..input.len()], input);
+        assert_eq!(raw[input.len()..], [4; 4]);
+
+        // Any ciphertext block can be decrypted on its own with the one before it as the IV,
+        // but flipping a bit in the previous block breaks the padding.
+        assert_eq!(decrypt_cbc_checked(key, &encrypted[16..32], &encrypted[32..48]),
+                   Ok(input[32..].to_vec()));
+        let mut tampered = encrypted.clone();
+        tampered[31] ^= 1;
+        assert_eq!(decrypt_cbc_checked(key, iv, &tampered), Err(PaddingError::Padding));
+        assert_eq!(decrypt_cbc(key, iv, &tampered), Err("Invalid PKCS#7 padding."));
+        assert_eq!(decrypt_cbc_checked(key, iv, &encrypted[..40]), Err(PaddingError::Length));
+        assert_eq!(decrypt_cbc_checked(key, &iv[..8], &encrypted), Err(PaddingError::Length));
+    }
+
+    #[test]
+    fn test_cbc_cs() {
+        let key = &b"YELLOW SUBMARINE"[..];
+        let iv = &b"abcdefghijklmnop"[..];
+        let message = b"The quick brown fox jumps over the lazy dog, twice over.";
+        for &stealing in &[Stealing::Cs1, Stealing::Cs2, Stealing::Cs3] {
+            for len in 16..message.len() + 1 {
+                let encrypted = encrypt_cbc_cs(key, iv, &message[..len], stealing).unwrap();
+                assert_eq!(encrypted.len(), len);
+                let decrypted = decrypt_cbc_cs(key, iv, &encrypted, stealing).unwrap();
+                assert_eq!(decrypted, &message[..len]);
+            }
+            assert!(encrypt_cbc_cs(key, iv, &message[..15], stealing).is_err());
+        }
+
+        // Whole blocks give plain CBC for CS1 and CS2, and CS3 swaps the last two.
+        let whole = encrypt_cbc_raw(key, iv, &message[..48]);
+        assert_eq!(encrypt_cbc_cs(key, iv, &message[..48], Stealing::Cs2), Ok(whole.clone()));
+        let cs3 = encrypt_cbc_cs(key, iv, &message[..48], Stealing::Cs3).unwrap();
+        assert_eq!(cs3, [&whole[..16], &whole[32..], &whole[16..32]].concat());
+
+        // With no padding there's no padding oracle: every forgery decrypts to something.
+        // It's still CBC, though, and a flipped bit in one block flips the same bit in the
+        // plaintext of the next.
+        let mut encrypted = encrypt_cbc_cs(key, iv, message, Stealing::Cs3).unwrap();
+        encrypted[3] ^= 1;
+        let decrypted = decrypt_cbc_cs(key, iv, &encrypted, Stealing::Cs3).unwrap();
+        assert_eq!(decrypted[19], message[19] ^ 1);
+        assert_eq!(&decrypted[20..], &message[20..]);
+    }
+
+    #[test]
+    fn test_cbc_cs_vectors() {
+        // The AES-128 vectors from RFC 3962, appendix B. Kerberos uses CS3.
+        let key = &b"chicken teriyaki"[..];
+        let message = b"I would like the General Gau's Chicken, please, and wonton soup.";
+        let vectors = [(17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+                       (31, "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+                       (32, "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+                       (47,
+                        "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+                         39312523a78662d5be7fcbcc98ebf5"),
+                       (48,
+                        "97687268d6ecccc0c07b25e25ecfe5849dad8bbb96c4cdc03bc103e1a194bbd8\
+                         39312523a78662d5be7fcbcc98ebf5a8"),
+                       (64,
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+                         4807efe836ee89a526730dbc2f7bc8409dad8bbb96c4cdc03bc103e1a194bbd8")];
+        for &(len, expected) in &vectors {
+            let cs3 = bytes::hex_to_bytes(expected).unwrap();
+
+            // CS1 puts the short block back before the last one. CS2 does that only when
+            // the last block is whole, which makes both plain CBC.
+            let short = (len - 1) % 16 + 1;
+            let (head, tail) = cs3.split_at(len - 16 - short);
+            let cs1 = [head, &tail[16..], &tail[..16]].concat();
+            let cs2 = if short == 16 { cs1.clone() } else { cs3.clone() };
+
+            for &(stealing, ref ciphertext) in &[(Stealing::Cs1, cs1),
+                                                 (Stealing::Cs2, cs2),
+                                                 (Stealing::Cs3, cs3)] {
+                assert_eq!(encrypt_cbc_cs(key, &[0; 16], &message[..len], stealing),
+                           Ok(ciphertext.clone()));
+                assert_eq!(decrypt_cbc_cs(key, &[0; 16], ciphertext, stealing),
+                           Ok(message[..len].to_vec()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ctr() {
+        let ciphertext = bytes::base64_to_bytes("L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyy\
+                                                 MTJULu/6/kXX0KSvoOLSFQ==")
+            .unwrap();
+        let plaintext = ctr(b"YELLOW SUBMARINE", 0, &ciphertext);
+        assert_eq!(bytes::to_string(&plaintext),
+                   "Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ");
+
+        // Seeking into the middle of a block gives the same bytes as decrypting from zero.
+        let mut ctr = Ctr::new(b"YELLOW SUBMARINE", 0);
+        let mut range = ciphertext[21..40].to_vec();
+        ctr.seek(21);
+        ctr.apply_keystream(&mut range);
+        assert_eq!(range, &plaintext[21..40]);
+        assert_eq!(ctr.offset(), 40);
+    }
+
+    #[test]
+    fn test_strip_pkcs7() {
+        assert_eq!(strip_pkcs7(b"ICE ICE BABY\x04\x04\x04\x04"), Some(b"ICE ICE BABY".to_vec()));
+        assert_eq!(strip_pkcs7(b"ICE ICE BABY\x00"), None);
+        assert_eq!(strip_pkcs7(b"\x05\x05\x05"), None);
+        assert_eq!(strip_pkcs7(b""), None);
     }
 }
@@ -0,0 +1,720 @@
+use std::slice;
+
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use utils::{bytes, crypto, math};
+use utils::ec::x25519;
+use utils::protocol::{self, Envelope, Interceptor, Party, Router};
+use utils::protocol::codec::MacCodec;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::sha1::sha1;
+use utils::sha256::hmac_sha256;
+
+// The MODP primes from RFC 3526. All are safe primes, p = 2q + 1 with q prime, and 2
+// generates the subgroup of order q. The 1536-bit one is the prime given in challenge 33.
+static MODP_1536: &'static str = "\
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b139b22514a08798e34\
+    04ddef9519b3cd3a431b302b0a6df25f14374fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6\
+    f406b7edee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf0598da48361c55d39a6916\
+    3fa8fd24cf5f83655d23dca3ad961c62f356208552bb9ed529077096966d670c354e4abc9804f1746c08ca237327\
+    ffffffffffffffff";
+static MODP_2048: &'static str = "\
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b139b22514a08798e34\
+    04ddef9519b3cd3a431b302b0a6df25f14374fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6\
+    f406b7edee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf0598da48361c55d39a6916\
+    3fa8fd24cf5f83655d23dca3ad961c62f356208552bb9ed529077096966d670c354e4abc9804f1746c08ca18217c\
+    32905e462e36ce3be39e772c180e86039b2783a2ec07a28fb5c55df06f4c52c9de2bcbf6955817183995497cea95\
+    6ae515d2261898fa051015728e5a8aacaa68ffffffffffffffff";
+static MODP_3072: &'static str = "\
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b139b22514a08798e34\
+    04ddef9519b3cd3a431b302b0a6df25f14374fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6\
+    f406b7edee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf0598da48361c55d39a6916\
+    3fa8fd24cf5f83655d23dca3ad961c62f356208552bb9ed529077096966d670c354e4abc9804f1746c08ca18217c\
+    32905e462e36ce3be39e772c180e86039b2783a2ec07a28fb5c55df06f4c52c9de2bcbf6955817183995497cea95\
+    6ae515d2261898fa051015728e5a8aaac42dad33170d04507a33a85521abdf1cba64ecfb850458dbef0a8aea7157\
+    5d060c7db3970f85a6e1e4c7abf5ae8cdb0933d71e8c94e04a25619dcee3d2261ad2ee6bf12ffa06d98a0864d876\
+    02733ec86a64521f2b18177b200cbbe117577a615d6c770988c0bad946e208e24fa074e5ab3143db5bfce0fd108e\
+    4b82d120a93ad2caffffffffffffffff";
+
+/// Diffie-Hellman parameters: a prime modulus and a generator, and the order of the
+/// subgroup the generator generates, if it's known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub p: BigInt,
+    pub g: BigInt,
+    pub q: Option<BigInt>,
+}
+
+/// What factoring p - 1 reveals about a group's modulus. Every divisor of p - 1 is the
+/// order of a subgroup of the integers modulo p.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audit {
+    /// p - 1, as far as it could be factored.
+    pub factorization: math::Factorization,
+    /// The prime orders, up to the bound, of subgroups an attacker can confine public keys
+    /// to, in increasing order. Against a peer that doesn't validate public keys, each one
+    /// leaks the peer's private key modulo that order, as in challenge 57.
+    pub small_subgroups: Vec<BigInt>,
+    /// Whether p = 2q + 1 with p and q both prime, which leaves {1, p - 1} as the only
+    /// small subgroup.
+    pub safe_prime: bool,
+}
+
+/// A Diffie-Hellman key pair.
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    pub private: BigInt,
+    pub public: BigInt,
+}
+
+impl Group {
+    /// A group with parameters from elsewhere, such as the other side of a key exchange,
+    /// where nothing is known about the order of g.
+    pub fn new(p: BigInt, g: BigInt) -> Self {
+        Group { p: p, g: g, q: None }
+    }
+
+    // One of the RFC 3526 groups: generator 2, in the subgroup of order (p - 1) / 2.
+    fn modp(prime: &str) -> Self {
+        let p = BigInt::parse_bytes(prime.as_bytes(), 16).unwrap();
+        let q: BigInt = (&p - 1) >> 1usize;
+        Group {
+            p: p,
+            g: BigInt::from(2),
+            q: Some(q),
+        }
+    }
+
+    /// The NIST group used throughout set 5, which is the 1536-bit MODP group.
+    pub fn nist() -> Self {
+        Self::modp_1536()
+    }
+
+    /// The 1536-bit MODP group from RFC 3526, group 5 in IKE.
+    pub fn modp_1536() -> Self {
+        Self::modp(MODP_1536)
+    }
+
+    /// The 2048-bit MODP group from RFC 3526, group 14 in IKE.
+    pub fn modp_2048() -> Self {
+        Self::modp(MODP_2048)
+    }
+
+    /// The 3072-bit MODP group from RFC 3526, group 15 in IKE.
+    pub fn modp_3072() -> Self {
+        Self::modp(MODP_3072)
+    }
+
+    /// Check the parameters for the problems the set 5 and 8 attacks exploit: g must not be
+    /// 0, 1 or p - 1, which confine the shared secret to a couple of values, as g does in
+    /// challenge 35. If the subgroup order is known, g must actually have that order. This
+    /// doesn't check that p is prime, which is too slow to do on every handshake; use the
+    /// named groups for that.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let p_minus_one: BigInt = &self.p - 1;
+        if self.g <= BigInt::one() || self.g >= p_minus_one {
+            return Err("The generator must be in [2, p - 2].");
+        }
+        if let Some(ref q) = self.q {
+            if !p_minus_one.is_multiple_of(q) || !math::modexp(&self.g, q, &self.p).is_one() {
+                return Err("The generator doesn't generate a subgroup of the given order.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a public key before using it: it must be in [2, p - 2], ruling out the keys
+    /// that force the shared secret, like the p that Mallory sends in challenge 34. If the
+    /// subgroup order is known, the key must also be in the subgroup, so that a key of small
+    /// order can't leak the private key a few bits at a time, as in challenge 57.
+    pub fn validate_public(&self, public: &BigInt) -> Result<(), &'static str> {
+        let p_minus_one: BigInt = &self.p - 1;
+        if *public <= BigInt::one() || *public >= p_minus_one {
+            return Err("Public keys must be in [2, p - 2].");
+        }
+        if let Some(ref q) = self.q {
+            if !math::modexp(public, q, &self.p).is_one() {
+                return Err("The public key isn't in the subgroup.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Audit the modulus. See audit_with_source().
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn audit(&self, bound: u64) -> Audit {
+        self.audit_with_source(bound, &mut OsSource::new())
+    }
+
+    /// Audit the modulus by factoring p - 1, finding every prime factor up to `bound` and
+    /// reporting the small subgroups they give, and checking whether p is a safe prime.
+    pub fn audit_with_source<R>(&self, bound: u64, rng: &mut R) -> Audit
+        where R: RandomSource + ?Sized
+    {
+        let factorization = math::factor(&(&self.p - 1), bound, rng);
+        let bound = BigInt::from(bound);
+        let small_subgroups = factorization.factors
+            .iter()
+            .map(|&(ref r, _)| r.clone())
+            .filter(|r| *r <= bound)
+            .collect();
+        let safe_prime = match *factorization.factors.as_slice() {
+            [(ref two, 1), (_, 1)] => {
+                *two == BigInt::from(2) && factorization.is_complete() &&
+                math::is_probable_prime(&self.p, 40, rng)
+            }
+            _ => false,
+        };
+        Audit {
+            factorization: factorization,
+            small_subgroups: small_subgroups,
+            safe_prime: safe_prime,
+        }
+    }
+
+    /// Generate a random key pair.
+    pub fn generate_keypair(&self) -> KeyPair {
+        let size = self.p.to_bytes_be().1.len();
+        let private = BigInt::from_bytes_be(Sign::Plus, &bytes::random(size)) % &self.p;
+        KeyPair {
+            public: math::modexp(&self.g, &private, &self.p),
+            private: private,
+        }
+    }
+
+    /// Compute the shared secret from our private key and the other party's public key.
+    pub fn shared_secret(&self, private: &BigInt, public: &BigInt) -> BigInt {
+        math::modexp(public, private, &self.p)
+    }
+}
+
+/// Derive an AES-128 key from a shared secret, as in challenge 34: the first 16 bytes of
+/// the SHA-1 digest of the secret's big-endian bytes.
+pub fn session_key(secret: &BigInt) -> Vec<u8> {
+    sha1(&secret.to_bytes_be().1)[..16].to_vec()
+}
+
+// The X25519 counterpart of `session_key`, from our private key and the other party's
+// public key.
+fn x25519_session_key(private: &[u8; 32], public: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if public.len() != 32 {
+        return Err("X25519 public keys must be 32 bytes long.");
+    }
+    let mut point = [0u8; 32];
+    point.copy_from_slice(public);
+    Ok(sha1(&x25519::x25519(private, &point))[..16].to_vec())
+}
+
+fn x25519_private_key() -> [u8; 32] {
+    let mut private = [0u8; 32];
+    private.copy_from_slice(&bytes::random(32));
+    private
+}
+
+fn to_bytes(n: &BigInt) -> Vec<u8> {
+    n.to_bytes_be().1
+}
+
+fn from_bytes(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+// Messages after the handshake are AES-CBC ciphertexts with the IV appended.
+fn seal(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let iv = bytes::random(16);
+    let mut sealed = crypto::encrypt_cbc(key, &iv, message);
+    sealed.extend(iv);
+    sealed
+}
+
+fn open(key: &[u8], sealed: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if sealed.len() < 32 || sealed.len() % 16 != 0 {
+        return Err("Malformed ciphertext.");
+    }
+    let (ciphertext, iv) = sealed.split_at(sealed.len() - 16);
+    crypto::decrypt_cbc_checked(key, iv, ciphertext).map_err(|_| "Bad padding.")
+}
+
+// The MACs on a sequenced echo session, one for each direction, with keys derived from the
+// session key and the sender's name so that a message can't be reflected back either.
+struct Sequencer {
+    sending: MacCodec,
+    receiving: MacCodec,
+}
+
+impl Sequencer {
+    fn new(key: &[u8], name: &str, peer: &str) -> Self {
+        Sequencer {
+            sending: MacCodec::new(&hmac_sha256(key, name.as_bytes())),
+            receiving: MacCodec::new(&hmac_sha256(key, peer.as_bytes())),
+        }
+    }
+}
+
+// Put an encrypted message in a MAC'd frame, if the session is sequenced.
+fn seal_frame(sequencer: &mut Option<Sequencer>, sealed: Vec<u8>) -> Vec<u8> {
+    match *sequencer {
+        Some(ref mut sequencer) => sequencer.sending.seal(&sealed),
+        None => sealed,
+    }
+}
+
+// Take an encrypted message out of its frame, if the session is sequenced, checking that
+// it's the next one due.
+fn open_frame(sequencer: &mut Option<Sequencer>, payload: &[u8]) -> Result<Vec<u8>, &'static str> {
+    match *sequencer {
+        Some(ref mut sequencer) => {
+            match sequencer.receiving.open(payload) {
+                Ok((sealed, rest)) if rest.is_empty() => Ok(sealed),
+                _ => Err("Bad MAC or sequence number."),
+            }
+        }
+        None => Ok(payload.to_vec()),
+    }
+}
+
+// Alice's half of the key exchange.
+enum Exchange {
+    Group(Group, KeyPair),
+    X25519([u8; 32]),
+}
+
+/// Alice's side of the challenge 34 echo protocol. She sends the group parameters and her
+/// public key, then a message encrypted under the shared key, and expects it echoed back.
+/// Alternatively, she can send just an X25519 public key, and the rest is the same.
+pub struct EchoClient {
+    exchange: Exchange,
+    message: Vec<u8>,
+    key: Option<Vec<u8>>,
+    sequenced: bool,
+    sequencer: Option<Sequencer>,
+    /// The decrypted echo, once it arrives.
+    pub echo: Option<Vec<u8>>,
+}
+
+impl EchoClient {
+    pub fn new(group: Group, message: &[u8]) -> Self {
+        EchoClient {
+            exchange: Exchange::Group(group.clone(), group.generate_keypair()),
+            message: message.to_vec(),
+            key: None,
+            sequenced: false,
+            sequencer: None,
+            echo: None,
+        }
+    }
+
+    /// A client that agrees on a key with X25519 instead of finite-field Diffie-Hellman.
+    pub fn x25519(message: &[u8]) -> Self {
+        EchoClient {
+            exchange: Exchange::X25519(x25519_private_key()),
+            message: message.to_vec(),
+            key: None,
+            sequenced: false,
+            sequencer: None,
+            echo: None,
+        }
+    }
+
+    /// Put every encrypted message in a `MacCodec` frame, with a key derived from the
+    /// session key, so that a replayed or reordered message is rejected. The server must be
+    /// sequenced too.
+    pub fn sequenced(mut self) -> Self {
+        self.sequenced = true;
+        self
+    }
+}
+
+impl Party for EchoClient {
+    fn name(&self) -> &str {
+        "alice"
+    }
+
+    fn start(&mut self) -> Vec<Envelope> {
+        let payload = match self.exchange {
+            Exchange::Group(ref group, ref keys) => {
+                protocol::encode_fields(&[&to_bytes(&group.p),
+                                          &to_bytes(&group.g),
+                                          &to_bytes(&keys.public)])
+            }
+            Exchange::X25519(ref private) => {
+                protocol::encode_fields(&[&x25519::public_key(private)])
+            }
+        };
+        vec![Envelope::new("alice", "bob", &payload)]
+    }
+
+    fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+        match self.key.clone() {
+            None => {
+                let key = match self.exchange {
+                    Exchange::Group(ref group, ref keys) => {
+                        let public = from_bytes(&message.payload);
+                        session_key(&group.shared_secret(&keys.private, &public))
+                    }
+                    Exchange::X25519(ref private) => {
+                        match x25519_session_key(private, &message.payload) {
+                            Ok(key) => key,
+                            Err(err) => {
+                                warn!("Alice couldn't agree on a key: {}", err);
+                                return Vec::new();
+                            }
+                        }
+                    }
+                };
+                if self.sequenced {
+                    self.sequencer = Some(Sequencer::new(&key, "alice", "bob"));
+                }
+                let sealed = seal_frame(&mut self.sequencer, seal(&key, &self.message));
+                self.key = Some(key);
+                vec![Envelope::new("alice", "bob", &sealed)]
+            }
+            Some(key) => {
+                let echo = open_frame(&mut self.sequencer, &message.payload)
+                    .and_then(|sealed| open(&key, &sealed));
+                match echo {
+                    Ok(echo) => self.echo = Some(echo),
+                    Err(err) => warn!("Alice couldn't read the echo: {}", err),
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.echo.is_some()
+    }
+}
+
+/// Bob's side of the echo protocol. He accepts whatever group parameters he's sent, replies
+/// with his public key, and echoes each message back re-encrypted under a fresh IV. If
+/// he's sent a lone X25519 public key, he replies with his own.
+#[derive(Default)]
+pub struct EchoServer {
+    key: Option<Vec<u8>>,
+    sequenced: bool,
+    sequencer: Option<Sequencer>,
+    /// Every message Bob has decrypted.
+    pub received: Vec<Vec<u8>>,
+}
+
+impl EchoServer {
+    pub fn new() -> Self {
+        EchoServer::default()
+    }
+
+    /// Expect every encrypted message in a `MacCodec` frame, as from a sequenced
+    /// `EchoClient`, and frame the echoes the same way.
+    pub fn sequenced(mut self) -> Self {
+        self.sequenced = true;
+        self
+    }
+
+    fn set_key(&mut self, key: Vec<u8>, peer: &str) {
+        if self.sequenced {
+            self.sequencer = Some(Sequencer::new(&key, "bob", peer));
+        }
+        self.key = Some(key);
+    }
+}
+
+impl Party for EchoServer {
+    fn name(&self) -> &str {
+        "bob"
+    }
+
+    fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+        let reply = match self.key.clone() {
+            None => {
+                let fields = match protocol::decode_fields(&message.payload) {
+                    Ok(ref fields) if fields.len() == 3 || fields.len() == 1 => fields.clone(),
+                    _ => {
+                        warn!("Bob expected p, g and A, or an X25519 key, from {}",
+                              message.from);
+                        return Vec::new();
+                    }
+                };
+                if fields.len() == 1 {
+                    let private = x25519_private_key();
+                    match x25519_session_key(&private, &fields[0]) {
+                        Ok(key) => self.set_key(key, &message.from),
+                        Err(err) => {
+                            warn!("Bob couldn't agree on a key with {}: {}", message.from, err);
+                            return Vec::new();
+                        }
+                    }
+                    return vec![Envelope::new("bob",
+                                              &message.from,
+                                              &x25519::public_key(&private))];
+                }
+                let group = Group::new(from_bytes(&fields[0]), from_bytes(&fields[1]));
+                let keys = group.generate_keypair();
+                let secret = group.shared_secret(&keys.private, &from_bytes(&fields[2]));
+                self.set_key(session_key(&secret), &message.from);
+                to_bytes(&keys.public)
+            }
+            Some(key) => {
+                let plaintext = open_frame(&mut self.sequencer, &message.payload)
+                    .and_then(|sealed| open(&key, &sealed));
+                match plaintext {
+                    Ok(plaintext) => {
+                        let sealed = seal_frame(&mut self.sequencer, seal(&key, &plaintext));
+                        self.received.push(plaintext);
+                        sealed
+                    }
+                    Err(err) => {
+                        warn!("Bob couldn't read a message from {}: {}", message.from, err);
+                        return Vec::new();
+                    }
+                }
+            }
+        };
+        vec![Envelope::new("bob", &message.from, &reply)]
+    }
+
+    fn finished(&self) -> bool {
+        !self.received.is_empty()
+    }
+}
+
+/// What Mallory swaps into the key exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Injection {
+    /// Replace both public keys with p, as in challenge 34, so both secrets are 0.
+    PublicKeys,
+    /// Send Bob g = 1, as in challenge 35, so his public key and both secrets are 1.
+    GeneratorOne,
+    /// Send Bob g = p, so his public key and both secrets are 0.
+    GeneratorP,
+    /// Send Bob g = p - 1, so his public key and secret are 1 or p - 1.
+    GeneratorPMinusOne,
+}
+
+impl Default for Injection {
+    fn default() -> Self {
+        Injection::PublicKeys
+    }
+}
+
+/// The parameter injection attacks from challenges 34 and 35. Mallory tampers with the key
+/// exchange so that each side's shared secret is one she can predict, then decrypts
+/// everything that follows without knowing either private key.
+///
+/// When she sends Bob a malicious g, she sends it as Alice's public key too, so his secret
+/// is his own public key. If that's p - 1, Alice's secret would be 1 or p - 1 depending on
+/// the parity of her private key, so Mallory passes her (p - 1)^2 = 1 instead, and
+/// re-encrypts each message for the side it's going to.
+#[derive(Default)]
+pub struct ParameterInjection {
+    injection: Injection,
+    seen: usize,
+    p: BigInt,
+    alice_key: Vec<u8>,
+    bob_key: Vec<u8>,
+    /// Every message Mallory has decrypted, in order.
+    pub recovered: Vec<Vec<u8>>,
+}
+
+impl ParameterInjection {
+    pub fn new() -> Self {
+        ParameterInjection::default()
+    }
+
+    /// Tamper with the parameters as given, rather than by swapping the public keys.
+    pub fn with_injection(mut self, injection: Injection) -> Self {
+        self.injection = injection;
+        self
+    }
+
+    // The g Bob is sent, or None to swap the public keys instead.
+    fn generator(&self) -> Option<BigInt> {
+        match self.injection {
+            Injection::PublicKeys => None,
+            Injection::GeneratorOne => Some(BigInt::one()),
+            Injection::GeneratorP => Some(self.p.clone()),
+            Injection::GeneratorPMinusOne => Some(&self.p - 1),
+        }
+    }
+}
+
+impl Interceptor for ParameterInjection {
+    fn intercept(&mut self, mut message: Envelope) -> Vec<Envelope> {
+        self.seen += 1;
+        match self.seen {
+            // Alice's p, g and A: pass on p, and swap A for p, or g and A for the new g.
+            1 => {
+                if let Ok(fields) = protocol::decode_fields(&message.payload) {
+                    if fields.len() == 3 {
+                        self.p = from_bytes(&fields[0]);
+                        let (g, public) = match self.generator() {
+                            Some(g) => (to_bytes(&g), to_bytes(&g)),
+                            None => (fields[1].clone(), fields[0].clone()),
+                        };
+                        message.payload = protocol::encode_fields(&[&fields[0], &g, &public]);
+                    }
+                }
+            }
+            // Bob's B: swap it for p, or note that it's his secret, since his peer's public
+            // key was g.
+            2 => {
+                let (alice_secret, bob_secret) = if self.generator().is_none() {
+                    message.payload = to_bytes(&self.p);
+                    (BigInt::zero(), BigInt::zero())
+                } else {
+                    let public = from_bytes(&message.payload);
+                    if public == &self.p - 1 {
+                        message.payload = to_bytes(&BigInt::one());
+                        (BigInt::one(), public)
+                    } else {
+                        (public.clone(), public)
+                    }
+                };
+                self.alice_key = session_key(&alice_secret);
+                self.bob_key = session_key(&bob_secret);
+            }
+            // Everything else is encrypted under a key Mallory knows.
+            _ => {
+                let (key, next_key) = if message.from == "alice" {
+                    (&self.alice_key, &self.bob_key)
+                } else {
+                    (&self.bob_key, &self.alice_key)
+                };
+                match open(key, &message.payload) {
+                    Ok(plaintext) => {
+                        if key != next_key {
+                            message.payload = seal(next_key, &plaintext);
+                        }
+                        self.recovered.push(plaintext);
+                    }
+                    Err(err) => warn!("Mallory couldn't read a message: {}", err),
+                }
+            }
+        }
+        vec![message]
+    }
+}
+
+/// Replay Alice's encrypted message to Bob after their echo session, as an attacker who
+/// recorded it could, and return how many messages Bob accepted. In the plain protocol, he
+/// decrypts the copy and echoes it like the original, so a recorded "transfer $100" can be
+/// repeated at will, and the answer is 2. With both sides sequenced, the copy carries the
+/// MAC for the first message, not the second, and he rejects it, so the answer is 1.
+pub fn replay_echo(sequenced: bool) -> usize {
+    let mut alice = EchoClient::new(Group::nist(), b"transfer $100 to mallory");
+    let mut bob = EchoServer::new();
+    if sequenced {
+        alice = alice.sequenced();
+        bob = bob.sequenced();
+    }
+    let transcript = Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+
+    // The handshake comes first, then the encrypted message.
+    let recorded = transcript.iter().filter(|message| message.from == "alice").nth(1).unwrap();
+    Router::new().add(&mut alice).add(&mut bob).replay(slice::from_ref(recorded)).unwrap();
+    bob.received.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_secret() {
+        let group = Group::nist();
+        let a = group.generate_keypair();
+        let b = group.generate_keypair();
+        assert_eq!(group.shared_secret(&a.private, &b.public),
+                   group.shared_secret(&b.private, &a.public));
+    }
+
+    #[test]
+    fn test_validate() {
+        for group in &[Group::modp_1536(), Group::modp_2048(), Group::modp_3072()] {
+            assert_eq!(group.validate(), Ok(()));
+            assert_eq!(group.validate_public(&group.generate_keypair().public), Ok(()));
+            assert!(group.validate_public(&group.p).is_err());
+            assert!(group.validate_public(&BigInt::one()).is_err());
+
+            // The subgroup is the squares, and -2 isn't one.
+            assert!(group.validate_public(&(&group.p - 2)).is_err());
+        }
+        assert_eq!(Group::nist().p.bits(), 1536);
+        assert!(Group::new(Group::nist().p, BigInt::one()).validate().is_err());
+    }
+
+    #[test]
+    fn test_audit() {
+        use utils::random::SeededRng;
+
+        let mut rng = SeededRng::new(0);
+        // A 128-bit safe prime, since primality tests on the MODP primes are slow.
+        let p = BigInt::parse_bytes(b"229067972870640956458409099131312782059", 10).unwrap();
+        let audit = Group::new(p, BigInt::from(2)).audit_with_source(1 << 16, &mut rng);
+        assert!(audit.safe_prime);
+        assert_eq!(audit.small_subgroups, vec![BigInt::from(2)]);
+
+        // The group from challenge 57, where p - 1 has plenty of small factors.
+        let p = BigInt::parse_bytes(b"7199773997391911030609999317773941274322764333428698\
+                                      9217363396439283464537000853588029739004855929104754\
+                                      80089726140708102474957429903531369589969318716771",
+                                    10)
+            .unwrap();
+        let audit = Group::new(p, BigInt::from(2)).audit_with_source(1 << 16, &mut rng);
+        assert!(!audit.safe_prime);
+        let expected = [2, 3, 5, 109, 7963, 8539, 20641, 38833, 39341, 46337, 51977, 54319,
+                        57529];
+        assert_eq!(audit.small_subgroups,
+                   expected.iter().map(|&r| BigInt::from(r)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_echo() {
+        let mut alice = EchoClient::new(Group::nist(), b"hello bob");
+        let mut bob = EchoServer::new();
+        Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        assert_eq!(alice.echo.unwrap(), b"hello bob");
+
+        let mut alice = EchoClient::x25519(b"hello bob");
+        let mut bob = EchoServer::new();
+        Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        assert_eq!(alice.echo.unwrap(), b"hello bob");
+    }
+
+    #[test]
+    fn test_replay_echo() {
+        assert_eq!(replay_echo(false), 2);
+        assert_eq!(replay_echo(true), 1);
+
+        let mut alice = EchoClient::x25519(b"hello bob").sequenced();
+        let mut bob = EchoServer::new().sequenced();
+        Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        assert_eq!(alice.echo.unwrap(), b"hello bob");
+    }
+
+    #[test]
+    fn test_parameter_injection() {
+        let mut alice = EchoClient::new(Group::nist(), b"attack at dawn");
+        let mut bob = EchoServer::new();
+        let mut mallory = ParameterInjection::new();
+        Router::new().add(&mut alice).add(&mut bob).intercept(&mut mallory).run().unwrap();
+
+        // Neither side notices, and Mallory reads both directions.
+        assert_eq!(alice.echo.unwrap(), b"attack at dawn");
+        assert_eq!(mallory.recovered, vec![b"attack at dawn".to_vec(); 2]);
+
+        for &injection in &[Injection::GeneratorOne,
+                            Injection::GeneratorP,
+                            Injection::GeneratorPMinusOne] {
+            let mut alice = EchoClient::new(Group::nist(), b"attack at dusk");
+            let mut bob = EchoServer::new();
+            let mut mallory = ParameterInjection::new().with_injection(injection);
+            Router::new().add(&mut alice).add(&mut bob).intercept(&mut mallory).run().unwrap();
+            assert_eq!(alice.echo.unwrap(), b"attack at dusk");
+            assert_eq!(mallory.recovered, vec![b"attack at dusk".to_vec(); 2]);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Differential tests of the from-scratch primitives against OpenSSL. Each check draws a
+//! random key, IV or nonce, and message from the source, runs them through both
+//! implementations, and fails if the outputs differ in any byte.
+//!
+//! The modes in `utils::crypto` run on `cipher::aes_128`, which is OpenSSL's block cipher
+//! when both backends are built, so the native AES is checked block by block and the modes
+//! are checked separately against OpenSSL's own. Between them, they cover everything the
+//! native backend would run.
+
+use openssl::symm::{self, Cipher};
+
+use utils::aead::{Aead, Gcm};
+use utils::aes::Aes;
+use utils::cipher::{BlockCipher, OpensslAes};
+use utils::crypto;
+use utils::mac::{Gmac, Mac};
+use utils::random::RandomSource;
+
+// Messages are up to this many bytes, which covers empty messages, partial blocks, and a
+// few whole ones.
+const MAX_LEN: usize = 100;
+
+/// Encrypt and decrypt a random block with the native AES and OpenSSL's, under a random
+/// 16, 24 or 32-byte key.
+pub fn check_block<R>(rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    let key_len = 8 * rng.range(2, 5);
+    let key = rng.bytes(key_len);
+    let mut native = Aes::new(&key);
+    let mut openssl = OpensslAes::new(&key);
+
+    let plaintext = rng.bytes(16);
+    let mut ours = plaintext.clone();
+    let mut theirs = plaintext.clone();
+    native.encrypt_block(&mut ours);
+    openssl.encrypt_block(&mut theirs);
+    if ours != theirs {
+        return Err("AES encryption differs.");
+    }
+    native.decrypt_block(&mut ours);
+    openssl.decrypt_block(&mut theirs);
+    if ours != plaintext || theirs != plaintext {
+        return Err("AES decryption differs.");
+    }
+    Ok(())
+}
+
+/// Encrypt a random message with AES-128-ECB and PKCS#7 padding.
+pub fn check_ecb<R>(rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    let key = rng.bytes(16);
+    let len = rng.range(0, MAX_LEN);
+    let plaintext = rng.bytes(len);
+    let ours = crypto::encrypt_ecb(&key, &plaintext, true);
+    let theirs = symm::encrypt(Cipher::aes_128_ecb(), &key, None, &plaintext).unwrap();
+    if ours != theirs {
+        return Err("AES-128-ECB encryption differs.");
+    }
+    if crypto::decrypt_ecb(&key, &theirs, true) != plaintext {
+        return Err("AES-128-ECB decryption differs.");
+    }
+    Ok(())
+}
+
+/// Encrypt a random message with AES-128-CBC and PKCS#7 padding.
+pub fn check_cbc<R>(rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    let key = rng.bytes(16);
+    let iv = rng.bytes(16);
+    let len = rng.range(0, MAX_LEN);
+    let plaintext = rng.bytes(len);
+    let ours = crypto::encrypt_cbc(&key, &iv, &plaintext);
+    let theirs = symm::encrypt(Cipher::aes_128_cbc(), &key, Some(&iv), &plaintext).unwrap();
+    if ours != theirs {
+        return Err("AES-128-CBC encryption differs.");
+    }
+    if crypto::decrypt_cbc_checked(&key, &iv, &theirs) != Ok(plaintext) {
+        return Err("AES-128-CBC decryption differs.");
+    }
+    Ok(())
+}
+
+/// Encrypt a random message with AES-128-CTR. The counter blocks here are a little-endian
+/// nonce and block count, while OpenSSL counts big-endian across the whole block, so each
+/// block is compared with OpenSSL starting from that block's counter.
+pub fn check_ctr<R>(rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    let key = rng.bytes(16);
+    let nonce = rng.next_u64();
+    let len = rng.range(0, MAX_LEN);
+    let plaintext = rng.bytes(len);
+    let ours = crypto::ctr(&key, nonce, &plaintext);
+    for (i, (block, ours)) in plaintext.chunks(16).zip(ours.chunks(16)).enumerate() {
+        let mut counter = nonce.to_le_bytes().to_vec();
+        counter.extend_from_slice(&(i as u64).to_le_bytes());
+        let theirs = symm::encrypt(Cipher::aes_128_ctr(), &key, Some(&counter), block).unwrap();
+        if ours != &theirs[..] {
+            return Err("AES-128-CTR differs.");
+        }
+    }
+    Ok(())
+}
+
+/// Authenticate a random message with GMAC, against AES-128-GCM with no plaintext, then
+/// seal a random plaintext and associated data with AES-128-GCM.
+pub fn check_gcm<R>(rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    let key = rng.bytes(16);
+    let nonce = rng.bytes(12);
+    let len = rng.range(0, MAX_LEN);
+    let message = rng.bytes(len);
+    let ours = Gmac::new(&key, &nonce).tag(&message);
+    let mut theirs = vec![0u8; 16];
+    symm::encrypt_aead(Cipher::aes_128_gcm(), &key, Some(&nonce), &message, b"", &mut theirs)
+        .unwrap();
+    if ours != theirs {
+        return Err("AES-128-GCM tags differ.");
+    }
+
+    let len = rng.range(0, MAX_LEN);
+    let plaintext = rng.bytes(len);
+    let mut ours = plaintext.clone();
+    let our_tag = Gcm::new(&key).seal(&nonce, &message, &mut ours);
+    let mut their_tag = vec![0u8; 16];
+    let theirs = symm::encrypt_aead(Cipher::aes_128_gcm(),
+                                    &key,
+                                    Some(&nonce),
+                                    &message,
+                                    &plaintext,
+                                    &mut their_tag)
+        .unwrap();
+    if ours != theirs || our_tag != their_tag {
+        return Err("AES-128-GCM differs.");
+    }
+    Ok(())
+}
+
+/// Run every check the given number of times, stopping at the first difference.
+pub fn run<R>(iterations: usize, rng: &mut R) -> Result<(), &'static str>
+    where R: RandomSource + ?Sized
+{
+    for _ in 0..iterations {
+        check_block(rng)?;
+        check_ecb(rng)?;
+        check_cbc(rng)?;
+        check_ctr(rng)?;
+        check_gcm(rng)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_differential() {
+        assert_eq!(run(1000, &mut SeededRng::new(0)), Ok(()));
+    }
+}
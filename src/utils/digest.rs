@@ -0,0 +1,163 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use utils::bytes;
+
+/// A hash function that takes its input in pieces. Like `mac::Mac`, `finalize` returns the
+/// digest of everything since the last `finalize`, so one instance can hash any number of
+/// messages.
+pub trait Digest {
+    /// The size of the blocks the compression function works on, in bytes.
+    fn block_size(&self) -> usize;
+
+    /// The length of the digests this hash produces, in bytes.
+    fn output_len(&self) -> usize;
+
+    /// Add data to the message being hashed.
+    fn update(&mut self, data: &[u8]);
+
+    /// Return the digest of the message so far, and start a new message.
+    fn finalize(&mut self) -> Vec<u8>;
+
+    /// Hash a complete message in one go.
+    fn digest(&mut self, message: &[u8]) -> Vec<u8> {
+        self.update(message);
+        self.finalize()
+    }
+
+    /// A hasher that carries on from the given internal state, as if it had already
+    /// processed `processed_len` bytes, which must be a whole number of blocks. For the
+    /// Merkle-Damgard hashes here the state is just a digest, which is what makes length
+    /// extension attacks possible. Hashes whose state can't be recovered from their output
+    /// return an error.
+    fn from_raw_state(state: &[u8], processed_len: u64) -> Result<Self, &'static str>
+        where Self: Sized;
+}
+
+// The message buffering shared by the Merkle-Damgard hashes: whole blocks go straight to
+// the compression function, and the rest waits for more data or for the final padding.
+#[derive(Clone, Debug)]
+pub(crate) struct Buffer {
+    pending: Vec<u8>,
+    len: u64,
+}
+
+impl Buffer {
+    pub(crate) fn new(processed_len: u64) -> Self {
+        Buffer {
+            pending: Vec::new(),
+            len: processed_len,
+        }
+    }
+
+    pub(crate) fn update<F>(&mut self, data: &[u8], mut compress: F)
+        where F: FnMut(&[u8])
+    {
+        self.len = self.len.wrapping_add(data.len() as u64);
+        self.pending.extend_from_slice(data);
+        let whole = self.pending.len() - self.pending.len() % 64;
+        for block in self.pending[..whole].chunks(64) {
+            compress(block);
+        }
+        self.pending.drain(..whole);
+    }
+
+    // Pad the message with its length in bits as a big-endian u64, compress what's left,
+    // and reset for the next message.
+    pub(crate) fn finish<F>(&mut self, mut compress: F)
+        where F: FnMut(&[u8])
+    {
+        let mut last = self.pending.split_off(0);
+        last.extend(bytes::md_padding_be(self.len));
+        for block in last.chunks(64) {
+            compress(block);
+        }
+        self.len = 0;
+    }
+}
+
+/// Compute HMAC (RFC 2104) of a message under the given key, with any of the hashes.
+pub fn hmac<D>(hash: &mut D, key: &[u8], message: &[u8]) -> Vec<u8>
+    where D: Digest + ?Sized
+{
+    // Keys longer than a block are hashed first; shorter ones are padded with zeros.
+    let mut block = if key.len() > hash.block_size() {
+        hash.digest(key)
+    } else {
+        key.to_vec()
+    };
+    block.resize(hash.block_size(), 0);
+
+    hash.update(&block.iter().map(|byte| byte ^ 0x36).collect::<Vec<u8>>());
+    hash.update(message);
+    let inner = hash.finalize();
+    hash.update(&block.iter().map(|byte| byte ^ 0x5c).collect::<Vec<u8>>());
+    hash.update(&inner);
+    hash.finalize()
+}
+
+/// Derive `len` bytes of key material from a secret with HKDF (RFC 5869): extract a
+/// pseudorandom key from the secret with HMAC keyed by the salt, then expand it by chaining
+/// HMAC blocks over the context info. At most 255 blocks of output can be derived.
+pub fn hkdf<D>(hash: &mut D,
+               salt: &[u8],
+               secret: &[u8],
+               info: &[u8],
+               len: usize)
+               -> Result<Vec<u8>, &'static str>
+    where D: Digest + ?Sized
+{
+    if len > 255 * hash.output_len() {
+        return Err("HKDF can't derive that much key material.");
+    }
+
+    // An empty salt means a block of zeros, which HMAC pads it to anyway.
+    let prk = hmac(hash, salt, secret);
+    let mut okm = Vec::with_capacity(len);
+    let mut block = Vec::new();
+    let mut counter = 1u8;
+    while okm.len() < len {
+        block = hmac(hash, &prk, &[&block[..], info, &[counter]].concat());
+        okm.extend_from_slice(&block);
+        counter = counter.wrapping_add(1);
+    }
+    okm.truncate(len);
+    Ok(okm)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::boxed::Box;
+
+    use super::*;
+    use utils::bytes;
+    use utils::sha1::Sha1;
+    use utils::sha256::Sha256;
+
+    #[test]
+    fn test_streaming() {
+        let message = (0..200).collect::<Vec<u8>>();
+        let hashes: Vec<Box<dyn Digest>> = vec![Box::new(Sha1::new()), Box::new(Sha256::new())];
+        for mut hash in hashes {
+            let expected = hash.digest(&message);
+            for piece in message.chunks(7) {
+                hash.update(piece);
+            }
+            assert_eq!(hash.finalize(), expected);
+        }
+
+        // Test case 2 from RFC 2202.
+        let mac = hmac(&mut Sha1::new(), b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(bytes::hex_from_bytes(&mac), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+
+        // Test case 1 from RFC 5869.
+        let salt = (0..13).collect::<Vec<u8>>();
+        let info = (0xf0..0xfa).collect::<Vec<u8>>();
+        let okm = hkdf(&mut Sha256::new(), &salt, &[0x0b; 22], &info, 42).unwrap();
+        assert_eq!(bytes::hex_from_bytes(&okm),
+                   "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b8\
+                    87185865");
+        assert!(hkdf(&mut Sha256::new(), &salt, b"", b"", 255 * 32 + 1).is_err());
+    }
+}
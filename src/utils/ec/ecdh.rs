@@ -0,0 +1,120 @@
+use num_bigint::BigInt;
+
+use utils::digest;
+use utils::ec::{self, Curve, Point};
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::sha256::Sha256;
+
+/// One side of an elliptic curve Diffie-Hellman exchange: a private scalar d, the public
+/// point dG, and whether to check the other side's point before multiplying it by d.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ecdh {
+    pub curve: Curve,
+    pub private: BigInt,
+    pub public: Point,
+    /// Whether `derive_key` rejects peer points that fail `Curve::validate_point`. With
+    /// this off, a peer can send a point of small order on a different curve and learn d
+    /// modulo that order from the derived key, as in challenge 59.
+    pub validate_peer_point: bool,
+}
+
+impl Ecdh {
+    /// Generate a random key on the given curve that validates peer points.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn generate(curve: &Curve) -> Self {
+        Self::generate_with_source(curve, &mut OsSource::new())
+    }
+
+    /// Generate a key on the given curve, drawing the scalar from the given source.
+    pub fn generate_with_source<R>(curve: &Curve, rng: &mut R) -> Self
+        where R: RandomSource + ?Sized
+    {
+        Self::from_scalar(curve, curve.random_scalar(rng))
+    }
+
+    /// The key with the given scalar, which should be in [1, n). Peer points are validated
+    /// until `validate_peer_point` is turned off.
+    pub fn from_scalar(curve: &Curve, d: BigInt) -> Self {
+        Ecdh {
+            public: curve.mul(&curve.g, &d),
+            curve: curve.clone(),
+            private: d,
+            validate_peer_point: true,
+        }
+    }
+
+    /// Multiply the peer's point by our scalar, and derive `len` bytes of key material from
+    /// the x-coordinate of the result with HKDF-SHA256, using `info` to bind the key to its
+    /// purpose. Fails if the peer's point is rejected, or the result is the point at
+    /// infinity.
+    pub fn derive_key(&self,
+                      peer: &Point,
+                      info: &[u8],
+                      len: usize)
+                      -> Result<Vec<u8>, &'static str> {
+        if self.validate_peer_point {
+            self.curve.validate_point(peer)?;
+        }
+        let x = match self.curve.mul(peer, &self.private) {
+            Point::Infinity => return Err("The shared point is the point at infinity."),
+            Point::Affine(x, _) => x,
+        };
+        let secret = ec::to_fixed_bytes(&x, self.curve.size())?;
+        digest::hkdf(&mut Sha256::new(), b"", &secret, info, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_ecdh() {
+        let curve = Curve::p256();
+        let mut rng = SeededRng::new(0);
+        let alice = Ecdh::generate_with_source(&curve, &mut rng);
+        let bob = Ecdh::generate_with_source(&curve, &mut rng);
+        let key = alice.derive_key(&bob.public, b"test", 32).unwrap();
+        assert_eq!(bob.derive_key(&alice.public, b"test", 32), Ok(key.clone()));
+        assert!(alice.derive_key(&bob.public, b"other", 32).unwrap() != key);
+        assert!(alice.derive_key(&Point::Infinity, b"test", 32).is_err());
+    }
+
+    #[test]
+    fn test_invalid_curve() {
+        // y^2 = x^3 + x + 9 over GF(233), with g of order 103. The point (22, 111) is on
+        // y^2 = x^3 + x + 20 instead, where it has order 13, and addition never uses b.
+        let curve = Curve {
+            p: BigInt::from(233),
+            a: BigInt::from(1),
+            b: BigInt::from(9),
+            g: Point::Affine(BigInt::from(123), BigInt::from(93)),
+            n: BigInt::from(103),
+            h: BigInt::from(2),
+        };
+        let bogus = Point::Affine(BigInt::from(22), BigInt::from(111));
+        let mut victim = Ecdh::from_scalar(&curve, BigInt::from(57));
+        assert!(victim.derive_key(&bogus, b"", 16).is_err());
+
+        // Without the check, the key is one of the 13 the attacker can compute, which gives
+        // d mod 13 up to sign.
+        victim.validate_peer_point = false;
+        let key = victim.derive_key(&bogus, b"", 16).unwrap();
+        let candidates = (1..13)
+            .filter(|&k| {
+                let guess = Ecdh { private: BigInt::from(k), ..victim.clone() };
+                guess.derive_key(&bogus, b"", 16) == Ok(key.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(candidates, vec![57 % 13, 13 - 57 % 13]);
+
+        // Shifting x by p doesn't change the point. 27 = 11011 in binary, so the private
+        // key reaches 12P = -P before its last additions.
+        let shifted = Point::Affine(BigInt::from(22 + 233), BigInt::from(111));
+        let victim = Ecdh { private: BigInt::from(27), ..victim };
+        assert_eq!(victim.derive_key(&shifted, b"", 16), victim.derive_key(&bogus, b"", 16));
+    }
+}
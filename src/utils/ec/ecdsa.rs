@@ -0,0 +1,224 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Zero;
+
+use utils::ec::{Curve, Point};
+use utils::math;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::sha256::{hmac_sha256, sha256};
+
+/// An ECDSA signature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub r: BigInt,
+    pub s: BigInt,
+}
+
+/// An ECDSA public key: a point on the curve.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+    pub curve: Curve,
+    pub point: Point,
+}
+
+/// An ECDSA private key: a scalar d, and the public point dG.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivateKey {
+    pub curve: Curve,
+    pub d: BigInt,
+    pub point: Point,
+}
+
+// Turn a hash into an integer, keeping only as many leading bits as the order has. This is
+// bits2int from RFC 6979, section 2.3.2.
+fn bits_to_int(curve: &Curve, hash: &[u8]) -> BigInt {
+    let n = BigInt::from_bytes_be(Sign::Plus, hash);
+    let excess = (8 * hash.len()).saturating_sub(curve.n.bits());
+    n >> excess
+}
+
+// A nonnegative integer below n as a big-endian byte string as long as n.
+fn int_to_bytes(curve: &Curve, n: &BigInt) -> Vec<u8> {
+    let size = (curve.n.bits() + 7) / 8;
+    let bytes = n.to_bytes_be().1;
+    let mut padded = vec![0u8; size - bytes.len()];
+    padded.extend(bytes);
+    padded
+}
+
+impl PublicKey {
+    /// Decode a SEC 1 encoded public key, rejecting points that aren't valid keys.
+    pub fn from_bytes(curve: &Curve, data: &[u8]) -> Result<Self, &'static str> {
+        let point = curve.decode_point(data)?;
+        curve.validate_point(&point)?;
+        Ok(PublicKey {
+            curve: curve.clone(),
+            point: point,
+        })
+    }
+
+    /// Encode the key as a SEC 1 point, compressed or not.
+    pub fn to_bytes(&self, compressed: bool) -> Vec<u8> {
+        self.curve.encode_point(&self.point, compressed)
+    }
+
+    /// Verify a signature on a message hashed with SHA-256.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let curve = &self.curve;
+        let in_range = |x: &BigInt| x.sign() == Sign::Plus && *x < curve.n;
+        if !in_range(&signature.r) || !in_range(&signature.s) {
+            return false;
+        }
+
+        let e = bits_to_int(curve, &sha256(message));
+        let w = math::invmod(&signature.s, &curve.n).unwrap();
+        let u1 = (e * &w).mod_floor(&curve.n);
+        let u2 = (&signature.r * &w).mod_floor(&curve.n);
+        match curve.add(&curve.mul(&curve.g, &u1), &curve.mul(&self.point, &u2)) {
+            Point::Infinity => false,
+            Point::Affine(x, _) => x.mod_floor(&curve.n) == signature.r,
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Generate a random key on the given curve.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn generate(curve: &Curve) -> Self {
+        Self::generate_with_source(curve, &mut OsSource::new())
+    }
+
+    /// Generate a key on the given curve, drawing the scalar from the given source.
+    pub fn generate_with_source<R>(curve: &Curve, rng: &mut R) -> Self
+        where R: RandomSource + ?Sized
+    {
+        Self::from_scalar(curve, curve.random_scalar(rng))
+    }
+
+    /// The key with the given scalar, which should be in [1, n).
+    pub fn from_scalar(curve: &Curve, d: BigInt) -> Self {
+        PrivateKey {
+            point: curve.mul(&curve.g, &d),
+            curve: curve.clone(),
+            d: d,
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            curve: self.curve.clone(),
+            point: self.point.clone(),
+        }
+    }
+
+    /// Sign a message hashed with SHA-256, using a random nonce.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.sign_with_source(message, &mut OsSource::new())
+    }
+
+    /// Sign a message, drawing the nonce from the given source.
+    pub fn sign_with_source<R>(&self, message: &[u8], rng: &mut R) -> Signature
+        where R: RandomSource + ?Sized
+    {
+        loop {
+            let k = self.curve.random_scalar(rng);
+            if let Some(signature) = self.sign_with_nonce(message, &k) {
+                return signature;
+            }
+        }
+    }
+
+    /// Sign a message with a nonce derived from the key and the message hash as in
+    /// RFC 6979, so that signing needs no randomness at all and can't leak the key through
+    /// a bad random number generator.
+    pub fn sign_deterministic(&self, message: &[u8]) -> Signature {
+        let curve = &self.curve;
+        let x = int_to_bytes(curve, &self.d);
+        let h = int_to_bytes(curve, &bits_to_int(curve, &sha256(message)).mod_floor(&curve.n));
+
+        // Section 3.2, steps b through f.
+        let mut v = vec![0x01u8; 32];
+        let mut k = vec![0x00u8; 32];
+        for &separator in &[0x00, 0x01] {
+            k = hmac_sha256(&k, &[&v[..], &[separator], &x[..], &h[..]].concat()).to_vec();
+            v = hmac_sha256(&k, &v).to_vec();
+        }
+
+        // Step h: generate candidates until one is in range and gives a valid signature.
+        loop {
+            let mut t = Vec::new();
+            while 8 * t.len() < curve.n.bits() {
+                v = hmac_sha256(&k, &v).to_vec();
+                t.extend_from_slice(&v);
+            }
+            let nonce = bits_to_int(curve, &t);
+            if !nonce.is_zero() && nonce < curve.n {
+                if let Some(signature) = self.sign_with_nonce(message, &nonce) {
+                    return signature;
+                }
+            }
+            k = hmac_sha256(&k, &[&v[..], &[0x00]].concat()).to_vec();
+            v = hmac_sha256(&k, &v).to_vec();
+        }
+    }
+
+    /// Sign a message with the given nonce, which must be in [1, n) and must never be
+    /// reused or even partly predictable. Returns None in the unlikely event that the nonce
+    /// gives r = 0 or s = 0. This is exposed so that the nonce attacks can pick bad nonces.
+    pub fn sign_with_nonce(&self, message: &[u8], k: &BigInt) -> Option<Signature> {
+        let curve = &self.curve;
+        let r = match curve.mul(&curve.g, k) {
+            Point::Infinity => return None,
+            Point::Affine(x, _) => x.mod_floor(&curve.n),
+        };
+        let e = bits_to_int(curve, &sha256(message));
+        let s = (math::invmod(k, &curve.n)? * (e + &r * &self.d)).mod_floor(&curve.n);
+        if r.is_zero() || s.is_zero() {
+            return None;
+        }
+        Some(Signature { r: r, s: s })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    fn hex(s: &str) -> BigInt {
+        BigInt::parse_bytes(s.as_bytes(), 16).unwrap()
+    }
+
+    #[test]
+    fn test_ecdsa() {
+        let key = PrivateKey::generate_with_source(&Curve::p256(), &mut SeededRng::new(0));
+        let public = key.public_key();
+        assert!(key.curve.contains(&public.point));
+        let encoded = public.to_bytes(true);
+        assert_eq!(PublicKey::from_bytes(&key.curve, &encoded), Ok(public.clone()));
+        assert!(PublicKey::from_bytes(&key.curve, &[0]).is_err());
+
+        let signature = key.sign_with_source(b"hi mom", &mut SeededRng::new(1));
+        assert!(public.verify(b"hi mom", &signature));
+        assert!(!public.verify(b"hi dad", &signature));
+        let mut forged = signature.clone();
+        forged.s = &key.curve.n - &forged.s + 1;
+        assert!(!public.verify(b"hi mom", &forged));
+    }
+
+    #[test]
+    fn test_rfc6979() {
+        // The P-256 and SHA-256 example from RFC 6979, appendix A.2.5.
+        let d = hex("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721");
+        let key = PrivateKey::from_scalar(&Curve::p256(), d);
+        let signature = key.sign_deterministic(b"sample");
+        assert_eq!(signature.r,
+                   hex("efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716"));
+        assert_eq!(signature.s,
+                   hex("f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8"));
+        assert!(key.public_key().verify(b"sample", &signature));
+    }
+}
@@ -0,0 +1,359 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+use utils::math;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+
+pub mod ecdh;
+pub mod ecdsa;
+pub mod montgomery;
+pub mod x25519;
+
+/// A point on an elliptic curve, in affine coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Point {
+    /// The point at infinity, which is the group's identity.
+    Infinity,
+    Affine(BigInt, BigInt),
+}
+
+/// A short Weierstrass curve y^2 = x^3 + ax + b over the prime field of order p, along
+/// with a base point g of prime order n, and the cofactor h, which is the number of points
+/// on the curve divided by n.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    pub p: BigInt,
+    pub a: BigInt,
+    pub b: BigInt,
+    pub g: Point,
+    pub n: BigInt,
+    pub h: BigInt,
+}
+
+fn hex(s: &str) -> BigInt {
+    BigInt::parse_bytes(s.as_bytes(), 16).unwrap()
+}
+
+// A nonnegative integer as a big-endian byte string of the given length. Fails if the
+// integer is negative or doesn't fit.
+fn to_fixed_bytes(n: &BigInt, size: usize) -> Result<Vec<u8>, &'static str> {
+    let (sign, bytes) = n.to_bytes_be();
+    if sign == Sign::Minus || bytes.len() > size {
+        return Err("Integer doesn't fit in the encoding.");
+    }
+    let mut padded = vec![0u8; size - bytes.len()];
+    padded.extend(bytes);
+    Ok(padded)
+}
+
+impl Curve {
+    /// NIST P-256, also known as secp256r1 (FIPS 186-4, appendix D.1.2.3).
+    pub fn p256() -> Self {
+        let p = hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff");
+        let gx = hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+        let gy = hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5");
+        Curve {
+            a: &p - 3,
+            b: hex("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b"),
+            g: Point::Affine(gx, gy),
+            n: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            h: BigInt::one(),
+            p: p,
+        }
+    }
+
+    /// The size of the field and of scalars in bytes.
+    pub fn size(&self) -> usize {
+        (self.p.bits() + 7) / 8
+    }
+
+    /// Check whether a point satisfies the curve equation. The point at infinity always
+    /// does.
+    pub fn contains(&self, point: &Point) -> bool {
+        match *point {
+            Point::Infinity => true,
+            Point::Affine(ref x, ref y) => {
+                let lhs = (y * y).mod_floor(&self.p);
+                let rhs = (x * x * x + &self.a * x + &self.b).mod_floor(&self.p);
+                lhs == rhs
+            }
+        }
+    }
+
+    /// Check that a point from someone else is safe to multiply by a secret scalar: it
+    /// mustn't be the point at infinity, its coordinates must be in [0, p), and it must be
+    /// on the curve and in the subgroup generated by g. The invalid-curve attack sends
+    /// points that aren't on the curve, and on curves with a cofactor, small-subgroup
+    /// attacks send points that are on it but outside the subgroup.
+    pub fn validate_point(&self, point: &Point) -> Result<(), &'static str> {
+        let in_field = |z: &BigInt| !z.is_negative() && *z < self.p;
+        match *point {
+            Point::Infinity => return Err("The point at infinity isn't a valid key."),
+            Point::Affine(ref x, ref y) if !in_field(x) || !in_field(y) => {
+                return Err("Coordinates must be in [0, p).");
+            }
+            _ => {}
+        }
+        if !self.contains(point) {
+            return Err("The point isn't on the curve.");
+        }
+        if !self.h.is_one() && self.mul(point, &self.n) != Point::Infinity {
+            return Err("The point isn't in the subgroup generated by g.");
+        }
+        Ok(())
+    }
+
+    /// Check the curve parameters. See validate_with_source().
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn validate(&self) -> Result<(), &'static str> {
+        self.validate_with_source(&mut OsSource::new())
+    }
+
+    /// Check the curve parameters: p and n must be prime, the curve mustn't be singular,
+    /// and g must be a point on it of order n. The number of points h n must also be within
+    /// Hasse's bound, |p + 1 - h n| <= 2 sqrt(p), which pins down h when n is large.
+    pub fn validate_with_source<R>(&self, rng: &mut R) -> Result<(), &'static str>
+        where R: RandomSource + ?Sized
+    {
+        if !math::is_probable_prime(&self.p, 40, rng) ||
+           !math::is_probable_prime(&self.n, 40, rng) {
+            return Err("The field size and the order of g must be prime.");
+        }
+        let discriminant: BigInt = 4 * &self.a * &self.a * &self.a + 27 * &self.b * &self.b;
+        if discriminant.mod_floor(&self.p).is_zero() {
+            return Err("The curve is singular.");
+        }
+        if self.g == Point::Infinity || !self.contains(&self.g) ||
+           self.mul(&self.g, &self.n) != Point::Infinity {
+            return Err("The base point must be on the curve, with order n.");
+        }
+        let trace: BigInt = &self.p + 1 - &self.h * &self.n;
+        if !self.h.is_positive() || &trace * &trace > 4 * &self.p {
+            return Err("The cofactor doesn't match the number of points.");
+        }
+        Ok(())
+    }
+
+    /// Encode a point as in SEC 1, section 2.3.3: a zero byte for the point at infinity,
+    /// and otherwise 0x04, x and y, or in compressed form, 0x02 or 0x03 for the parity of y,
+    /// then just x. Coordinates take `size()` bytes each, after being reduced mod p, so a
+    /// point built with coordinates out of range is encoded as the point they stand for.
+    pub fn encode_point(&self, point: &Point, compressed: bool) -> Vec<u8> {
+        let (x, y) = match *point {
+            Point::Infinity => return vec![0],
+            Point::Affine(ref x, ref y) => (x.mod_floor(&self.p), y.mod_floor(&self.p)),
+        };
+        let mut encoded = if !compressed {
+            vec![4]
+        } else if y.is_odd() {
+            vec![3]
+        } else {
+            vec![2]
+        };
+        // Reduced coordinates are less than p, so they always fit.
+        encoded.extend(to_fixed_bytes(&x, self.size()).unwrap());
+        if !compressed {
+            encoded.extend(to_fixed_bytes(&y, self.size()).unwrap());
+        }
+        encoded
+    }
+
+    /// Decode a point in either SEC 1 form. A compressed point is decompressed by taking a
+    /// square root of x^3 + ax + b and picking the one with the right parity, which puts it
+    /// on the curve; an uncompressed point is rejected unless it's on the curve. Neither is
+    /// checked to be in the subgroup; see validate_point().
+    pub fn decode_point(&self, data: &[u8]) -> Result<Point, &'static str> {
+        let size = self.size();
+        let (x, y) = match data.first() {
+            Some(&0) if data.len() == 1 => return Ok(Point::Infinity),
+            Some(&4) if data.len() == 1 + 2 * size => {
+                (BigInt::from_bytes_be(Sign::Plus, &data[1..1 + size]),
+                 BigInt::from_bytes_be(Sign::Plus, &data[1 + size..]))
+            }
+            Some(&tag @ 2) | Some(&tag @ 3) if data.len() == 1 + size => {
+                let x = BigInt::from_bytes_be(Sign::Plus, &data[1..]);
+                let rhs = &x * &x * &x + &self.a * &x + &self.b;
+                let y = math::mod_sqrt(&rhs, &self.p)
+                    .ok_or("No point on the curve has that x-coordinate.")?;
+                if y.is_odd() == (tag == 3) {
+                    (x, y)
+                } else {
+                    (x, &self.p - y)
+                }
+            }
+            _ => return Err("Invalid point encoding."),
+        };
+        if x >= self.p || y >= self.p {
+            return Err("Coordinates must be in [0, p).");
+        }
+        let point = Point::Affine(x, y);
+        if !self.contains(&point) {
+            return Err("The point isn't on the curve.");
+        }
+        Ok(point)
+    }
+
+    pub fn negate(&self, point: &Point) -> Point {
+        match *point {
+            Point::Infinity => Point::Infinity,
+            Point::Affine(ref x, ref y) => Point::Affine(x.clone(), (-y).mod_floor(&self.p)),
+        }
+    }
+
+    // The same point with its coordinates reduced mod p.
+    fn reduce(&self, point: &Point) -> Point {
+        match *point {
+            Point::Infinity => Point::Infinity,
+            Point::Affine(ref x, ref y) => {
+                Point::Affine(x.mod_floor(&self.p), y.mod_floor(&self.p))
+            }
+        }
+    }
+
+    /// Add two points with the chord-and-tangent rule. Neither point is checked to be on
+    /// the curve, which is what invalid-curve attacks rely on, but coordinates are reduced
+    /// mod p first, so a peer can't make the slope's denominator vanish by sending x + p.
+    pub fn add(&self, first: &Point, second: &Point) -> Point {
+        let (first, second) = (self.reduce(first), self.reduce(second));
+        let (x1, y1, x2, y2) = match (&first, &second) {
+            (&Point::Infinity, _) => return second.clone(),
+            (_, &Point::Infinity) => return first.clone(),
+            (&Point::Affine(ref x1, ref y1), &Point::Affine(ref x2, ref y2)) => (x1, y1, x2, y2),
+        };
+        // A vertical chord or tangent meets the curve again at infinity. On the curve, that
+        // means the points are each other's negation, or a point of order 2 doubled.
+        if x1 == x2 && (y1 != y2 || y1.is_zero()) {
+            return Point::Infinity;
+        }
+
+        let slope = if x1 == x2 {
+            (3 * x1 * x1 + &self.a) * math::invmod(&(2 * y1), &self.p).unwrap()
+        } else {
+            (y2 - y1) * math::invmod(&(x2 - x1).mod_floor(&self.p), &self.p).unwrap()
+        };
+        let slope = slope.mod_floor(&self.p);
+        let x3 = (&slope * &slope - x1 - x2).mod_floor(&self.p);
+        let y3 = (slope * (x1 - &x3) - y1).mod_floor(&self.p);
+        Point::Affine(x3, y3)
+    }
+
+    /// Multiply a point by a scalar with double-and-add. Negative scalars multiply the
+    /// negated point.
+    pub fn mul(&self, point: &Point, k: &BigInt) -> Point {
+        if k.is_negative() {
+            return self.mul(&self.negate(point), &-k);
+        }
+        let mut result = Point::Infinity;
+        for byte in k.to_bytes_be().1 {
+            for i in (0..8).rev() {
+                result = self.add(&result, &result);
+                if byte >> i & 1 == 1 {
+                    result = self.add(&result, point);
+                }
+            }
+        }
+        result
+    }
+
+    /// A uniformly random scalar in [1, n).
+    pub fn random_scalar<R>(&self, rng: &mut R) -> BigInt
+        where R: RandomSource + ?Sized
+    {
+        let size = (self.n.bits() + 7) / 8;
+        loop {
+            let k = BigInt::from_bytes_be(Sign::Plus, &rng.bytes(size));
+            if !k.is_zero() && k < self.n {
+                return k;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p256() {
+        let curve = Curve::p256();
+        assert!(curve.contains(&curve.g));
+        assert_eq!(curve.mul(&curve.g, &curve.n), Point::Infinity);
+
+        let two_g = curve.add(&curve.g, &curve.g);
+        let three_g = curve.add(&two_g, &curve.g);
+        assert!(curve.contains(&three_g));
+        assert_eq!(curve.mul(&curve.g, &BigInt::from(3)), three_g);
+        assert_eq!(curve.mul(&curve.g, &BigInt::from(-3)), curve.negate(&three_g));
+        assert_eq!(curve.add(&three_g, &curve.negate(&two_g)), curve.g);
+
+        // Coordinates that aren't reduced stand for the same point.
+        if let Point::Affine(ref x, ref y) = curve.g {
+            let unreduced = Point::Affine(x + &curve.p, y.clone());
+            assert_eq!(curve.add(&curve.g, &unreduced), two_g);
+            assert_eq!(curve.add(&curve.negate(&curve.g), &unreduced), Point::Infinity);
+        }
+    }
+
+    #[test]
+    fn test_encode_point() {
+        let curve = Curve::p256();
+        let three_g = curve.mul(&curve.g, &BigInt::from(3));
+        for point in &[curve.g.clone(), three_g, Point::Infinity] {
+            for &compressed in &[false, true] {
+                let encoded = curve.encode_point(point, compressed);
+                assert_eq!(curve.decode_point(&encoded), Ok(point.clone()));
+            }
+        }
+        assert_eq!(curve.encode_point(&curve.g, true).len(), 33);
+        assert_eq!(curve.encode_point(&curve.g, false).len(), 65);
+
+        // Nudging y takes the point off the curve.
+        let mut encoded = curve.encode_point(&curve.g, false);
+        encoded[64] ^= 1;
+        assert!(curve.decode_point(&encoded).is_err());
+        assert!(curve.decode_point(&encoded[..33]).is_err());
+        assert!(curve.decode_point(&[]).is_err());
+
+        // Coordinates out of range are reduced rather than overflowing their bytes.
+        if let Point::Affine(ref x, ref y) = curve.g {
+            let unreduced = Point::Affine(x + &curve.p, y - &curve.p);
+            assert_eq!(curve.encode_point(&unreduced, false), curve.encode_point(&curve.g, false));
+        }
+        assert!(to_fixed_bytes(&curve.p, 31).is_err());
+        assert!(to_fixed_bytes(&BigInt::from(-1), 32).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let curve = Curve::p256();
+        assert_eq!(curve.validate(), Ok(()));
+        assert_eq!(curve.validate_point(&curve.g), Ok(()));
+        assert!(curve.validate_point(&Point::Infinity).is_err());
+        let off_curve = Point::Affine(BigInt::from(1), BigInt::from(1));
+        assert!(curve.validate_point(&off_curve).is_err());
+        let mut wrong_order = curve.clone();
+        wrong_order.n = &curve.n + 2;
+        assert!(wrong_order.validate().is_err());
+
+        // y^2 = x^3 + x + 9 over GF(233) has 206 = 2 * 103 points, and (155, 0) has order 2.
+        let toy = Curve {
+            p: BigInt::from(233),
+            a: BigInt::from(1),
+            b: BigInt::from(9),
+            g: Point::Affine(BigInt::from(123), BigInt::from(93)),
+            n: BigInt::from(103),
+            h: BigInt::from(2),
+        };
+        assert_eq!(toy.validate(), Ok(()));
+        let order_two = Point::Affine(BigInt::from(155), BigInt::zero());
+        assert!(toy.contains(&order_two));
+        assert!(toy.validate_point(&order_two).is_err());
+        assert_eq!(toy.decode_point(&toy.encode_point(&order_two, true)), Ok(order_two));
+        let mut wrong_cofactor = toy.clone();
+        wrong_cofactor.h = BigInt::from(4);
+        assert!(wrong_cofactor.validate().is_err());
+    }
+}
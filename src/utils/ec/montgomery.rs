@@ -0,0 +1,190 @@
+use num_bigint::BigInt;
+use num_traits::{One, ToPrimitive};
+
+use utils::math::{self, Factorization};
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+
+/// A Montgomery curve Bv^2 = u^3 + Au^2 + u over the prime field of order p, with a base
+/// point of prime order n, which is the range private keys are in. The curve is only
+/// described by its parameters; the arithmetic on it is the ladder in `x25519`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Montgomery {
+    pub p: BigInt,
+    pub a: BigInt,
+    pub b: BigInt,
+    pub n: BigInt,
+}
+
+/// What the twist of a Montgomery curve gives an attacker. A ladder that only uses
+/// u-coordinates accepts a u that's on the twist rather than the curve without noticing, so
+/// a peer can send points of small order on the twist and learn the private key modulo each
+/// order, as in challenge 60.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TwistAudit {
+    /// The number of points on the curve, including the point at infinity.
+    pub curve_order: BigInt,
+    /// The number of points on the twist, which is 2p + 2 minus the curve's.
+    pub twist_order: BigInt,
+    /// The twist order, as far as it could be factored.
+    pub factorization: Factorization,
+    /// The orders up to the bound of the twist's small subgroups, one prime power for each
+    /// small prime, in increasing order.
+    pub small_subgroups: Vec<BigInt>,
+    /// How many bits of the private key the small subgroups leak.
+    pub leaked_bits: f64,
+    /// The base 2 logarithm of the work for the whole attack: brute force in each small
+    /// subgroup, then a kangaroo search for the rest of the key. This ignores the sign
+    /// ambiguity of u-coordinates, which only costs a few more guesses.
+    pub attack_bits: f64,
+    /// The base 2 logarithm of the work for a generic discrete log, sqrt(n), to compare.
+    pub generic_bits: f64,
+}
+
+fn log2(n: &BigInt) -> f64 {
+    n.to_f64().unwrap().log2()
+}
+
+impl Montgomery {
+    /// Curve25519 from RFC 7748, with A = 486662 and B = 1.
+    pub fn curve25519() -> Self {
+        Montgomery {
+            p: (BigInt::one() << 255) - 19,
+            a: BigInt::from(486662),
+            b: BigInt::one(),
+            n: (BigInt::one() << 252) +
+               BigInt::parse_bytes(b"27742317777372353535851937790883648493", 10).unwrap(),
+        }
+    }
+
+    /// Count the points on the curve, including the point at infinity, by checking every u
+    /// for a v. Each u where Bv^2 = u^3 + Au^2 + u is a nonzero square gives two points, so
+    /// this takes p Legendre symbols, and fails for fields bigger than 2^20; the orders of
+    /// real curves come from the curves' specifications instead.
+    pub fn count_points(&self) -> Result<BigInt, &'static str> {
+        if self.p.bits() > 20 {
+            return Err("The field is too big to count points one at a time.");
+        }
+        let p = self.p.to_i64().unwrap();
+        let b = math::legendre(&self.b, &self.p) as i64;
+        let points = (0..p).fold(1, |points, u| {
+            let u = BigInt::from(u);
+            let rhs = &u * &u * &u + &self.a * &u * &u + &u;
+            points + 1 + b * math::legendre(&rhs, &self.p) as i64
+        });
+        Ok(BigInt::from(points))
+    }
+
+    /// The number of points on the twist, given the number on the curve. The two add up to
+    /// 2p + 2, since every u is on one or the other, or both if v = 0.
+    pub fn twist_order(&self, curve_order: &BigInt) -> BigInt {
+        2 * &self.p + 2 - curve_order
+    }
+
+    /// Audit the twist. See audit_twist_with_source().
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn audit_twist(&self,
+                       curve_order: Option<&BigInt>,
+                       bound: u64)
+                       -> Result<TwistAudit, &'static str> {
+        self.audit_twist_with_source(curve_order, bound, &mut OsSource::new())
+    }
+
+    /// Audit the twist: factor its order, finding every prime factor up to `bound`, and work
+    /// out what a twist attack on a key without point validation would cost. The curve
+    /// order is counted if it isn't given, which is only possible for tiny fields.
+    pub fn audit_twist_with_source<R>(&self,
+                                      curve_order: Option<&BigInt>,
+                                      bound: u64,
+                                      rng: &mut R)
+                                      -> Result<TwistAudit, &'static str>
+        where R: RandomSource + ?Sized
+    {
+        let curve_order = match curve_order {
+            Some(order) => order.clone(),
+            None => self.count_points()?,
+        };
+        let twist_order = self.twist_order(&curve_order);
+        let factorization = math::factor(&twist_order, bound, rng);
+
+        let bound = BigInt::from(bound);
+        let small_subgroups = factorization.factors
+            .iter()
+            .map(|&(ref r, power)| (0..power).fold(BigInt::one(), |order, _| order * r))
+            .filter(|order| *order <= bound)
+            .collect::<Vec<_>>();
+
+        // Each subgroup gives the key modulo its order, for as many guesses as the order,
+        // and a kangaroo search over the remaining range takes about its square root.
+        let leaked = small_subgroups.iter().fold(BigInt::one(), |product, order| product * order);
+        let guesses = small_subgroups.iter().map(|order| order.to_f64().unwrap()).sum::<f64>();
+        let leaked_bits = log2(&leaked).min(log2(&self.n));
+        let remaining = (log2(&self.n) - leaked_bits) / 2.0;
+        let attack_bits = (guesses + remaining.exp2()).log2();
+        Ok(TwistAudit {
+            curve_order: curve_order,
+            twist_order: twist_order,
+            factorization: factorization,
+            small_subgroups: small_subgroups,
+            leaked_bits: leaked_bits,
+            attack_bits: attack_bits,
+            generic_bits: log2(&self.n) / 2.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse_bytes(s.as_bytes(), 10).unwrap()
+    }
+
+    #[test]
+    fn test_count_points() {
+        let curve = Montgomery {
+            p: BigInt::from(101),
+            a: BigInt::from(3),
+            b: BigInt::from(2),
+            n: BigInt::one(),
+        };
+        let on_curve = (0..101i64)
+            .flat_map(|u| (0..101i64).map(move |v| (u, v)))
+            .filter(|&(u, v)| (2 * v * v - u * u * u - 3 * u * u - u) % 101 == 0)
+            .count();
+        assert_eq!(curve.count_points(), Ok(BigInt::from(on_curve + 1)));
+        assert!(Montgomery::curve25519().count_points().is_err());
+    }
+
+    #[test]
+    fn test_audit_twist() {
+        let mut rng = SeededRng::new(0);
+
+        // Curve25519 was chosen so that its twist has order 4 times a large prime.
+        let curve = Montgomery::curve25519();
+        let audit = curve.audit_twist_with_source(Some(&(8 * &curve.n)), 1 << 24, &mut rng)
+            .unwrap();
+        assert_eq!(audit.small_subgroups, vec![BigInt::from(4)]);
+        assert_eq!(audit.leaked_bits, 2.0);
+        assert!(audit.attack_bits > 120.0);
+
+        // The curve from challenge 60, whose twist falls apart.
+        let curve = Montgomery {
+            p: big("233970423115425145524320034830162017933"),
+            a: BigInt::from(534),
+            b: BigInt::one(),
+            n: big("29246302889428143187362802287225875743"),
+        };
+        let order = big("233970423115425145498902418297807005944");
+        let audit = curve.audit_twist_with_source(Some(&order), 1 << 24, &mut rng).unwrap();
+        assert!(audit.factorization.is_complete());
+        let expected = [4, 11, 107, 197, 1621, 105143, 405373, 2323367];
+        assert_eq!(audit.small_subgroups,
+                   expected.iter().map(|&r| BigInt::from(r)).collect::<Vec<_>>());
+        assert!(audit.leaked_bits > 80.0);
+        assert!(audit.attack_bits < 30.0);
+    }
+}
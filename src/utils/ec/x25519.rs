@@ -0,0 +1,131 @@
+use std::mem;
+
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::One;
+
+use utils::math;
+
+/// The u-coordinate of the base point, 9.
+pub const BASE_POINT: [u8; 32] = [9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                                  0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+// The field is the integers modulo 2^255 - 19. Elements are BigInts kept in [0, p), and
+// are encoded as 32 little-endian bytes.
+fn prime() -> BigInt {
+    (BigInt::one() << 255) - 19
+}
+
+fn decode(bytes: &[u8; 32]) -> BigInt {
+    let mut bytes = bytes.to_vec();
+    bytes.reverse();
+    BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+fn encode(n: &BigInt) -> [u8; 32] {
+    let mut encoded = [0u8; 32];
+    for (i, byte) in n.to_bytes_le().1.into_iter().enumerate() {
+        encoded[i] = byte;
+    }
+    encoded
+}
+
+/// The X25519 function from RFC 7748: multiply the point with the given u-coordinate by
+/// the clamped scalar, and return the u-coordinate of the result. This uses the Montgomery
+/// ladder, like challenge 60, but on BigInts, so it isn't constant time.
+pub fn x25519(scalar: &[u8; 32], u: &[u8; 32]) -> [u8; 32] {
+    // Clear the low three bits, so the scalar is a multiple of the cofactor 8, and fix the
+    // top bit, so the ladder always does the same number of steps.
+    let mut k = *scalar;
+    k[0] &= 248;
+    k[31] &= 127;
+    k[31] |= 64;
+    let k = decode(&k);
+
+    // The top bit of the u-coordinate is ignored.
+    let mut u = *u;
+    u[31] &= 127;
+
+    let p = prime();
+    let a24 = BigInt::from(121665);
+    let x1 = decode(&u).mod_floor(&p);
+    let (mut x2, mut z2) = (BigInt::one(), BigInt::from(0));
+    let (mut x3, mut z3) = (x1.clone(), BigInt::one());
+    let mut swap = false;
+    for t in (0..255).rev() {
+        let bit = (&k >> t).is_odd();
+        if swap != bit {
+            mem::swap(&mut x2, &mut x3);
+            mem::swap(&mut z2, &mut z3);
+        }
+        swap = bit;
+
+        let a = &x2 + &z2;
+        let aa = (&a * &a).mod_floor(&p);
+        let b = &x2 - &z2;
+        let bb = (&b * &b).mod_floor(&p);
+        let e = &aa - &bb;
+        let c = &x3 + &z3;
+        let d = &x3 - &z3;
+        let da = (d * a).mod_floor(&p);
+        let cb = (c * b).mod_floor(&p);
+        let sum = &da + &cb;
+        let difference = da - cb;
+        x3 = (&sum * &sum).mod_floor(&p);
+        z3 = (&x1 * &difference * &difference).mod_floor(&p);
+        x2 = (&aa * &bb).mod_floor(&p);
+        z2 = (&e * (aa + &a24 * &e)).mod_floor(&p);
+    }
+    if swap {
+        mem::swap(&mut x2, &mut x3);
+        mem::swap(&mut z2, &mut z3);
+    }
+
+    // z2 is zero if the result is the point at infinity, in which case this gives zero.
+    let z_inverse = math::modexp(&z2, &(&p - 2), &p);
+    encode(&(x2 * z_inverse).mod_floor(&p))
+}
+
+/// The public key for a private key: the private key times the base point.
+pub fn public_key(private: &[u8; 32]) -> [u8; 32] {
+    x25519(private, &BASE_POINT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    fn key(hex: &str) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&bytes::hex_to_bytes(hex).unwrap());
+        key
+    }
+
+    #[test]
+    fn test_x25519() {
+        // The first test vector from RFC 7748, section 5.2, and one iteration of the
+        // iterated test.
+        let scalar = key("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let u = key("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        assert_eq!(bytes::hex_from_bytes(&x25519(&scalar, &u)),
+                   "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552");
+        assert_eq!(bytes::hex_from_bytes(&x25519(&BASE_POINT, &BASE_POINT)),
+                   "422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079");
+    }
+
+    #[test]
+    fn test_diffie_hellman() {
+        // The example from RFC 7748, section 6.1.
+        let alice = key("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = key("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        assert_eq!(bytes::hex_from_bytes(&public_key(&alice)),
+                   "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        assert_eq!(bytes::hex_from_bytes(&public_key(&bob)),
+                   "de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = x25519(&alice, &public_key(&bob));
+        assert_eq!(shared, x25519(&bob, &public_key(&alice)));
+        assert_eq!(bytes::hex_from_bytes(&shared),
+                   "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+// Render a row of bytes as hex, leaving room for `width` bytes so that short rows line up.
+fn hex_column<F>(row: &[u8], width: usize, show: F) -> String
+    where F: Fn(usize) -> bool
+{
+    (0..width)
+        .map(|i| match row.get(i) {
+            Some(byte) if show(i) => format!("{:02x}", byte),
+            Some(_) => "..".to_string(),
+            None => "  ".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Render a row of bytes as ASCII, replacing unprintable bytes with '.'.
+fn ascii_column(row: &[u8]) -> String {
+    row.iter()
+        .map(|&byte| if byte >= 0x20 && byte < 0x7f { byte as char } else { '.' })
+        .collect()
+}
+
+/// Format a byte array as a hexdump with one cipher block per row, showing the offset,
+/// hex, and ASCII representation of each block. A block that repeats an earlier block
+/// (the telltale sign of ECB mode) is marked with the offset of its first occurrence.
+pub fn dump(data: &[u8], block_size: usize) -> String {
+    assert!(block_size > 0, "Block size must be nonzero.");
+    let mut first_seen = HashMap::new();
+    let mut output = String::new();
+
+    for (i, row) in data.chunks(block_size).enumerate() {
+        let offset = i * block_size;
+        let line = format!("{:04x}  {}  |{}|",
+                           offset,
+                           hex_column(row, block_size, |_| true),
+                           ascii_column(row));
+        output.push_str(line.trim_end());
+
+        // Only full blocks count as repeats, since a short final block can't be identical
+        // to a full one.
+        if row.len() == block_size {
+            let first = *first_seen.entry(row).or_insert(offset);
+            if first != offset {
+                output.push_str(&format!("  repeats {:04x}", first));
+            }
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Format two byte arrays (e.g., a ciphertext before and after tampering) as a hexdump
+/// with one cipher block per row. Each row of `a` is followed by the same row of `b`, in
+/// which bytes equal to those in `a` are shown as ".." so that the differing bytes stand
+/// out. Rows where the two arrays are identical are only shown once.
+pub fn diff(a: &[u8], b: &[u8], block_size: usize) -> String {
+    assert!(block_size > 0, "Block size must be nonzero.");
+    let num_rows = (a.len().max(b.len()) + block_size - 1) / block_size;
+    let mut output = String::new();
+
+    for i in 0..num_rows {
+        let offset = i * block_size;
+        let row_a = &a[offset.min(a.len())..(offset + block_size).min(a.len())];
+        let row_b = &b[offset.min(b.len())..(offset + block_size).min(b.len())];
+
+        let line = format!("{:04x}  {}  |{}|",
+                           offset,
+                           hex_column(row_a, block_size, |_| true),
+                           ascii_column(row_a));
+        output.push_str(line.trim_end());
+        output.push('\n');
+
+        if row_a != row_b {
+            let line = format!("      {}  |{}|",
+                               hex_column(row_b, block_size, |j| row_a.get(j) != row_b.get(j)),
+                               ascii_column(row_b));
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let data = &b"YELLOW SUBMARINEYELLOW SUBMARINE\x00\x01!"[..];
+        let expected = "\
+0000  59 45 4c 4c 4f 57 20 53 55 42 4d 41 52 49 4e 45  |YELLOW SUBMARINE|
+0010  59 45 4c 4c 4f 57 20 53 55 42 4d 41 52 49 4e 45  |YELLOW SUBMARINE|  repeats 0000
+0020  00 01 21                                         |..!|
+";
+        assert_eq!(dump(data, 16), expected);
+        assert_eq!(dump(&[], 16), "");
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = &b"comment1=cooking:admin<true"[..];
+        let b = &b"comment1=cooking;admin=true!"[..];
+        let expected = "\
+0000  63 6f 6d 6d 65 6e 74 31  |comment1|
+0008  3d 63 6f 6f 6b 69 6e 67  |=cooking|
+0010  3a 61 64 6d 69 6e 3c 74  |:admin<t|
+      3b .. .. .. .. .. 3d ..  |;admin=t|
+0018  72 75 65                 |rue|
+      .. .. .. 21              |rue!|
+";
+        assert_eq!(diff(a, b, 8), expected);
+    }
+}
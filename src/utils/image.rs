@@ -0,0 +1,229 @@
+#[cfg(feature = "aes")]
+use utils::crypto::{self, Ctr};
+
+/// An uncompressed RGB image: the pixels row by row from the top left, three bytes each.
+/// This is all the ECB penguin needs, and it round-trips through binary PPM files and 24-bit
+/// BMP files, whose headers are rebuilt on the way out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+// Read a little-endian integer of `len` bytes at `offset`.
+fn read_le(data: &[u8], offset: usize, len: usize) -> Result<u32, &'static str> {
+    let bytes = data.get(offset..offset + len).ok_or("Truncated BMP header.")?;
+    Ok(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u32))
+}
+
+// Skip whitespace and comments in a PPM header, then read a decimal number.
+fn read_ppm_number(data: &[u8], pos: &mut usize) -> Result<usize, &'static str> {
+    loop {
+        match data.get(*pos) {
+            Some(byte) if byte.is_ascii_whitespace() => *pos += 1,
+            Some(b'#') => {
+                while data.get(*pos).map_or(false, |&byte| byte != b'\n') {
+                    *pos += 1;
+                }
+            }
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).map_or(false, |byte| byte.is_ascii_digit()) {
+        *pos += 1;
+    }
+    if *pos == start || *pos - start > 9 {
+        return Err("Invalid number in PPM header.");
+    }
+    Ok(data[start..*pos].iter().fold(0, |n, &digit| n * 10 + (digit - b'0') as usize))
+}
+
+impl Image {
+    /// Read a binary ("P6") PPM file with 8 bits per channel.
+    pub fn from_ppm(data: &[u8]) -> Result<Self, &'static str> {
+        if !data.starts_with(b"P6") {
+            return Err("Not a binary PPM file.");
+        }
+        let mut pos = 2;
+        let width = read_ppm_number(data, &mut pos)?;
+        let height = read_ppm_number(data, &mut pos)?;
+        if width == 0 || height == 0 {
+            return Err("Invalid PPM dimensions.");
+        }
+        if read_ppm_number(data, &mut pos)? != 255 {
+            return Err("Only PPM files with 8 bits per channel are supported.");
+        }
+        // A single whitespace character separates the header from the pixels.
+        if !data.get(pos).map_or(false, |byte| byte.is_ascii_whitespace()) {
+            return Err("Invalid PPM header.");
+        }
+        let len = width * height * 3;
+        let pixels = data.get(pos + 1..pos + 1 + len).ok_or("Truncated PPM pixel data.")?;
+        Ok(Image {
+            width: width,
+            height: height,
+            pixels: pixels.to_vec(),
+        })
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+
+    /// Read an uncompressed 24-bit BMP file, stored either bottom-up or top-down.
+    pub fn from_bmp(data: &[u8]) -> Result<Self, &'static str> {
+        if !data.starts_with(b"BM") {
+            return Err("Not a BMP file.");
+        }
+        let offset = read_le(data, 10, 4)? as usize;
+        if read_le(data, 14, 4)? < 40 {
+            return Err("Unsupported BMP header.");
+        }
+        if read_le(data, 28, 2)? != 24 || read_le(data, 30, 4)? != 0 {
+            return Err("Only uncompressed 24-bit BMP files are supported.");
+        }
+        let width = read_le(data, 18, 4)? as i32;
+        let height = read_le(data, 22, 4)? as i32;
+        if width <= 0 || height == 0 || width > 1 << 16 || height.abs() > 1 << 16 {
+            return Err("Invalid BMP dimensions.");
+        }
+        let (width, top_down) = (width as usize, height < 0);
+        let height = height.unsigned_abs() as usize;
+
+        // Rows are stored in BGR order, padded to a multiple of 4 bytes.
+        let stride = (width * 3 + 3) / 4 * 4;
+        let rows = data.get(offset..).ok_or("Truncated BMP pixel data.")?;
+        if rows.len() < stride * height {
+            return Err("Truncated BMP pixel data.");
+        }
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            let row = if top_down { y } else { height - 1 - y };
+            for bgr in rows[row * stride..row * stride + width * 3].chunks(3) {
+                pixels.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+            }
+        }
+        Ok(Image {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Write a bottom-up 24-bit BMP file with a BITMAPINFOHEADER.
+    pub fn to_bmp(&self) -> Vec<u8> {
+        let stride = (self.width * 3 + 3) / 4 * 4;
+        let size = 54 + stride * self.height;
+        let mut data = b"BM".to_vec();
+        for &(value, len) in &[(size, 4), (0, 4), (54, 4), (40, 4), (self.width, 4),
+                               (self.height, 4), (1, 2), (24, 2), (0, 4),
+                               (stride * self.height, 4), (2835, 4), (2835, 4), (0, 4),
+                               (0, 4)] {
+            data.extend_from_slice(&(value as u32).to_le_bytes()[..len]);
+        }
+        for row in self.pixels.chunks(self.width * 3).rev() {
+            for rgb in row.chunks(3) {
+                data.extend_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+            }
+            data.resize(data.len() + stride - self.width * 3, 0);
+        }
+        data
+    }
+
+    /// Read a PPM or BMP file, whichever the data starts like.
+    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
+        if data.starts_with(b"BM") {
+            Self::from_bmp(data)
+        } else {
+            Self::from_ppm(data)
+        }
+    }
+}
+
+/// Encrypt the pixels of an image with AES-128-ECB, leaving the image the same size, so it
+/// can be saved and looked at: the ECB penguin. Each run of pixels that fills a block the
+/// same way encrypts the same way, so areas of flat color stay flat, in some other color,
+/// and the outline of the picture survives. The bytes after the last whole block are left
+/// as they are.
+#[cfg(feature = "aes")]
+pub fn encrypt_ecb(image: &Image, key: &[u8]) -> Image {
+    let whole = image.pixels.len() / 16 * 16;
+    let mut pixels = crypto::encrypt_ecb(key, &image.pixels[..whole], false);
+    pixels.extend_from_slice(&image.pixels[whole..]);
+    Image { pixels: pixels, ..image.clone() }
+}
+
+/// Encrypt the pixels of an image with AES-128-CTR, for comparison with `encrypt_ecb`: the
+/// result is noise. The keystream runs on from one row to the next, as if the rows were
+/// streamed in one at a time.
+#[cfg(feature = "aes")]
+pub fn encrypt_ctr(image: &Image, key: &[u8], nonce: u64) -> Image {
+    let mut ctr = Ctr::new(key, nonce);
+    let mut pixels = image.pixels.clone();
+    for row in pixels.chunks_mut(image.width * 3) {
+        ctr.apply_keystream(row);
+    }
+    Image { pixels: pixels, ..image.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A white image with a black disc in the middle.
+    fn disc(size: usize) -> Image {
+        let mut pixels = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let (dx, dy) = (x as i64 - size as i64 / 2, y as i64 - size as i64 / 2);
+                let value = if dx * dx + dy * dy < (size * size / 9) as i64 { 0 } else { 255 };
+                pixels.extend_from_slice(&[value; 3]);
+            }
+        }
+        Image {
+            width: size,
+            height: size,
+            pixels: pixels,
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        // An odd width, so BMP rows need padding.
+        let mut image = disc(7);
+        image.pixels[0] = 0x12;
+        assert_eq!(Image::decode(&image.to_ppm()), Ok(image.clone()));
+        assert_eq!(Image::decode(&image.to_bmp()), Ok(image.clone()));
+
+        let ppm = b"P6 # a comment\n2 1\n255\n\x01\x02\x03\x04\x05\x06";
+        assert_eq!(Image::from_ppm(ppm).unwrap().pixels, vec![1, 2, 3, 4, 5, 6]);
+        assert!(Image::from_ppm(&ppm[..ppm.len() - 1]).is_err());
+        let bmp = image.to_bmp();
+        assert!(Image::from_bmp(&bmp[..bmp.len() - 1]).is_err());
+        assert!(Image::from_bmp(&bmp[..30]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_penguin() {
+        use std::collections::HashSet;
+
+        let image = disc(48);
+        let key = b"YELLOW SUBMARINE";
+        let distinct = |image: &Image| {
+            image.pixels.chunks(16).collect::<HashSet<_>>().len()
+        };
+
+        // The disc and the background each come out as a handful of repeated blocks.
+        let ecb = encrypt_ecb(&image, key);
+        assert_eq!((ecb.width, ecb.height, ecb.pixels.len()), (48, 48, image.pixels.len()));
+        assert!(distinct(&ecb) < 100);
+        let ctr = encrypt_ctr(&image, key, 0);
+        assert_eq!(distinct(&ctr), image.pixels.len() / 16);
+        assert_eq!(encrypt_ctr(&ctr, key, 0), image);
+    }
+}
@@ -0,0 +1,431 @@
+#[cfg(feature = "aes")]
+use utils::{bytes, crypto};
+#[cfg(feature = "aes")]
+use utils::cipher::{self, BlockCipher};
+use utils::poly1305::poly1305;
+use utils::sha256::hmac_sha256;
+
+/// A message authentication code with a fixed key. Data is fed in with `update`, and
+/// `finalize` returns the tag for everything since the last `finalize`, so one instance can
+/// authenticate any number of messages. The exceptions are the one-time MACs, Poly1305 and
+/// GMAC, which panic if they're finalized twice.
+pub trait Mac {
+    /// The length of the tags this MAC produces, in bytes.
+    fn tag_len(&self) -> usize;
+
+    /// Add data to the message being authenticated.
+    fn update(&mut self, data: &[u8]);
+
+    /// Return the tag for the message so far, and start a new message.
+    fn finalize(&mut self) -> Vec<u8>;
+
+    /// Finish the message and check its tag in constant time.
+    fn verify(&mut self, tag: &[u8]) -> bool {
+        verify(&self.finalize(), tag)
+    }
+
+    /// Compute the tag for a complete message in one go.
+    fn tag(&mut self, message: &[u8]) -> Vec<u8> {
+        self.update(message);
+        self.finalize()
+    }
+}
+
+/// Compare two tags in constant time, so that a forger can't learn how many leading bytes
+/// of a guess are right from how long the comparison takes.
+pub fn verify(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len() &&
+    expected.iter().zip(actual).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// The hashes and Poly1305 here only work on whole messages, so the MACs built on them
+// buffer the message until it's finalized.
+
+/// HMAC-SHA256.
+pub struct Hmac {
+    key: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl Hmac {
+    pub fn new(key: &[u8]) -> Self {
+        Hmac {
+            key: key.to_vec(),
+            message: Vec::new(),
+        }
+    }
+}
+
+impl Mac for Hmac {
+    fn tag_len(&self) -> usize {
+        32
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let tag = hmac_sha256(&self.key, &self.message).to_vec();
+        self.message.clear();
+        tag
+    }
+}
+
+/// Poly1305 with a 32-byte key. The key must only ever authenticate one message, since
+/// two tags under it give away the key, so finalizing an instance a second time panics.
+pub struct Poly1305 {
+    key: Vec<u8>,
+    message: Vec<u8>,
+    finalized: bool,
+}
+
+impl Poly1305 {
+    /// Create the MAC with a 32-byte key. Panics if the key is the wrong length.
+    pub fn new(key: &[u8]) -> Self {
+        assert_eq!(key.len(), 32, "Poly1305 keys must be 32 bytes long.");
+        Poly1305 {
+            key: key.to_vec(),
+            message: Vec::new(),
+            finalized: false,
+        }
+    }
+}
+
+impl Mac for Poly1305 {
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        assert!(!self.finalized, "Poly1305 keys can't authenticate more than one message.");
+        self.finalized = true;
+        let tag = poly1305(&self.key, &self.message).to_vec();
+        self.message.clear();
+        tag
+    }
+}
+
+/// CBC-MAC over AES-128 as in challenge 49: the last block of the CBC encryption of the
+/// PKCS#7-padded message under a zero IV. Only secure for messages of a fixed length,
+/// which is what the challenge exploits.
+#[cfg(feature = "aes")]
+pub struct CbcMac {
+    cipher: Box<dyn BlockCipher>,
+    message: Vec<u8>,
+}
+
+#[cfg(feature = "aes")]
+impl CbcMac {
+    pub fn new(key: &[u8]) -> Self {
+        CbcMac {
+            cipher: cipher::aes_128(key),
+            message: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "aes")]
+impl Mac for CbcMac {
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let padded_len = (self.message.len() / 16 + 1) * 16;
+        let padded = crypto::pad_pkcs7(&self.message, padded_len).unwrap();
+        self.message.clear();
+        cbc_mac(&mut *self.cipher, &padded)
+    }
+}
+
+// CBC-encrypt whole blocks under a zero IV, keeping only the last block.
+#[cfg(feature = "aes")]
+fn cbc_mac(cipher: &mut dyn BlockCipher, data: &[u8]) -> Vec<u8> {
+    let mut state = vec![0u8; 16];
+    for block in data.chunks(16) {
+        state = bytes::xor(&state, block);
+        cipher.encrypt_block(&mut state);
+    }
+    state
+}
+
+/// CMAC over AES-128 (RFC 4493), which fixes CBC-MAC for messages of any length by mixing
+/// a subkey into the last block.
+#[cfg(feature = "aes")]
+pub struct Cmac {
+    cipher: Box<dyn BlockCipher>,
+    subkeys: (Vec<u8>, Vec<u8>),
+    message: Vec<u8>,
+}
+
+#[cfg(feature = "aes")]
+impl Cmac {
+    pub fn new(key: &[u8]) -> Self {
+        let mut cipher = cipher::aes_128(key);
+        let mut l = vec![0u8; 16];
+        cipher.encrypt_block(&mut l);
+        let k1 = double(&l);
+        let k2 = double(&k1);
+        Cmac {
+            cipher: cipher,
+            subkeys: (k1, k2),
+            message: Vec::new(),
+        }
+    }
+}
+
+// Multiply by x in GF(2^128), with the block read as a big-endian polynomial.
+#[cfg(feature = "aes")]
+fn double(block: &[u8]) -> Vec<u8> {
+    let mut doubled = block.iter()
+        .zip(block[1..].iter().chain(Some(&0)))
+        .map(|(byte, next)| byte << 1 | next >> 7)
+        .collect::<Vec<u8>>();
+    if block[0] & 0x80 != 0 {
+        doubled[15] ^= 0x87;
+    }
+    doubled
+}
+
+#[cfg(feature = "aes")]
+impl Mac for Cmac {
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        // A complete last block is mixed with the first subkey. Anything else is padded
+        // with a 1 bit and zeros, and mixed with the second.
+        let mut message = self.message.split_off(0);
+        let complete = !message.is_empty() && message.len() % 16 == 0;
+        let subkey = if complete { &self.subkeys.0 } else { &self.subkeys.1 };
+        if !complete {
+            message.push(0x80);
+            message.resize((message.len() + 15) / 16 * 16, 0);
+        }
+        let last = message.len() - 16;
+        for (byte, key) in message[last..].iter_mut().zip(subkey) {
+            *byte ^= key;
+        }
+        cbc_mac(&mut *self.cipher, &message)
+    }
+}
+
+/// The multiplicative identity of GF(2^128) as GCM represents it.
+pub const GF128_ONE: u128 = 1 << 127;
+
+/// Multiply two elements of GF(2^128) as GCM represents them, as big-endian 128-bit
+/// integers: the first bit of the first byte is the coefficient of 1, so the bits are
+/// reversed compared to `double` above, and the reduction by x^128 + x^7 + x^2 + x + 1
+/// shifts in from the right.
+pub fn gf128_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut product = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        if x >> i & 1 == 1 {
+            product ^= v;
+        }
+        v = if v & 1 == 1 { v >> 1 ^ R } else { v >> 1 };
+    }
+    product
+}
+
+/// Raise an element of GF(2^128) to a power, by square-and-multiply.
+pub fn gf128_pow(x: u128, exponent: u64) -> u128 {
+    (0..64).rev().fold(GF128_ONE, |result, i| {
+        let square = gf128_mul(result, result);
+        if exponent >> i & 1 == 1 { gf128_mul(square, x) } else { square }
+    })
+}
+
+/// The inverse of a nonzero element of GF(2^128), which is x^(2^128 - 2): the product of
+/// x^2, x^4, and so on up to x^(2^127). Zero has no inverse, and comes back as zero.
+pub fn gf128_inverse(x: u128) -> u128 {
+    let mut square = x;
+    let mut inverse = GF128_ONE;
+    for _ in 1..128 {
+        square = gf128_mul(square, square);
+        inverse = gf128_mul(inverse, square);
+    }
+    inverse
+}
+
+/// GHASH from GCM (NIST SP 800-38D) under the 16-byte hash key H: the associated data and
+/// the ciphertext are each padded with zeros to whole blocks and followed by a block of
+/// their lengths in bits, and the blocks are the coefficients of a polynomial evaluated at
+/// H. Being a polynomial in H is what lets a repeated nonce give the key away.
+pub fn ghash(h: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    assert_eq!(h.len(), 16, "GHASH keys must be 16 bytes long.");
+    let to_u128 = |block: &[u8]| {
+        let mut padded = [0u8; 16];
+        padded[..block.len()].copy_from_slice(block);
+        u128::from_be_bytes(padded)
+    };
+    let h = to_u128(h);
+    let lengths = ((associated_data.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    let y = associated_data.chunks(16)
+        .chain(ciphertext.chunks(16))
+        .map(to_u128)
+        .chain(Some(lengths))
+        .fold(0, |y, block| gf128_mul(y ^ block, h));
+    y.to_be_bytes().to_vec()
+}
+
+/// GMAC over AES-128: GCM with nothing to encrypt, so the whole message is associated data.
+/// The tag is GHASH of the message under H = E(0), masked with the encryption of the
+/// nonce's counter block. Like GCM, it falls apart if a nonce is ever reused, so an instance
+/// tags exactly one message: finalizing it a second time panics rather than masking another
+/// tag with the same nonce. Make a new instance, with a fresh nonce, for each message.
+#[cfg(feature = "aes")]
+pub struct Gmac {
+    h: Vec<u8>,
+    mask: Vec<u8>,
+    message: Vec<u8>,
+    finalized: bool,
+}
+
+#[cfg(feature = "aes")]
+impl Gmac {
+    /// Create the MAC with a 16-byte key and a 12-byte nonce. Panics if the nonce is the
+    /// wrong length.
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        assert_eq!(nonce.len(), 12, "GMAC nonces must be 12 bytes long.");
+        let mut cipher = cipher::aes_128(key);
+        let mut h = vec![0u8; 16];
+        cipher.encrypt_block(&mut h);
+        let mut mask = nonce.to_vec();
+        mask.extend_from_slice(&[0, 0, 0, 1]);
+        cipher.encrypt_block(&mut mask);
+        Gmac {
+            h: h,
+            mask: mask,
+            message: Vec::new(),
+            finalized: false,
+        }
+    }
+}
+
+#[cfg(feature = "aes")]
+impl Mac for Gmac {
+    fn tag_len(&self) -> usize {
+        16
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message.extend_from_slice(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        assert!(!self.finalized, "GMAC instances can't be reused without reusing the nonce.");
+        self.finalized = true;
+        let tag = bytes::xor(&ghash(&self.h, &self.message, b""), &self.mask);
+        self.message.clear();
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    #[test]
+    fn test_hmac() {
+        let mut mac = Hmac::new(b"Jefe");
+        mac.update(b"what do ya ");
+        mac.update(b"want for nothing?");
+        assert_eq!(bytes::hex_from_bytes(&mac.finalize()),
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+
+        // Each finalize starts a new message.
+        let tag = mac.tag(b"hello");
+        mac.update(b"hello");
+        assert!(mac.verify(&tag));
+        mac.update(b"hello");
+        assert!(!mac.verify(&tag[1..]));
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_cmac() {
+        // Examples 1 and 2 from RFC 4493, section 4.
+        let key = bytes::hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+        let mut mac = Cmac::new(&key);
+        assert_eq!(bytes::hex_from_bytes(&mac.tag(b"")), "bb1d6929e95937287fa37d129b756746");
+        let message = bytes::hex_to_bytes("6bc1bee22e409f96e93d7e117393172a").unwrap();
+        assert_eq!(bytes::hex_from_bytes(&mac.tag(&message)),
+                   "070a16b46b4d4144f79bdd9dd04a287c");
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_gmac() {
+        // Test case 1 from the GCM specification, and an empty plaintext vector from NIST's
+        // CAVP GCM tests.
+        let mut mac = Gmac::new(&[0; 16], &[0; 12]);
+        assert_eq!(bytes::hex_from_bytes(&mac.tag(b"")), "58e2fccefa7e3061367f1d57a4e7455a");
+        let key = bytes::hex_to_bytes("77be63708971c4e240d1cb79e8d77feb").unwrap();
+        let nonce = bytes::hex_to_bytes("e0e00f19fed7ba0136a797f3").unwrap();
+        let mut mac = Gmac::new(&key, &nonce);
+        mac.update(&bytes::hex_to_bytes("7a43ec1d9c0a5a78").unwrap());
+        mac.update(&bytes::hex_to_bytes("a0b16533a6213cab").unwrap());
+        assert_eq!(bytes::hex_from_bytes(&mac.finalize()), "209fcc8d3675ed938e9c7166709dd946");
+        let mut mac = Gmac::new(&key, &bytes::hex_to_bytes("e0e00f19fed7ba0136a797f4").unwrap());
+        assert_eq!(bytes::hex_from_bytes(&mac.tag(b"attack at dawn")),
+                   "ec9f56fcd445712364462b53cfe8bf72");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_poly1305_reuse() {
+        let mut mac = Poly1305::new(&[1; 32]);
+        mac.tag(b"attack at dawn");
+        mac.tag(b"attack at dusk");
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    #[should_panic]
+    fn test_gmac_reuse() {
+        let mut mac = Gmac::new(&[0; 16], &[0; 12]);
+        mac.tag(b"attack at dawn");
+        mac.tag(b"attack at dusk");
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_generic_macs() {
+        // A fresh instance for each message, since the one-time MACs mustn't be reused.
+        let macs: Vec<Box<dyn Fn() -> Box<dyn Mac>>> = vec![
+            Box::new(|| Box::new(Hmac::new(b"key"))),
+            Box::new(|| Box::new(Poly1305::new(&[1u8; 32]))),
+            Box::new(|| Box::new(CbcMac::new(b"YELLOW SUBMARINE"))),
+            Box::new(|| Box::new(Cmac::new(b"YELLOW SUBMARINE"))),
+            Box::new(|| Box::new(Gmac::new(b"YELLOW SUBMARINE", &[0; 12]))),
+        ];
+        for new_mac in macs {
+            let tag = new_mac().tag(b"attack at dawn");
+            assert_eq!(tag.len(), new_mac().tag_len());
+            let mut mac = new_mac();
+            mac.update(b"attack at dawn");
+            assert!(mac.verify(&tag));
+            let mut mac = new_mac();
+            mac.update(b"attack at dusk");
+            assert!(!mac.verify(&tag));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+/// A matrix over GF(2), with each row packed into 64-bit words.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    words: Vec<Vec<u64>>,
+}
+
+fn word_count(cols: usize) -> usize {
+    (cols + 63) / 64
+}
+
+impl Matrix {
+    /// The all-zero matrix of the given size.
+    pub fn zero(rows: usize, cols: usize) -> Self {
+        Matrix {
+            rows: rows,
+            cols: cols,
+            words: vec![vec![0; word_count(cols)]; rows],
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        self.words[row][col / 64] >> (col % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, bit: bool) {
+        let mask = 1 << (col % 64);
+        if bit {
+            self.words[row][col / 64] |= mask;
+        } else {
+            self.words[row][col / 64] &= !mask;
+        }
+    }
+
+    /// Multiply by a column vector.
+    pub fn mul_vector(&self, v: &[bool]) -> Vec<bool> {
+        assert_eq!(v.len(), self.cols, "Vector length must match the number of columns.");
+        (0..self.rows)
+            .map(|row| (0..self.cols).fold(false, |sum, col| sum ^ (self.get(row, col) && v[col])))
+            .collect()
+    }
+
+    // Reduce to row echelon form in place, applying the same row operations to `rhs`, and
+    // return the pivot column of each nonzero row.
+    fn eliminate(&mut self, rhs: &mut [bool]) -> Vec<usize> {
+        let mut pivots = Vec::new();
+        for col in 0..self.cols {
+            let row = pivots.len();
+            let pivot = match (row..self.rows).find(|&r| self.get(r, col)) {
+                Some(pivot) => pivot,
+                None => continue,
+            };
+            self.words.swap(row, pivot);
+            rhs.swap(row, pivot);
+            for other in 0..self.rows {
+                if other != row && self.get(other, col) {
+                    for i in 0..self.words[row].len() {
+                        let word = self.words[row][i];
+                        self.words[other][i] ^= word;
+                    }
+                    rhs[other] ^= rhs[row];
+                }
+            }
+            pivots.push(col);
+        }
+        pivots
+    }
+
+    pub fn rank(&self) -> usize {
+        self.clone().eliminate(&mut vec![false; self.rows]).len()
+    }
+
+    /// Solve Mx = rhs by Gaussian elimination. Returns None if there's no solution. If
+    /// there's more than one, which happens when the rank is less than the number of
+    /// columns, the free variables are set to zero.
+    pub fn solve(&self, rhs: &[bool]) -> Option<Vec<bool>> {
+        assert_eq!(rhs.len(), self.rows, "Right-hand side must have one bit per row.");
+        let mut reduced = self.clone();
+        let mut rhs = rhs.to_vec();
+        let pivots = reduced.eliminate(&mut rhs);
+        if rhs[pivots.len()..].iter().any(|&bit| bit) {
+            return None;
+        }
+
+        let mut solution = vec![false; self.cols];
+        for (row, &col) in pivots.iter().enumerate() {
+            solution[col] = rhs[row];
+        }
+        Some(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        // x0 + x1 = 1, x1 + x2 = 0, x0 + x1 + x2 = 0.
+        let mut m = Matrix::zero(3, 3);
+        for &(row, col) in &[(0, 0), (0, 1), (1, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            m.set(row, col, true);
+        }
+        assert_eq!(m.rank(), 3);
+        let x = m.solve(&[true, false, false]).unwrap();
+        assert_eq!(x, vec![false, true, true]);
+        assert_eq!(m.mul_vector(&x), vec![true, false, false]);
+
+        // Dropping the last column leaves an inconsistent system.
+        let mut m2 = Matrix::zero(3, 2);
+        for &(row, col) in &[(0, 0), (0, 1), (1, 1), (2, 0), (2, 1)] {
+            m2.set(row, col, true);
+        }
+        assert_eq!(m2.rank(), 2);
+        assert_eq!(m2.solve(&[true, false, false]), None);
+        assert_eq!(m2.solve(&[true, true, true]), Some(vec![false, true]));
+    }
+}
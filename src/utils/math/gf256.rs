@@ -0,0 +1,116 @@
+/// The AES reduction polynomial, x^8 + x^4 + x^3 + x + 1.
+pub const AES_POLYNOMIAL: u16 = 0x11b;
+
+/// Add two field elements, which in a field of characteristic 2 is just XOR.
+pub const fn add(a: u8, b: u8) -> u8 {
+    a ^ b
+}
+
+/// Multiply a field element by x, reducing modulo the AES polynomial. This is the
+/// `xtime` operation from FIPS-197, and the building block of MixColumns.
+pub const fn xtime(a: u8) -> u8 {
+    let shifted = (a as u16) << 1;
+    if shifted & 0x100 != 0 {
+        (shifted ^ AES_POLYNOMIAL) as u8
+    } else {
+        shifted as u8
+    }
+}
+
+/// Multiply two field elements modulo the AES polynomial.
+pub const fn mul(a: u8, b: u8) -> u8 {
+    // Shift-and-add: for each bit set in b, add the corresponding multiple of a.
+    let mut product = 0;
+    let mut a = a;
+    let mut b = b;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// Raise a field element to the given power.
+pub const fn pow(a: u8, exponent: u32) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exponent = exponent;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Compute the multiplicative inverse of a field element. The nonzero elements form a
+/// group of order 255, so a^254 is the inverse of a. As in AES, 0 is mapped to 0.
+pub const fn inv(a: u8) -> u8 {
+    pow(a, 254)
+}
+
+/// Generate the AES S-box: the inverse of each byte in GF(2^8), followed by the affine
+/// transformation from FIPS-197 section 5.1.1.
+pub const fn sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let b = inv(i as u8);
+        sbox[i] = b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^
+                  b.rotate_left(4) ^ 0x63;
+        i += 1;
+    }
+    sbox
+}
+
+/// Generate the inverse AES S-box, for use in decryption.
+pub const fn inv_sbox() -> [u8; 256] {
+    let sbox = sbox();
+    let mut inv_sbox = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv_sbox[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv_sbox
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        // Examples from FIPS-197 section 4.2.
+        assert_eq!(add(0x57, 0x83), 0xd4);
+        assert_eq!(xtime(0x57), 0xae);
+        assert_eq!(xtime(0x8e), 0x07);
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+
+        assert_eq!(inv(0), 0);
+        assert_eq!(inv(0x53), 0xca);
+        for a in 1..256 {
+            assert_eq!(mul(a as u8, inv(a as u8)), 1);
+        }
+    }
+
+    #[test]
+    fn test_sbox() {
+        let sbox = sbox();
+        assert_eq!(sbox[0x00], 0x63);
+        assert_eq!(sbox[0x01], 0x7c);
+        assert_eq!(sbox[0x53], 0xed);
+        assert_eq!(sbox[0xff], 0x16);
+
+        let inv_sbox = inv_sbox();
+        for i in 0..256 {
+            assert_eq!(inv_sbox[sbox[i] as usize] as usize, i);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::mem;
+
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+use utils::math;
+
+/// The dot product of two integer vectors.
+pub fn dot(u: &[BigInt], v: &[BigInt]) -> BigInt {
+    u.iter().zip(v).fold(BigInt::zero(), |sum, (x, y)| sum + x * y)
+}
+
+// The Lovasz constant 99/100: close to 1, for a well reduced basis, but not so close that
+// the reduction takes forever.
+const DELTA: (i64, i64) = (99, 100);
+
+// The state of the integral LLL algorithm from Cohen's "A Course in Computational Algebraic
+// Number Theory", algorithm 2.6.7. Everything is kept as an integer by tracking the Gram
+// determinants d[i] and the scaled Gram-Schmidt coefficients lambda[k][j] = d[j + 1] mu[k][j]
+// instead of the Gram-Schmidt vectors themselves, so the reduction is exact.
+struct Lll {
+    basis: Vec<Vec<BigInt>>,
+    d: Vec<BigInt>,
+    lambda: Vec<Vec<BigInt>>,
+}
+
+impl Lll {
+    // Extend the Gram-Schmidt data to cover basis vector k.
+    fn orthogonalize(&mut self, k: usize) {
+        for j in 0..k + 1 {
+            let mut u = dot(&self.basis[k], &self.basis[j]);
+            for i in 0..j {
+                u = (&self.d[i + 1] * u - &self.lambda[k][i] * &self.lambda[j][i]) / &self.d[i];
+            }
+            if j < k {
+                self.lambda[k][j] = u;
+            } else {
+                assert!(!u.is_zero(), "Basis vectors must be linearly independent.");
+                self.d[k + 1] = u;
+            }
+        }
+    }
+
+    // Size-reduce basis vector k against vector l < k.
+    fn reduce(&mut self, k: usize, l: usize) {
+        if 2 * self.lambda[k][l].abs() <= self.d[l + 1] {
+            return;
+        }
+        // The nearest integer to lambda / d.
+        let twice: BigInt = 2 * &self.lambda[k][l] + &self.d[l + 1];
+        let q = twice.div_floor(&(2 * &self.d[l + 1]));
+        for i in 0..self.basis[k].len() {
+            let x = &q * &self.basis[l][i];
+            self.basis[k][i] -= x;
+        }
+        self.lambda[k][l] -= &q * &self.d[l + 1];
+        for i in 0..l {
+            let x = &q * &self.lambda[l][i];
+            self.lambda[k][i] -= x;
+        }
+    }
+
+    // Whether vectors k - 1 and k violate the Lovasz condition and should be swapped.
+    fn should_swap(&self, k: usize) -> bool {
+        let (numerator, denominator) = DELTA;
+        let lambda = &self.lambda[k][k - 1];
+        let lhs = denominator * &self.d[k + 1] * &self.d[k - 1];
+        let rhs = numerator * &self.d[k] * &self.d[k] - denominator * lambda * lambda;
+        lhs < rhs
+    }
+
+    // Swap vectors k - 1 and k, and update the Gram-Schmidt data for vectors up to
+    // `last`, the furthest the algorithm has got.
+    fn swap(&mut self, k: usize, last: usize) {
+        self.basis.swap(k, k - 1);
+        for j in 0..k - 1 {
+            let x = mem::replace(&mut self.lambda[k][j], BigInt::zero());
+            self.lambda[k][j] = mem::replace(&mut self.lambda[k - 1][j], x);
+        }
+        let lambda = self.lambda[k][k - 1].clone();
+        let b = (&self.d[k - 1] * &self.d[k + 1] + &lambda * &lambda) / &self.d[k];
+        for i in k + 1..last + 1 {
+            let t = self.lambda[i][k].clone();
+            self.lambda[i][k] = (&self.d[k + 1] * &self.lambda[i][k - 1] - &lambda * &t) /
+                                &self.d[k];
+            self.lambda[i][k - 1] = (&b * t + &lambda * &self.lambda[i][k]) / &self.d[k + 1];
+        }
+        self.d[k] = b;
+    }
+}
+
+/// Reduce a lattice basis with the LLL algorithm. The rows of `basis` must be linearly
+/// independent. The result spans the same lattice, and its first vector is within a factor
+/// of about (4/3)^((n - 1)/2) of the shortest nonzero vector in the lattice, which for the
+/// small dimensions the attacks use is usually the shortest vector itself.
+pub fn lll(basis: &[Vec<BigInt>]) -> Vec<Vec<BigInt>> {
+    let n = basis.len();
+    if n < 2 {
+        return basis.to_vec();
+    }
+
+    let mut state = Lll {
+        basis: basis.to_vec(),
+        d: vec![BigInt::one(); n + 1],
+        lambda: vec![vec![BigInt::zero(); n]; n],
+    };
+    state.orthogonalize(0);
+
+    let mut k = 1;
+    let mut last = 0;
+    while k < n {
+        if k > last {
+            state.orthogonalize(k);
+            last = k;
+        }
+        state.reduce(k, k - 1);
+        if state.should_swap(k) {
+            state.swap(k, last);
+            k = 1.max(k - 1);
+        } else {
+            for l in (0..k - 1).rev() {
+                state.reduce(k, l);
+            }
+            k += 1;
+        }
+    }
+    state.basis
+}
+
+// Polynomials over the integers are coefficient vectors, constant term first.
+
+fn poly_mul(f: &[BigInt], g: &[BigInt]) -> Vec<BigInt> {
+    let mut product = vec![BigInt::zero(); f.len() + g.len() - 1];
+    for (i, a) in f.iter().enumerate() {
+        for (j, b) in g.iter().enumerate() {
+            product[i + j] += a * b;
+        }
+    }
+    product
+}
+
+fn poly_eval(f: &[BigInt], x: &BigInt) -> BigInt {
+    f.iter().rev().fold(BigInt::zero(), |sum, coefficient| sum * x + coefficient)
+}
+
+fn poly_trim(f: &mut Vec<BigInt>) {
+    while f.len() > 1 && f.last().unwrap().is_zero() {
+        f.pop();
+    }
+}
+
+// Integers c in [low, high] such that every point in [low, high] where f changes sign or
+// is zero lies in some [c, c + 1]. Between the points found for f', f is monotone, so each
+// monotone piece is searched by bisection.
+fn sign_changes(f: &[BigInt], low: &BigInt, high: &BigInt) -> Vec<BigInt> {
+    let sign = |x: &BigInt| poly_eval(f, x).sign();
+    if f.len() < 2 {
+        return Vec::new();
+    }
+
+    let derivative = f[1..]
+        .iter()
+        .enumerate()
+        .map(|(i, coefficient)| coefficient * BigInt::from(i + 1))
+        .collect::<Vec<_>>();
+    let turns = sign_changes(&derivative, low, high);
+
+    // The monotone pieces run between the turning points, which are each in a unit
+    // interval that's checked separately.
+    let mut found = Vec::new();
+    let mut start = low.clone();
+    for turn in turns.iter().chain(Some(high)) {
+        let end = if turn == high { high.clone() } else { turn.clone() };
+        if start <= end {
+            let (mut a, mut b) = (start.clone(), end.clone());
+            let start_sign = sign(&a);
+            if start_sign == Sign::NoSign {
+                found.push(a.clone());
+            } else if sign(&b) != start_sign {
+                while &b - &a > BigInt::one() {
+                    let middle: BigInt = (&a + &b) >> 1;
+                    if sign(&middle) == start_sign {
+                        a = middle;
+                    } else {
+                        b = middle;
+                    }
+                }
+                found.push(a);
+            }
+        }
+        if turn != high {
+            let next = turn + 1;
+            if sign(turn) == Sign::NoSign || sign(&next) != sign(turn) {
+                found.push(turn.clone());
+            }
+            start = next;
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+// The integer roots of f in [low, high].
+fn integer_roots(f: &[BigInt], low: &BigInt, high: &BigInt) -> Vec<BigInt> {
+    let mut roots = sign_changes(f, low, high)
+        .into_iter()
+        .flat_map(|c| {
+            let next = &c + 1;
+            vec![c, next]
+        })
+        .filter(|x| x <= high && poly_eval(f, x).is_zero())
+        .collect::<Vec<_>>();
+    roots.dedup();
+    roots
+}
+
+/// Find the roots x of a polynomial f modulo N with |x| < X, by Coppersmith's method in
+/// Howgrave-Graham's formulation. The coefficients are given constant term first, and the
+/// leading one must be invertible modulo N. The polynomials x^j N^(h - i) f(x)^i, for i up
+/// to the multiplicity h, all vanish at the roots modulo N^h; LLL finds a short combination
+/// of them, which for small enough X vanishes at the roots over the integers too, where
+/// they're easy to find. This works for X up to about N^(h / ((h + 1)d - 1)) for f of
+/// degree d, so raising the multiplicity approaches Coppersmith's bound of N^(1/d), at the
+/// cost of a bigger lattice.
+pub fn small_roots(f: &[BigInt],
+                   modulus: &BigInt,
+                   bound: &BigInt,
+                   multiplicity: usize)
+                   -> Vec<BigInt> {
+    let mut f = f.to_vec();
+    poly_trim(&mut f);
+    let degree = f.len() - 1;
+    assert!(degree >= 1, "The polynomial must not be constant.");
+    let inverse = math::invmod(&f[degree], modulus)
+        .expect("The leading coefficient must be invertible.");
+    let f = f.iter().map(|c| (c * &inverse).mod_floor(modulus)).collect::<Vec<_>>();
+
+    // Row i * d + j is x^j N^(h - i) f(x)^i, with its coefficients scaled by powers of X
+    // so that short vectors give polynomials that are small on [-X, X].
+    let h = multiplicity;
+    let dimension = (h + 1) * degree;
+    let mut basis = Vec::with_capacity(dimension);
+    let mut f_power = vec![BigInt::one()];
+    for i in 0..h + 1 {
+        let scale = num_traits::pow(modulus.clone(), h - i);
+        for j in 0..degree {
+            let mut row = vec![BigInt::zero(); dimension];
+            for (k, coefficient) in f_power.iter().enumerate() {
+                row[j + k] = coefficient * &scale * num_traits::pow(bound.clone(), j + k);
+            }
+            basis.push(row);
+        }
+        f_power = poly_mul(&f_power, &f);
+    }
+
+    let low = -bound + 1;
+    let high = bound - 1;
+    let mut roots = Vec::new();
+    for vector in lll(&basis) {
+        let mut g = vector.iter()
+            .enumerate()
+            .map(|(k, coefficient)| coefficient / num_traits::pow(bound.clone(), k))
+            .collect::<Vec<_>>();
+        poly_trim(&mut g);
+        if g.len() < 2 {
+            continue;
+        }
+        for root in integer_roots(&g, &low, &high) {
+            if poly_eval(&f, &root).mod_floor(modulus).is_zero() && !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        if !roots.is_empty() {
+            break;
+        }
+    }
+    roots.sort();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectors(rows: &[&[i64]]) -> Vec<Vec<BigInt>> {
+        rows.iter().map(|row| row.iter().map(|&x| BigInt::from(x)).collect()).collect()
+    }
+
+    #[test]
+    fn test_lll() {
+        let reduced = lll(&vectors(&[&[1, 1, 1], &[-1, 0, 2], &[3, 5, 6]]));
+        assert_eq!(reduced, vectors(&[&[0, 1, 0], &[1, 0, 1], &[-1, 0, 2]]));
+
+        // A knapsack-style lattice hiding the short vector (1, 0, 1, 1, 0): the subset of
+        // weights that sums to 1000.
+        let weights = [366, 385, 183, 451, 222];
+        let mut rows = (0..5)
+            .map(|i| {
+                let mut row = vec![0; 6];
+                row[i] = 1;
+                row[5] = weights[i];
+                row
+            })
+            .collect::<Vec<_>>();
+        rows.push(vec![0, 0, 0, 0, 0, -1000]);
+        let rows = rows.iter().map(|row| &row[..]).collect::<Vec<_>>();
+        let reduced = lll(&vectors(&rows));
+        let target = vectors(&[&[1, 0, 1, 1, 0, 0]]).remove(0);
+        let negated = target.iter().map(|x| -x).collect::<Vec<_>>();
+        assert!(reduced.iter().any(|v| *v == target || *v == negated));
+    }
+
+    #[test]
+    fn test_small_roots() {
+        // f(x) = (x - 1234)(x + 567), which is hidden once the coefficients are reduced
+        // modulo N.
+        let n = BigInt::from(1000000007i64 * 998244353);
+        let f = vectors(&[&[-1234 * 567, 567 - 1234, 1]]).remove(0);
+        let roots = small_roots(&f, &n, &BigInt::from(2000), 1);
+        assert_eq!(roots, vec![BigInt::from(-567), BigInt::from(1234)]);
+
+        assert_eq!(integer_roots(&vectors(&[&[6, -5, 1]]).remove(0),
+                                 &BigInt::from(-10),
+                                 &BigInt::from(10)),
+                   vec![BigInt::from(2), BigInt::from(3)]);
+    }
+}
@@ -0,0 +1,713 @@
+// Only `gf256`, which the native AES is built on, is available without the `std` feature.
+#[cfg(feature = "std")]
+use std::mem;
+
+#[cfg(feature = "std")]
+use num_bigint::{BigInt, Sign};
+#[cfg(feature = "std")]
+use num_integer::Integer;
+#[cfg(feature = "std")]
+use num_traits::{One, Signed, Zero};
+
+#[cfg(feature = "std")]
+use utils::random::RandomSource;
+
+#[cfg(feature = "std")]
+pub mod gf2;
+pub mod gf256;
+#[cfg(feature = "std")]
+pub mod lattice;
+#[cfg(feature = "std")]
+pub mod nist;
+
+/// Compute the inner product of two vectors.
+#[cfg(feature = "std")]
+pub fn dot(u: &[f64], v: &[f64]) -> f64 {
+    u.iter().zip(v.iter()).map(|(x, y)| (x * y)).sum()
+}
+
+/// Compute the L1-norm of a vector.
+#[cfg(feature = "std")]
+pub fn l1_norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x.abs()).sum()
+}
+
+/// Compute the L2-norm of a vector.
+#[cfg(feature = "std")]
+pub fn l2_norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x.powi(2)).sum::<f64>().sqrt()
+}
+
+/// Normalize a vector using the L1 norm.
+#[cfg(feature = "std")]
+pub fn l1_normalize(v: &[f64]) -> Vec<f64> {
+    let v_norm = l1_norm(&v);
+    v.iter().map(|x| x / v_norm).collect()
+}
+
+/// Normalize a vector using the L2 norm.
+#[cfg(feature = "std")]
+pub fn l2_normalize(v: &[f64]) -> Vec<f64> {
+    let v_norm = l2_norm(&v);
+    v.iter().map(|x| x / v_norm).collect()
+}
+
+/// Compute the L1 (Manhattan) distance between two vectors.
+#[cfg(feature = "std")]
+pub fn l1_dist(u: &[f64], v: &[f64]) -> f64 {
+    u.iter().zip(v.iter()).map(|(x, y)| (x - y).abs()).sum()
+}
+
+/// Compute the L2 (Euclidean) distance between two vectors.
+#[cfg(feature = "std")]
+pub fn l2_dist(u: &[f64], v: &[f64]) -> f64 {
+    u.iter().zip(v.iter()).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt()
+}
+
+/// Compute the normalized L1 similarity between two vectors.
+#[cfg(feature = "std")]
+pub fn l1_sim(u: &[f64], v: &[f64]) -> f64 {
+    1.0 - l1_dist(&l1_normalize(&u), &l1_normalize(&v))
+}
+
+/// Compute the normalized L2 similarity between two vectors.
+#[cfg(feature = "std")]
+pub fn l2_sim(u: &[f64], v: &[f64]) -> f64 {
+    1.0 - l2_dist(&l2_normalize(&u), &l2_normalize(&v))
+}
+
+/// Compute the cosine similarity of two vectors.
+#[cfg(feature = "std")]
+pub fn cosine_sim(u: &[f64], v: &[f64]) -> f64 {
+    dot(&u, &v) / (l2_norm(&u) * l2_norm(&v))
+}
+
+/// Shannon entropy of the distribution of bytes in the data, in bits per byte: 8 if every
+/// value is equally common, and 0 for a single repeated byte. Short inputs can't reach 8,
+/// since n bytes have an entropy of at most log2(n).
+#[cfg(feature = "std")]
+pub fn entropy(data: &[u8]) -> f64 {
+    byte_counts(data)
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// The index of coincidence of the data: the probability that bytes at two different
+/// positions are equal. Uniformly random bytes give about 1/256, and English text about
+/// 0.067. XORing every byte with the same value permutes the counts, so leaves it alone.
+#[cfg(feature = "std")]
+pub fn index_of_coincidence(data: &[u8]) -> f64 {
+    if data.len() < 2 {
+        return 0.0;
+    }
+    let n = data.len() as f64;
+    let pairs = byte_counts(data)
+        .iter()
+        .map(|&count| (count * count.saturating_sub(1)) as f64)
+        .sum::<f64>();
+    pairs / (n * (n - 1.0))
+}
+
+#[cfg(feature = "std")]
+fn byte_counts(data: &[u8]) -> [usize; 256] {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    counts
+}
+
+/// Pearson's chi-squared statistic for the byte counts in the data against the uniform
+/// distribution, with 255 degrees of freedom. The test is only reliable with at least five
+/// expected occurrences of every value, which takes 1280 bytes.
+#[cfg(feature = "std")]
+pub fn chi_squared_bytes(data: &[u8]) -> f64 {
+    let expected = data.len() as f64 / 256.0;
+    byte_counts(data)
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+/// The probability of a chi-squared statistic at least this large with the given degrees
+/// of freedom, by the Wilson-Hilferty approximation, which is good to a few decimal places
+/// for the hundreds of degrees of freedom in byte distribution tests.
+#[cfg(feature = "std")]
+pub fn chi_squared_p_value(statistic: f64, degrees: f64) -> f64 {
+    let variance = 2.0 / (9.0 * degrees);
+    let z = ((statistic / degrees).cbrt() - (1.0 - variance)) / variance.sqrt();
+    erfc(z / 2f64.sqrt()) / 2.0
+}
+
+/// The complementary error function, using the Chebyshev approximation from Numerical
+/// Recipes, which has a relative error below 1.2e-7 everywhere.
+#[cfg(feature = "std")]
+pub fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x.abs());
+    let coefficients = [-1.26551223, 1.00002368, 0.37409196, 0.09678418, -0.18628806,
+                        0.27886807, -1.13520398, 1.48851587, -0.82215223, 0.17087277];
+    let polynomial = coefficients.iter().rev().fold(0.0, |acc, c| acc * t + c);
+    let result = t * (-x * x + polynomial).exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+/// Pearson correlation between the data and itself shifted by `lag` bytes, wrapping around
+/// at the end. Near zero for random data, and near 1 or -1 for data where each byte
+/// predicts the one `lag` later. Returns 1 for data with no variation at all.
+#[cfg(feature = "std")]
+pub fn autocorrelation(data: &[u8], lag: usize) -> f64 {
+    let n = data.len() as f64;
+    let shifted = data.iter().cycle().skip(lag);
+    let (mut sum, mut sum_squares, mut sum_products) = (0.0, 0.0, 0.0);
+    for (&x, &y) in data.iter().zip(shifted) {
+        let (x, y) = (x as f64, y as f64);
+        sum += x;
+        sum_squares += x * x;
+        sum_products += x * y;
+    }
+    let variance = n * sum_squares - sum * sum;
+    if variance == 0.0 {
+        return 1.0;
+    }
+    (n * sum_products - sum * sum) / variance
+}
+
+/// The serial correlation coefficient, as reported by ent: the autocorrelation between
+/// each byte and the next.
+#[cfg(feature = "std")]
+pub fn serial_correlation(data: &[u8]) -> f64 {
+    autocorrelation(data, 1)
+}
+
+/// Solve a system of congruences x = a_i (mod m_i) using the Chinese Remainder Theorem.
+/// Each element of `residues` is a pair (a_i, m_i). Returns the unique solution in the
+/// range [0, M), where M is the product of the moduli, or an error if any modulus isn't
+/// positive or the moduli aren't pairwise coprime (in which case a solution may not
+/// exist, and isn't unique modulo M if it does).
+#[cfg(feature = "std")]
+pub fn crt(residues: &[(BigInt, BigInt)]) -> Result<BigInt, &'static str> {
+    let mut result = BigInt::zero();
+    let mut modulus = BigInt::one();
+
+    // Fold in one congruence at a time. If x = r (mod M) satisfies the congruences so far,
+    // then x = r + M * ((a - r) * M^-1 mod m) also satisfies x = a (mod m).
+    for &(ref a, ref m) in residues {
+        if !m.is_positive() {
+            return Err("Moduli must be positive.");
+        }
+
+        let (gcd, inverse, _) = egcd(&modulus, m);
+        if !gcd.is_one() {
+            return Err("Moduli are not pairwise coprime.");
+        }
+
+        let t = ((a - &result) * inverse).mod_floor(m);
+        result = result + &modulus * t;
+        modulus = modulus * m;
+    }
+
+    Ok(result)
+}
+
+/// Extended Euclidean algorithm. Returns (g, x, y) such that ax + by = g, where g is the
+/// (nonnegative) greatest common divisor of a and b.
+#[cfg(feature = "std")]
+pub fn egcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    let (mut old_r, mut r) = (a.clone(), b.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    let (mut old_t, mut t) = (BigInt::zero(), BigInt::one());
+
+    while !r.is_zero() {
+        let q = &old_r / &r;
+        let next_r = &old_r - &q * &r;
+        old_r = mem::replace(&mut r, next_r);
+        let next_s = &old_s - &q * &s;
+        old_s = mem::replace(&mut s, next_s);
+        let next_t = &old_t - &q * &t;
+        old_t = mem::replace(&mut t, next_t);
+    }
+
+    // Normalize the sign so that the gcd is nonnegative.
+    if old_r.is_negative() {
+        (-old_r, -old_s, -old_t)
+    } else {
+        (old_r, old_s, old_t)
+    }
+}
+
+/// Compute the inverse of a modulo m, i.e., the x in [0, m) such that ax = 1 (mod m).
+/// Returns None if a and m aren't coprime, in which case no inverse exists.
+#[cfg(feature = "std")]
+pub fn invmod(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    let (gcd, x, _) = egcd(a, m);
+    if gcd.is_one() {
+        Some(x.mod_floor(m))
+    } else {
+        None
+    }
+}
+
+/// Compute base^exponent mod modulus by square-and-multiply, returning a result in
+/// [0, modulus). Panics if the exponent is negative or the modulus isn't positive.
+#[cfg(feature = "std")]
+pub fn modexp(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    assert!(!exponent.is_negative(), "Exponent must be nonnegative.");
+    assert!(modulus.is_positive(), "Modulus must be positive.");
+
+    let mut result = BigInt::one().mod_floor(modulus);
+    let mut base = base.mod_floor(modulus);
+    let mut exponent = exponent.clone();
+    let two = BigInt::from(2);
+    while !exponent.is_zero() {
+        if exponent.is_odd() {
+            result = (result * &base).mod_floor(modulus);
+        }
+        base = (&base * &base).mod_floor(modulus);
+        exponent = exponent / &two;
+    }
+    result
+}
+
+/// Compute the Legendre symbol (a/p) for an odd prime p using Euler's criterion. Returns
+/// 1 if a is a nonzero quadratic residue modulo p, -1 if it is a nonresidue, and 0 if p
+/// divides a.
+#[cfg(feature = "std")]
+pub fn legendre(a: &BigInt, p: &BigInt) -> i32 {
+    let symbol = modexp(a, &((p - 1) / 2), p);
+    if symbol.is_zero() {
+        0
+    } else if symbol.is_one() {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Compute a square root of a modulo the prime p using the Tonelli-Shanks algorithm.
+/// Returns the root r in [0, p / 2], or None if a is a nonresidue; the other root is
+/// p - r. Results are meaningless if p isn't prime.
+#[cfg(feature = "std")]
+pub fn mod_sqrt(a: &BigInt, p: &BigInt) -> Option<BigInt> {
+    let a = a.mod_floor(p);
+    let two = BigInt::from(2);
+    if a.is_zero() || *p == two {
+        return Some(a);
+    }
+    if legendre(&a, p) != 1 {
+        return None;
+    }
+
+    // Write p - 1 = q * 2^s with q odd.
+    let mut q: BigInt = p - 1;
+    let mut s = 0;
+    while q.is_even() {
+        q = q / &two;
+        s += 1;
+    }
+
+    // Find any quadratic nonresidue z.
+    let mut z = two.clone();
+    while legendre(&z, p) != -1 {
+        z = z + 1;
+    }
+
+    // Loop invariant: r^2 = a * t (mod p), where t has order dividing 2^m. Each iteration
+    // strictly lowers the order of t, and r is a root once t = 1.
+    let mut m = s;
+    let mut c = modexp(&z, &q, p);
+    let mut t = modexp(&a, &q, p);
+    let mut r = modexp(&a, &((&q + 1) / &two), p);
+    while !t.is_one() {
+        // Find the least i such that t^(2^i) = 1.
+        let mut i = 0;
+        let mut t_pow = t.clone();
+        while !t_pow.is_one() {
+            t_pow = (&t_pow * &t_pow).mod_floor(p);
+            i += 1;
+        }
+
+        let b = modexp(&c, &(BigInt::one() << (m - i - 1)), p);
+        m = i;
+        c = (&b * &b).mod_floor(p);
+        t = (t * &c).mod_floor(p);
+        r = (r * b).mod_floor(p);
+    }
+
+    let other = p - &r;
+    Some(if other < r { other } else { r })
+}
+
+/// Test whether n is prime with the Miller-Rabin test, using the given number of random
+/// bases. A composite passes with probability at most 4^-rounds.
+#[cfg(feature = "std")]
+pub fn is_probable_prime<R>(n: &BigInt, rounds: usize, rng: &mut R) -> bool
+    where R: RandomSource + ?Sized
+{
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    // Trial division rules out most candidates far more cheaply than a round would.
+    for small in 2..256u32 {
+        let small = BigInt::from(small);
+        if *n == small {
+            return true;
+        }
+        if n.is_multiple_of(&small) {
+            return false;
+        }
+    }
+
+    // Write n - 1 = d * 2^s with d odd.
+    let n_minus_one: BigInt = n - 1;
+    let mut d = n_minus_one.clone();
+    let mut s = 0;
+    while d.is_even() {
+        d >>= 1;
+        s += 1;
+    }
+
+    let size = n.to_bytes_be().1.len();
+    'rounds: for _ in 0..rounds {
+        // A base in [2, n - 2]. The slight modulo bias doesn't matter here.
+        let base = BigInt::from_bytes_be(Sign::Plus, &rng.bytes(size)) % (n - 3) + 2;
+        let mut x = modexp(&base, &d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = (&x * &x).mod_floor(n);
+            if x == n_minus_one {
+                continue 'rounds;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Generate a random prime of exactly the given number of bits, which must be a multiple
+/// of 8. The top two bits are set, so the product of two such primes has twice as many
+/// bits.
+#[cfg(feature = "std")]
+pub fn random_prime<R>(bits: usize, rng: &mut R) -> BigInt
+    where R: RandomSource + ?Sized
+{
+    assert!(bits >= 16 && bits % 8 == 0, "Prime size must be a multiple of 8 bits.");
+    loop {
+        let mut candidate = rng.bytes(bits / 8);
+        candidate[0] |= 0xc0;
+        candidate[bits / 8 - 1] |= 1;
+        let candidate = BigInt::from_bytes_be(Sign::Plus, &candidate);
+        if is_probable_prime(&candidate, 40, rng) {
+            return candidate;
+        }
+    }
+}
+
+/// A factorization of a positive integer, as far as it got: the integer is the product of
+/// the prime factors, each raised to its power, and the cofactor, which is 1 if the
+/// factorization is complete, and otherwise a composite the search couldn't split.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Factorization {
+    /// Prime factors in increasing order, with their multiplicities.
+    pub factors: Vec<(BigInt, u32)>,
+    pub cofactor: BigInt,
+}
+
+#[cfg(feature = "std")]
+impl Factorization {
+    pub fn is_complete(&self) -> bool {
+        self.cofactor.is_one()
+    }
+}
+
+/// Find a nontrivial factor of a composite n with Pollard's rho, giving up after the given
+/// number of steps. A prime factor r usually turns up after about sqrt(r) steps, however
+/// large n is.
+#[cfg(feature = "std")]
+pub fn pollard_rho<R>(n: &BigInt, steps: usize, rng: &mut R) -> Option<BigInt>
+    where R: RandomSource + ?Sized
+{
+    if n.is_even() {
+        return Some(BigInt::from(2));
+    }
+
+    let size = n.to_bytes_be().1.len();
+    let mut steps_left = steps;
+    while steps_left > 0 {
+        // Walk x -> x^2 + c from a random start, with the hare y going twice as fast as the
+        // tortoise x. Modulo a factor of n the walk soon cycles, and then the two meet.
+        let c = BigInt::from_bytes_be(Sign::Plus, &rng.bytes(size)) % n;
+        let f = |x: &BigInt| (x * x + &c).mod_floor(n);
+        let mut x = BigInt::from_bytes_be(Sign::Plus, &rng.bytes(size)) % n;
+        let mut y = x.clone();
+        while steps_left > 0 {
+            steps_left -= 1;
+            x = f(&x);
+            y = f(&f(&y));
+            let d = (&x - &y).gcd(n);
+            if d == *n {
+                // They met modulo every factor at once, so try another walk.
+                break;
+            }
+            if !d.is_one() {
+                return Some(d);
+            }
+        }
+    }
+    None
+}
+
+/// Factor a positive integer by trial division by the numbers below 1024, then Pollard's
+/// rho, which finds any prime factor up to about `bound`, and larger ones with some luck.
+/// Factors that are left over count as prime if they pass the Miller-Rabin test.
+#[cfg(feature = "std")]
+pub fn factor<R>(n: &BigInt, bound: u64, rng: &mut R) -> Factorization
+    where R: RandomSource + ?Sized
+{
+    assert!(n.is_positive(), "Only positive integers can be factored.");
+
+    let mut primes = Vec::new();
+    let mut remaining = n.clone();
+    for small in 2..1024u32 {
+        // Composites never divide what's left, since their factors are already gone.
+        let small = BigInt::from(small);
+        while remaining.is_multiple_of(&small) {
+            remaining = remaining / &small;
+            primes.push(small.clone());
+        }
+    }
+
+    let steps = 8 * (bound as f64).sqrt() as usize + 64;
+    let mut cofactor = BigInt::one();
+    let mut pending = vec![remaining];
+    while let Some(m) = pending.pop() {
+        if m.is_one() {
+            continue;
+        }
+        if is_probable_prime(&m, 40, rng) {
+            primes.push(m);
+            continue;
+        }
+        match pollard_rho(&m, steps, rng) {
+            Some(d) => {
+                pending.push(&m / &d);
+                pending.push(d);
+            }
+            None => cofactor = cofactor * m,
+        }
+    }
+
+    primes.sort();
+    let mut factors: Vec<(BigInt, u32)> = Vec::new();
+    for prime in primes {
+        match factors.last_mut() {
+            Some(&mut (ref last, ref mut power)) if *last == prime => *power += 1,
+            _ => factors.push((prime, 1)),
+        }
+    }
+    Factorization {
+        factors: factors,
+        cofactor: cofactor,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn big(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b""), 0.0);
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+        assert_eq!(entropy(&(0..=255).collect::<Vec<u8>>()), 8.0);
+    }
+
+    #[test]
+    fn test_index_of_coincidence() {
+        assert_eq!(index_of_coincidence(b"a"), 0.0);
+        assert_eq!(index_of_coincidence(b"aaaa"), 1.0);
+        assert_eq!(index_of_coincidence(b"abab"), 1.0 / 3.0);
+        let uniform = (0..=255).cycle().take(4096).collect::<Vec<u8>>();
+        assert!((index_of_coincidence(&uniform) - 1.0 / 256.0).abs() < 1e-3);
+        let masked = b"abab".iter().map(|byte| byte ^ 0x5a).collect::<Vec<_>>();
+        assert_eq!(index_of_coincidence(&masked), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_chi_squared() {
+        let uniform = (0..=255).cycle().take(4096).collect::<Vec<u8>>();
+        assert_eq!(chi_squared_bytes(&uniform), 0.0);
+        assert!(chi_squared_p_value(chi_squared_bytes(&uniform), 255.0) > 0.999);
+        assert!(chi_squared_p_value(chi_squared_bytes(&[0; 4096]), 255.0) < 1e-6);
+
+        // The 5% critical value for 255 degrees of freedom is about 293.2.
+        assert!((chi_squared_p_value(293.25, 255.0) - 0.05).abs() < 0.001);
+        assert!((erfc(1.0) - 0.157_299_2).abs() < 1e-7);
+        assert!((erfc(-1.0) - 1.842_700_8).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_serial_correlation() {
+        let counter = (0..=255).collect::<Vec<u8>>();
+        assert!(serial_correlation(&counter) > 0.95);
+        let alternating = [0, 255].iter().cycle().take(100).cloned().collect::<Vec<u8>>();
+        assert_eq!(serial_correlation(&alternating), -1.0);
+        assert_eq!(autocorrelation(&alternating, 2), 1.0);
+    }
+
+    #[test]
+    fn test_egcd() {
+        assert_eq!(egcd(&big(240), &big(46)), (big(2), big(-9), big(47)));
+        assert_eq!(egcd(&big(0), &big(5)), (big(5), big(0), big(1)));
+        assert_eq!(egcd(&big(-12), &big(18)).0, big(6));
+
+        for a in -30..30 {
+            for b in -30..30 {
+                let (g, x, y) = egcd(&big(a), &big(b));
+                assert_eq!(big(a) * x + big(b) * y, g);
+                assert_eq!(g, big(a).gcd(&big(b)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invmod() {
+        assert_eq!(invmod(&big(17), &big(3120)), Some(big(2753)));
+        assert_eq!(invmod(&big(-3), &big(7)), Some(big(2)));
+        assert_eq!(invmod(&big(6), &big(9)), None);
+
+        let p = big(1_000_000_007);
+        for a in 1..100 {
+            let inverse = invmod(&big(a), &p).unwrap();
+            assert_eq!((big(a) * inverse) % &p, big(1));
+        }
+    }
+
+    #[test]
+    fn test_modexp() {
+        assert_eq!(modexp(&big(4), &big(13), &big(497)), big(445));
+        assert_eq!(modexp(&big(-2), &big(3), &big(5)), big(2));
+        assert_eq!(modexp(&big(5), &big(0), &big(1)), big(0));
+        assert_eq!(modexp(&big(5), &big(0), &big(7)), big(1));
+
+        // Fermat's little theorem.
+        let p = big(1_000_000_007);
+        assert_eq!(modexp(&big(123_456_789), &(&p - 1), &p), big(1));
+    }
+
+    #[test]
+    fn test_is_probable_prime() {
+        use utils::random::SeededRng;
+
+        let mut rng = SeededRng::new(0);
+        let primes = (0..1000).filter(|&n| is_probable_prime(&big(n), 10, &mut rng)).count();
+        assert_eq!(primes, 168);
+
+        // A Carmichael number, which fools the Fermat test for every coprime base, with
+        // factors too big for the trial division to find.
+        assert!(!is_probable_prime(&big(271 * 541 * 811), 10, &mut rng));
+        assert!(is_probable_prime(&big(1_000_000_007), 10, &mut rng));
+        assert!(is_probable_prime(&((BigInt::one() << 127) - 1), 10, &mut rng));
+
+        let p = random_prime(128, &mut rng);
+        assert_eq!(p.bits(), 128);
+        assert!(is_probable_prime(&p, 10, &mut rng));
+    }
+
+    #[test]
+    fn test_factor() {
+        use utils::random::SeededRng;
+
+        let mut rng = SeededRng::new(0);
+        let n = big(2 * 2 * 3 * 1_009 * 1_000_003) * big(1_000_000_007);
+        let factorization = factor(&n, 1 << 20, &mut rng);
+        assert!(factorization.is_complete());
+        assert_eq!(factorization.factors,
+                   vec![(big(2), 2), (big(3), 1), (big(1_009), 1), (big(1_000_003), 1),
+                        (big(1_000_000_007), 1)]);
+
+        // Two 64-bit primes are far beyond the bound, and stay together in the cofactor.
+        let p = (BigInt::one() << 64) - 59;
+        let q = (BigInt::one() << 64) - 83;
+        let factorization = factor(&(big(65_537) * &p * &q), 1 << 16, &mut rng);
+        assert_eq!(factorization.factors, vec![(big(65_537), 1)]);
+        assert_eq!(factorization.cofactor, p * q);
+    }
+
+    #[test]
+    fn test_legendre() {
+        let p = big(23);
+        let residues = (1..23).map(|x| (x * x) % 23).collect::<Vec<i64>>();
+        for a in 0..23 {
+            let expected = if a == 0 {
+                0
+            } else if residues.contains(&a) {
+                1
+            } else {
+                -1
+            };
+            assert_eq!(legendre(&big(a), &p), expected);
+        }
+    }
+
+    #[test]
+    fn test_mod_sqrt() {
+        // 17 and 41 have 2^4 and 2^3 dividing p - 1, which exercises the main loop of
+        // Tonelli-Shanks; 23 is 3 mod 4, where the first guess is always right.
+        for &p in &[2, 17, 23, 41, 1_000_000_007] {
+            let p = big(p);
+            for a in 0..50 {
+                let a = big(a);
+                match mod_sqrt(&a, &p) {
+                    Some(r) => {
+                        assert_eq!((&r * &r).mod_floor(&p), a.mod_floor(&p));
+                        assert!(&r * 2 <= p);
+                    }
+                    None => assert_eq!(legendre(&a, &p), -1),
+                }
+            }
+        }
+
+        // Curve25519's field prime, 2^255 - 19.
+        let p = (BigInt::one() << 255) - 19;
+        let x = big(123_456_789_123_456_789);
+        let r = mod_sqrt(&(&x * &x), &p).unwrap();
+        assert!(r == x || r == &p - &x);
+    }
+
+    #[test]
+    fn test_crt() {
+        let residues = [(big(2), big(3)), (big(3), big(5)), (big(2), big(7))];
+        assert_eq!(crt(&residues), Ok(big(23)));
+
+        // Residues don't need to be reduced, and may be negative.
+        let residues = [(big(-1), big(3)), (big(13), big(5)), (big(9), big(7))];
+        assert_eq!(crt(&residues), Ok(big(23)));
+
+        assert_eq!(crt(&[]), Ok(big(0)));
+        assert!(crt(&[(big(1), big(4)), (big(3), big(6))]).is_err());
+        assert!(crt(&[(big(1), big(0))]).is_err());
+
+        // Recover a cube from its residues modulo three large coprime moduli, as in the
+        // e = 3 RSA broadcast attack.
+        let m = big(1_000_000_007) * big(998_244_353);
+        let moduli = [big(1_000_000_009) * big(1_000_000_021),
+                      big(999_999_937) * big(999_999_929),
+                      big(1_000_000_087) * big(1_000_000_093)];
+        let c = &m * &m * &m;
+        let residues = moduli.iter().map(|n| (&c % n, n.clone())).collect::<Vec<_>>();
+        assert_eq!(crt(&residues), Ok(c));
+    }
+}
@@ -0,0 +1,178 @@
+use utils::math::erfc;
+use utils::random::RandomSource;
+
+/// The significance level recommended by NIST SP 800-22: a sequence fails a test if its
+/// p-value is below this.
+pub const SIGNIFICANCE: f64 = 0.01;
+
+/// Split bytes into bits, most significant first.
+pub fn bits(data: &[u8]) -> Vec<bool> {
+    data.iter().flat_map(|&byte| (0..8).rev().map(move |i| byte >> i & 1 == 1)).collect()
+}
+
+/// The frequency (monobit) test from SP 800-22, section 2.1: are there about as many ones
+/// as zeros? Returns the p-value.
+pub fn monobit(bits: &[bool]) -> f64 {
+    let sum = bits.iter().map(|&bit| if bit { 1.0 } else { -1.0 }).sum::<f64>();
+    let statistic = sum.abs() / (bits.len() as f64).sqrt();
+    erfc(statistic / 2f64.sqrt())
+}
+
+/// The frequency test within a block, from section 2.2: is the proportion of ones in each
+/// block of the given size about a half? Bits past the last whole block are ignored.
+/// Returns the p-value.
+pub fn block_frequency(bits: &[bool], block_size: usize) -> f64 {
+    let blocks = bits.chunks(block_size).filter(|block| block.len() == block_size);
+    let mut count = 0;
+    let mut statistic = 0.0;
+    for block in blocks {
+        let ones = block.iter().filter(|&&bit| bit).count();
+        statistic += (ones as f64 / block_size as f64 - 0.5).powi(2);
+        count += 1;
+    }
+    statistic *= 4.0 * block_size as f64;
+    igamc(count as f64 / 2.0, statistic / 2.0)
+}
+
+/// The runs test from section 2.3: do runs of identical bits switch about as often as they
+/// should? Sequences too unbalanced for the test to apply, which fail the monobit test
+/// anyway, get a p-value of zero.
+pub fn runs(bits: &[bool]) -> f64 {
+    let n = bits.len() as f64;
+    let proportion = bits.iter().filter(|&&bit| bit).count() as f64 / n;
+    if (proportion - 0.5).abs() >= 2.0 / n.sqrt() {
+        return 0.0;
+    }
+
+    let changes = bits.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    let observed = (changes + 1) as f64;
+    let spread = proportion * (1.0 - proportion);
+    erfc((observed - 2.0 * n * spread).abs() / (2.0 * (2.0 * n).sqrt() * spread))
+}
+
+/// The p-values of all three tests for one sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub monobit: f64,
+    pub block_frequency: f64,
+    pub runs: f64,
+}
+
+impl Report {
+    /// Run every test on the bits of the data, using blocks of 128 bits for the block
+    /// frequency test. NIST recommends at least 100 bits.
+    pub fn new(data: &[u8]) -> Self {
+        let bits = bits(data);
+        Report {
+            monobit: monobit(&bits),
+            block_frequency: block_frequency(&bits, 128),
+            runs: runs(&bits),
+        }
+    }
+
+    /// Run every test on the given number of bytes from a random source, to see whether
+    /// its output looks random, whether or not it's predictable.
+    pub fn for_source<R>(rng: &mut R, len: usize) -> Self
+        where R: RandomSource + ?Sized
+    {
+        Self::new(&rng.bytes(len))
+    }
+
+    /// Whether every p-value is at least the significance level.
+    pub fn passed(&self) -> bool {
+        [self.monobit, self.block_frequency, self.runs].iter().all(|&p| p >= SIGNIFICANCE)
+    }
+}
+
+// The regularized upper incomplete gamma function Q(a, x), by its series for small x and
+// its continued fraction otherwise, as in Numerical Recipes.
+fn igamc(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut denominator = a;
+        for _ in 0..1000 {
+            denominator += 1.0;
+            term *= x / denominator;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * prefix
+    } else {
+        // Lentz's method, with tiny values standing in for zeros.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        prefix * h
+    }
+}
+
+// The log of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    let coefficients = [76.18009172947146, -86.50532032941677, 24.01409824083091,
+                        -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5];
+    let mut tmp = x + 5.5;
+    tmp -= (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    let mut y = x;
+    for c in &coefficients {
+        y += 1.0;
+        series += c / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    fn parse(bits: &str) -> Vec<bool> {
+        bits.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn test_examples() {
+        // The worked examples from SP 800-22, sections 2.1.4, 2.2.4 and 2.3.4.
+        assert!((monobit(&parse("1011010101")) - 0.527089).abs() < 1e-6);
+        assert!((block_frequency(&parse("0110011010"), 3) - 0.801252).abs() < 1e-6);
+        assert!((runs(&parse("1001101011")) - 0.147232).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_report() {
+        assert!(Report::for_source(&mut SeededRng::new(0), 4096).passed());
+
+        // Perfectly balanced, but far too regular.
+        let report = Report::new(&[0x55; 4096]);
+        assert!(report.monobit > 0.99);
+        assert!(report.runs < SIGNIFICANCE);
+        assert!(!report.passed());
+    }
+}
@@ -0,0 +1,159 @@
+use utils::digest::Digest;
+use utils::mac;
+
+// Leaves and interior nodes are hashed with different prefixes, as in RFC 6962, so that an
+// interior node can't be passed off as a leaf.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+fn hash_leaf<D>(hash: &mut D, leaf: &[u8]) -> Vec<u8>
+    where D: Digest + ?Sized
+{
+    hash.update(&[LEAF_PREFIX]);
+    hash.digest(leaf)
+}
+
+fn hash_node<D>(hash: &mut D, left: &[u8], right: &[u8]) -> Vec<u8>
+    where D: Digest + ?Sized
+{
+    hash.update(&[NODE_PREFIX]);
+    hash.update(left);
+    hash.digest(right)
+}
+
+/// A binary Merkle tree over a list of leaves. When a level has an odd number of nodes, the
+/// last one moves up to the next level as it is, rather than being paired with a copy of
+/// itself, so two different lists of leaves can never have the same root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    // Every level of node hashes, from the leaves up to the root.
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+/// Proof that a leaf is at a given position in a tree: the hashes of the siblings of each
+/// node on the path from the leaf to the root, skipping levels where the node has none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub siblings: Vec<Vec<u8>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over the given leaves. There must be at least one.
+    pub fn new<D>(hash: &mut D, leaves: &[Vec<u8>]) -> Result<Self, &'static str>
+        where D: Digest + ?Sized
+    {
+        if leaves.is_empty() {
+            return Err("A Merkle tree needs at least one leaf.");
+        }
+
+        let mut levels = vec![leaves.iter().map(|leaf| hash_leaf(hash, leaf)).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match *pair {
+                    [ref left, ref right] => hash_node(hash, left, right),
+                    _ => pair[0].clone(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(MerkleTree { levels: levels })
+    }
+
+    pub fn root(&self) -> &[u8] {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// The number of leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Trees always have at least one leaf.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// A proof that the leaf at the given index is in the tree, or None if there's no leaf
+    /// there.
+    pub fn proof(&self, index: usize) -> Option<Proof> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(sibling.clone());
+            }
+            position /= 2;
+        }
+        Some(Proof {
+            index: index,
+            leaf_count: self.len(),
+            siblings: siblings,
+        })
+    }
+}
+
+/// Check that a proof shows the leaf is at its index in the tree with the given root.
+pub fn verify<D>(hash: &mut D, root: &[u8], leaf: &[u8], proof: &Proof) -> bool
+    where D: Digest + ?Sized
+{
+    if proof.index >= proof.leaf_count {
+        return false;
+    }
+
+    let mut node = hash_leaf(hash, leaf);
+    let mut siblings = proof.siblings.iter();
+    let (mut position, mut width) = (proof.index, proof.leaf_count);
+    while width > 1 {
+        if position ^ 1 < width {
+            let sibling = match siblings.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            node = if position % 2 == 0 {
+                hash_node(hash, &node, sibling)
+            } else {
+                hash_node(hash, sibling, &node)
+            };
+        }
+        position /= 2;
+        width = (width + 1) / 2;
+    }
+    siblings.next().is_none() && mac::verify(&node, root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::sha256::Sha256;
+
+    #[test]
+    fn test_merkle_tree() {
+        let mut hash = Sha256::new();
+        for count in 1..10 {
+            let leaves = (0..count).map(|i| vec![i as u8; i]).collect::<Vec<_>>();
+            let tree = MerkleTree::new(&mut hash, &leaves).unwrap();
+            assert_eq!(tree.len(), count);
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify(&mut hash, tree.root(), leaf, &proof));
+                assert!(!verify(&mut hash, tree.root(), b"forged", &proof));
+            }
+            assert_eq!(tree.proof(count), None);
+        }
+
+        // Pairing the odd node with a copy of itself would give these the same root.
+        let three = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        let four = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"c".to_vec()];
+        assert!(MerkleTree::new(&mut hash, &three).unwrap().root() !=
+                MerkleTree::new(&mut hash, &four).unwrap().root());
+        assert!(MerkleTree::new(&mut hash, &[]).is_err());
+    }
+}
@@ -1,6 +1,77 @@
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod aead;
+#[cfg(feature = "backend-native")]
+pub mod aes;
+#[cfg(feature = "std")]
 pub mod attacks;
 pub mod bytes;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod chacha20;
+#[cfg(feature = "std")]
+pub mod classical;
+#[cfg(feature = "aes")]
+pub mod cipher;
+#[cfg(feature = "std")]
+pub mod collisions;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod compose;
+#[cfg(feature = "std")]
 pub mod crypto;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod dh;
+#[cfg(feature = "differential")]
+pub mod differential;
+pub mod digest;
+#[cfg(feature = "std")]
+pub mod ec;
+#[cfg(feature = "std")]
+pub mod hexdump;
+#[cfg(feature = "std")]
+pub mod image;
+#[cfg(feature = "std")]
+pub mod mac;
 pub mod math;
+#[cfg(feature = "std")]
+pub mod merkle;
+#[cfg(feature = "std")]
+pub mod nonce;
+#[cfg(all(feature = "std", feature = "aes"))]
 pub mod oracles;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod poly1305;
+#[cfg(feature = "std")]
+pub mod prng;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "std")]
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod random;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod rsa;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod schnorr;
+pub mod sha1;
+pub mod sha256;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod srp;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
 pub mod text;
+#[cfg(feature = "std")]
+pub mod timing;
+#[cfg(feature = "std")]
+pub mod tokens;
+#[cfg(feature = "std")]
+pub mod trials;
+#[cfg(all(feature = "std", feature = "aes"))]
+pub mod webapp;
+#[cfg(feature = "std")]
+pub mod wordlist;
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use utils::bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+
+/// A source of nonces for a stream of messages under one key. Each construction asks for
+/// nonces of its own length: 8 bytes for `crypto::Ctr`, 12 for GCM and ChaCha20, and 7 to
+/// 13 for CCM.
+pub trait NonceSequence {
+    /// The nonce for the next message, `len` bytes long.
+    fn next_nonce(&mut self, len: usize) -> Vec<u8>;
+}
+
+// A 64-bit value as a big-endian nonce of the given length: zero-padded on the left if the
+// nonce is longer, and keeping only the low bytes if it's shorter.
+fn encode(value: u64, len: usize) -> Vec<u8> {
+    let mut nonce = vec![0u8; len.saturating_sub(8)];
+    nonce.extend_from_slice(&value.to_be_bytes()[8 - len.min(8)..]);
+    nonce
+}
+
+/// Nonces counting up from a starting value, big-endian. Never repeats, short of sending
+/// 2^64 messages, or 2^(8 * len) for nonces shorter than 8 bytes.
+pub struct CounterNonces {
+    next: u64,
+}
+
+impl CounterNonces {
+    pub fn new(start: u64) -> Self {
+        CounterNonces { next: start }
+    }
+}
+
+impl NonceSequence for CounterNonces {
+    fn next_nonce(&mut self, len: usize) -> Vec<u8> {
+        let nonce = encode(self.next, len);
+        self.next = self.next.wrapping_add(1);
+        nonce
+    }
+}
+
+/// Uniformly random nonces. A repeat becomes likely after about 2^(4 * len) messages, so
+/// 2^32 for 8-byte nonces and 2^48 for 12-byte ones.
+pub struct RandomNonces<R: RandomSource> {
+    rng: R,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RandomNonces<OsSource> {
+    pub fn new() -> Self {
+        Self::with_source(OsSource::new())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for RandomNonces<OsSource> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: RandomSource> RandomNonces<R> {
+    pub fn with_source(rng: R) -> Self {
+        RandomNonces { rng: rng }
+    }
+}
+
+impl<R: RandomSource> NonceSequence for RandomNonces<R> {
+    fn next_nonce(&mut self, len: usize) -> Vec<u8> {
+        self.rng.bytes(len)
+    }
+}
+
+/// The same nonce for every message. This is the mistake that the nonce reuse challenges
+/// exploit, so it's here to be attacked, not used. The value is encoded as by
+/// `CounterNonces`.
+pub struct FixedNonce {
+    nonce: u64,
+}
+
+impl FixedNonce {
+    pub fn new(nonce: u64) -> Self {
+        FixedNonce { nonce: nonce }
+    }
+}
+
+impl NonceSequence for FixedNonce {
+    fn next_nonce(&mut self, len: usize) -> Vec<u8> {
+        encode(self.nonce, len)
+    }
+}
+
+/// Wraps another sequence and remembers every nonce it hands out, flagging any that are
+/// handed out more than once during the session.
+pub struct ReuseDetector<N: NonceSequence> {
+    inner: N,
+    seen: HashSet<Vec<u8>>,
+    reused: Vec<Vec<u8>>,
+}
+
+impl<N: NonceSequence> ReuseDetector<N> {
+    pub fn new(inner: N) -> Self {
+        ReuseDetector {
+            inner: inner,
+            seen: HashSet::new(),
+            reused: Vec::new(),
+        }
+    }
+
+    /// Nonces that were handed out again after their first use, in order. A nonce that
+    /// appears n times is listed n - 1 times.
+    pub fn reused(&self) -> &[Vec<u8>] {
+        &self.reused
+    }
+
+    /// Whether every nonce handed out so far was unique.
+    pub fn is_safe(&self) -> bool {
+        self.reused.is_empty()
+    }
+
+    /// Fail if any nonce has been reused, for callers that want to stop a session early.
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.is_safe() { Ok(()) } else { Err("Nonce reused.") }
+    }
+}
+
+impl<N: NonceSequence> NonceSequence for ReuseDetector<N> {
+    fn next_nonce(&mut self, len: usize) -> Vec<u8> {
+        let nonce = self.inner.next_nonce(len);
+        if !self.seen.insert(nonce.clone()) {
+            warn!("Nonce {} reused.", bytes::hex_from_bytes(&nonce));
+            self.reused.push(nonce.clone());
+        }
+        nonce
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_reuse_detector() {
+        let mut counter = ReuseDetector::new(CounterNonces::new(u64::max_value()));
+        assert_eq!(counter.next_nonce(8), vec![0xff; 8]);
+        assert_eq!(counter.next_nonce(12), vec![0; 12]);
+        assert_eq!(counter.next_nonce(12), [vec![0; 11], vec![1]].concat());
+        assert!(counter.check().is_ok());
+
+        let mut random = ReuseDetector::new(RandomNonces::with_source(SeededRng::new(0)));
+        for _ in 0..1000 {
+            assert_eq!(random.next_nonce(12).len(), 12);
+        }
+        assert!(random.is_safe());
+
+        let mut fixed = ReuseDetector::new(FixedNonce::new(0x0107));
+        for _ in 0..3 {
+            assert_eq!(fixed.next_nonce(4), vec![0, 0, 1, 7]);
+        }
+        assert_eq!(fixed.next_nonce(1), vec![7]);
+        assert_eq!(fixed.reused(), &[vec![0, 0, 1, 7], vec![0, 0, 1, 7]]);
+        assert!(fixed.check().is_err());
+    }
+}
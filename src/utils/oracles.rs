@@ -1,37 +1,41 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use itertools::Itertools;
-use rand::{Rng, OsRng};
-use rustc_serialize::base64::*;
 
 use utils::{bytes, crypto};
+use utils::crypto::PaddingError;
+use utils::random::{OsSource, RandomSource};
 
 /// Encrypt the given data using 128-bit AES with a randomly generated key.
 /// CBC mode will be used 50% of the time (with a randomly generated IV),
 /// and ECB mode will be used otherwise. Returns the encrypted data along
 /// with a boolean indicating that CBC mode was used.
 pub fn encryption_oracle(data: &[u8]) -> (Vec<u8>, bool) {
-    let mut rng = OsRng::new().unwrap();
+    encryption_oracle_with(data, &mut OsSource::new())
+}
 
+/// Same as `encryption_oracle`, but drawing randomness from the given source.
+pub fn encryption_oracle_with<R>(data: &[u8], rng: &mut R) -> (Vec<u8>, bool)
+    where R: RandomSource + ?Sized
+{
     // Generate random AES key.
-    let key = &mut [0u8; 16][..];
-    rng.fill_bytes(key);
+    let key = rng.bytes(16);
 
     // Add random prefix and suffix to data.
-    let prefix_len = rng.gen_range(5usize, 10);
-    let suffix_len = rng.gen_range(5usize, 10);
+    let prefix_len = rng.range(5, 10);
+    let suffix_len = rng.range(5, 10);
     let mut plaintext = Vec::with_capacity(prefix_len + data.len() + suffix_len);
-    plaintext.extend(rng.gen_iter::<u8>().take(prefix_len));
+    plaintext.extend(rng.bytes(prefix_len));
     plaintext.extend(data);
-    plaintext.extend(rng.gen_iter::<u8>().take(suffix_len));
+    plaintext.extend(rng.bytes(suffix_len));
 
-    let cbc = rng.gen_weighted_bool(2);
+    let cbc = rng.coin();
     let result = if cbc {
-        let iv = &mut [0u8; 16][..];
-        rng.fill_bytes(iv);
-        crypto::encrypt_cbc(key, iv, &plaintext)
+        let iv = rng.bytes(16);
+        crypto::encrypt_cbc(&key, &iv, &plaintext)
     } else {
-        crypto::encrypt_ecb(key, None, &plaintext, true)
+        crypto::encrypt_ecb(&key, &plaintext, true)
     };
 
     (result, cbc)
@@ -49,27 +53,33 @@ pub struct UnknownStringOracle {
 /// string to it, and encrypts the result using AES-128-ECB using its fixed, unknown key.
 impl UnknownStringOracle {
     pub fn new(add_prefix: bool) -> Self {
+        Self::with_source(add_prefix, &mut OsSource::new())
+    }
+
+    /// Create an oracle whose key and prefix are drawn from the given source.
+    pub fn with_source<R>(add_prefix: bool, rng: &mut R) -> Self
+        where R: RandomSource + ?Sized
+    {
+        let key = rng.bytes(16);
         let prefix = if add_prefix {
-            let mut rng = OsRng::new().unwrap();
-            let size = rng.gen_range(0usize, 64);
-            Some(bytes::random(size))
+            let size = rng.range(0, 64);
+            Some(rng.bytes(size))
         } else {
             None
         };
 
         UnknownStringOracle {
-            key: bytes::random(16),
+            key: key,
             prefix: prefix
         }
     }
 
     pub fn unknown_string() -> Vec<u8> {
-        "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg\
-         aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq\
-         dXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUg\
-         YnkK"
-            .from_base64()
-            .unwrap()
+        let encoded = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg\
+                       aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq\
+                       dXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUg\
+                       YnkK";
+        bytes::base64_to_bytes(encoded).unwrap()
     }
 
     pub fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
@@ -79,33 +89,156 @@ impl UnknownStringOracle {
         }
         plaintext.extend(bytes);
         plaintext.extend(Self::unknown_string());
-        crypto::encrypt_ecb(&self.key, None, &plaintext, true)
+        crypto::encrypt_ecb(&self.key, &plaintext, true)
+    }
+}
+
+/// Wraps an encryption oracle and counts the queries made to it, for comparing how many
+/// different attacks need. Against an oracle on the other end of a network, that's what
+/// decides how long an attack takes.
+pub struct CountingOracle<F> {
+    oracle: F,
+    queries: usize,
+}
+
+impl<F> CountingOracle<F>
+    where F: FnMut(&[u8]) -> Vec<u8>
+{
+    pub fn new(oracle: F) -> Self {
+        CountingOracle {
+            oracle: oracle,
+            queries: 0,
+        }
+    }
+
+    pub fn encrypt(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.queries += 1;
+        (self.oracle)(bytes)
+    }
+
+    /// The number of queries so far.
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+}
+
+/// What a server does with a decrypted cookie before parsing it. The challenges' servers
+/// accept anything, but real ones differ, and an attack that scrambles a block of plaintext,
+/// as CBC bitflipping does, only works against some of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaintextPolicy {
+    /// Accept any plaintext, replacing invalid UTF-8, as the challenges do.
+    Lenient,
+    /// Reject plaintexts with bytes above 0x7f, which a scrambled block almost always has.
+    RejectHighAscii,
+    /// Reject plaintexts that aren't valid UTF-8.
+    StrictUtf8,
+    /// Accept any plaintext, but percent-encode control characters, bytes above 0x7f and '%'
+    /// itself first, as a server that logs or echoes cookies might, so garbage can't be
+    /// mistaken for anything else and the result decodes back to the plaintext. Other
+    /// printable characters, including ';' and '=', are left alone.
+    QuoteNonPrintable,
+}
+
+impl PlaintextPolicy {
+    /// Apply the policy to a decrypted plaintext, returning the text to parse.
+    pub fn apply(&self, plaintext: Vec<u8>) -> Result<String, CookieError> {
+        match *self {
+            PlaintextPolicy::Lenient => Ok(bytes::to_string(&plaintext)),
+            PlaintextPolicy::RejectHighAscii if plaintext.iter().any(|&byte| byte > 0x7f) => {
+                Err(CookieError::Rejected(plaintext))
+            }
+            PlaintextPolicy::RejectHighAscii | PlaintextPolicy::StrictUtf8 => {
+                String::from_utf8(plaintext).map_err(|err| CookieError::Rejected(err.into_bytes()))
+            }
+            PlaintextPolicy::QuoteNonPrintable => {
+                Ok(plaintext.iter()
+                    .map(|&byte| if byte != b'%' && (byte == b' ' || byte.is_ascii_graphic()) {
+                        (byte as char).to_string()
+                    } else {
+                        format!("%{:02X}", byte)
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+impl Default for PlaintextPolicy {
+    fn default() -> Self {
+        PlaintextPolicy::Lenient
+    }
+}
+
+/// Why a cookie oracle refused a cookie.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CookieError {
+    /// The ciphertext didn't decrypt.
+    Padding(PaddingError),
+    /// The ciphertext decrypted, but the oracle's `PlaintextPolicy` rejected the plaintext,
+    /// which is included the way a server's error message might include it.
+    Rejected(Vec<u8>),
+}
+
+impl From<PaddingError> for CookieError {
+    fn from(err: PaddingError) -> Self {
+        CookieError::Padding(err)
+    }
+}
+
+impl fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CookieError::Padding(ref err) => err.fmt(f),
+            CookieError::Rejected(ref plaintext) => {
+                write!(f, "Invalid cookie: {}", bytes::hex_from_bytes(plaintext))
+            }
+        }
     }
 }
 
 pub struct ProfileCookieOracle {
     key: Vec<u8>,
+    policy: PlaintextPolicy,
 }
 
 impl ProfileCookieOracle {
     pub fn new() -> Self {
-        ProfileCookieOracle { key: bytes::random(16) }
+        Self::with_source(&mut OsSource::new())
+    }
+
+    /// Create an oracle whose key is drawn from the given source.
+    pub fn with_source<R: RandomSource + ?Sized>(rng: &mut R) -> Self {
+        ProfileCookieOracle {
+            key: rng.bytes(16),
+            policy: PlaintextPolicy::default(),
+        }
+    }
+
+    /// Apply the given policy to decrypted cookies.
+    pub fn with_policy(mut self, policy: PlaintextPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     /// Generate an encrypted profile cookie with the given email address.
     pub fn encrypt_cookie(&self, email: &str) -> Vec<u8> {
         let profile = bytes::from_string(&Self::profile_for(email));
-        crypto::encrypt_ecb(&self.key, None, &profile, true)
+        crypto::encrypt_ecb(&self.key, &profile, true)
     }
 
     /// Decrypt and parse the given byte string as a cookie.
-    pub fn decrypt_cookie(&self, bytes: &[u8]) -> HashMap<String, String> {
-        let profile = bytes::to_string(&crypto::decrypt_ecb(&self.key, None, bytes, true));
-        Self::parse_cookie(&profile)
+    pub fn decrypt_cookie(&self, bytes: &[u8]) -> Result<HashMap<String, String>, CookieError> {
+        if bytes.is_empty() || bytes.len() % 16 != 0 {
+            return Err(PaddingError::Length.into());
+        }
+        let decrypted = crypto::decrypt_ecb(&self.key, bytes, false);
+        let profile = crypto::strip_pkcs7(&decrypted).ok_or(PaddingError::Padding)?;
+        Ok(Self::parse_cookie(&self.policy.apply(profile)?))
     }
 
     /// Parse a string of key-value pairs delimited by '&' and '=' into a HashMap.
-    fn parse_cookie(string: &str) -> HashMap<String, String> {
+    pub fn parse_cookie(string: &str) -> HashMap<String, String> {
         let mut map = HashMap::new();
         for token in string.split('&') {
             let mut pair = token.split('=');
@@ -145,16 +278,29 @@ impl ProfileCookieOracle {
 pub struct CBCCookieOracle {
     key: Vec<u8>,
     iv: Vec<u8>,
+    policy: PlaintextPolicy,
 }
 
 impl CBCCookieOracle {
     pub fn new() -> Self {
+        Self::with_source(&mut OsSource::new())
+    }
+
+    /// Create an oracle whose key and IV are drawn from the given source.
+    pub fn with_source<R: RandomSource + ?Sized>(rng: &mut R) -> Self {
         CBCCookieOracle {
-            key: bytes::random(16),
-            iv: bytes::random(16)
+            key: rng.bytes(16),
+            iv: rng.bytes(16),
+            policy: PlaintextPolicy::default(),
         }
     }
 
+    /// Apply the given policy to decrypted cookies.
+    pub fn with_policy(mut self, policy: PlaintextPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Generate a cookie with the given user data, encrypted with AES-128-CBC.
     /// User data is sanitized before encryption.
     pub fn encrypt(&self, data: &str) -> Vec<u8> {
@@ -166,25 +312,146 @@ impl CBCCookieOracle {
         crypto::encrypt_cbc(&self.key, &self.iv, &bytes)
     }
 
-    /// Decrypt an encrypted cookie generated by Self::encrypt().
-    pub fn decrypt(&self, encrypted: &[u8]) -> String {
-        let decrypted = crypto::decrypt_cbc(&self.key, &self.iv, &encrypted);
-        bytes::to_string(&decrypted)
+    /// Decrypt an encrypted cookie generated by Self::encrypt(), or a tampered one, which
+    /// may fail to decrypt, or be rejected by the oracle's policy.
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<String, CookieError> {
+        let decrypted = crypto::decrypt_cbc_checked(&self.key, &self.iv, encrypted)?;
+        self.policy.apply(decrypted)
+    }
+}
+
+/// The CBC padding oracle from challenge 17. It holds a secret encrypted under a random key
+/// and IV, and will check any ciphertext for valid PKCS#7 padding, revealing nothing but
+/// whether the padding was valid.
+pub struct PaddingOracle {
+    key: Vec<u8>,
+    iv: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+impl PaddingOracle {
+    pub fn new(secret: &[u8]) -> Self {
+        Self::with_source(secret, &mut OsSource::new())
+    }
+
+    /// Create an oracle whose key and IV are drawn from the given source.
+    pub fn with_source<R: RandomSource + ?Sized>(secret: &[u8], rng: &mut R) -> Self {
+        PaddingOracle {
+            key: rng.bytes(16),
+            iv: rng.bytes(16),
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// The encrypted secret, as the IV and the ciphertext.
+    pub fn token(&self) -> (Vec<u8>, Vec<u8>) {
+        (self.iv.clone(), crypto::encrypt_cbc(&self.key, &self.iv, &self.secret))
+    }
+
+    /// Decrypt a ciphertext under the oracle's key and report whether its padding is valid.
+    pub fn check(&self, iv: &[u8], ciphertext: &[u8]) -> bool {
+        crypto::decrypt_cbc_checked(&self.key, iv, ciphertext).is_ok()
+    }
+}
+
+/// CBC with predictable IVs, as in SSL 3.0 and TLS 1.0: the oracle keeps one CBC stream
+/// going across messages, so each message is encrypted with the last ciphertext block of the
+/// one before as its IV, which anyone watching already knows. It will send a secret, and
+/// encrypt anything else it's asked to on the same connection, which is what the BEAST
+/// attack needs.
+pub struct PredictableIvOracle {
+    key: Vec<u8>,
+    iv: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+impl PredictableIvOracle {
+    pub fn new(secret: &[u8]) -> Self {
+        Self::with_source(secret, &mut OsSource::new())
+    }
+
+    /// Create an oracle whose key and first IV are drawn from the given source.
+    pub fn with_source<R: RandomSource + ?Sized>(secret: &[u8], rng: &mut R) -> Self {
+        PredictableIvOracle {
+            key: rng.bytes(16),
+            iv: rng.bytes(16),
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// Encrypt a message on the connection, with PKCS#7 padding.
+    pub fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let ciphertext = crypto::encrypt_cbc(&self.key, &self.iv, data);
+        self.iv = ciphertext[ciphertext.len() - 16..].to_vec();
+        ciphertext
+    }
+
+    /// Send the secret on the connection, returning the IV it was encrypted with along with
+    /// the ciphertext, as an eavesdropper would see them.
+    pub fn send_secret(&mut self) -> (Vec<u8>, Vec<u8>) {
+        let iv = self.iv.clone();
+        let secret = self.secret.clone();
+        (iv, self.encrypt(&secret))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::math;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_seeded_oracles() {
+        let data = [0u8; 48];
+        let first = encryption_oracle_with(&data, &mut SeededRng::new(7));
+        assert_eq!(first, encryption_oracle_with(&data, &mut SeededRng::new(7)));
+
+        let a = UnknownStringOracle::with_source(true, &mut SeededRng::new(7));
+        let b = UnknownStringOracle::with_source(true, &mut SeededRng::new(7));
+        assert_eq!(a.encrypt(&data), b.encrypt(&data));
+    }
 
     #[test]
     fn test_profile_cookie() {
         let oracle = ProfileCookieOracle::new();
         let cookie = oracle.encrypt_cookie("foo@bar.com&role=admin");
-        let result = oracle.decrypt_cookie(&cookie);
+        let result = oracle.decrypt_cookie(&cookie).unwrap();
 
         assert_eq!(result.get("uid"), Some(&"10".to_string()));
         assert_eq!(result.get("email"), Some(&"foo@bar.com%26role%3Dadmin".to_string()));
         assert_eq!(result.get("role"), Some(&"user".to_string()));
+
+        // A truncated cookie is an error, not a panic.
+        let err = oracle.decrypt_cookie(&cookie[..20]).unwrap_err();
+        assert_eq!(err, CookieError::Padding(PaddingError::Length));
+        assert_eq!(PlaintextPolicy::QuoteNonPrintable.apply(b"a=\x01\xff;%".to_vec()),
+                   Ok("a=%01%FF;%25".to_string()));
+    }
+
+    #[test]
+    fn test_ciphertext_statistics() {
+        // CBC should hide even the most repetitive input, while ECB gives it away.
+        let data = "A".repeat(4096);
+        let oracle = CBCCookieOracle::with_source(&mut SeededRng::new(7));
+        let ciphertext = oracle.encrypt(&data);
+        assert!(math::chi_squared_p_value(math::chi_squared_bytes(&ciphertext), 255.0) > 0.001);
+        assert!(math::serial_correlation(&ciphertext).abs() < 0.05);
+
+        let oracle = UnknownStringOracle::with_source(false, &mut SeededRng::new(7));
+        let ciphertext = oracle.encrypt(data.as_bytes());
+        assert!(math::chi_squared_p_value(math::chi_squared_bytes(&ciphertext), 255.0) < 1e-6);
+    }
+
+    #[test]
+    fn test_padding_oracle() {
+        let oracle = PaddingOracle::new(b"YELLOW SUBMARINE");
+        let (iv, mut ciphertext) = oracle.token();
+        assert!(oracle.check(&iv, &ciphertext));
+
+        // The last block is all padding, so any change to the block before breaks it.
+        ciphertext[31] ^= 1;
+        assert!(!oracle.check(&iv, &ciphertext));
+        assert!(!oracle.check(&iv, &ciphertext[..20]));
     }
 }
@@ -0,0 +1,127 @@
+#[cfg(feature = "parallel")]
+use std::sync::mpsc;
+#[cfg(feature = "parallel")]
+use std::thread;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Apply a function to every item in a slice, returning the results in order. With the
+/// `parallel` feature enabled the items are processed on rayon's thread pool; otherwise
+/// they are processed one at a time on the current thread. Either way the output is the
+/// same, so callers that pick a winner from the results stay deterministic.
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+    where T: Sync,
+          U: Send,
+          F: Fn(&T) -> U + Sync + Send
+{
+    #[cfg(feature = "parallel")]
+    let results = items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    let results = items.iter().map(f).collect();
+    results
+}
+
+/// Like `map`, but over the items of an iterator, which are produced as they're needed
+/// rather than collected up front. The results are still returned in order.
+pub fn map_iter<I, U, F>(items: I, f: F) -> Vec<U>
+    where I: Iterator + Send,
+          I::Item: Send,
+          U: Send,
+          F: Fn(I::Item) -> U + Sync + Send
+{
+    #[cfg(feature = "parallel")]
+    let results = {
+        // Bridged items arrive in no particular order, so tag them with their indices.
+        let mut results = items.enumerate()
+            .par_bridge()
+            .map(|(i, item)| (i, f(item)))
+            .collect::<Vec<_>>();
+        results.sort_by_key(|&(i, _)| i);
+        results.into_iter().map(|(_, result)| result).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = items.map(f).collect();
+    results
+}
+
+/// Apply a function to every item in a slice, passing each result to `consume` on the
+/// current thread as soon as it's ready, along with the index of its item. With the
+/// `parallel` feature enabled the items are processed on rayon's thread pool and the results
+/// stream back through a channel in whatever order they finish; otherwise they're processed
+/// in order on the current thread. Unlike `map`, the results never all have to be held at
+/// once, and the consumer can report progress, which is only seen on the thread that
+/// installed the progress callback.
+pub fn for_each<T, U, F, C>(items: &[T], f: F, mut consume: C)
+    where T: Sync,
+          U: Send,
+          F: Fn(&T) -> U + Sync + Send,
+          C: FnMut(usize, U)
+{
+    #[cfg(feature = "parallel")]
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        // Feed the pool from a thread of its own, so the current thread is free to consume.
+        scope.spawn(move || {
+            items.par_iter().enumerate().for_each_with(sender, |sender, (i, item)| {
+                // This only fails if `consume` panicked, which the scope passes on.
+                let _ = sender.send((i, f(item)));
+            })
+        });
+        for (i, result) in receiver {
+            consume(i, result);
+        }
+    });
+    #[cfg(not(feature = "parallel"))]
+    for (i, item) in items.iter().enumerate() {
+        consume(i, f(item));
+    }
+}
+
+/// Find the index of the first item in a slice that satisfies the predicate. With the
+/// `parallel` feature enabled the items are tested on rayon's thread pool, which stops
+/// testing items past a match once one is found; otherwise they're tested in order on the
+/// current thread. Either way the answer is the first match, not just any match.
+pub fn position<T, F>(items: &[T], f: F) -> Option<usize>
+    where T: Sync,
+          F: Fn(&T) -> bool + Sync + Send
+{
+    #[cfg(feature = "parallel")]
+    let position = items.par_iter().position_first(f);
+    #[cfg(not(feature = "parallel"))]
+    let position = items.iter().position(f);
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_order() {
+        let items = (0..1000).collect::<Vec<u32>>();
+        let squares = map(&items, |x| x * x);
+        assert_eq!(squares, items.iter().map(|x| x * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_iter_order() {
+        let squares = map_iter(0..1000u32, |x| x * x);
+        assert_eq!(squares, (0..1000).map(|x| x * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_for_each() {
+        let items = (0..1000).collect::<Vec<u32>>();
+        let mut squares = vec![None; items.len()];
+        for_each(&items, |x| x * x, |i, square| squares[i] = Some(square));
+        assert!(squares.iter().zip(&items).all(|(square, x)| *square == Some(x * x)));
+    }
+
+    #[test]
+    fn test_position() {
+        let items = (0..1000).collect::<Vec<u32>>();
+        assert_eq!(position(&items, |x| x % 7 == 6 && *x > 100), Some(104));
+        assert_eq!(position(&items, |&x| x > 1000), None);
+    }
+}
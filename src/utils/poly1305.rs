@@ -0,0 +1,77 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::One;
+
+/// The prime 2^130 - 5 that Poly1305 evaluates its polynomial modulo.
+pub fn prime() -> BigInt {
+    (BigInt::one() << 130) - 5
+}
+
+// Bits of r that clamping clears: the top four bits of every 32-bit word, and the bottom
+// two bits of all but the first.
+const CLAMP: u128 = 0x0ffffffc0ffffffc0ffffffc0fffffff;
+
+/// Whether r could be a clamped Poly1305 key half.
+pub fn is_clamped(r: &BigInt) -> bool {
+    r.sign() != Sign::Minus && r.bits() <= 128 && to_u128(r) & !CLAMP == 0
+}
+
+fn from_u128(n: u128) -> BigInt {
+    BigInt::from_bytes_le(Sign::Plus, &n.to_le_bytes())
+}
+
+// The low 128 bits of a nonnegative number.
+fn to_u128(n: &BigInt) -> u128 {
+    let mut bytes = [0u8; 16];
+    let le = n.mod_floor(&(BigInt::one() << 128)).to_bytes_le().1;
+    bytes[..le.len()].copy_from_slice(&le);
+    u128::from_le_bytes(bytes)
+}
+
+/// Evaluate the Poly1305 polynomial for a message at r, modulo 2^130 - 5. Each 16-byte
+/// chunk of the message, with a 1 byte appended, is a coefficient.
+pub fn evaluate(r: &BigInt, message: &[u8]) -> BigInt {
+    let p = prime();
+    message.chunks(16).fold(BigInt::from(0), |acc, chunk| {
+        let coefficient = BigInt::from_bytes_le(Sign::Plus, chunk) +
+                          (BigInt::one() << (8 * chunk.len()));
+        ((acc + coefficient) * r).mod_floor(&p)
+    })
+}
+
+/// Compute the Poly1305 tag of a message under a 32-byte one-time key: r, which is clamped,
+/// followed by s. Panics if the key is the wrong length.
+pub fn poly1305(key: &[u8], message: &[u8]) -> [u8; 16] {
+    assert_eq!(key.len(), 32, "Poly1305 keys must be 32 bytes long.");
+    let r = from_u128(to_u128(&BigInt::from_bytes_le(Sign::Plus, &key[..16])) & CLAMP);
+    let s = BigInt::from_bytes_le(Sign::Plus, &key[16..]);
+    to_u128(&(evaluate(&r, message) + s)).to_le_bytes()
+}
+
+/// The message that the ChaCha20-Poly1305 AEAD (RFC 8439) authenticates: the associated
+/// data and ciphertext, each zero-padded to a multiple of 16 bytes, then their lengths.
+pub fn aead_input(associated_data: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let mut input = associated_data.to_vec();
+    input.resize((input.len() + 15) / 16 * 16, 0);
+    input.extend_from_slice(ciphertext);
+    input.resize((input.len() + 15) / 16 * 16, 0);
+    input.extend_from_slice(&(associated_data.len() as u64).to_le_bytes());
+    input.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    #[test]
+    fn test_poly1305() {
+        // Test vector from RFC 8439, section 2.5.2.
+        let key = bytes::hex_to_bytes("85d6be7857556d337f4452fe42d506a8\
+                                       0103808afb0db2fd4abff6af4149f51b")
+            .unwrap();
+        assert_eq!(bytes::hex_from_bytes(&poly1305(&key, b"Cryptographic Forum Research Group")),
+                   "a8061dc1305136c6c22b8baf0c0127a9");
+    }
+}
@@ -0,0 +1,197 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use utils::math::{self, lattice};
+use utils::random::RandomSource;
+
+/// A linear congruential generator: each output is the next state x' = ax + c mod m. The
+/// modulus must fit in a u64, so it can be at most 2^64 - 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lcg {
+    pub a: u64,
+    pub c: u64,
+    pub m: u64,
+    pub state: u64,
+}
+
+impl Lcg {
+    /// A generator with the given parameters and seed. Panics if the modulus is zero.
+    pub fn new(a: u64, c: u64, m: u64, seed: u64) -> Self {
+        assert!(m != 0, "The modulus must be nonzero.");
+        Lcg {
+            a: a % m,
+            c: c % m,
+            m: m,
+            state: seed % m,
+        }
+    }
+
+    /// The parameters of Knuth's MMIX generator, with the modulus reduced to fit in a u64.
+    pub fn mmix(seed: u64) -> Self {
+        Self::new(6364136223846793005, 1442695040888963407, 1 << 63, seed)
+    }
+
+    /// Step the generator, and return the new state.
+    pub fn next_state(&mut self) -> u64 {
+        let next = (self.a as u128 * self.state as u128 + self.c as u128) % self.m as u128;
+        self.state = next as u64;
+        self.state
+    }
+}
+
+/// Each byte is the top eight bits of a state, scaled to the modulus: the low bits of an
+/// LCG with a power-of-two modulus are notoriously poor.
+impl RandomSource for Lcg {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            *byte = (self.next_state() as u128 * 256 / self.m as u128) as u8;
+        }
+    }
+}
+
+// The generator that produced the given outputs, positioned to produce whatever comes after
+// them, if it really did produce all of them.
+fn check(a: u64, c: u64, m: u64, outputs: &[u64]) -> Result<Lcg, &'static str> {
+    let mut lcg = Lcg::new(a, c, m, outputs[0]);
+    if outputs[1..].iter().all(|&output| lcg.next_state() == output) {
+        Ok(lcg)
+    } else {
+        Err("Outputs don't come from a single LCG.")
+    }
+}
+
+/// Recover an LCG's multiplier, increment and modulus from consecutive full outputs. With
+/// differences t[n] = x[n + 1] - x[n], each t[n + 2] t[n] - t[n + 1]^2 is a multiple of the
+/// modulus, so the GCD of a few of them is the modulus, and then the rest is linear algebra.
+/// Half a dozen outputs are usually enough; more make a wrong modulus less likely.
+pub fn recover_parameters(outputs: &[u64]) -> Result<Lcg, &'static str> {
+    if outputs.len() < 4 {
+        return Err("Need at least four outputs.");
+    }
+    let x = outputs.iter().map(|&x| BigInt::from(x)).collect::<Vec<_>>();
+    let t = x.windows(2).map(|pair| &pair[1] - &pair[0]).collect::<Vec<_>>();
+    let m = t.windows(3)
+        .map(|t| (&t[2] * &t[0] - &t[1] * &t[1]).abs())
+        .fold(BigInt::zero(), |m, u| m.gcd(&u));
+
+    // The modulus must also be larger than every output.
+    let largest = outputs.iter().max().unwrap();
+    let m = match m.to_u64() {
+        Some(m) if m > *largest => m,
+        _ => return Err("Couldn't find the modulus."),
+    };
+
+    // t[n + 1] = a t[n] mod m, so a is t[1] / t[0], if t[0] is invertible.
+    let modulus = BigInt::from(m);
+    for pair in t.windows(2) {
+        if let Some(inverse) = math::invmod(&pair[0].mod_floor(&modulus), &modulus) {
+            let a = (&pair[1] * inverse).mod_floor(&modulus);
+            let c = (&x[1] - &a * &x[0]).mod_floor(&modulus);
+            return check(a.to_u64().unwrap(), c.to_u64().unwrap(), m, outputs);
+        }
+    }
+    Err("Couldn't find the multiplier.")
+}
+
+/// Recover the state of an LCG with known parameters from consecutive outputs truncated to
+/// their high bits, output >> shift, and return the generator positioned after them.
+///
+/// Writing b[i] for the state after i steps from a state of zero, each state is
+/// x[i] = a^i x[0] + b[i] mod m, so the vector of x[i] - b[i] is a point in the lattice
+/// generated by (1, a, a^2, ...) and multiples of m. The outputs give an approximation of
+/// that point, off by less than 2^shift in each coordinate, and LLL finds the error as the
+/// shortest vector of the lattice with the approximation embedded in it. This works as long
+/// as the error is well below the lattice's typical spacing, m^((n - 1)/n) for n outputs.
+pub fn recover_truncated_state(a: u64,
+                               c: u64,
+                               m: u64,
+                               shift: usize,
+                               outputs: &[u64])
+                               -> Result<Lcg, &'static str> {
+    let n = outputs.len();
+    if n < 2 {
+        return Err("Need at least two outputs.");
+    }
+    let modulus = BigInt::from(m);
+
+    let mut powers = vec![BigInt::one()];
+    let mut offsets = vec![BigInt::zero()];
+    for i in 1..n {
+        powers.push((&powers[i - 1] * a).mod_floor(&modulus));
+        offsets.push((&offsets[i - 1] * a + c).mod_floor(&modulus));
+    }
+    let approximation = outputs.iter()
+        .zip(&offsets)
+        .map(|(&y, b)| (BigInt::from(y) << shift) - b)
+        .collect::<Vec<_>>();
+
+    // The lattice basis, plus one extra dimension holding the approximation and a weight as
+    // large as the error, so that the reduced basis contains (error, weight).
+    let weight = BigInt::one() << shift;
+    let mut basis = Vec::new();
+    let mut row = powers.clone();
+    row.push(BigInt::zero());
+    basis.push(row);
+    for i in 1..n {
+        let mut row = vec![BigInt::zero(); n + 1];
+        row[i] = modulus.clone();
+        basis.push(row);
+    }
+    let mut row = approximation.clone();
+    row.push(weight.clone());
+    basis.push(row);
+
+    for vector in lattice::lll(&basis) {
+        // The vector is (approximation - point) up to sign, so the first state is the first
+        // approximation minus the first coordinate.
+        let error = if vector[n] == weight {
+            vector[0].clone()
+        } else if vector[n] == -&weight {
+            -&vector[0]
+        } else {
+            continue;
+        };
+        let x0: BigInt = (&approximation[0] - error).mod_floor(&modulus);
+        if let Some(x0) = x0.to_u64() {
+            let mut lcg = Lcg::new(a, c, m, x0);
+            if lcg.state >> shift == outputs[0] &&
+               outputs[1..].iter().all(|&output| lcg.next_state() >> shift == output) {
+                return Ok(lcg);
+            }
+        }
+    }
+    Err("Couldn't recover the state; try more outputs.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_parameters() {
+        let mut lcg = Lcg::new(1103515245, 12345, (1 << 31) - 1, 42);
+        let outputs = (0..10).map(|_| lcg.next_state()).collect::<Vec<_>>();
+        let mut recovered = recover_parameters(&outputs).unwrap();
+        assert_eq!(recovered, lcg);
+        assert_eq!(recovered.next_state(), lcg.next_state());
+
+        let mut outputs = outputs;
+        outputs[5] ^= 1;
+        assert!(recover_parameters(&outputs).is_err());
+    }
+
+    #[test]
+    fn test_recover_truncated_state() {
+        // Only the top 32 of 63 bits of each state are revealed.
+        let mut lcg = Lcg::mmix(0xdeadbeef);
+        let outputs = (0..8).map(|_| lcg.next_state() >> 31).collect::<Vec<_>>();
+        let mut recovered = recover_truncated_state(lcg.a, lcg.c, lcg.m, 31, &outputs).unwrap();
+        assert_eq!(recovered, lcg);
+        assert_eq!(recovered.next_state(), lcg.next_state());
+
+        let mut lcg = Lcg::new(48271, 0, (1 << 31) - 1, 1);
+        let outputs = (0..6).map(|_| lcg.next_state() >> 8).collect::<Vec<_>>();
+        assert_eq!(recover_truncated_state(48271, 0, (1 << 31) - 1, 8, &outputs), Ok(lcg));
+    }
+}
@@ -0,0 +1,175 @@
+use utils::math::nist;
+use utils::random::RandomSource;
+
+/// A Fibonacci linear feedback shift register over GF(2). With taps c1, ..., cL, each new
+/// bit is s[n] = c1 s[n-1] + c2 s[n-2] + ... + cL s[n-L], so the register is described by
+/// its connection polynomial 1 + c1 x + ... + cL x^L. Used as a stream cipher, the key is
+/// the initial state, and knowing 2L bits of keystream is enough to recover everything.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lfsr {
+    taps: Vec<bool>,
+    // The next L output bits, oldest first.
+    state: Vec<bool>,
+}
+
+impl Lfsr {
+    /// A register with the given taps c1, ..., cL, whose first L output bits are the given
+    /// state. Panics if the lengths differ.
+    pub fn new(taps: Vec<bool>, state: Vec<bool>) -> Self {
+        assert_eq!(taps.len(), state.len(), "The state must have one bit per tap.");
+        Lfsr {
+            taps: taps,
+            state: state,
+        }
+    }
+
+    pub fn taps(&self) -> &[bool] {
+        &self.taps
+    }
+
+    /// The length L of the register.
+    pub fn len(&self) -> usize {
+        self.taps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.taps.is_empty()
+    }
+
+    /// Output one bit and clock the register.
+    pub fn next_bit(&mut self) -> bool {
+        if self.state.is_empty() {
+            return false;
+        }
+        let length = self.state.len();
+        let feedback = self.taps
+            .iter()
+            .enumerate()
+            .fold(false, |bit, (i, &tap)| bit ^ (tap && self.state[length - 1 - i]));
+        let output = self.state.remove(0);
+        self.state.push(feedback);
+        output
+    }
+
+    /// Output the given number of bytes of keystream. Bits are packed most significant
+    /// first, the same order `nist::bits` unpacks them in.
+    pub fn keystream(&mut self, len: usize) -> Vec<u8> {
+        self.bytes(len)
+    }
+
+    /// Encrypt or decrypt by XORing with the keystream.
+    pub fn apply(&mut self, data: &[u8]) -> Vec<u8> {
+        data.iter().zip(self.keystream(data.len())).map(|(x, k)| x ^ k).collect()
+    }
+}
+
+/// An LFSR makes a perfectly good `RandomSource` for anything that doesn't need security,
+/// and a good demonstration of why statistical tests say nothing about predictability.
+impl RandomSource for Lfsr {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            *byte = (0..8).fold(0, |acc, _| acc << 1 | self.next_bit() as u8);
+        }
+    }
+}
+
+/// Find the shortest LFSR that generates the given bits with Berlekamp-Massey, and return
+/// its taps. The length of the result is the linear complexity of the sequence; if the
+/// sequence came from a register of length L, 2L bits are enough to find it.
+pub fn berlekamp_massey(bits: &[bool]) -> Vec<bool> {
+    // The connection polynomial so far, the one from before the last length change, and
+    // how many steps ago that change happened.
+    let mut connection = vec![false; bits.len() + 1];
+    connection[0] = true;
+    let mut previous = connection.clone();
+    let mut length = 0;
+    let mut shift = 1;
+
+    for n in 0..bits.len() {
+        let discrepancy = (1..length + 1)
+            .fold(bits[n], |d, i| d ^ (connection[i] && bits[n - i]));
+        if !discrepancy {
+            shift += 1;
+            continue;
+        }
+
+        // Cancel the discrepancy by adding x^shift times the previous polynomial.
+        let saved = connection.clone();
+        for i in 0..connection.len() - shift {
+            connection[i + shift] ^= previous[i];
+        }
+        if 2 * length <= n {
+            length = n + 1 - length;
+            previous = saved;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+
+    connection[1..length + 1].to_vec()
+}
+
+/// Recover the register that generated the given keystream bits, positioned at the first
+/// bit. Fails if even the shortest register is more than half as long as the keystream.
+/// The answer is only sure to be right if the real register is at most that long too.
+pub fn recover(keystream: &[bool]) -> Result<Lfsr, &'static str> {
+    let taps = berlekamp_massey(keystream);
+    let length = taps.len();
+    if 2 * length > keystream.len() {
+        return Err("Not enough keystream to recover the register.");
+    }
+    Ok(Lfsr::new(taps, keystream[..length].to_vec()))
+}
+
+/// Decrypt a whole LFSR-encrypted ciphertext given a prefix of its plaintext. The known
+/// plaintext gives away that much keystream, Berlekamp-Massey turns it into the register,
+/// and the register gives the rest of the keystream.
+pub fn decrypt_with_known_prefix(ciphertext: &[u8],
+                                 prefix: &[u8])
+                                 -> Result<Vec<u8>, &'static str> {
+    if prefix.len() > ciphertext.len() {
+        return Err("Known plaintext is longer than the ciphertext.");
+    }
+    let keystream = ciphertext.iter().zip(prefix).map(|(c, p)| c ^ p).collect::<Vec<_>>();
+    let mut lfsr = recover(&nist::bits(&keystream))?;
+    Ok(lfsr.apply(ciphertext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x^16 + x^14 + x^13 + x^11 + 1, which is primitive, so the register has the maximal
+    // period of 2^16 - 1.
+    fn taps() -> Vec<bool> {
+        (1..17).map(|i| [11, 13, 14, 16].contains(&i)).collect()
+    }
+
+    #[test]
+    fn test_berlekamp_massey() {
+        let state = nist::bits(&[0xac, 0xe1]);
+        let mut lfsr = Lfsr::new(taps(), state);
+        let bits = (0..64).map(|_| lfsr.next_bit()).collect::<Vec<_>>();
+        assert_eq!(berlekamp_massey(&bits), taps());
+        let expected = Lfsr::new(taps(), bits[..16].to_vec());
+        assert_eq!(recover(&bits[..32]), Ok(expected.clone()));
+        assert!(recover(&bits[..24]) != Ok(expected));
+
+        // A repeating pattern of period 3 is generated by s[n] = s[n-3].
+        let bits = nist::bits(&[0b10010010, 0b01001001]);
+        assert_eq!(berlekamp_massey(&bits), vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_known_plaintext() {
+        let message = b"Attack at dawn. Bring the LFSR, leave the one-time pad at home.";
+        let key = nist::bits(&[0x13, 0x37]);
+        let ciphertext = Lfsr::new(taps(), key.clone()).apply(message);
+        assert_eq!(Lfsr::new(taps(), key).apply(&ciphertext), message.to_vec());
+
+        // Four bytes of known plaintext are 32 bits of keystream: just enough.
+        assert_eq!(decrypt_with_known_prefix(&ciphertext, b"Atta"), Ok(message.to_vec()));
+        assert!(decrypt_with_known_prefix(&ciphertext, b"Att") != Ok(message.to_vec()));
+    }
+}
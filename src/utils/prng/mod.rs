@@ -0,0 +1,4 @@
+pub mod lcg;
+pub mod lfsr;
+pub mod mt19937;
+pub mod xorshift;
@@ -0,0 +1,186 @@
+use utils::math::gf2::Matrix;
+use utils::random::RandomSource;
+
+/// xorshift128+, with the shift constants from Vigna's original paper, which are also the
+/// ones V8 uses for Math.random. The state update is linear over GF(2); only the addition
+/// that produces each output isn't.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Xorshift128Plus {
+    pub state: [u64; 2],
+}
+
+// One step of the state update, on words or on their symbolic equivalents.
+fn step<T>(state: &mut [T; 2],
+           xor: fn(&T, &T) -> T,
+           shl: fn(&T, u32) -> T,
+           shr: fn(&T, u32) -> T)
+    where T: Clone
+{
+    let s0 = state[1].clone();
+    let mut s1 = state[0].clone();
+    s1 = xor(&s1, &shl(&s1, 23));
+    s1 = xor(&s1, &shr(&s1, 17));
+    s1 = xor(&s1, &s0);
+    s1 = xor(&s1, &shr(&s0, 26));
+    state[0] = s0;
+    state[1] = s1;
+}
+
+impl Xorshift128Plus {
+    /// A generator with the given state, which mustn't be all zeros.
+    pub fn new(state: [u64; 2]) -> Self {
+        assert!(state != [0, 0], "The state must not be all zeros.");
+        Xorshift128Plus { state: state }
+    }
+
+    /// Output the sum of the two state words, then step.
+    pub fn next_u64(&mut self) -> u64 {
+        let output = self.state[0].wrapping_add(self.state[1]);
+        step(&mut self.state, |x, y| x ^ y, |x, n| x << n, |x, n| x >> n);
+        output
+    }
+
+    /// Step, then output a double in [0, 1) made from the top 52 bits of the first state
+    /// word, the way V8 implements Math.random. This leaks state bits directly.
+    pub fn next_f64(&mut self) -> f64 {
+        step(&mut self.state, |x, y| x ^ y, |x, n| x << n, |x, n| x >> n);
+        f64::from_bits(self.state[0] >> 12 | 0x3ff0000000000000) - 1.0
+    }
+}
+
+impl RandomSource for Xorshift128Plus {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+// A state word as a function of the initial state: bit i of the word is the XOR of the
+// initial state bits set in word[i], where bits 0-63 are the first word and 64-127 the
+// second.
+type Symbolic = [u128; 64];
+
+fn symbolic_xor(x: &Symbolic, y: &Symbolic) -> Symbolic {
+    let mut z = *x;
+    for i in 0..64 {
+        z[i] ^= y[i];
+    }
+    z
+}
+
+fn symbolic_shl(x: &Symbolic, n: u32) -> Symbolic {
+    let mut z = [0; 64];
+    z[n as usize..].copy_from_slice(&x[..64 - n as usize]);
+    z
+}
+
+fn symbolic_shr(x: &Symbolic, n: u32) -> Symbolic {
+    let mut z = [0; 64];
+    z[..64 - n as usize].copy_from_slice(&x[n as usize..]);
+    z
+}
+
+// Run the generator symbolically, calling `leak` with the symbolic state before each of
+// `steps` steps to collect equations, then solve them for the initial state.
+fn solve<F>(steps: usize, mut leak: F) -> Result<[u64; 2], &'static str>
+    where F: FnMut(usize, &[Symbolic; 2], &mut Vec<(u128, bool)>)
+{
+    let mut state = [[0u128; 64]; 2];
+    for (word, symbolic) in state.iter_mut().enumerate() {
+        for (i, bit) in symbolic.iter_mut().enumerate() {
+            *bit = 1 << (64 * word + i);
+        }
+    }
+    let mut equations = Vec::new();
+    for n in 0..steps {
+        leak(n, &state, &mut equations);
+        step(&mut state, symbolic_xor, symbolic_shl, symbolic_shr);
+    }
+
+    let mut matrix = Matrix::zero(equations.len(), 128);
+    for (row, &(mask, _)) in equations.iter().enumerate() {
+        for col in 0..128 {
+            matrix.set(row, col, mask >> col & 1 == 1);
+        }
+    }
+    if matrix.rank() < 128 {
+        return Err("Not enough outputs to determine the state.");
+    }
+    let rhs = equations.iter().map(|&(_, bit)| bit).collect::<Vec<_>>();
+    let bits = matrix.solve(&rhs).ok_or("Outputs don't come from xorshift128+.")?;
+    let word = |offset: usize| (0..64).fold(0, |word, i| word | (bits[offset + i] as u64) << i);
+    // All zero outputs solve to the all zero state, which the generator can never be in.
+    match [word(0), word(64)] {
+        [0, 0] => Err("Outputs don't come from a nonzero state."),
+        state => Ok(state),
+    }
+}
+
+/// Recover the state a generator had before producing the given outputs of `next_u64`. The
+/// addition is linear in the lowest bit, where it's just XOR, so each output gives one
+/// linear equation in the 128 state bits, and about 130 outputs are enough to solve them.
+pub fn recover_from_u64s(outputs: &[u64]) -> Result<Xorshift128Plus, &'static str> {
+    let state = solve(outputs.len(), |n, state, equations| {
+        equations.push((state[0][0] ^ state[1][0], outputs[n] & 1 == 1));
+    })?;
+    let mut generator = Xorshift128Plus::new(state);
+    if outputs.iter().all(|&output| generator.next_u64() == output) {
+        Ok(Xorshift128Plus::new(state))
+    } else {
+        Err("Outputs don't come from xorshift128+.")
+    }
+}
+
+/// Recover the state a generator had before producing the given outputs of `next_f64`, as
+/// in the well-known attacks on Math.random. Each output gives 52 bits of state directly,
+/// but they overlap, so it takes four outputs rather than three to pin down all 128.
+pub fn recover_from_f64s(outputs: &[f64]) -> Result<Xorshift128Plus, &'static str> {
+    let state = solve(outputs.len() + 1, |n, state, equations| {
+        if n == 0 {
+            return;
+        }
+        let mantissa = (outputs[n - 1] + 1.0).to_bits() & ((1 << 52) - 1);
+        for i in 0..52 {
+            equations.push((state[0][12 + i], mantissa >> i & 1 == 1));
+        }
+    })?;
+    let mut generator = Xorshift128Plus::new(state);
+    if outputs.iter().all(|&output| generator.next_f64() == output) {
+        Ok(Xorshift128Plus::new(state))
+    } else {
+        Err("Outputs don't come from xorshift128+.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_from_u64s() {
+        let mut generator = Xorshift128Plus::new([0x0123456789abcdef, 0xfedcba9876543210]);
+        let start = generator.clone();
+        let outputs = (0..160).map(|_| generator.next_u64()).collect::<Vec<_>>();
+        assert_eq!(recover_from_u64s(&outputs), Ok(start));
+        assert!(recover_from_u64s(&outputs[..100]).is_err());
+        assert_eq!(recover_from_u64s(&[0; 160]), Err("Outputs don't come from a nonzero state."));
+    }
+
+    #[test]
+    fn test_recover_from_f64s() {
+        let mut generator = Xorshift128Plus::new([0xdeadbeefcafebabe, 0x0badf00dd15ea5e5]);
+        let start = generator.clone();
+        let outputs = (0..4).map(|_| generator.next_f64()).collect::<Vec<_>>();
+        assert!(outputs.iter().all(|&x| (0.0..1.0).contains(&x)));
+        assert!(recover_from_f64s(&outputs[..3]).is_err());
+        assert_eq!(recover_from_f64s(&[0.0; 4]), Err("Outputs don't come from a nonzero state."));
+        let mut recovered = recover_from_f64s(&outputs).unwrap();
+        assert_eq!(recovered, start);
+        for _ in 0..4 {
+            recovered.next_f64();
+        }
+        assert_eq!(recovered.next_f64(), generator.next_f64());
+    }
+}
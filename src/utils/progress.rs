@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// A snapshot of a long-running attack's progress, as passed to the progress callback.
+#[derive(Clone, Debug)]
+pub struct Progress<'a> {
+    /// Short description of the attack.
+    pub attack: &'static str,
+    /// Number of steps completed so far.
+    pub done: usize,
+    /// Total number of steps, if known in advance.
+    pub total: Option<usize>,
+    /// Time since the attack started.
+    pub elapsed: Duration,
+    /// The attack's current best guess at its result.
+    pub best_guess: &'a str,
+}
+
+impl<'a> Progress<'a> {
+    /// Estimate the time remaining by extrapolating the rate so far. Returns None if the
+    /// total is unknown or no steps have completed yet.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        if self.done == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.done) as f64;
+        Some(Duration::from_secs_f64(self.elapsed.as_secs_f64() * remaining / self.done as f64))
+    }
+}
+
+thread_local! {
+    static CALLBACK: RefCell<Option<Box<dyn FnMut(&Progress)>>> = RefCell::new(None);
+}
+
+/// Install a callback to be invoked whenever a long-running attack on the current thread
+/// makes progress, e.g. to draw a progress bar. Replaces any previously installed callback.
+pub fn set_callback<F>(callback: F)
+    where F: FnMut(&Progress) + 'static
+{
+    CALLBACK.with(|cell| *cell.borrow_mut() = Some(Box::new(callback)));
+}
+
+/// Remove the progress callback for the current thread, if any.
+pub fn clear_callback() {
+    CALLBACK.with(|cell| *cell.borrow_mut() = None);
+}
+
+/// Tracks the progress of an attack, logging each step and forwarding it to the progress
+/// callback if one is installed.
+pub struct Tracker {
+    attack: &'static str,
+    total: Option<usize>,
+    start: Instant,
+}
+
+impl Tracker {
+    /// Start tracking an attack that will take the given number of steps, if known.
+    pub fn new(attack: &'static str, total: Option<usize>) -> Tracker {
+        match total {
+            Some(total) => debug!("{}: starting ({} steps)", attack, total),
+            None => debug!("{}: starting", attack),
+        }
+        Tracker {
+            attack: attack,
+            total: total,
+            start: Instant::now(),
+        }
+    }
+
+    /// Record that the given number of steps have completed.
+    pub fn update(&self, done: usize, best_guess: &str) {
+        let progress = Progress {
+            attack: self.attack,
+            done: done,
+            total: self.total,
+            elapsed: self.start.elapsed(),
+            best_guess: best_guess,
+        };
+        trace!("{}: {}/{} steps, best guess {:?}",
+               self.attack,
+               done,
+               self.total.map_or("?".to_string(), |total| total.to_string()),
+               best_guess);
+
+        // A callback that itself runs an attack would try to borrow the cell again; the
+        // nested attack just goes unreported in that case.
+        CALLBACK.with(|cell| if let Ok(mut callback) = cell.try_borrow_mut() {
+            if let Some(ref mut callback) = *callback {
+                callback(&progress);
+            }
+        });
+    }
+
+    /// Record that the attack has finished.
+    pub fn finish(&self, result: &str) {
+        debug!("{}: finished in {:?}, result {:?}", self.attack, self.start.elapsed(), result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_callback() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = seen.clone();
+        set_callback(move |progress| {
+            sink.borrow_mut().push((progress.done, progress.best_guess.to_string()));
+        });
+
+        let tracker = Tracker::new("test", Some(2));
+        tracker.update(1, "a");
+        tracker.update(2, "ab");
+        clear_callback();
+        tracker.update(3, "abc");
+
+        assert_eq!(*seen.borrow(), vec![(1, "a".to_string()), (2, "ab".to_string())]);
+    }
+
+    #[test]
+    fn test_eta() {
+        let mut progress = Progress {
+            attack: "test",
+            done: 0,
+            total: Some(4),
+            elapsed: Duration::from_secs(2),
+            best_guess: "",
+        };
+        assert_eq!(progress.eta(), None);
+        progress.done = 1;
+        assert_eq!(progress.eta(), Some(Duration::from_secs(6)));
+        progress.total = None;
+        assert_eq!(progress.eta(), None);
+    }
+}
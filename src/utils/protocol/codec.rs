@@ -0,0 +1,236 @@
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use utils::mac::{Hmac, Mac};
+
+/// The largest frame body `split_frame` accepts. A length prefix is the first thing an
+/// attacker gets to choose, and a reader that trusts it allocates whatever it's told to.
+pub const MAX_FRAME: usize = 1 << 20;
+
+/// Why a stream of frames couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodecError {
+    /// The data ended partway through a frame.
+    Truncated,
+    /// A length prefix was larger than `MAX_FRAME`.
+    Oversized(usize),
+    /// An authenticated frame's tag didn't match, because the frame was modified or isn't
+    /// the one whose turn it was.
+    Authentication,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodecError::Truncated => write!(f, "Truncated frame."),
+            CodecError::Oversized(len) => write!(f, "Frame of {} bytes is too long.", len),
+            CodecError::Authentication => write!(f, "Invalid frame tag."),
+        }
+    }
+}
+
+impl error::Error for CodecError {}
+
+/// Wrap a message in a frame: a 4-byte big-endian length, then the message.
+pub fn frame(body: &[u8]) -> Vec<u8> {
+    let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// Read the frame at the start of the data, returning its body and whatever follows it.
+pub fn split_frame(data: &[u8]) -> Result<(&[u8], &[u8]), CodecError> {
+    if data.len() < 4 {
+        return Err(CodecError::Truncated);
+    }
+    let (len, rest) = data.split_at(4);
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if len > MAX_FRAME {
+        return Err(CodecError::Oversized(len));
+    }
+    if rest.len() < len {
+        return Err(CodecError::Truncated);
+    }
+    Ok(rest.split_at(len))
+}
+
+/// Write a frame, as built by `frame`, to a stream.
+pub fn write_to<W: Write>(writer: &mut W, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&frame(body))
+}
+
+/// Read one frame from a stream and return its body. As with `split_frame`, a length over
+/// `MAX_FRAME` is refused before anything is allocated for it.
+pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, CodecError::Oversized(len)));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Split a stream of back-to-back frames into their bodies.
+pub fn split_frames(mut data: &[u8]) -> Result<Vec<&[u8]>, CodecError> {
+    let mut bodies = Vec::new();
+    while !data.is_empty() {
+        let (body, rest) = split_frame(data)?;
+        bodies.push(body);
+        data = rest;
+    }
+    Ok(bodies)
+}
+
+/// Frames with a MAC tag, over the frame's sequence number as well as its body, for one
+/// direction of a connection. Each side keeps its own count of frames sealed and opened, so
+/// a frame that's modified, replayed, dropped from the middle, or delivered out of order
+/// fails to open. Frames cut off the end of a stream aren't noticed, though, unless the
+/// protocol itself says when it's done.
+pub struct MacCodec {
+    mac: Box<dyn Mac>,
+    sealed: u64,
+    opened: u64,
+}
+
+impl MacCodec {
+    /// Tag frames with HMAC-SHA256 under the given key.
+    pub fn new(key: &[u8]) -> Self {
+        MacCodec::with_mac(Box::new(Hmac::new(key)))
+    }
+
+    /// Tag frames with any MAC that can safely authenticate more than one message.
+    pub fn with_mac(mac: Box<dyn Mac>) -> Self {
+        MacCodec {
+            mac: mac,
+            sealed: 0,
+            opened: 0,
+        }
+    }
+
+    /// Frame the next message, followed by its tag.
+    pub fn seal(&mut self, body: &[u8]) -> Vec<u8> {
+        self.mac.update(&self.sealed.to_be_bytes());
+        self.mac.update(body);
+        let mut tagged = body.to_vec();
+        tagged.extend(self.mac.finalize());
+        self.sealed += 1;
+        frame(&tagged)
+    }
+
+    /// Read the frame at the start of the data and check its tag, returning its body and
+    /// whatever follows it. A frame that fails to open doesn't count towards the sequence.
+    pub fn open<'a>(&mut self, data: &'a [u8]) -> Result<(Vec<u8>, &'a [u8]), CodecError> {
+        let (tagged, rest) = split_frame(data)?;
+        let tag_len = self.mac.tag_len();
+        if tagged.len() < tag_len {
+            return Err(CodecError::Authentication);
+        }
+        let (body, tag) = tagged.split_at(tagged.len() - tag_len);
+        self.mac.update(&self.opened.to_be_bytes());
+        self.mac.update(body);
+        if !self.mac.verify(tag) {
+            return Err(CodecError::Authentication);
+        }
+        self.opened += 1;
+        Ok((body.to_vec(), rest))
+    }
+
+    /// Open every frame in a stream, in order.
+    pub fn open_all(&mut self, mut data: &[u8]) -> Result<Vec<Vec<u8>>, CodecError> {
+        let mut bodies = Vec::new();
+        while !data.is_empty() {
+            let (body, rest) = self.open(data)?;
+            bodies.push(body);
+            data = rest;
+        }
+        Ok(bodies)
+    }
+}
+
+/// Cut a stream off after its first `keep` frames, on a frame boundary, as an attacker who
+/// closes the connection early would. The receiver sees a stream that ends cleanly.
+pub fn truncate(stream: &[u8], keep: usize) -> Result<Vec<u8>, CodecError> {
+    Ok(split_frames(stream)?.iter().take(keep).flat_map(|body| frame(body)).collect())
+}
+
+/// Rearrange the frames of a stream, as an attacker sitting on the connection could: the
+/// result has the frames at the given indices, in that order, so frames can also be dropped
+/// or replayed. The frames themselves are passed on untouched, tags and all.
+pub fn reorder(stream: &[u8], order: &[usize]) -> Result<Vec<u8>, CodecError> {
+    let bodies = split_frames(stream)?;
+    let mut reordered = Vec::new();
+    for &i in order {
+        let body = bodies.get(i).ok_or(CodecError::Truncated)?;
+        reordered.extend(frame(body));
+    }
+    Ok(reordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let stream = [frame(b"hello"), frame(b""), frame(b"world")].concat();
+        assert_eq!(&stream[..9], b"\x00\x00\x00\x05hello");
+        assert_eq!(split_frames(&stream), Ok(vec![&b"hello"[..], b"", b"world"]));
+        assert_eq!(split_frames(&stream[..stream.len() - 1]), Err(CodecError::Truncated));
+        assert_eq!(split_frame(b"\x7f\xff\xff\xff"), Err(CodecError::Oversized(0x7fffffff)));
+
+        assert_eq!(split_frames(&truncate(&stream, 1).unwrap()), Ok(vec![&b"hello"[..]]));
+        let reordered = reorder(&stream, &[2, 0, 0]).unwrap();
+        assert_eq!(split_frames(&reordered), Ok(vec![&b"world"[..], b"hello", b"hello"]));
+        assert!(reorder(&stream, &[3]).is_err());
+
+        let mut written = Vec::new();
+        write_to(&mut written, b"hello").unwrap();
+        assert_eq!(written, frame(b"hello"));
+        assert_eq!(read_from(&mut &stream[..]).unwrap(), b"hello");
+        let err = read_from(&mut &b"\x7f\xff\xff\xff"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_from(&mut &stream[..7]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_mac_codec() {
+        let mut sender = MacCodec::new(b"key");
+        let stream = [sender.seal(b"transfer $10"), sender.seal(b"to bob"), sender.seal(b"")]
+            .concat();
+        let messages = vec![b"transfer $10".to_vec(), b"to bob".to_vec(), Vec::new()];
+        assert_eq!(MacCodec::new(b"key").open_all(&stream), Ok(messages));
+        assert_eq!(MacCodec::new(b"other").open_all(&stream), Err(CodecError::Authentication));
+
+        // Reordering, replaying or tampering with a frame is caught.
+        for order in &[vec![1, 0, 2], vec![0, 0, 1, 2], vec![0, 2]] {
+            let reordered = reorder(&stream, order).unwrap();
+            assert_eq!(MacCodec::new(b"key").open_all(&reordered),
+                       Err(CodecError::Authentication));
+        }
+        let mut tampered = stream.clone();
+        tampered[4] ^= 1;
+        assert_eq!(MacCodec::new(b"key").open_all(&tampered), Err(CodecError::Authentication));
+
+        // But cutting off the end isn't.
+        let truncated = truncate(&stream, 1).unwrap();
+        assert_eq!(MacCodec::new(b"key").open_all(&truncated),
+                   Ok(vec![b"transfer $10".to_vec()]));
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn test_mac_codec_cmac() {
+        use utils::mac::Cmac;
+
+        // Any MAC that handles more than one message will do.
+        let mut sender = MacCodec::with_mac(Box::new(Cmac::new(b"YELLOW SUBMARINE")));
+        let sealed = sender.seal(b"hello");
+        assert_eq!(sealed.len(), 4 + 5 + 16);
+        let mut receiver = MacCodec::with_mac(Box::new(Cmac::new(b"YELLOW SUBMARINE")));
+        assert_eq!(receiver.open_all(&sealed), Ok(vec![b"hello".to_vec()]));
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+pub mod codec;
+
+// Upper bound on the number of messages the router will deliver, so that a pair of parties
+// that keep replying to each other fails instead of hanging.
+const MAX_MESSAGES: usize = 10_000;
+
+/// A message from one party to another, addressed by name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope {
+    pub from: String,
+    pub to: String,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(from: &str, to: &str, payload: &[u8]) -> Self {
+        Envelope {
+            from: from.to_string(),
+            to: to.to_string(),
+            payload: payload.to_vec(),
+        }
+    }
+}
+
+/// A participant in a protocol, written as a state machine that reacts to messages. The
+/// same party can be driven in-process by a `Router`, or over a `Transport` by `drive`.
+pub trait Party {
+    /// The name other parties use to address this one.
+    fn name(&self) -> &str;
+
+    /// Messages to send when the protocol starts. Only the initiator usually has any.
+    fn start(&mut self) -> Vec<Envelope> {
+        Vec::new()
+    }
+
+    /// Handle a message addressed to this party, returning any replies.
+    fn receive(&mut self, message: Envelope) -> Vec<Envelope>;
+
+    /// Whether this party has finished its part of the protocol.
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// A man-in-the-middle, which sees every message before it's delivered. It can pass the
+/// message on, modify or drop it, or inject messages of its own.
+pub trait Interceptor {
+    fn intercept(&mut self, message: Envelope) -> Vec<Envelope>;
+}
+
+/// Delivers messages between parties in the same process, optionally through an
+/// interceptor. Parties are borrowed, so their final state can be inspected once the
+/// router is done with them.
+#[derive(Default)]
+pub struct Router<'a> {
+    parties: Vec<&'a mut dyn Party>,
+    interceptor: Option<&'a mut dyn Interceptor>,
+}
+
+impl<'a> Router<'a> {
+    pub fn new() -> Self {
+        Router {
+            parties: Vec::new(),
+            interceptor: None,
+        }
+    }
+
+    /// Add a party to the network.
+    pub fn add(&mut self, party: &'a mut dyn Party) -> &mut Self {
+        self.parties.push(party);
+        self
+    }
+
+    /// Route every message through the given interceptor.
+    pub fn intercept(&mut self, interceptor: &'a mut dyn Interceptor) -> &mut Self {
+        self.interceptor = Some(interceptor);
+        self
+    }
+
+    /// Start every party and deliver messages until none are left. Returns the messages
+    /// that were delivered, in order, after any tampering by the interceptor. This is a
+    /// recording of the run that `replay` can play back.
+    pub fn run(&mut self) -> Result<Vec<Envelope>, &'static str> {
+        let mut queue = VecDeque::new();
+        for party in &mut self.parties {
+            queue.extend(party.start());
+        }
+        self.deliver(queue)
+    }
+
+    /// Deliver previously recorded messages again, as an attacker who captured them could,
+    /// without starting any of the parties. The parties keep whatever state they're in, so
+    /// replaying messages into the session they came from tests whether it notices. Any
+    /// replies are delivered as usual, and the messages delivered are returned as by `run`.
+    pub fn replay(&mut self, messages: &[Envelope]) -> Result<Vec<Envelope>, &'static str> {
+        self.deliver(messages.iter().cloned().collect())
+    }
+
+    fn deliver(&mut self, mut queue: VecDeque<Envelope>) -> Result<Vec<Envelope>, &'static str> {
+        let mut transcript = Vec::new();
+        while let Some(message) = queue.pop_front() {
+            let messages = match self.interceptor {
+                Some(ref mut interceptor) => interceptor.intercept(message),
+                None => vec![message],
+            };
+            for message in messages {
+                if transcript.len() == MAX_MESSAGES {
+                    return Err("Protocol did not terminate.");
+                }
+                let party = self.parties
+                    .iter_mut()
+                    .find(|party| party.name() == message.to)
+                    .ok_or("Message addressed to unknown party.")?;
+                trace!("{} -> {}: {} bytes", message.from, message.to, message.payload.len());
+                transcript.push(message.clone());
+                queue.extend(party.receive(message));
+            }
+        }
+        Ok(transcript)
+    }
+}
+
+/// A connection over which a party exchanges messages with the rest of the network.
+pub trait Transport {
+    fn send(&mut self, message: &Envelope) -> io::Result<()>;
+
+    /// Wait for the next message. Fails with `UnexpectedEof` once the other end has gone.
+    fn recv(&mut self) -> io::Result<Envelope>;
+}
+
+/// Run a party over a transport until it's finished, or the connection is closed.
+pub fn drive<T: Transport>(party: &mut dyn Party, transport: &mut T) -> io::Result<()> {
+    for message in party.start() {
+        transport.send(&message)?;
+    }
+    while !party.finished() {
+        let message = match transport.recv() {
+            Ok(message) => message,
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        };
+        for reply in party.receive(message) {
+            transport.send(&reply)?;
+        }
+    }
+    Ok(())
+}
+
+/// One end of an in-process connection, for running parties on separate threads.
+pub struct ChannelTransport {
+    sender: Sender<Envelope>,
+    receiver: Receiver<Envelope>,
+}
+
+/// Create a connected pair of in-process transports.
+pub fn channel_pair() -> (ChannelTransport, ChannelTransport) {
+    let (a_sender, b_receiver) = mpsc::channel();
+    let (b_sender, a_receiver) = mpsc::channel();
+    (ChannelTransport {
+        sender: a_sender,
+        receiver: a_receiver,
+    },
+     ChannelTransport {
+        sender: b_sender,
+        receiver: b_receiver,
+    })
+}
+
+impl Transport for ChannelTransport {
+    fn send(&mut self, message: &Envelope) -> io::Result<()> {
+        self.sender
+            .send(message.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Channel closed."))
+    }
+
+    fn recv(&mut self) -> io::Result<Envelope> {
+        self.receiver
+            .recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "Channel closed."))
+    }
+}
+
+/// A TCP connection carrying length-prefixed frames, as written by `write_frame`.
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        TcpTransport { stream: stream }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &Envelope) -> io::Result<()> {
+        write_frame(&mut self.stream, message)
+    }
+
+    fn recv(&mut self) -> io::Result<Envelope> {
+        read_frame(&mut self.stream)
+    }
+}
+
+/// Write a message as three `codec` frames: sender, recipient and payload. The format is
+/// simple enough to pick out of a packet capture by eye.
+pub fn write_frame<W: Write>(writer: &mut W, message: &Envelope) -> io::Result<()> {
+    codec::write_to(writer, message.from.as_bytes())?;
+    codec::write_to(writer, message.to.as_bytes())?;
+    codec::write_to(writer, &message.payload)?;
+    writer.flush()
+}
+
+/// Pack several fields into a single payload, each in a `codec::frame`, for protocols whose
+/// messages carry more than one value.
+pub fn encode_fields(fields: &[&[u8]]) -> Vec<u8> {
+    fields.iter().flat_map(|field| codec::frame(field)).collect()
+}
+
+/// Split a payload packed by `encode_fields` back into its fields.
+pub fn decode_fields(payload: &[u8]) -> Result<Vec<Vec<u8>>, &'static str> {
+    codec::split_frames(payload)
+        .map(|fields| fields.iter().map(|field| field.to_vec()).collect())
+        .map_err(|_| "Truncated field.")
+}
+
+/// Read a frame written by `write_frame`. Fields longer than `codec::MAX_FRAME` are
+/// rejected, so a peer can't make the reader allocate whatever it likes.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Envelope> {
+    let name = |field: Vec<u8>| {
+        String::from_utf8(field)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Name is not UTF-8."))
+    };
+    Ok(Envelope {
+        from: name(codec::read_from(reader)?)?,
+        to: name(codec::read_from(reader)?)?,
+        payload: codec::read_from(reader)?,
+    })
+}
+
+/// Forward frames between a client and a server over TCP, passing each one through an
+/// interceptor, until either side hangs up. Messages go to the other side from where they
+/// came from, unless the interceptor addresses one back to its sender.
+pub fn relay(interceptor: &mut dyn Interceptor,
+             client: TcpStream,
+             server: TcpStream)
+             -> io::Result<()> {
+    let mut streams = [client, server];
+    let mut names = [None, None];
+
+    // Read from both sides at once on separate threads, funneling frames to this one.
+    let (sender, receiver) = mpsc::channel();
+    for (side, stream) in streams.iter().enumerate() {
+        let mut stream = stream.try_clone()?;
+        let sender = sender.clone();
+        thread::spawn(move || {
+            loop {
+                let result = read_frame(&mut stream);
+                let done = result.is_err();
+                if sender.send((side, result)).is_err() || done {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut forward = || -> io::Result<()> {
+        for (side, result) in &receiver {
+            let message = match result {
+                Ok(message) => message,
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            };
+            names[side] = Some(message.from.clone());
+            for message in interceptor.intercept(message) {
+                let to = if names[side].as_ref() == Some(&message.to) { side } else { 1 - side };
+                write_frame(&mut streams[to], &message)?;
+            }
+        }
+        Ok(())
+    };
+    let result = forward();
+
+    // Hang up on both sides, which also stops the reader threads.
+    for stream in &streams {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Sends a greeting and waits for it to be echoed back.
+    struct Greeter {
+        reply: Option<Vec<u8>>,
+    }
+
+    impl Party for Greeter {
+        fn name(&self) -> &str {
+            "alice"
+        }
+
+        fn start(&mut self) -> Vec<Envelope> {
+            vec![Envelope::new("alice", "bob", b"hello")]
+        }
+
+        fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+            self.reply = Some(message.payload);
+            Vec::new()
+        }
+
+        fn finished(&self) -> bool {
+            self.reply.is_some()
+        }
+    }
+
+    struct Echo;
+
+    impl Party for Echo {
+        fn name(&self) -> &str {
+            "bob"
+        }
+
+        fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+            vec![Envelope::new("bob", &message.from, &message.payload)]
+        }
+    }
+
+    struct Shouter;
+
+    impl Interceptor for Shouter {
+        fn intercept(&mut self, mut message: Envelope) -> Vec<Envelope> {
+            message.payload.make_ascii_uppercase();
+            vec![message]
+        }
+    }
+
+    #[test]
+    fn test_router() {
+        let mut alice = Greeter { reply: None };
+        let mut bob = Echo;
+        let transcript = Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        assert_eq!(transcript.len(), 2);
+        assert_eq!(alice.reply.unwrap(), b"hello");
+
+        let mut alice = Greeter { reply: None };
+        let mut bob = Echo;
+        let mut mallory = Shouter;
+        Router::new().add(&mut alice).add(&mut bob).intercept(&mut mallory).run().unwrap();
+        assert_eq!(alice.reply.unwrap(), b"HELLO");
+
+        // Replaying Alice's greeting gets another echo.
+        let mut alice = Greeter { reply: None };
+        let mut bob = Echo;
+        let transcript = Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        alice.reply = None;
+        let replayed = Router::new().add(&mut alice).add(&mut bob).replay(&transcript[..1]);
+        assert_eq!(replayed.unwrap(), transcript);
+        assert_eq!(alice.reply.unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_channel_transport() {
+        let (mut a, mut b) = channel_pair();
+        let bob = thread::spawn(move || drive(&mut Echo, &mut b));
+        let mut alice = Greeter { reply: None };
+        drive(&mut alice, &mut a).unwrap();
+        drop(a);
+        bob.join().unwrap().unwrap();
+        assert_eq!(alice.reply.unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_encode_fields() {
+        let payload = encode_fields(&[b"p", b"", b"generator"]);
+        let fields = decode_fields(&payload).unwrap();
+        assert_eq!(fields, vec![b"p".to_vec(), Vec::new(), b"generator".to_vec()]);
+        assert!(decode_fields(&payload[..payload.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_tcp_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let bob = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            drive(&mut Echo, &mut TcpTransport::new(stream))
+        });
+
+        let mut alice = Greeter { reply: None };
+        let mut transport = TcpTransport::new(TcpStream::connect(address).unwrap());
+        drive(&mut alice, &mut transport).unwrap();
+        drop(transport);
+        bob.join().unwrap().unwrap();
+        assert_eq!(alice.reply.unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(not(target_arch = "wasm32"))]
+use rand::OsRng;
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+/// A source of randomness. Everything in the crate that needs random values takes one of
+/// these, so that tests can substitute a `SeededRng` and get reproducible results.
+pub trait RandomSource {
+    /// Fill the given buffer with random bytes.
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+
+    /// Generate a vector of random bytes.
+    fn bytes(&mut self, size: usize) -> Vec<u8> {
+        let mut bytes = vec![0; size];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// Generate a random u64.
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Generate a number uniformly distributed in the range [low, high).
+    fn range(&mut self, low: usize, high: usize) -> usize {
+        assert!(low < high, "Empty range.");
+        let span = (high - low) as u64;
+
+        // Reject values from the incomplete final copy of the range to avoid modulo bias.
+        let zone = u64::max_value() - u64::max_value() % span;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return low + (value % span) as usize;
+            }
+        }
+    }
+
+    /// Flip a fair coin.
+    fn coin(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
+
+/// Randomness from the operating system. This is the default source. Not available on
+/// WebAssembly, where there is no operating system to ask.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct OsSource {
+    rng: OsRng,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl OsSource {
+    pub fn new() -> Self {
+        OsSource { rng: OsRng::new().unwrap() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for OsSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RandomSource for OsSource {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+    }
+}
+
+/// A deterministic, seeded generator for tests. Not remotely suitable for cryptography.
+pub struct SeededRng {
+    rng: XorShiftRng,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // XorShift can't be seeded with all zeros, so mix in some constant bits.
+        let (low, high) = (seed as u32, (seed >> 32) as u32);
+        let seed = [low, high, low ^ 0x9e3779b9, high ^ 0x7f4a7c15];
+        SeededRng { rng: XorShiftRng::from_seed(seed) }
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng() {
+        let mut a = SeededRng::new(0);
+        let mut b = SeededRng::new(0);
+        let mut c = SeededRng::new(1);
+        let bytes = a.bytes(32);
+        assert_eq!(bytes, b.bytes(32));
+        assert!(bytes != c.bytes(32));
+
+        let mut counts = [0; 5];
+        for _ in 0..1000 {
+            counts[a.range(10, 15) - 10] += 1;
+        }
+        assert!(counts.iter().all(|&count| count > 150));
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use utils::bytes;
+use utils::text::Frequencies;
+
+// The page around the sections, with `{title}` and `{body}` to fill in.
+static TEMPLATE: &'static str = include_str!("report.html");
+
+// The colors for observed and expected values in the charts.
+static OBSERVED: &'static str = "#4477aa";
+static EXPECTED: &'static str = "#cccccc";
+
+/// Escape text for use in HTML, in element content or quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An HTML page showing why an analysis decided what it did: the letter frequencies a
+/// scorer compared, the repeated blocks an ECB detector found, tables of candidates and
+/// biases. The page is self-contained, with the charts drawn in inline SVG and no scripts
+/// or external resources, so it can be opened straight from disk.
+pub struct Report {
+    title: String,
+    sections: Vec<String>,
+}
+
+impl Report {
+    pub fn new(title: &str) -> Self {
+        Report {
+            title: title.to_string(),
+            sections: Vec::new(),
+        }
+    }
+
+    fn section(&mut self, heading: &str, content: String) {
+        self.sections.push(format!("<h2>{}</h2>\n{}\n", escape(heading), content));
+    }
+
+    /// A section of preformatted text, such as a plaintext.
+    pub fn text(&mut self, heading: &str, text: &str) {
+        self.section(heading, format!("<pre>{}</pre>", escape(text)));
+    }
+
+    /// A table with a header row. Cells that parse as numbers are aligned on the right.
+    pub fn table(&mut self, heading: &str, headers: &[&str], rows: &[Vec<String>]) {
+        let mut html = String::from("<table>\n<tr>");
+        for header in headers {
+            write!(html, "<th>{}</th>", escape(header)).unwrap();
+        }
+        html.push_str("</tr>\n");
+        for row in rows {
+            html.push_str("<tr>");
+            for cell in row {
+                let class = if cell.parse::<f64>().is_ok() { " class=\"number\"" } else { "" };
+                write!(html, "<td{}>{}</td>", class, escape(cell)).unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>");
+        self.section(heading, html);
+    }
+
+    /// A bar chart of the observed frequencies of each letter, spaces, and other printable
+    /// characters, next to the expected ones, which is what the frequency scorers compare.
+    pub fn frequencies(&mut self, heading: &str, observed: &Frequencies, expected: &Frequencies) {
+        let labels = (b'a'..=b'z').map(|c| (c as char).to_string())
+            .chain(vec!["space".to_string(), "other".to_string()]);
+        let values = |freqs: &Frequencies| {
+            freqs.letters.iter().cloned().chain(vec![freqs.space, freqs.other]).collect::<Vec<_>>()
+        };
+        let (observed, expected) = (values(observed), values(expected));
+        let max = observed.iter().chain(&expected).cloned().fold(0.01, f64::max);
+
+        let (bar, height) = (10, 160);
+        let width = observed.len() * 3 * bar;
+        let mut svg = format!("<svg width=\"{}\" height=\"{}\">\n", width, height + 40);
+        for (i, label) in labels.enumerate() {
+            let x = i * 3 * bar;
+            let bars = [(0, observed[i], OBSERVED), (bar, expected[i], EXPECTED)];
+            for &(offset, value, color) in &bars {
+                let h = value / max * height as f64;
+                writeln!(svg,
+                         "<rect x=\"{}\" y=\"{:.1}\" width=\"{}\" height=\"{:.1}\" fill=\"{}\">\
+                          <title>{}: {:.4}</title></rect>",
+                         x + offset,
+                         height as f64 - h,
+                         bar,
+                         h,
+                         color,
+                         label,
+                         value)
+                    .unwrap();
+            }
+            let (rotate, anchor) = if label.len() > 1 { (45, "start") } else { (0, "middle") };
+            writeln!(svg,
+                     "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\" transform=\"rotate({} {} {})\">\
+                      {}</text>",
+                     x + bar,
+                     height + 14,
+                     anchor,
+                     rotate,
+                     x + bar,
+                     height + 14,
+                     label)
+                .unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg.push_str(&legend(&[("observed", OBSERVED), ("expected", EXPECTED)]));
+        self.section(heading, svg);
+    }
+
+    /// A map of the data, one square per block, in rows of 16 blocks. Blocks whose value
+    /// occurs more than once share a color, and every other block is gray, so the structure
+    /// ECB leaves in a ciphertext stands out. Hovering over a block shows its offset and
+    /// value.
+    pub fn block_map(&mut self, heading: &str, data: &[u8], block_size: usize) {
+        assert!(block_size > 0, "Block size must be nonzero.");
+        let blocks = data.chunks(block_size).collect::<Vec<_>>();
+        let mut counts = HashMap::new();
+        for block in &blocks {
+            *counts.entry(*block).or_insert(0) += 1;
+        }
+        // Number the repeated values in order of first appearance, for their colors.
+        let mut colors = HashMap::new();
+        for block in &blocks {
+            if counts[block] > 1 && !colors.contains_key(block) {
+                let hue = (colors.len() * 137) % 360;
+                colors.insert(*block, format!("hsl({}, 70%, 50%)", hue));
+            }
+        }
+
+        let (size, per_row) = (16, 16);
+        let rows = (blocks.len() + per_row - 1) / per_row;
+        let mut svg = format!("<svg width=\"{}\" height=\"{}\">\n", per_row * size, rows * size);
+        for (i, block) in blocks.iter().enumerate() {
+            let color = colors.get(block).map_or("#dddddd", |color| color.as_str());
+            writeln!(svg,
+                     "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                      stroke=\"white\"><title>{:04x}: {} ({}x)</title></rect>",
+                     (i % per_row) * size,
+                     (i / per_row) * size,
+                     size,
+                     size,
+                     color,
+                     i * block_size,
+                     bytes::hex_from_bytes(block),
+                     counts[block])
+                .unwrap();
+        }
+        svg.push_str("</svg>\n");
+        let repeats = blocks.len() - counts.len();
+        write!(svg,
+               "<p>{} blocks of {} bytes, {} of them repeats of an earlier block.</p>",
+               blocks.len(),
+               block_size,
+               repeats)
+            .unwrap();
+        self.section(heading, svg);
+    }
+
+    /// A table of values shaded by size, such as the bias of each byte value at each
+    /// position of a keystream. Darker cells are further from `neutral`.
+    pub fn heatmap(&mut self,
+                   heading: &str,
+                   row_labels: &[String],
+                   column_labels: &[String],
+                   values: &[Vec<f64>],
+                   neutral: f64) {
+        let max = values.iter()
+            .flat_map(|row| row.iter())
+            .map(|value| (value - neutral).abs())
+            .fold(0.0, f64::max);
+        let mut html = String::from("<table>\n<tr><th></th>");
+        for label in column_labels {
+            write!(html, "<th>{}</th>", escape(label)).unwrap();
+        }
+        html.push_str("</tr>\n");
+        for (label, row) in row_labels.iter().zip(values) {
+            write!(html, "<tr><th>{}</th>", escape(label)).unwrap();
+            for value in row {
+                let shade = if max > 0.0 { (value - neutral).abs() / max } else { 0.0 };
+                write!(html,
+                       "<td class=\"number\" style=\"background: rgba(68, 119, 170, {:.2})\">\
+                        {:.4}</td>",
+                       shade,
+                       value)
+                    .unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>");
+        self.section(heading, html);
+    }
+
+    /// Render the whole page.
+    pub fn to_html(&self) -> String {
+        // Fill in each side of the body separately, so that nothing in the title or the
+        // sections is mistaken for a placeholder.
+        let title = escape(&self.title);
+        let (head, tail) = TEMPLATE.split_at(TEMPLATE.find("{body}").unwrap());
+        head.replace("{title}", &title) + &self.sections.concat() +
+        &tail["{body}".len()..].replace("{title}", &title)
+    }
+}
+
+fn legend(entries: &[(&str, &str)]) -> String {
+    let items = entries.iter()
+        .map(|&(label, color)| format!("<span style=\"background: {}\"></span>{}", color, label))
+        .collect::<Vec<_>>();
+    format!("<p class=\"legend\">{}</p>", items.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::new("<script>");
+        report.text("Plaintext", "a < b & c");
+        report.table("Candidates", &["key", "score"], &[vec!["x".to_string(), "0.5".to_string()]]);
+        let text = b"the quick brown fox jumps over the lazy dog";
+        report.frequencies("Letters", &Frequencies::from_corpus(text), &Frequencies::english());
+        let mut data = vec![0u8; 64];
+        data.extend_from_slice(&[1; 16]);
+        report.block_map("Blocks", &data, 16);
+        report.heatmap("Biases", &["0".to_string()], &["a".to_string()], &[vec![0.1]], 0.0);
+
+        let html = report.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>&lt;script&gt;</title>"));
+        assert!(!html.contains("<script>") && !html.contains("{body}"));
+        assert!(html.contains("<pre>a &lt; b &amp; c</pre>"));
+        assert!(html.contains("<td class=\"number\">0.5</td>"));
+        // Four repeats of one block share a color, and the last block is unique.
+        assert_eq!(html.matches("hsl(0, 70%, 50%)").count(), 4);
+        assert!(html.contains("5 blocks of 16 bytes, 3 of them repeats of an earlier block."));
+    }
+}
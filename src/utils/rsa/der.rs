@@ -0,0 +1,146 @@
+use num_bigint::BigInt;
+
+use utils::bytes;
+
+// The only ASN.1 types PKCS#1 keys need.
+const INTEGER: u8 = 0x02;
+const SEQUENCE: u8 = 0x30;
+
+/// Encode a DER value with the given tag and contents.
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if contents.len() < 0x80 {
+        encoded.push(contents.len() as u8);
+    } else {
+        let len = (contents.len() as u64).to_be_bytes();
+        let skip = len.iter().take_while(|&&byte| byte == 0).count();
+        encoded.push(0x80 | (8 - skip) as u8);
+        encoded.extend_from_slice(&len[skip..]);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// Encode an integer as a DER INTEGER, in the fewest two's complement bytes.
+pub fn encode_integer(n: &BigInt) -> Vec<u8> {
+    encode(INTEGER, &n.to_signed_bytes_be())
+}
+
+/// Encode a DER SEQUENCE of already encoded values.
+pub fn encode_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode(SEQUENCE, &items.concat())
+}
+
+/// Reads DER values one after another from a buffer, rejecting anything that isn't in the
+/// one canonical encoding DER allows.
+#[derive(Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data: data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Read a value with the given tag, and return its contents.
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], &'static str> {
+        let data = self.data;
+        if data.len() < 2 {
+            return Err("Truncated DER value.");
+        }
+        if data[0] != tag {
+            return Err("Unexpected DER tag.");
+        }
+
+        let (len, header) = match data[1] {
+            len @ 0..=0x7f => (len as usize, 2),
+            0x81..=0x88 => {
+                let count = (data[1] & 0x7f) as usize;
+                let len_bytes = data.get(2..2 + count).ok_or("Truncated DER value.")?;
+                if len_bytes[0] == 0 || (count == 1 && len_bytes[0] < 0x80) {
+                    return Err("DER lengths must be minimally encoded.");
+                }
+                let len = len_bytes.iter().fold(0u64, |len, &byte| len << 8 | byte as u64);
+                (len as usize, 2 + count)
+            }
+            _ => return Err("Invalid DER length."),
+        };
+
+        if data.len() - header < len {
+            return Err("Truncated DER value.");
+        }
+        self.data = &data[header + len..];
+        Ok(&data[header..header + len])
+    }
+
+    pub fn read_integer(&mut self) -> Result<BigInt, &'static str> {
+        let contents = self.read(INTEGER)?;
+        match *contents {
+            [] => Err("DER integers can't be empty."),
+            [0x00, next, ..] if next < 0x80 => Err("DER integers must be minimally encoded."),
+            [0xff, next, ..] if next >= 0x80 => Err("DER integers must be minimally encoded."),
+            _ => Ok(BigInt::from_signed_bytes_be(contents)),
+        }
+    }
+
+    /// Read a SEQUENCE, and return a reader for the values inside it.
+    pub fn read_sequence(&mut self) -> Result<Reader<'a>, &'static str> {
+        self.read(SEQUENCE).map(Reader::new)
+    }
+}
+
+/// Armor DER data as PEM with the given label, such as "RSA PRIVATE KEY".
+pub fn pem_encode(label: &str, der: &[u8]) -> String {
+    let base64 = bytes::base64_from_bytes(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in base64.as_bytes().chunks(64) {
+        pem.push_str(&bytes::to_string(line));
+        pem.push('\n');
+    }
+    pem + &format!("-----END {}-----\n", label)
+}
+
+/// Extract the DER data from the first PEM block with the given label, ignoring anything
+/// around it.
+pub fn pem_decode(label: &str, pem: &str) -> Result<Vec<u8>, &'static str> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let start = pem.find(&begin).ok_or("Missing PEM header.")? + begin.len();
+    let stop = pem[start..].find(&end).ok_or("Missing PEM footer.")? + start;
+    let base64 = pem[start..stop].split_whitespace().collect::<String>();
+    bytes::base64_to_bytes(&base64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der() {
+        assert_eq!(encode_integer(&BigInt::from(0)), vec![0x02, 0x01, 0x00]);
+        assert_eq!(encode_integer(&BigInt::from(128)), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(encode_integer(&BigInt::from(-129)), vec![0x02, 0x02, 0xff, 0x7f]);
+
+        let long = vec![0xab; 300];
+        let encoded = encode_sequence(&[encode_integer(&BigInt::from(1)), encode(0x04, &long)]);
+        assert_eq!(&encoded[..4], &[0x30, 0x82, 0x01, 0x33]);
+        let mut sequence = Reader::new(&encoded).read_sequence().unwrap();
+        assert_eq!(sequence.read_integer(), Ok(BigInt::from(1)));
+        assert_eq!(sequence.read(0x04), Ok(&long[..]));
+        assert!(sequence.is_empty());
+
+        assert!(Reader::new(&[0x02, 0x02, 0x00, 0x01]).read_integer().is_err());
+        assert!(Reader::new(&[0x02, 0x81, 0x01, 0x01]).read_integer().is_err());
+        assert!(Reader::new(&[0x02, 0x03, 0x01]).read_integer().is_err());
+
+        let pem = pem_encode("TEST", &long);
+        assert!(pem.lines().all(|line| line.len() <= 64));
+        assert_eq!(pem_decode("TEST", &pem), Ok(long));
+        assert!(pem_decode("OTHER", &pem).is_err());
+    }
+}
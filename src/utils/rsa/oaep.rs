@@ -0,0 +1,157 @@
+use utils::bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::rsa::{self, Hash, PrivateKey, PublicKey};
+
+/// The MGF1 mask generation function from RFC 8017: the hashes of the seed followed by a
+/// 32-bit counter, concatenated and truncated to the given length.
+pub fn mgf1(hash: Hash, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.output_len());
+    let mut counter = 0u32;
+    while mask.len() < len {
+        let mut input = seed.to_vec();
+        input.extend_from_slice(&counter.to_be_bytes());
+        mask.extend(hash.digest(&input));
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+/// EME-OAEP encoding (RFC 8017, section 7.1.1) of a message into a block of k bytes, with
+/// a random seed from the given source.
+pub fn encode<R>(hash: Hash,
+                 message: &[u8],
+                 label: &[u8],
+                 k: usize,
+                 rng: &mut R)
+                 -> Result<Vec<u8>, &'static str>
+    where R: RandomSource + ?Sized
+{
+    let h_len = hash.output_len();
+    if k < 2 * h_len + 2 || message.len() > k - 2 * h_len - 2 {
+        return Err("Message too long.");
+    }
+
+    // DB = lHash || PS || 0x01 || M, where PS is however many zeros fill the block.
+    let mut db = hash.digest(label);
+    db.resize(k - h_len - 2 - message.len(), 0);
+    db.push(0x01);
+    db.extend_from_slice(message);
+
+    let seed = rng.bytes(h_len);
+    let masked_db = bytes::xor(&db, &mgf1(hash, &seed, db.len()));
+    let masked_seed = bytes::xor(&seed, &mgf1(hash, &masked_db, h_len));
+
+    let mut encoded = vec![0x00];
+    encoded.extend(masked_seed);
+    encoded.extend(masked_db);
+    Ok(encoded)
+}
+
+/// EME-OAEP decoding. Each way the encoding can be malformed has its own error, which is
+/// exactly what RFC 8017 says not to do: Manger's attack needs nothing more than knowing
+/// whether the leading byte was zero.
+pub fn decode(hash: Hash, encoded: &[u8], label: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let h_len = hash.output_len();
+    if encoded.len() < 2 * h_len + 2 {
+        return Err("Encoded message too short.");
+    }
+    if encoded[0] != 0 {
+        return Err("Leading byte is not zero.");
+    }
+
+    let (masked_seed, masked_db) = encoded[1..].split_at(h_len);
+    let seed = bytes::xor(masked_seed, &mgf1(hash, masked_db, h_len));
+    let db = bytes::xor(masked_db, &mgf1(hash, &seed, masked_db.len()));
+
+    if db[..h_len] != hash.digest(label)[..] {
+        return Err("Label hash mismatch.");
+    }
+    match db[h_len..].iter().position(|&byte| byte != 0) {
+        Some(i) if db[h_len + i] == 0x01 => Ok(db[h_len + i + 1..].to_vec()),
+        _ => Err("Missing 0x01 separator."),
+    }
+}
+
+/// Encrypt a message with RSA-OAEP.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encrypt(key: &PublicKey,
+               hash: Hash,
+               message: &[u8],
+               label: &[u8])
+               -> Result<Vec<u8>, &'static str> {
+    encrypt_with_source(key, hash, message, label, &mut OsSource::new())
+}
+
+/// Encrypt a message with RSA-OAEP, drawing the seed from the given source.
+pub fn encrypt_with_source<R>(key: &PublicKey,
+                              hash: Hash,
+                              message: &[u8],
+                              label: &[u8],
+                              rng: &mut R)
+                              -> Result<Vec<u8>, &'static str>
+    where R: RandomSource + ?Sized
+{
+    let encoded = encode(hash, message, label, key.size(), rng)?;
+    let c = key.encrypt(&rsa::int_from_bytes(&encoded));
+    Ok(rsa::int_to_bytes(&c, key.size()).unwrap())
+}
+
+/// Decrypt an RSA-OAEP ciphertext. With `leak_errors` set, the error says what was wrong
+/// with the decoded message, which makes this an oracle for Manger's attack. Without it,
+/// every failure is the same, as the RFC requires.
+pub fn decrypt(key: &PrivateKey,
+               hash: Hash,
+               ciphertext: &[u8],
+               label: &[u8],
+               leak_errors: bool)
+               -> Result<Vec<u8>, &'static str> {
+    let c = rsa::int_from_bytes(ciphertext);
+    if ciphertext.len() != key.size() || c >= key.n {
+        return Err("Decryption error.");
+    }
+    let encoded = rsa::int_to_bytes(&key.decrypt(&c), key.size()).unwrap();
+    decode(hash, &encoded, label).map_err(|err| {
+        if leak_errors { err } else { "Decryption error." }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_mgf1() {
+        assert_eq!(bytes::hex_from_bytes(&mgf1(Hash::Sha1, b"foo", 3)), "1ac907");
+        assert_eq!(bytes::hex_from_bytes(&mgf1(Hash::Sha1, b"foo", 5)), "1ac9075cd4");
+        assert_eq!(bytes::hex_from_bytes(&mgf1(Hash::Sha1, b"bar", 5)), "bc0c655e01");
+    }
+
+    #[test]
+    fn test_oaep() {
+        let mut rng = SeededRng::new(0);
+        let key = PrivateKey::generate_with_source(1024, &BigInt::from(65537), &mut rng);
+        let public = key.public_key();
+
+        for &hash in &[Hash::Sha1, Hash::Sha256] {
+            let message = b"attack at dawn";
+            let ciphertext = encrypt_with_source(&public, hash, message, b"", &mut rng).unwrap();
+            assert_eq!(decrypt(&key, hash, &ciphertext, b"", false).unwrap(), message);
+            assert!(decrypt(&key, hash, &ciphertext, b"label", false).is_err());
+
+            let too_long = vec![0u8; key.size() - 2 * hash.output_len() - 1];
+            assert!(encrypt_with_source(&public, hash, &too_long, b"", &mut rng).is_err());
+        }
+
+        // n - 1 decrypts to a block with a nonzero leading byte.
+        let ciphertext = rsa::int_to_bytes(&public.encrypt(&(&key.n - 1)), key.size()).unwrap();
+        assert_eq!(decrypt(&key, Hash::Sha1, &ciphertext, b"", true),
+                   Err("Leading byte is not zero."));
+        assert_eq!(decrypt(&key, Hash::Sha1, &ciphertext, b"", false),
+                   Err("Decryption error."));
+    }
+}
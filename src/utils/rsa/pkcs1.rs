@@ -0,0 +1,216 @@
+use utils::mac::verify as constant_time_eq;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::rsa::{self, Hash, PrivateKey, PublicKey};
+
+/// How strictly to parse PKCS#1 v1.5 padding. Sloppy parsing is what makes the attacks
+/// work, so both the attacks and the oracles they target can pick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parsing {
+    /// Check everything the standard requires.
+    Strict,
+    /// For encryption, only check that the block starts with 00 02, which is the oracle in
+    /// challenges 47 and 48. For signatures, find the hash by scanning past the padding and
+    /// ignore whatever follows it, as in challenge 42.
+    Sloppy,
+}
+
+// The DER encoding of the DigestInfo that precedes the hash in a signature, up to the hash
+// itself (RFC 8017, section 9.2, note 1).
+fn digest_info_prefix(hash: Hash) -> &'static [u8] {
+    match hash {
+        Hash::Sha1 => b"\x30\x21\x30\x09\x06\x05\x2b\x0e\x03\x02\x1a\x05\x00\x04\x14",
+        Hash::Sha256 => {
+            b"\x30\x31\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01\x05\x00\x04\x20"
+        }
+    }
+}
+
+/// EME-PKCS1-v1_5 encoding (RFC 8017, section 7.2.1): 00 02, at least eight random nonzero
+/// padding bytes, 00, and the message, filling a block of k bytes.
+pub fn encode_encryption<R>(message: &[u8],
+                            k: usize,
+                            rng: &mut R)
+                            -> Result<Vec<u8>, &'static str>
+    where R: RandomSource + ?Sized
+{
+    if message.len() + 11 > k {
+        return Err("Message too long.");
+    }
+    let mut encoded = vec![0x00, 0x02];
+    while encoded.len() < k - message.len() - 1 {
+        let byte = rng.bytes(1)[0];
+        if byte != 0 {
+            encoded.push(byte);
+        }
+    }
+    encoded.push(0x00);
+    encoded.extend_from_slice(message);
+    Ok(encoded)
+}
+
+/// EME-PKCS1-v1_5 decoding. Sloppy parsing accepts any block that starts with 00 02, and
+/// returns whatever follows the first zero after that, or nothing if there isn't one. Each
+/// way the block can be malformed has its own error, which is a Bleichenbacher oracle even
+/// with strict parsing, so `decrypt` only passes them on when asked to.
+pub fn decode_encryption(encoded: &[u8], parsing: Parsing) -> Result<Vec<u8>, &'static str> {
+    if encoded.len() < 11 || encoded[0] != 0x00 || encoded[1] != 0x02 {
+        return Err("Block type is not 00 02.");
+    }
+    let separator = encoded[2..].iter().position(|&byte| byte == 0);
+    match (separator, parsing) {
+        (Some(len), _) if len >= 8 => Ok(encoded[3 + len..].to_vec()),
+        (Some(len), Parsing::Sloppy) => Ok(encoded[3 + len..].to_vec()),
+        (None, Parsing::Sloppy) => Ok(Vec::new()),
+        (Some(_), Parsing::Strict) => Err("Padding string too short."),
+        (None, Parsing::Strict) => Err("Missing 00 separator."),
+    }
+}
+
+/// EMSA-PKCS1-v1_5 encoding (RFC 8017, section 9.2): 00 01, FF bytes, 00, then the
+/// DigestInfo holding the message's hash, filling a block of k bytes.
+pub fn encode_signature(hash: Hash, message: &[u8], k: usize) -> Result<Vec<u8>, &'static str> {
+    let mut digest_info = digest_info_prefix(hash).to_vec();
+    digest_info.extend(hash.digest(message));
+    if digest_info.len() + 11 > k {
+        return Err("Modulus too short for the hash.");
+    }
+    let mut encoded = vec![0x00, 0x01];
+    encoded.resize(k - digest_info.len() - 1, 0xff);
+    encoded.push(0x00);
+    encoded.extend(digest_info);
+    Ok(encoded)
+}
+
+/// Check an encoded signature block against a message. Strict parsing re-encodes the
+/// message and compares the whole block. Sloppy parsing checks the block type, skips any
+/// run of FF bytes, and compares only the DigestInfo and hash that follow, so a forger can
+/// put anything in the rest of the block.
+pub fn verify_signature(hash: Hash, message: &[u8], encoded: &[u8], parsing: Parsing) -> bool {
+    match parsing {
+        Parsing::Strict => {
+            match encode_signature(hash, message, encoded.len()) {
+                Ok(expected) => constant_time_eq(&expected, encoded),
+                Err(_) => false,
+            }
+        }
+        Parsing::Sloppy => {
+            if encoded.len() < 3 || encoded[0] != 0x00 || encoded[1] != 0x01 {
+                return false;
+            }
+            let padding = encoded[2..].iter().take_while(|&&byte| byte == 0xff).count();
+            let rest = &encoded[2 + padding..];
+            let mut expected = vec![0x00];
+            expected.extend_from_slice(digest_info_prefix(hash));
+            expected.extend(hash.digest(message));
+            padding > 0 && rest.starts_with(&expected)
+        }
+    }
+}
+
+/// Encrypt a message with RSAES-PKCS1-v1_5.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encrypt(key: &PublicKey, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+    encrypt_with_source(key, message, &mut OsSource::new())
+}
+
+/// Encrypt a message with RSAES-PKCS1-v1_5, drawing the padding from the given source.
+pub fn encrypt_with_source<R>(key: &PublicKey,
+                              message: &[u8],
+                              rng: &mut R)
+                              -> Result<Vec<u8>, &'static str>
+    where R: RandomSource + ?Sized
+{
+    let encoded = encode_encryption(message, key.size(), rng)?;
+    let c = key.encrypt(&rsa::int_from_bytes(&encoded));
+    Ok(rsa::int_to_bytes(&c, key.size()).unwrap())
+}
+
+/// Decrypt an RSAES-PKCS1-v1_5 ciphertext. With `leak_errors` set, the error says what was
+/// wrong with the padding. Without it, every failure is the same, as with `oaep::decrypt`.
+pub fn decrypt(key: &PrivateKey,
+               ciphertext: &[u8],
+               parsing: Parsing,
+               leak_errors: bool)
+               -> Result<Vec<u8>, &'static str> {
+    let c = rsa::int_from_bytes(ciphertext);
+    if ciphertext.len() != key.size() || c >= key.n {
+        return Err("Decryption error.");
+    }
+    let encoded = rsa::int_to_bytes(&key.decrypt(&c), key.size()).unwrap();
+    decode_encryption(&encoded, parsing).map_err(|err| {
+        if leak_errors { err } else { "Decryption error." }
+    })
+}
+
+/// Sign a message with RSASSA-PKCS1-v1_5.
+pub fn sign(key: &PrivateKey, hash: Hash, message: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let encoded = encode_signature(hash, message, key.size())?;
+    let s = key.decrypt(&rsa::int_from_bytes(&encoded));
+    Ok(rsa::int_to_bytes(&s, key.size()).unwrap())
+}
+
+/// Verify an RSASSA-PKCS1-v1_5 signature.
+pub fn verify(key: &PublicKey,
+              hash: Hash,
+              message: &[u8],
+              signature: &[u8],
+              parsing: Parsing)
+              -> bool {
+    let s = rsa::int_from_bytes(signature);
+    if signature.len() != key.size() || s >= key.n {
+        return false;
+    }
+    let encoded = rsa::int_to_bytes(&key.encrypt(&s), key.size()).unwrap();
+    verify_signature(hash, message, &encoded, parsing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_encryption_padding() {
+        let mut rng = SeededRng::new(0);
+        let key = PrivateKey::generate_with_source(512, &BigInt::from(3), &mut rng);
+        let ciphertext = encrypt_with_source(&key.public_key(), b"kick it, CC", &mut rng).unwrap();
+        assert_eq!(decrypt(&key, &ciphertext, Parsing::Strict, false).unwrap(), b"kick it, CC");
+
+        // Every padding failure looks the same unless errors are leaked on purpose.
+        let public = key.public_key();
+        let bad_type = public.encrypt(&BigInt::from(1));
+        let bad_type = rsa::int_to_bytes(&bad_type, key.size()).unwrap();
+        assert_eq!(decrypt(&key, &bad_type, Parsing::Strict, false), Err("Decryption error."));
+        assert_eq!(decrypt(&key, &bad_type, Parsing::Strict, true),
+                   Err("Block type is not 00 02."));
+
+        // A padding string shorter than eight bytes only gets past sloppy parsing.
+        let short = b"\x00\x02\x01\x02\x03\x00hello";
+        assert!(decode_encryption(short, Parsing::Strict).is_err());
+        assert_eq!(decode_encryption(short, Parsing::Sloppy).unwrap(), b"hello");
+        assert!(decode_encryption(b"\x00\x01\xff\xff\xff\xff\xff\xff\xff\xff\x00",
+                                  Parsing::Sloppy)
+            .is_err());
+    }
+
+    #[test]
+    fn test_signature_padding() {
+        let key = PrivateKey::generate_with_source(1024, &BigInt::from(3), &mut SeededRng::new(0));
+        let signature = sign(&key, Hash::Sha1, b"hi mom").unwrap();
+        for &parsing in &[Parsing::Strict, Parsing::Sloppy] {
+            assert!(verify(&key.public_key(), Hash::Sha1, b"hi mom", &signature, parsing));
+            assert!(!verify(&key.public_key(), Hash::Sha1, b"hi dad", &signature, parsing));
+        }
+
+        // Garbage after the hash, which challenge 42's forgery relies on.
+        let mut forged = vec![0x00, 0x01, 0xff, 0x00];
+        forged.extend_from_slice(digest_info_prefix(Hash::Sha1));
+        forged.extend(Hash::Sha1.digest(b"hi mom"));
+        forged.resize(128, 0x42);
+        assert!(verify_signature(Hash::Sha1, b"hi mom", &forged, Parsing::Sloppy));
+        assert!(!verify_signature(Hash::Sha1, b"hi mom", &forged, Parsing::Strict));
+    }
+}
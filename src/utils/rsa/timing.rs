@@ -0,0 +1,131 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed};
+
+use utils::math;
+use utils::random::RandomSource;
+use utils::rsa::{self, PrivateKey};
+
+// Montgomery multiplication modulo an odd n, with R the smallest power of two above n.
+struct Montgomery {
+    n: BigInt,
+    // -n^-1 mod R.
+    n_prime: BigInt,
+    r_bits: usize,
+}
+
+impl Montgomery {
+    fn new(n: &BigInt) -> Self {
+        let r_bits = n.bits();
+        let r = BigInt::one() << r_bits;
+        let n_prime = (&r - math::invmod(n, &r).unwrap()).mod_floor(&r);
+        Montgomery {
+            n: n.clone(),
+            n_prime: n_prime,
+            r_bits: r_bits,
+        }
+    }
+
+    fn to_montgomery(&self, x: &BigInt) -> BigInt {
+        (x << self.r_bits).mod_floor(&self.n)
+    }
+
+    // Compute abR^-1 mod n, and whether it took the final subtraction, the "extra
+    // reduction" whose presence depends on the operands.
+    fn mul(&self, a: &BigInt, b: &BigInt) -> (BigInt, bool) {
+        let mask = (BigInt::one() << self.r_bits) - 1;
+        let t = a * b;
+        let m = ((&t & &mask) * &self.n_prime) & &mask;
+        let u = (t + m * &self.n) >> self.r_bits;
+        if u >= self.n {
+            (u - &self.n, true)
+        } else {
+            (u, false)
+        }
+    }
+}
+
+/// Modular exponentiation by left-to-right square-and-multiply over Montgomery
+/// multiplication, the way many real implementations do it, along with a simulated running
+/// time: one unit per multiplication, plus one per extra reduction. The number of
+/// multiplications only depends on the exponent, but the extra reductions depend on the
+/// base too, which is the leak that Kocher's and Schindler's timing attacks exploit to
+/// recover the exponent a bit at a time from chosen bases. The modulus must be odd.
+pub fn modexp_timed(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> (BigInt, u64) {
+    assert!(modulus.is_odd(), "Montgomery multiplication needs an odd modulus.");
+    assert!(!exponent.is_negative(), "Exponent must be nonnegative.");
+
+    let montgomery = Montgomery::new(modulus);
+    let base = montgomery.to_montgomery(base);
+    let mut result = montgomery.to_montgomery(&BigInt::one());
+    let mut time = 0;
+    let mut step = |x: &BigInt, y: &BigInt| {
+        let (product, extra) = montgomery.mul(x, y);
+        time += 1 + extra as u64;
+        product
+    };
+    for i in (0..exponent.bits()).rev() {
+        result = step(&result, &result);
+        if (exponent >> i).is_odd() {
+            result = step(&result, &base);
+        }
+    }
+    let result = step(&result, &BigInt::one());
+    (result, time)
+}
+
+/// Raw RSA decryption with the simulated running time from `modexp_timed`. For a given
+/// ciphertext the time is always the same, so an attacker who picks the ciphertexts can
+/// line the times up against guesses about the bits of d.
+pub fn decrypt_timed(key: &PrivateKey, c: &BigInt) -> (BigInt, u64) {
+    modexp_timed(c, &key.d, &key.n)
+}
+
+/// Raw RSA decryption with blinding, as in `rsa::decrypt_blinded`, and the simulated
+/// running time. The exponentiation sees a random base unrelated to the ciphertext, so the
+/// time varies from run to run and tells an attacker nothing about the ciphertext they
+/// chose.
+pub fn decrypt_blinded_timed<R>(key: &PrivateKey, c: &BigInt, rng: &mut R) -> (BigInt, u64)
+    where R: RandomSource + ?Sized
+{
+    let (r, r_inverse) = rsa::blinding_factor(key, rng);
+    let blinded = (c * math::modexp(&r, &key.e, &key.n)).mod_floor(&key.n);
+    let (m, time) = modexp_timed(&blinded, &key.d, &key.n);
+    ((m * r_inverse).mod_floor(&key.n), time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_timing() {
+        let mut rng = SeededRng::new(0);
+        let key = PrivateKey::generate_with_source(256, &BigInt::from(65537), &mut rng);
+        let public = key.public_key();
+        let messages = (2..10).map(BigInt::from).collect::<Vec<_>>();
+        let ciphertexts = messages.iter().map(|m| public.encrypt(m)).collect::<Vec<_>>();
+
+        // Without blinding, the time is a function of the ciphertext, and differs between
+        // ciphertexts even though the key doesn't change.
+        let times = ciphertexts.iter()
+            .map(|c| {
+                let (m, time) = decrypt_timed(&key, c);
+                assert_eq!(decrypt_timed(&key, c), (m, time));
+                time
+            })
+            .collect::<Vec<_>>();
+        assert!(times.iter().any(|&time| time != times[0]));
+
+        // With it, decrypting the same ciphertext takes different amounts of time.
+        let blinded = (0..8)
+            .map(|_| {
+                let (m, time) = decrypt_blinded_timed(&key, &ciphertexts[0], &mut rng);
+                assert_eq!(m, messages[0]);
+                time
+            })
+            .collect::<Vec<_>>();
+        assert!(blinded.iter().any(|&time| time != blinded[0]));
+    }
+}
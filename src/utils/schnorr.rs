@@ -0,0 +1,166 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Zero;
+
+use utils::dh::Group;
+use utils::math;
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+use utils::sha256::sha256;
+
+/// A Schnorr signature: the challenge e and the response s.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub e: BigInt,
+    pub s: BigInt,
+}
+
+/// A Schnorr key pair in a Diffie-Hellman group: a private exponent x and y = g^x.
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    pub group: Group,
+    pub private: BigInt,
+    pub public: BigInt,
+}
+
+// The order of g. The NIST prime is a safe prime p = 2q + 1, and 2 is a square modulo it,
+// so g = 2 generates the subgroup of prime order q.
+fn order(group: &Group) -> BigInt {
+    (&group.p - 1) / 2
+}
+
+// A uniformly random exponent in [1, q).
+fn random_exponent<R>(q: &BigInt, rng: &mut R) -> BigInt
+    where R: RandomSource + ?Sized
+{
+    let size = (q.bits() + 7) / 8;
+    loop {
+        let x = BigInt::from_bytes_be(Sign::Plus, &rng.bytes(size));
+        if !x.is_zero() && x < *q {
+            return x;
+        }
+    }
+}
+
+// e = H(r || m) mod q.
+fn challenge(group: &Group, r: &BigInt, message: &[u8]) -> BigInt {
+    let mut input = r.to_bytes_be().1;
+    input.extend_from_slice(message);
+    BigInt::from_bytes_be(Sign::Plus, &sha256(&input)).mod_floor(&order(group))
+}
+
+impl KeyPair {
+    /// Generate a random key pair in the given group.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn generate(group: &Group) -> Self {
+        Self::generate_with_source(group, &mut OsSource::new())
+    }
+
+    /// Generate a key pair, drawing the private exponent from the given source.
+    pub fn generate_with_source<R>(group: &Group, rng: &mut R) -> Self
+        where R: RandomSource + ?Sized
+    {
+        let private = random_exponent(&order(group), rng);
+        KeyPair {
+            public: math::modexp(&group.g, &private, &group.p),
+            group: group.clone(),
+            private: private,
+        }
+    }
+
+    /// Sign a message with a random nonce.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.sign_with_source(message, &mut OsSource::new())
+    }
+
+    /// Sign a message, drawing the nonce from the given source.
+    pub fn sign_with_source<R>(&self, message: &[u8], rng: &mut R) -> Signature
+        where R: RandomSource + ?Sized
+    {
+        let k = random_exponent(&order(&self.group), rng);
+        self.sign_with_nonce(message, &k)
+    }
+
+    /// Sign a message with the given nonce: r = g^k, e = H(r || m) and s = k - xe mod q.
+    /// Signing two messages with the same nonce gives away the private key.
+    pub fn sign_with_nonce(&self, message: &[u8], k: &BigInt) -> Signature {
+        let q = order(&self.group);
+        let r = math::modexp(&self.group.g, k, &self.group.p);
+        let e = challenge(&self.group, &r, message);
+        let s = (k - &self.private * &e).mod_floor(&q);
+        Signature { e: e, s: s }
+    }
+}
+
+// The commitment r = g^s * y^e that a signature's challenge should be the hash of.
+fn commitment(group: &Group, public: &BigInt, signature: &Signature) -> BigInt {
+    let gs = math::modexp(&group.g, &signature.s, &group.p);
+    let ye = math::modexp(public, &signature.e, &group.p);
+    (gs * ye).mod_floor(&group.p)
+}
+
+/// Verify a signature against a public key.
+pub fn verify(group: &Group, public: &BigInt, message: &[u8], signature: &Signature) -> bool {
+    let q = order(group);
+    let in_range = |x: &BigInt| x.sign() != Sign::Minus && *x < q;
+    if !in_range(&signature.e) || !in_range(&signature.s) {
+        return false;
+    }
+    challenge(group, &commitment(group, public, signature), message) == signature.e
+}
+
+/// Recover the private key from two signatures on different messages that used the same
+/// nonce. Reuse shows up as equal commitments, and then s1 - s2 = x(e2 - e1) mod q.
+pub fn recover_key_from_nonce_reuse(group: &Group,
+                                    public: &BigInt,
+                                    first: &Signature,
+                                    second: &Signature)
+                                    -> Result<BigInt, &'static str> {
+    if commitment(group, public, first) != commitment(group, public, second) {
+        return Err("Signatures don't share a nonce.");
+    }
+    let q = order(group);
+    let inverse = math::invmod(&(&second.e - &first.e).mod_floor(&q), &q)
+        .ok_or("Signatures have the same challenge.")?;
+    let private = ((&first.s - &second.s) * inverse).mod_floor(&q);
+    if math::modexp(&group.g, &private, &group.p) != *public {
+        return Err("Recovered key doesn't match.");
+    }
+    Ok(private)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::SeededRng;
+
+    #[test]
+    fn test_schnorr() {
+        let group = Group::nist();
+        let mut rng = SeededRng::new(0);
+        let keys = KeyPair::generate_with_source(&group, &mut rng);
+        let signature = keys.sign_with_source(b"hi mom", &mut rng);
+        assert!(verify(&group, &keys.public, b"hi mom", &signature));
+        assert!(!verify(&group, &keys.public, b"hi dad", &signature));
+
+        let other = KeyPair::generate_with_source(&group, &mut rng);
+        assert!(!verify(&group, &other.public, b"hi mom", &signature));
+    }
+
+    #[test]
+    fn test_nonce_reuse() {
+        let group = Group::nist();
+        let mut rng = SeededRng::new(0);
+        let keys = KeyPair::generate_with_source(&group, &mut rng);
+        let k = random_exponent(&order(&group), &mut rng);
+        let first = keys.sign_with_nonce(b"attack at dawn", &k);
+        let second = keys.sign_with_nonce(b"attack at dusk", &k);
+        assert_eq!(recover_key_from_nonce_reuse(&group, &keys.public, &first, &second),
+                   Ok(keys.private.clone()));
+
+        let fresh = keys.sign_with_source(b"attack at dusk", &mut rng);
+        assert!(recover_key_from_nonce_reuse(&group, &keys.public, &first, &fresh).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use utils::bytes;
+use utils::digest::{Buffer, Digest};
+
+// Initial hash value from FIPS 180-4 section 5.3.1.
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// SHA-1, implemented from FIPS 180-4 rather than borrowed from a backend, so it's
+/// available with either one, and so its internals are there to poke at.
+#[derive(Clone, Debug)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: Buffer,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Sha1 {
+            state: INITIAL_STATE,
+            buffer: Buffer::new(0),
+        }
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Digest for Sha1 {
+    fn block_size(&self) -> usize {
+        64
+    }
+
+    fn output_len(&self) -> usize {
+        20
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.update(data, |block| compress(state, block));
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let state = &mut self.state;
+        self.buffer.finish(|block| compress(state, block));
+        let digest = bytes::pack_u32_be(state);
+        *state = INITIAL_STATE;
+        digest
+    }
+
+    fn from_raw_state(state: &[u8], processed_len: u64) -> Result<Self, &'static str> {
+        if state.len() != 20 {
+            return Err("SHA-1 states are 20 bytes long.");
+        }
+        if processed_len % 64 != 0 {
+            return Err("The processed length must be a whole number of blocks.");
+        }
+        let mut words = [0u32; 5];
+        words.copy_from_slice(&bytes::unpack_u32_be(state)?);
+        Ok(Sha1 {
+            state: words,
+            buffer: Buffer::new(processed_len),
+        })
+    }
+}
+
+/// Compute the SHA-1 digest of the given data.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut digest = [0u8; 20];
+    digest.copy_from_slice(&Sha1::new().digest(data));
+    digest
+}
+
+// Process one 64-byte block, updating the state in place.
+fn compress(state: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0u32; 80];
+    w[..16].copy_from_slice(&bytes::unpack_u32_be(block).unwrap());
+    for t in 16..80 {
+        w[t] = bytes::rotl32(w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16], 1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (t, &word) in w.iter().enumerate() {
+        let (f, k) = match t {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let temp = bytes::rotl32(a, 5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(word);
+        e = d;
+        d = c;
+        c = bytes::rotl32(b, 30);
+        b = a;
+        a = temp;
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(bytes::hex_from_bytes(&sha1(b"")),
+                   "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(bytes::hex_from_bytes(&sha1(b"abc")),
+                   "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+        // Two blocks once padded.
+        let message = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(bytes::hex_from_bytes(&sha1(message)),
+                   "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use utils::bytes;
+use utils::digest::{self, Buffer, Digest};
+
+// Initial hash value from FIPS 180-4 section 5.3.3.
+const INITIAL_STATE: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f,
+                                 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+// Round constants from FIPS 180-4 section 4.2.2.
+const K: [u32; 64] = [0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+                      0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+                      0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+                      0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+                      0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+                      0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+                      0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+                      0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+                      0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+                      0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+                      0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2];
+
+/// SHA-256, implemented from FIPS 180-4 like `sha1::Sha1`.
+#[derive(Clone, Debug)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: Buffer,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Sha256 {
+            state: INITIAL_STATE,
+            buffer: Buffer::new(0),
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Digest for Sha256 {
+    fn block_size(&self) -> usize {
+        64
+    }
+
+    fn output_len(&self) -> usize {
+        32
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.update(data, |block| compress(state, block));
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let state = &mut self.state;
+        self.buffer.finish(|block| compress(state, block));
+        let digest = bytes::pack_u32_be(state);
+        *state = INITIAL_STATE;
+        digest
+    }
+
+    fn from_raw_state(state: &[u8], processed_len: u64) -> Result<Self, &'static str> {
+        if state.len() != 32 {
+            return Err("SHA-256 states are 32 bytes long.");
+        }
+        if processed_len % 64 != 0 {
+            return Err("The processed length must be a whole number of blocks.");
+        }
+        let mut words = [0u32; 8];
+        words.copy_from_slice(&bytes::unpack_u32_be(state)?);
+        Ok(Sha256 {
+            state: words,
+            buffer: Buffer::new(processed_len),
+        })
+    }
+}
+
+/// Compute the SHA-256 digest of the given data.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::new().digest(data));
+    digest
+}
+
+// Process one 64-byte block, updating the state in place.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    w[..16].copy_from_slice(&bytes::unpack_u32_be(block).unwrap());
+    for t in 16..64 {
+        let s0 = bytes::rotr32(w[t - 15], 7) ^ bytes::rotr32(w[t - 15], 18) ^ (w[t - 15] >> 3);
+        let s1 = bytes::rotr32(w[t - 2], 17) ^ bytes::rotr32(w[t - 2], 19) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16].wrapping_add(s0).wrapping_add(w[t - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for t in 0..64 {
+        let s1 = bytes::rotr32(e, 6) ^ bytes::rotr32(e, 11) ^ bytes::rotr32(e, 25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[t]).wrapping_add(w[t]);
+        let s0 = bytes::rotr32(a, 2) ^ bytes::rotr32(a, 13) ^ bytes::rotr32(a, 22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+/// Compute HMAC-SHA256 (RFC 2104) of a message under the given key.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = [0u8; 32];
+    mac.copy_from_slice(&digest::hmac(&mut Sha256::new(), key, message));
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(bytes::hex_from_bytes(&sha256(b"abc")),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let message = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(bytes::hex_from_bytes(&sha256(message)),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // Test case 2 from RFC 4231.
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(bytes::hex_from_bytes(&mac),
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}
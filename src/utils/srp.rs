@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use num_bigint::{BigInt, Sign};
+
+use utils::{bytes, math};
+use utils::attacks::BruteForce;
+use utils::dh::{Group, KeyPair};
+use utils::protocol::{self, Envelope, Party};
+use utils::sha256::{hmac_sha256, sha256};
+
+// The multiplier parameter k, as in challenge 36.
+const K: u32 = 3;
+
+fn to_bytes(n: &BigInt) -> Vec<u8> {
+    n.to_bytes_be().1
+}
+
+fn from_bytes(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+// Interpret a SHA-256 digest of the concatenated inputs as an integer.
+fn hash_to_int(parts: &[&[u8]]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &sha256(&parts.concat()))
+}
+
+// What the server stores for each user. The password itself is never stored.
+struct Verifier {
+    salt: Vec<u8>,
+    v: BigInt,
+}
+
+// The server's half of a login that has started but not finished.
+struct Pending {
+    key: Vec<u8>,
+    salt: Vec<u8>,
+}
+
+/// The server side of SRP, as in challenge 36. Stores a password verifier per user, and
+/// checks logins in two steps: `start_login` answers the client's public key with the
+/// salt and the server's public key, and `finish_login` checks the client's proof that it
+/// derived the same session key.
+pub struct Server {
+    group: Group,
+    users: HashMap<String, Verifier>,
+    pending: HashMap<String, Pending>,
+}
+
+impl Server {
+    pub fn new(group: Group) -> Self {
+        Server {
+            group: group,
+            users: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Register a new user, storing a verifier derived from their password.
+    pub fn register(&mut self, email: &str, password: &str) -> Result<(), &'static str> {
+        if self.users.contains_key(email) {
+            return Err("User already exists.");
+        }
+        let salt = bytes::random(16);
+        let x = hash_to_int(&[&salt, password.as_bytes()]);
+        let v = math::modexp(&self.group.g, &x, &self.group.p);
+        self.users.insert(email.to_string(),
+                          Verifier {
+                              salt: salt,
+                              v: v,
+                          });
+        Ok(())
+    }
+
+    /// The salt and verifier stored for a user, as an attacker who dumped the server's
+    /// database would see them. That's all `dictionary_attack` needs.
+    pub fn verifier(&self, email: &str) -> Option<(&[u8], &BigInt)> {
+        self.users.get(email).map(|user| (&user.salt[..], &user.v))
+    }
+
+    /// Start a login with the client's public key A, returning the user's salt and the
+    /// server's public key B. Starting again replaces any login in progress for the user.
+    pub fn start_login(&mut self,
+                       email: &str,
+                       public_a: &BigInt)
+                       -> Result<(Vec<u8>, BigInt), &'static str> {
+        let p = &self.group.p;
+        let user = self.users.get(email).ok_or("Unknown user.")?;
+
+        let keys = self.group.generate_keypair();
+        let public_b = (BigInt::from(K) * &user.v + &keys.public) % p;
+        let u = hash_to_int(&[&to_bytes(public_a), &to_bytes(&public_b)]);
+
+        // S = (A * v^u)^b mod N
+        let base = public_a * math::modexp(&user.v, &u, p);
+        let secret = math::modexp(&base, &keys.private, p);
+
+        self.pending.insert(email.to_string(),
+                            Pending {
+                                key: sha256(&to_bytes(&secret)).to_vec(),
+                                salt: user.salt.clone(),
+                            });
+        Ok((user.salt.clone(), public_b))
+    }
+
+    /// Finish a login by checking the client's HMAC of the salt under the session key.
+    /// Either way, the login in progress is used up.
+    pub fn finish_login(&mut self, email: &str, mac: &[u8]) -> bool {
+        match self.pending.remove(email) {
+            Some(pending) => hmac_sha256(&pending.key, &pending.salt)[..] == *mac,
+            None => false,
+        }
+    }
+}
+
+/// The client side of SRP, for a single login attempt.
+pub struct Client {
+    group: Group,
+    email: String,
+    password: String,
+    keys: KeyPair,
+}
+
+impl Client {
+    pub fn new(group: Group, email: &str, password: &str) -> Self {
+        Client {
+            keys: group.generate_keypair(),
+            group: group,
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// The client's public key A, to send along with the email to start the login.
+    pub fn public_key(&self) -> &BigInt {
+        &self.keys.public
+    }
+
+    /// Given the server's salt and public key B, derive the session key and return the
+    /// proof of it to send back: an HMAC of the salt.
+    pub fn proof(&self, salt: &[u8], public_b: &BigInt) -> Vec<u8> {
+        let (p, g) = (&self.group.p, &self.group.g);
+        let u = hash_to_int(&[&to_bytes(&self.keys.public), &to_bytes(public_b)]);
+        let x = hash_to_int(&[salt, self.password.as_bytes()]);
+
+        // S = (B - k * g^x)^(a + u * x) mod N
+        let base = public_b - BigInt::from(K) * math::modexp(g, &x, p);
+        let secret = math::modexp(&base, &(&self.keys.private + u * x), p);
+        hmac_sha256(&sha256(&to_bytes(&secret)), salt).to_vec()
+    }
+}
+
+/// Recover a user's password from a stolen salt and verifier by trying each candidate in
+/// turn, such as the words of a `Wordlist`, or a `wordlist::mangled` stream of them. Nothing
+/// on the wire lets an eavesdropper test guesses like this, but v = g^H(salt || password) is
+/// as good as a password hash to anyone who has it, and a weak password falls to a
+/// dictionary as quickly as any other.
+pub fn dictionary_attack<I>(group: &Group,
+                            salt: &[u8],
+                            verifier: &BigInt,
+                            candidates: I)
+                            -> Option<String>
+    where I: IntoIterator<Item = String>
+{
+    BruteForce::new("SRP dictionary attack")
+        .run(candidates, |password| {
+            let x = hash_to_int(&[salt, password.as_bytes()]);
+            math::modexp(&group.g, &x, &group.p) == *verifier
+        })
+        .found()
+}
+
+/// A `Client` as a protocol party, which logs in to a `LoginServer` over the wire: it sends
+/// its email and A, answers the salt and B with its proof, and gets back "ok" or "denied".
+/// Every message is a payload of `protocol::encode_fields`.
+pub struct LoginClient {
+    client: Client,
+    proved: bool,
+    /// Whether the server accepted the login, once it has said.
+    pub accepted: Option<bool>,
+}
+
+impl LoginClient {
+    pub fn new(client: Client) -> Self {
+        LoginClient {
+            client: client,
+            proved: false,
+            accepted: None,
+        }
+    }
+}
+
+impl Party for LoginClient {
+    fn name(&self) -> &str {
+        "alice"
+    }
+
+    fn start(&mut self) -> Vec<Envelope> {
+        let payload = protocol::encode_fields(&[self.client.email().as_bytes(),
+                                                &to_bytes(self.client.public_key())]);
+        vec![Envelope::new("alice", "bob", &payload)]
+    }
+
+    fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+        let fields = protocol::decode_fields(&message.payload).unwrap_or_default();
+        // Anything but a salt and B, or "ok" after the proof, is a refusal.
+        if self.proved || fields.len() != 2 {
+            self.accepted = Some(self.proved && fields == [b"ok"]);
+            return Vec::new();
+        }
+        self.proved = true;
+        let proof = self.client.proof(&fields[0], &from_bytes(&fields[1]));
+        vec![Envelope::new("alice", &message.from, &protocol::encode_fields(&[&proof]))]
+    }
+
+    fn finished(&self) -> bool {
+        self.accepted.is_some()
+    }
+}
+
+/// A `Server` as a protocol party, answering `LoginClient`s. It remembers which email each
+/// party is logging in as between the two steps.
+pub struct LoginServer {
+    pub server: Server,
+    emails: HashMap<String, String>,
+}
+
+impl LoginServer {
+    pub fn new(server: Server) -> Self {
+        LoginServer {
+            server: server,
+            emails: HashMap::new(),
+        }
+    }
+}
+
+impl Party for LoginServer {
+    fn name(&self) -> &str {
+        "bob"
+    }
+
+    fn receive(&mut self, message: Envelope) -> Vec<Envelope> {
+        let fields = protocol::decode_fields(&message.payload).unwrap_or_default();
+        let reply = match fields.len() {
+            2 => {
+                let email = String::from_utf8_lossy(&fields[0]).into_owned();
+                match self.server.start_login(&email, &from_bytes(&fields[1])) {
+                    Ok((salt, public_b)) => {
+                        self.emails.insert(message.from.clone(), email);
+                        protocol::encode_fields(&[&salt, &to_bytes(&public_b)])
+                    }
+                    Err(_) => protocol::encode_fields(&[b"denied"]),
+                }
+            }
+            1 => {
+                let accepted = match self.emails.remove(&message.from) {
+                    Some(email) => self.server.finish_login(&email, &fields[0]),
+                    None => false,
+                };
+                let verdict: &[u8] = if accepted { b"ok" } else { b"denied" };
+                protocol::encode_fields(&[verdict])
+            }
+            _ => {
+                warn!("Bob couldn't parse a login message from {}", message.from);
+                protocol::encode_fields(&[b"denied"])
+            }
+        };
+        vec![Envelope::new("bob", &message.from, &reply)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login() {
+        let mut server = Server::new(Group::nist());
+        server.register("alice@example.com", "hunter2").unwrap();
+        assert!(server.register("alice@example.com", "again").is_err());
+
+        let client = Client::new(Group::nist(), "alice@example.com", "hunter2");
+        let (salt, public_b) = server.start_login(client.email(), client.public_key()).unwrap();
+        assert!(server.finish_login(client.email(), &client.proof(&salt, &public_b)));
+
+        let client = Client::new(Group::nist(), "alice@example.com", "hunter3");
+        let (salt, public_b) = server.start_login(client.email(), client.public_key()).unwrap();
+        assert!(!server.finish_login(client.email(), &client.proof(&salt, &public_b)));
+    }
+
+    #[test]
+    fn test_login_parties() {
+        use utils::protocol::Router;
+
+        let mut server = Server::new(Group::nist());
+        server.register("alice@example.com", "hunter2").unwrap();
+        let mut bob = LoginServer::new(server);
+
+        for &(password, accepted) in &[("hunter2", true), ("hunter3", false)] {
+            let client = Client::new(Group::nist(), "alice@example.com", password);
+            let mut alice = LoginClient::new(client);
+            let transcript = Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+            assert_eq!(transcript.len(), 4);
+            assert_eq!(alice.accepted, Some(accepted));
+        }
+
+        // An unknown user is turned away at the first step.
+        let mut alice = LoginClient::new(Client::new(Group::nist(), "eve@example.com", "x"));
+        Router::new().add(&mut alice).add(&mut bob).run().unwrap();
+        assert_eq!(alice.accepted, Some(false));
+    }
+
+    #[test]
+    fn test_dictionary_attack() {
+        use utils::wordlist::{self, Rule, Wordlist};
+
+        // A 128-bit safe prime, to keep the guesses quick.
+        let p = BigInt::parse_bytes(b"229067972870640956458409099131312782059", 10).unwrap();
+        let group = Group::new(p, BigInt::from(2));
+        let mut server = Server::new(group.clone());
+        server.register("alice@example.com", "Dragon7").unwrap();
+        server.register("bob@example.com", "correct horse battery staple").unwrap();
+
+        let rules = [Rule::ToggleCase, Rule::DigitSuffix(1)];
+        let crack = |email| {
+            let (salt, verifier) = server.verifier(email).unwrap();
+            let candidates = wordlist::mangled(Wordlist::passwords(), &rules);
+            dictionary_attack(&group, salt, verifier, candidates)
+        };
+        assert_eq!(crack("alice@example.com"), Some("Dragon7".to_string()));
+        assert_eq!(crack("bob@example.com"), None);
+    }
+}
@@ -0,0 +1,376 @@
+use std::io::{self, Read, Write};
+
+use utils::bytes::{Base64Config, HEX_LOWER};
+
+const BUFFER_SIZE: usize = 4096;
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes each byte written to it to the underlying writer as two lowercase hex digits.
+pub struct HexEncoder<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> HexEncoder<W> {
+    pub fn new(inner: W) -> HexEncoder<W> {
+        HexEncoder { inner: inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HexEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let digits = HEX_LOWER.as_bytes();
+        let mut hex = Vec::with_capacity(buf.len() * 2);
+        for byte in buf {
+            hex.push(digits[(byte >> 4) as usize]);
+            hex.push(digits[(byte & 0xF) as usize]);
+        }
+        self.inner.write_all(&hex)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads hex digits from the underlying reader and yields the bytes they encode. As with
+/// `bytes::hex_to_bytes`, whitespace is skipped and digits may be in either case.
+pub struct HexDecoder<R: Read> {
+    inner: R,
+    buffer: Vec<u8>,
+    high: Option<u8>,
+}
+
+impl<R: Read> HexDecoder<R> {
+    pub fn new(inner: R) -> HexDecoder<R> {
+        HexDecoder {
+            inner: inner,
+            buffer: vec![0; BUFFER_SIZE],
+            high: None,
+        }
+    }
+}
+
+impl<R: Read> Read for HexDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Two input digits are needed per output byte, so never read more than that.
+        loop {
+            let limit = (buf.len() * 2).min(self.buffer.len());
+            let n = self.inner.read(&mut self.buffer[..limit])?;
+            if n == 0 {
+                return match self.high {
+                    Some(_) => Err(invalid_data("Odd number of hexadecimal digits.")),
+                    None => Ok(0),
+                };
+            }
+
+            let mut written = 0;
+            for c in &self.buffer[..n] {
+                if (*c as char).is_whitespace() {
+                    continue;
+                }
+                let digit = (*c as char).to_digit(16)
+                    .ok_or_else(|| invalid_data("Invalid hexadecimal digit."))? as u8;
+                match self.high.take() {
+                    Some(high) => {
+                        buf[written] = high << 4 | digit;
+                        written += 1;
+                    }
+                    None => self.high = Some(digit),
+                }
+            }
+
+            // Keep reading if the chunk was all whitespace or a single digit.
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+/// Encodes bytes written to it as base64 according to the given configuration. Up to two
+/// bytes are held back until a complete group is available, so `finish` must be called to
+/// write the final group and any padding.
+pub struct Base64Encoder<W: Write> {
+    inner: W,
+    config: Base64Config,
+    pending: Vec<u8>,
+    column: usize,
+}
+
+impl<W: Write> Base64Encoder<W> {
+    pub fn new(inner: W, config: &Base64Config) -> Base64Encoder<W> {
+        Base64Encoder {
+            inner: inner,
+            config: *config,
+            pending: Vec::with_capacity(3),
+            column: 0,
+        }
+    }
+
+    /// Write out any buffered bytes and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            let group = self.pending.clone();
+            let mut out = Vec::with_capacity(6);
+            self.encode_group(&group, &mut out);
+            self.inner.write_all(&out)?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Encode a group of up to three bytes, inserting line breaks as configured.
+    fn encode_group(&mut self, group: &[u8], out: &mut Vec<u8>) {
+        let codes = self.config.alphabet.chars();
+        let mut bits = 0u32;
+        for (i, byte) in group.iter().enumerate() {
+            bits |= (*byte as u32) << ((2 - i) * 8);
+        }
+
+        let num_chars = if self.config.pad { 4 } else { group.len() + 1 };
+        for i in 0..num_chars {
+            if let Some(length) = self.config.line_length {
+                if length > 0 && self.column == length {
+                    out.extend(b"\r\n");
+                    self.column = 0;
+                }
+            }
+            if i <= group.len() {
+                out.push(codes[((bits >> ((3 - i) * 6)) & 0x3F) as usize]);
+            } else {
+                out.push(b'=');
+            }
+            self.column += 1;
+        }
+    }
+}
+
+impl<W: Write> Write for Base64Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out = Vec::with_capacity(buf.len() * 4 / 3 + 8);
+        let mut input = buf;
+
+        // Complete a group left over from the previous write first.
+        if !self.pending.is_empty() {
+            let needed = (3 - self.pending.len()).min(input.len());
+            self.pending.extend(&input[..needed]);
+            input = &input[needed..];
+            if self.pending.len() < 3 {
+                return Ok(buf.len());
+            }
+            let group = self.pending.clone();
+            self.pending.clear();
+            self.encode_group(&group, &mut out);
+        }
+
+        let whole = input.len() / 3 * 3;
+        for group in input[..whole].chunks(3) {
+            self.encode_group(group, &mut out);
+        }
+        self.pending.extend(&input[whole..]);
+
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads base64 from the underlying reader and yields the decoded bytes. Input is
+/// validated the same way as `bytes::base64_decode`, with errors reported as
+/// `io::ErrorKind::InvalidData`.
+pub struct Base64Decoder<R: Read> {
+    inner: R,
+    config: Base64Config,
+    codes: [Option<u8>; 256],
+    buffer: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    group: Vec<u8>,
+    padding: usize,
+    done: bool,
+}
+
+impl<R: Read> Base64Decoder<R> {
+    pub fn new(inner: R, config: &Base64Config) -> Base64Decoder<R> {
+        let mut codes = [None; 256];
+        for (i, c) in config.alphabet.chars().iter().enumerate() {
+            codes[*c as usize] = Some(i as u8);
+        }
+
+        Base64Decoder {
+            inner: inner,
+            config: *config,
+            codes: codes,
+            buffer: vec![0; BUFFER_SIZE],
+            output: Vec::with_capacity(BUFFER_SIZE),
+            position: 0,
+            group: Vec::with_capacity(4),
+            padding: 0,
+            done: false,
+        }
+    }
+
+    // Decode the digits of a (possibly partial) group into the output buffer.
+    fn flush_group(&mut self) {
+        let mut bits = 0u32;
+        for (i, code) in self.group.iter().enumerate() {
+            bits |= (*code as u32) << ((3 - i) * 6);
+        }
+        for i in 0..(self.group.len() - 1) {
+            self.output.push((bits >> ((2 - i) * 8)) as u8);
+        }
+        self.group.clear();
+    }
+
+    // Read and decode the next chunk of input into the output buffer.
+    fn fill(&mut self) -> io::Result<()> {
+        self.output.clear();
+        self.position = 0;
+
+        let n = self.inner.read(&mut self.buffer)?;
+        if n == 0 {
+            return self.finish_input();
+        }
+
+        for i in 0..n {
+            let c = self.buffer[i];
+            if self.config.line_length.is_some() && (c == b'\r' || c == b'\n') {
+                continue;
+            }
+
+            if c == b'=' {
+                self.padding += 1;
+                if self.group.len() < 2 || self.padding > 2 {
+                    return Err(invalid_data("Invalid base64 padding."));
+                }
+                if self.group.len() + self.padding == 4 {
+                    self.flush_group();
+                }
+                continue;
+            }
+
+            if self.padding > 0 {
+                return Err(invalid_data("Invalid base64 padding."));
+            }
+            let code = self.codes[c as usize]
+                .ok_or_else(|| invalid_data("Invalid base64 character."))?;
+            self.group.push(code);
+            if self.group.len() == 4 {
+                self.flush_group();
+            }
+        }
+
+        Ok(())
+    }
+
+    // Validate and decode whatever remains once the underlying reader is exhausted.
+    fn finish_input(&mut self) -> io::Result<()> {
+        self.done = true;
+        if self.padding > 0 {
+            // A complete padded group was already flushed, so any digits left over mean
+            // the padding was too short.
+            return if self.group.is_empty() {
+                Ok(())
+            } else {
+                Err(invalid_data("Invalid base64 padding."))
+            };
+        }
+        match self.group.len() {
+            0 => Ok(()),
+            _ if self.config.pad => Err(invalid_data("Missing base64 padding.")),
+            1 => Err(invalid_data("Invalid base64 length.")),
+            _ => {
+                self.flush_group();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Base64Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+
+        let n = buf.len().min(self.output.len() - self.position);
+        buf[..n].copy_from_slice(&self.output[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::bytes::{self, BASE64_MIME, BASE64_STANDARD, BASE64_URL_SAFE};
+
+    // Copy a reader into a vector a few bytes at a time to exercise partial reads.
+    fn read_slowly<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(result),
+                n => result.extend(&buf[..n]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_stream() {
+        let data = bytes::random(10000);
+        let mut encoder = HexEncoder::new(Vec::new());
+        for chunk in data.chunks(333) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let hex = encoder.into_inner();
+        assert_eq!(hex, bytes::hex_from_bytes(&data).into_bytes());
+
+        assert_eq!(read_slowly(HexDecoder::new(&hex[..])).unwrap(), data);
+        assert_eq!(read_slowly(HexDecoder::new(&b"0A ff\n12"[..])).unwrap(), [0x0a, 0xff, 0x12]);
+        assert!(read_slowly(HexDecoder::new(&b"abc"[..])).is_err());
+        assert!(read_slowly(HexDecoder::new(&b"zz"[..])).is_err());
+    }
+
+    #[test]
+    fn test_base64_stream() {
+        let data = bytes::random(10000);
+        for config in &[BASE64_STANDARD, BASE64_URL_SAFE, BASE64_MIME] {
+            for &size in &[1, 2, 5, 333, 10000] {
+                let mut encoder = Base64Encoder::new(Vec::new(), config);
+                for chunk in data[..size].chunks(7) {
+                    encoder.write_all(chunk).unwrap();
+                }
+                let encoded = encoder.finish().unwrap();
+                assert_eq!(encoded, bytes::base64_encode(&data[..size], config).into_bytes());
+
+                let decoded = read_slowly(Base64Decoder::new(&encoded[..], config)).unwrap();
+                assert_eq!(decoded, &data[..size]);
+            }
+        }
+
+        for input in &["Zm9vYg", "Zm=vYg==", "Zg===", "Zm9vY===", "Zm9v-_=="] {
+            let decoder = Base64Decoder::new(input.as_bytes(), &BASE64_STANDARD);
+            assert!(read_slowly(decoder).is_err(), "{}", input);
+        }
+    }
+}
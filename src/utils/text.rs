@@ -1,4 +1,14 @@
+use std::collections::{HashMap, HashSet};
+use std::f64;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, Read};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
 use utils::math::cosine_sim;
+use utils::wordlist::Wordlist;
 
 /// The alphabet stored as a static array for ease of access.
 static ALPHABET: [char; 26] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
@@ -15,6 +25,82 @@ static LETTER_FREQS: [f64; 26] =
      0.02758 /* U */, 0.00978 /* V */, 0.02360 /* W */, 0.00150 /* X */,
      0.01974 /* Y */, 0.00074 /* Z */];
 
+/// Letter frequencies in French, German, and Spanish, not counting accented letters.
+/// Values obtained from https://en.wikipedia.org/wiki/Letter_frequency.
+static FRENCH_FREQS: [f64; 26] =
+    [0.07636 /* A */, 0.00901 /* B */, 0.03260 /* C */, 0.03669 /* D */,
+     0.14715 /* E */, 0.01066 /* F */, 0.00866 /* G */, 0.00737 /* H */,
+     0.07529 /* I */, 0.00613 /* J */, 0.00074 /* K */, 0.05456 /* L */,
+     0.02968 /* M */, 0.07095 /* N */, 0.05796 /* O */, 0.02521 /* P */,
+     0.01362 /* Q */, 0.06693 /* R */, 0.07948 /* S */, 0.07244 /* T */,
+     0.06311 /* U */, 0.01838 /* V */, 0.00049 /* W */, 0.00427 /* X */,
+     0.00128 /* Y */, 0.00326 /* Z */];
+
+static GERMAN_FREQS: [f64; 26] =
+    [0.06516 /* A */, 0.01886 /* B */, 0.02732 /* C */, 0.05076 /* D */,
+     0.16396 /* E */, 0.01656 /* F */, 0.03009 /* G */, 0.04577 /* H */,
+     0.06550 /* I */, 0.00268 /* J */, 0.01417 /* K */, 0.03437 /* L */,
+     0.02534 /* M */, 0.09776 /* N */, 0.02594 /* O */, 0.00670 /* P */,
+     0.00018 /* Q */, 0.07003 /* R */, 0.07270 /* S */, 0.06154 /* T */,
+     0.04166 /* U */, 0.00846 /* V */, 0.01921 /* W */, 0.00034 /* X */,
+     0.00039 /* Y */, 0.01134 /* Z */];
+
+static SPANISH_FREQS: [f64; 26] =
+    [0.11525 /* A */, 0.02215 /* B */, 0.04019 /* C */, 0.05010 /* D */,
+     0.12181 /* E */, 0.00692 /* F */, 0.01768 /* G */, 0.00703 /* H */,
+     0.06247 /* I */, 0.00493 /* J */, 0.00011 /* K */, 0.04967 /* L */,
+     0.03157 /* M */, 0.06712 /* N */, 0.08683 /* O */, 0.02510 /* P */,
+     0.00877 /* Q */, 0.06871 /* R */, 0.07977 /* S */, 0.04632 /* T */,
+     0.02927 /* U */, 0.01138 /* V */, 0.00017 /* W */, 0.00215 /* X */,
+     0.01008 /* Y */, 0.00467 /* Z */];
+
+/// Natural languages with built-in letter frequency tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+impl Language {
+    /// All of the supported languages.
+    pub fn all() -> &'static [Language] {
+        static ALL: [Language; 4] =
+            [Language::English, Language::French, Language::German, Language::Spanish];
+        &ALL
+    }
+
+    /// Letter frequencies of this language.
+    pub fn letter_freqs(&self) -> &'static [f64; 26] {
+        match *self {
+            Language::English => &LETTER_FREQS,
+            Language::French => &FRENCH_FREQS,
+            Language::German => &GERMAN_FREQS,
+            Language::Spanish => &SPANISH_FREQS,
+        }
+    }
+
+    /// Character frequencies of this language, for use with the frequency-based scorers.
+    /// The frequencies of spaces and other characters are assumed to be the same as in
+    /// English.
+    pub fn frequencies(&self) -> Frequencies {
+        let letter_freqs = self.letter_freqs();
+        let letter_share = 1.0 - SPACE_FREQ - OTHER_FREQ - UNPRINTABLE_FREQ;
+        let total = letter_freqs.iter().sum::<f64>();
+        let mut letters = [0.0; 26];
+        for (letter, freq) in letters.iter_mut().zip(letter_freqs.iter()) {
+            *letter = freq / total * letter_share;
+        }
+
+        Frequencies {
+            letters: letters,
+            space: SPACE_FREQ,
+            other: OTHER_FREQ,
+        }
+    }
+}
+
 /// Find the position of the given character in the English alphabet.
 /// Return None if the character is not part of the alphabet.
 fn alphabet_position(c: char) -> Option<usize> {
@@ -29,28 +115,764 @@ fn alphabet_position(c: char) -> Option<usize> {
     None
 }
 
-/// Score a text based on similarity to known English letter frequencies.
-pub fn score(text: &str) -> f64 {
+/// Strip the accent from common accented Latin letters, so that they are counted as the
+/// corresponding unaccented letter. Other characters are returned unchanged.
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ä' | 'ã' | 'À' | 'Á' | 'Â' | 'Ä' | 'Ã' => 'a',
+        'ç' | 'Ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' | 'È' | 'É' | 'Ê' | 'Ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'Ì' | 'Í' | 'Î' | 'Ï' => 'i',
+        'ñ' | 'Ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'ö' | 'õ' | 'Ò' | 'Ó' | 'Ô' | 'Ö' | 'Õ' => 'o',
+        'ß' => 's',
+        'ù' | 'ú' | 'û' | 'ü' | 'Ù' | 'Ú' | 'Û' | 'Ü' => 'u',
+        'ÿ' => 'y',
+        _ => c,
+    }
+}
+
+/// Score a text based on similarity to known letter frequencies of the given language.
+/// Accented letters are counted as their unaccented equivalents.
+pub fn score(text: &str, language: Language) -> f64 {
+    match count_letters(text.chars().map(strip_accent)) {
+        Some((counts, _)) => cosine_sim(language.letter_freqs(), &counts),
+        None => 0.0,
+    }
+}
+
+/// Score a text against every supported language, returning the language whose letter
+/// frequencies the text most closely matches along with its score.
+pub fn detect_language(text: &str) -> (Language, f64) {
+    Language::all()
+        .iter()
+        .map(|&language| (language, score(text, language)))
+        .fold((Language::English, f64::MIN), |best, candidate| {
+            if candidate.1 > best.1 { candidate } else { best }
+        })
+}
+
+/// Score a byte string based on similarity to known English letter frequencies, without
+/// requiring it to be valid UTF-8. Bytes are interpreted as ASCII, and the score is
+/// penalized in proportion to the number of non-printable characters present.
+pub fn score_bytes(bytes: &[u8]) -> f64 {
+    cosine_score(bytes, &LETTER_FREQS)
+}
+
+// Implementation of score_bytes() against arbitrary letter frequencies.
+fn cosine_score(bytes: &[u8], letter_freqs: &[f64]) -> f64 {
+    let (counts, unprintable) = match count_letters(bytes.iter().map(|&byte| byte as char)) {
+        Some(counts) => counts,
+        None => return 0.0,
+    };
+
+    // Each non-printable character is penalized heavily, since even a handful of them
+    // make it very unlikely that the input is English text.
+    let printable_ratio = 1.0 - unprintable as f64 / bytes.len() as f64;
+    cosine_sim(letter_freqs, &counts) * printable_ratio.powi(4)
+}
+
+// Count the occurrences of each letter, and of characters that are neither letters nor
+// printable ASCII, for the cosine similarity scorers. The counts don't need normalizing,
+// since cosine similarity takes care of that.
+//
+// Returns None if there are no spaces: a crude heuristic, but text without them probably
+// isn't natural language. We need it because we don't have frequencies for characters
+// other than letters, and ignoring whitespace entirely gives incorrect results.
+fn count_letters<I>(chars: I) -> Option<([f64; 26], usize)>
+    where I: Iterator<Item = char>
+{
     let mut counts = [0.0f64; 26];
     let mut space = false;
+    let mut unprintable = 0;
 
-    // Count the occurrence of letters in the input text.
-    for c in text.chars() {
+    for c in chars {
         if c == ' ' {
             space = true;
         } else if let Some(i) = alphabet_position(c) {
-            *(&mut counts[i]) += 1.0;
+            counts[i] += 1.0;
+        } else if !c.is_ascii() || !is_printable(c as u8) {
+            unprintable += 1;
+        }
+    }
+
+    if space { Some((counts, unprintable)) } else { None }
+}
+
+/// Returns true if the given byte is a printable ASCII character or common whitespace.
+fn is_printable(byte: u8) -> bool {
+    (byte >= 0x20 && byte < 0x7F) || byte == b'\n' || byte == b'\r' || byte == b'\t'
+}
+
+/// Expected fraction of characters in English text that are spaces.
+const SPACE_FREQ: f64 = 0.17;
+
+/// Expected fraction of characters in English text that are printable, but neither letters
+/// nor spaces (e.g., punctuation, digits, and newlines).
+const OTHER_FREQ: f64 = 0.0299;
+
+/// Expected fraction of non-printable characters, which should never appear in English
+/// text. This is kept small but nonzero so that chi-squared statistics remain finite.
+const UNPRINTABLE_FREQ: f64 = 0.0001;
+
+/// Relative frequencies of the characters in a body of text, as fractions of the total
+/// number of characters. Letters are counted without regard to case.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frequencies {
+    pub letters: [f64; 26],
+    pub space: f64,
+    pub other: f64,
+}
+
+impl Frequencies {
+    /// Character frequencies of English text, based on the Oxford English Dictionary letter
+    /// frequencies and estimates of the frequencies of spaces and other characters.
+    pub fn english() -> Self {
+        Language::English.frequencies()
+    }
+
+    /// Count the characters in the given corpus. Printable characters other than letters
+    /// and spaces are counted together, and non-printable characters are ignored.
+    pub fn from_corpus(corpus: &[u8]) -> Self {
+        let mut letters = [0.0; 26];
+        let mut space = 0.0;
+        let mut other = 0.0;
+
+        for &byte in corpus {
+            if byte == b' ' {
+                space += 1.0;
+            } else if let Some(i) = alphabet_position(byte as char) {
+                letters[i] += 1.0;
+            } else if is_printable(byte) {
+                other += 1.0;
+            }
+        }
+
+        let total = (letters.iter().sum::<f64>() + space + other).max(1.0);
+        for letter in letters.iter_mut() {
+            *letter /= total;
+        }
+
+        Frequencies {
+            letters: letters,
+            space: space / total,
+            other: other / total,
+        }
+    }
+
+    /// Count the characters in the corpus stored in the given file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_corpus(&read_file(path)?))
+    }
+}
+
+/// Common interface for functions that rate how English-like a candidate plaintext is.
+/// Higher scores indicate more English-like text.
+pub trait Scorer {
+    fn score(&self, candidate: &[u8]) -> f64;
+}
+
+/// Scorer based on the cosine similarity of letter frequencies. See score_bytes().
+pub struct CosineScorer {
+    freqs: Frequencies,
+}
+
+impl CosineScorer {
+    /// Create a scorer that compares candidates against the given frequencies.
+    pub fn new(freqs: Frequencies) -> Self {
+        CosineScorer { freqs: freqs }
+    }
+}
+
+impl Default for CosineScorer {
+    fn default() -> Self {
+        Self::new(Frequencies::english())
+    }
+}
+
+impl Scorer for CosineScorer {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        cosine_score(candidate, &self.freqs.letters)
+    }
+}
+
+/// Scorer based on Pearson's chi-squared goodness-of-fit test against the frequencies of
+/// letters, spaces, and other characters in English text (or whatever frequencies it is
+/// given). Since lower chi-squared values indicate a better fit, the score is the negated
+/// statistic.
+pub struct ChiSquaredScorer {
+    freqs: Frequencies,
+}
+
+impl ChiSquaredScorer {
+    /// Create a scorer that compares candidates against the given frequencies.
+    pub fn new(freqs: Frequencies) -> Self {
+        ChiSquaredScorer { freqs: freqs }
+    }
+}
+
+impl Default for ChiSquaredScorer {
+    fn default() -> Self {
+        Self::new(Frequencies::english())
+    }
+}
+
+impl Scorer for ChiSquaredScorer {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        if candidate.is_empty() {
+            return f64::MIN;
+        }
+
+        // Observed counts of each letter, followed by spaces, other printable characters,
+        // and non-printable characters.
+        let mut counts = [0.0f64; 29];
+        for &byte in candidate {
+            let i = if byte == b' ' {
+                26
+            } else if let Some(i) = alphabet_position(byte as char) {
+                i
+            } else if is_printable(byte) {
+                27
+            } else {
+                28
+            };
+            counts[i] += 1.0;
+        }
+
+        // Characters that never appeared in the reference text get a small nonzero frequency
+        // so that the statistic remains finite.
+        let others = [self.freqs.space, self.freqs.other, UNPRINTABLE_FREQ];
+        let expected = self.freqs
+            .letters
+            .iter()
+            .chain(&others)
+            .map(|freq| freq.max(UNPRINTABLE_FREQ) * candidate.len() as f64);
+
+        -counts.iter()
+            .zip(expected)
+            .map(|(observed, expected)| (observed - expected).powi(2) / expected)
+            .sum::<f64>()
+    }
+}
+
+/// Table of English quadgram counts, one per line, derived from a sample of English prose.
+static ENGLISH_QUADGRAMS: &'static str = include_str!("../data/english_quadgrams.txt");
+
+/// Language model that scores text by the log-probabilities of the letter n-grams it
+/// contains. Much more discriminating than single-letter frequencies on short inputs,
+/// since it captures which letters tend to follow one another.
+#[derive(Clone, Debug)]
+pub struct NgramModel {
+    n: usize,
+    // Base-10 log-probability of each n-gram, indexed by treating the n-gram's letters as
+    // the digits of a base-26 number.
+    log_probs: Vec<f64>,
+    // Log-probability assigned to n-grams that never appeared in the training data.
+    floor: f64,
+}
+
+impl NgramModel {
+    /// Build a model from a table of n-gram counts, with one n-gram and its count per line,
+    /// separated by whitespace (e.g., "TION 13168"). All n-grams must have the same length,
+    /// and may only contain the letters A-Z (in either case).
+    pub fn from_counts(table: &str) -> Result<Self, &'static str> {
+        let mut counts = Vec::new();
+        for line in table.lines().filter(|line| !line.trim().is_empty()) {
+            let mut fields = line.split_whitespace();
+            let gram = fields.next().ok_or("Missing n-gram.")?;
+            let count = fields.next()
+                .and_then(|count| count.parse::<u64>().ok())
+                .ok_or("Missing or invalid n-gram count.")?;
+            counts.push((gram.as_bytes().to_vec(), count));
+        }
+        Self::from_count_pairs(&counts)
+    }
+
+    /// Build a model from the n-grams of the given length found in a corpus of text. This
+    /// allows scoring text in other languages or domains than general English.
+    pub fn from_corpus(corpus: &[u8], n: usize) -> Result<Self, &'static str> {
+        Self::from_count_pairs(&ngram_counts(corpus, n))
+    }
+
+    /// Build a model from the n-grams of the given length found in a file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file<P: AsRef<Path>>(path: P, n: usize) -> io::Result<Self> {
+        Self::from_corpus(&read_file(path)?, n)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// English language model over n-grams of the given length (from 1 to 4), derived from
+    /// the embedded quadgram table. Shorter n-grams are counted by summing the counts of
+    /// all quadgrams that begin with them.
+    pub fn english(n: usize) -> Self {
+        assert!(n >= 1 && n <= 4, "N-grams must be between 1 and 4 letters long.");
+
+        let mut counts = HashMap::new();
+        for line in ENGLISH_QUADGRAMS.lines() {
+            let mut fields = line.split_whitespace();
+            if let (Some(gram), Some(count)) = (fields.next(), fields.next()) {
+                let count = count.parse::<u64>().unwrap();
+                *counts.entry(gram.as_bytes()[0..n].to_vec()).or_insert(0) += count;
+            }
+        }
+
+        Self::from_count_pairs(&counts.into_iter().collect::<Vec<_>>()).unwrap()
+    }
+
+    // Build a model from a list of n-grams and their counts.
+    fn from_count_pairs(counts: &[(Vec<u8>, u64)]) -> Result<Self, &'static str> {
+        let n = counts.first().map(|&(ref gram, _)| gram.len()).ok_or("Empty n-gram table.")?;
+        if n == 0 || n > 4 {
+            return Err("N-grams must be between 1 and 4 letters long.");
+        }
+
+        let total = counts.iter().map(|&(_, count)| count as f64).sum::<f64>();
+        if total == 0.0 {
+            return Err("N-gram counts are all zero.");
+        }
+        let floor = (0.01 / total).log10();
+        let mut log_probs = vec![floor; 26usize.pow(n as u32)];
+        for &(ref gram, count) in counts {
+            if gram.len() != n {
+                return Err("N-grams must all have the same length.");
+            }
+            let index = Self::index(gram).ok_or("N-grams may only contain letters.")?;
+            // An n-gram counted zero times is scored like one that wasn't listed, rather
+            // than with a log-probability of minus infinity.
+            if count > 0 {
+                log_probs[index] = (count as f64 / total).log10();
+            }
+        }
+
+        Ok(NgramModel {
+            n: n,
+            log_probs: log_probs,
+            floor: floor,
+        })
+    }
+
+    /// Length of the n-grams used by this model.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Compute the log-probability of the given text under this model. Letters are scored
+    /// in overlapping windows of n, ignoring case. Spaces are assigned the probability of a
+    /// space in English text, and any other character is treated as an unknown n-gram, so
+    /// that candidates can't improve their score simply by containing fewer letters.
+    /// Scores are only comparable between inputs of equal length.
+    pub fn log_prob(&self, text: &[u8]) -> f64 {
+        let mut letters = Vec::with_capacity(text.len());
+        let mut penalty = 0.0;
+        for &byte in text {
+            if byte.is_ascii_alphabetic() {
+                letters.push(byte);
+            } else if byte == b' ' {
+                penalty += SPACE_FREQ.log10();
+            } else {
+                penalty += self.floor;
+            }
+        }
+
+        letters.windows(self.n)
+            .map(|gram| self.log_probs[Self::index(gram).unwrap()])
+            .sum::<f64>() + penalty
+    }
+
+    // Compute the index of the given n-gram in the log-probability table.
+    fn index(gram: &[u8]) -> Option<usize> {
+        gram.iter().fold(Some(0), |index, &byte| {
+            let letter = byte.to_ascii_uppercase();
+            if letter >= b'A' && letter <= b'Z' {
+                index.map(|index| index * 26 + (letter - b'A') as usize)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl Scorer for NgramModel {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        self.log_prob(candidate)
+    }
+}
+
+/// Scorer that splits candidates into words and measures the fraction of them that appear
+/// in a wordlist. Too coarse to rank candidates on its own, but useful for telling apart
+/// candidates whose frequency-based scores are close.
+pub struct WordlistScorer {
+    words: HashSet<Vec<u8>>,
+}
+
+impl WordlistScorer {
+    /// Create a scorer that checks words against the given list. Case is ignored.
+    pub fn new<I, W>(words: I) -> Self
+        where I: IntoIterator<Item = W>,
+              W: AsRef<[u8]>
+    {
+        WordlistScorer {
+            words: words.into_iter().map(|word| word.as_ref().to_ascii_lowercase()).collect(),
+        }
+    }
+
+    /// Split a candidate plaintext into words on whitespace, ignoring any punctuation at
+    /// either end of each word.
+    fn tokenize(candidate: &[u8]) -> Vec<Vec<u8>> {
+        candidate.split(|byte| byte.is_ascii_whitespace())
+            .filter_map(|token| {
+                let start = token.iter().position(|byte| byte.is_ascii_alphabetic())?;
+                let end = token.iter().rposition(|byte| byte.is_ascii_alphabetic())?;
+                Some(token[start..end + 1].to_ascii_lowercase())
+            })
+            .collect()
+    }
+
+    /// Check whether the given word is in the wordlist, also accepting possessive forms
+    /// of words in the list.
+    fn contains(&self, word: &[u8]) -> bool {
+        self.words.contains(word) ||
+        (word.ends_with(b"'s") && self.words.contains(&word[..word.len() - 2]))
+    }
+}
+
+impl Default for WordlistScorer {
+    /// A scorer that checks words against `Wordlist::english`.
+    fn default() -> Self {
+        Self::new(Wordlist::english().words())
+    }
+}
+
+impl<'a> From<&'a Wordlist> for WordlistScorer {
+    fn from(wordlist: &'a Wordlist) -> Self {
+        Self::new(wordlist.words())
+    }
+}
+
+impl Scorer for WordlistScorer {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        let tokens = Self::tokenize(candidate);
+        if tokens.is_empty() {
+            return 0.0;
+        }
+        let known = tokens.iter().filter(|token| self.contains(token)).count();
+        known as f64 / tokens.len() as f64
+    }
+}
+
+/// Scorer that ranks candidates by a primary scorer, but uses a secondary scorer (whose
+/// scores should fall between 0 and 1) to break near-ties. Candidates whose primary scores
+/// differ by more than the tolerance are never reordered.
+pub struct TieBreaker<P, S> {
+    pub primary: P,
+    pub secondary: S,
+    pub tolerance: f64,
+}
+
+impl TieBreaker<Weighted, WordlistScorer> {
+    /// English letter and character class frequencies (see `Weighted::english()`), with
+    /// ties broken by the embedded wordlist, suited to short plaintexts where frequency
+    /// scores alone are often close.
+    pub fn english() -> Self {
+        // The primary score adds two scores from 0 to 1, so this is 5% of its range.
+        TieBreaker {
+            primary: Weighted::english(),
+            secondary: WordlistScorer::default(),
+            tolerance: 0.1,
         }
     }
+}
 
-    // Crude heuristic: if there are no spaces, this probably isn't English text.
-    // We need this because we don't have non-alphabetic character frequencies, but
-    // we get incorrect results if we just ignore whitespace entirely.
-    if !space {
-        return 0.0;
+impl<P: Scorer, S: Scorer> Scorer for TieBreaker<P, S> {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        let secondary = self.secondary.score(candidate).max(0.0).min(1.0);
+        self.primary.score(candidate) + self.tolerance * secondary
     }
+}
+
+/// Scorer that measures the fraction of a candidate that is printable ASCII or common
+/// whitespace, from 0 to 1. It knows nothing about language, so on its own it only tells
+/// text from binary, but it suits plaintexts the frequency scorers don't, such as source
+/// code, numbers, or encoded data.
+#[derive(Default)]
+pub struct PrintableScorer;
+
+impl Scorer for PrintableScorer {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        if candidate.is_empty() {
+            return 0.0;
+        }
+        let printable = candidate.iter().filter(|&&byte| is_printable(byte)).count();
+        printable as f64 / candidate.len() as f64
+    }
+}
+
+/// Scorer that looks at what kinds of characters a candidate is made of rather than which
+/// letters: how much of it is letters, spaces, and other printable characters, compared
+/// with English text (or whatever frequencies it is given), with a penalty for control
+/// characters and bytes above 0x7f. Scores run from 0 to 1.
+///
+/// The letter frequency scorers see nothing but letters, so on short inputs they can prefer
+/// garbage such as "Te}$ 04$pk`e}", whose few letters happen to be common ones, over the
+/// real plaintext. Text like that is mostly punctuation, which this scorer catches, so it's
+/// best used alongside one of them, as `Weighted::english()` does.
+pub struct CharacterClassScorer {
+    freqs: Frequencies,
+}
+
+impl CharacterClassScorer {
+    /// Create a scorer that compares candidates against the given frequencies.
+    pub fn new(freqs: Frequencies) -> Self {
+        CharacterClassScorer { freqs: freqs }
+    }
+}
+
+impl Default for CharacterClassScorer {
+    fn default() -> Self {
+        Self::new(Frequencies::english())
+    }
+}
+
+impl Scorer for CharacterClassScorer {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        if candidate.is_empty() {
+            return 0.0;
+        }
+
+        // Letters, spaces, other printable characters, control characters and high bytes.
+        let mut counts = [0.0f64; 5];
+        for &byte in candidate {
+            let i = match byte {
+                b' ' => 1,
+                _ if byte.is_ascii_alphabetic() => 0,
+                _ if is_printable(byte) => 2,
+                _ if byte < 0x80 => 3,
+                _ => 4,
+            };
+            counts[i] += 1.0;
+        }
+        let len = candidate.len() as f64;
+        let observed = counts.iter().map(|count| count / len).collect::<Vec<_>>();
+
+        // The Bhattacharyya coefficient of the observed and expected shares of letters,
+        // spaces and other characters, which is 1 when they match. Raising it to the fourth
+        // power spreads out the scores of text that's nearly right.
+        let letters = self.freqs.letters.iter().sum::<f64>();
+        let total = letters + self.freqs.space + self.freqs.other;
+        let expected = [letters / total, self.freqs.space / total, self.freqs.other / total];
+        let fit = observed.iter().zip(&expected).map(|(p, q)| (p * q).sqrt()).sum::<f64>();
 
-    // Compute similarity against known English letter frequencies.
-    // No need to normalize the counts because cosine similarity takes care of this.
-    cosine_sim(&LETTER_FREQS, &counts)
+        // English never has control characters, so even a few rule a candidate out. High
+        // bytes could be accented letters in Latin-1 or UTF-8, so they count for less.
+        fit.powi(4) * (1.0 - observed[3]).powi(8) * (1.0 - observed[4]).powi(2)
+    }
+}
+
+/// Scorer that adds up the scores of other scorers, each multiplied by a weight. The
+/// scorers' scores are on different scales (cosine similarity runs from 0 to 1, while
+/// chi-squared and n-gram scores are large and negative), so the weights have to account
+/// for that as well as for how much each one should count.
+#[derive(Default)]
+pub struct Weighted {
+    scorers: Vec<(f64, Box<dyn Scorer + Send + Sync>)>,
+}
+
+impl Weighted {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// English letter frequencies, backed up by the mix of letters, spaces and punctuation
+    /// expected in English, which keeps short inputs from being won by punctuation-heavy
+    /// garbage.
+    pub fn english() -> Self {
+        Self::new()
+            .with(1.0, CosineScorer::default())
+            .with(1.0, CharacterClassScorer::default())
+    }
+
+    /// Add a scorer with the given weight.
+    pub fn with<S>(mut self, weight: f64, scorer: S) -> Self
+        where S: Scorer + Send + Sync + 'static
+    {
+        self.scorers.push((weight, Box::new(scorer)));
+        self
+    }
+}
+
+impl Scorer for Weighted {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        self.scorers.iter().map(|&(weight, ref scorer)| weight * scorer.score(candidate)).sum()
+    }
+}
+
+impl<S: Scorer + ?Sized> Scorer for &S {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        (**self).score(candidate)
+    }
+}
+
+impl<S: Scorer + ?Sized> Scorer for Box<S> {
+    fn score(&self, candidate: &[u8]) -> f64 {
+        (**self).score(candidate)
+    }
+}
+
+/// Count the letter n-grams of the given length in a corpus of text, ignoring case and any
+/// characters other than letters. Returns the upper case n-grams and their counts, sorted
+/// in descending order of count, in the same form as the table used by NgramModel.
+pub fn ngram_counts(corpus: &[u8], n: usize) -> Vec<(Vec<u8>, u64)> {
+    let letters = corpus.iter()
+        .filter(|byte| byte.is_ascii_alphabetic())
+        .map(|byte| byte.to_ascii_uppercase())
+        .collect::<Vec<u8>>();
+
+    let mut counts = HashMap::new();
+    for gram in letters.windows(n.max(1)) {
+        *counts.entry(gram.to_vec()).or_insert(0) += 1;
+    }
+
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|&(ref a, x), &(ref b, y)| y.cmp(&x).then(a.cmp(b)));
+    counts
+}
+
+// Read the entire contents of a file.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    File::open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_bytes_high_bytes() {
+        // Latin-1 encoded text isn't valid UTF-8, but should still score as English.
+        let latin1 = &b"the caf\xe9 on the corner serves coffee"[..];
+        let garbage = &b"\x8f\x01 kq\xff\xfe\x07zx\x90\x91 \x03jv\xa0\xb3\xc4"[..];
+        assert!(String::from_utf8(latin1.to_vec()).is_err());
+        assert!(score_bytes(latin1) > 0.5);
+        assert!(score_bytes(latin1) > score_bytes(garbage));
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let english = "It was the best of times, it was the worst of times, it was the age of \
+                       wisdom, it was the age of foolishness, it was the epoch of belief.";
+        let french = "Longtemps, je me suis couché de bonne heure. Parfois, à peine ma bougie \
+                      éteinte, mes yeux se fermaient si vite que je n'avais pas le temps de me \
+                      dire que je m'endormais.";
+        let german = "Als Gregor Samsa eines Morgens aus unruhigen Träumen erwachte, fand er \
+                      sich in seinem Bett zu einem ungeheueren Ungeziefer verwandelt.";
+        let spanish = "En un lugar de la Mancha, de cuyo nombre no quiero acordarme, no ha mucho \
+                       tiempo que vivía un hidalgo de los de lanza en astillero, adarga antigua, \
+                       rocín flaco y galgo corredor.";
+
+        assert_eq!(detect_language(english).0, Language::English);
+        assert_eq!(detect_language(french).0, Language::French);
+        assert_eq!(detect_language(german).0, Language::German);
+        assert_eq!(detect_language(spanish).0, Language::Spanish);
+    }
+
+    #[test]
+    fn test_wordlist() {
+        let scorer = WordlistScorer::default();
+        assert_eq!(scorer.score(b"Cooking MC's like a pound of bacon"), 6.0 / 7.0);
+        assert_eq!(scorer.score(b"  \"Don't\" stop,  it's the river's! "), 1.0);
+        assert_eq!(scorer.score(b"xkcd qwfp"), 0.0);
+        assert_eq!(scorer.score(b"..."), 0.0);
+        let passwords = WordlistScorer::from(&Wordlist::passwords());
+        assert_eq!(passwords.score(b"letmein, password"), 1.0);
+
+        let tiebreaker = TieBreaker::english();
+        assert!(tiebreaker.score(b"the cat sat") > tiebreaker.score(b"hte act tas"));
+    }
+
+    #[test]
+    fn test_chi_squared() {
+        let scorer = ChiSquaredScorer::default();
+        let english = &b"Cooking MC's like a pound of bacon"[..];
+        let shifted = english.iter().map(|b| b ^ 0x20).collect::<Vec<u8>>();
+        let garbage = english.iter().map(|b| b ^ 0x58).collect::<Vec<u8>>();
+        assert!(scorer.score(english) > scorer.score(&shifted));
+        assert!(scorer.score(english) > scorer.score(&garbage));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let text = &b"Hello, world!\n"[..];
+        let binary = &b"\x00\x01\xff hi"[..];
+        assert_eq!(PrintableScorer.score(text), 1.0);
+        assert_eq!(PrintableScorer.score(binary), 0.5);
+
+        let cosine = CosineScorer::default();
+        let scorer = Weighted::new()
+            .with(2.0, CosineScorer::default())
+            .with(-1.0, PrintableScorer);
+        assert_eq!(scorer.score(text), 2.0 * cosine.score(text) - 1.0);
+        assert_eq!(Weighted::new().score(text), 0.0);
+        // Boxed scorers are scorers too, so combinations can be built at run time.
+        let boxed: Box<dyn Scorer + Send + Sync> = Box::new(scorer);
+        let nested = Weighted::new().with(0.5, boxed);
+        assert_eq!(nested.score(binary), cosine.score(binary) - 0.25);
+    }
+
+    #[test]
+    fn test_character_classes() {
+        // The real plaintext, and the best-scoring decryption of it under another key.
+        let plaintext = &b"Pay $40 today"[..];
+        let garbage = &b"Te}$ 04$pk`e}"[..];
+        let cosine = CosineScorer::default();
+        assert!(cosine.score(garbage) > cosine.score(plaintext));
+
+        let classes = CharacterClassScorer::default();
+        assert!(classes.score(plaintext) > 0.75 && classes.score(garbage) < 0.5);
+        assert!(classes.score(b"Pay \x0440 today") < 0.5 * classes.score(plaintext));
+        assert!(classes.score(b"Pay \xa340 today") > 0.5 * classes.score(plaintext));
+        let english = Weighted::english();
+        assert!(english.score(plaintext) > english.score(garbage));
+    }
+
+    #[test]
+    fn test_ngram_model() {
+        let model = NgramModel::english(4);
+        assert_eq!(model.n(), 4);
+        assert!(model.log_prob(b"the weather is fine") > model.log_prob(b"xqz jvvkp wq yhgtr"));
+        assert!(model.log_prob(b"THE WEATHER") == model.log_prob(b"the weather"));
+
+        let bigrams = NgramModel::english(2);
+        assert!(bigrams.log_prob(b"then") > bigrams.log_prob(b"qzxj"));
+
+        assert!(NgramModel::from_counts("AB 1\nABC 2").is_err());
+        assert!(NgramModel::from_counts("A1 1").is_err());
+        assert!(NgramModel::from_counts("AB 0").is_err());
+
+        let model = NgramModel::from_counts("AB 0\nCD 4").unwrap();
+        assert!(model.log_prob(b"ab").is_finite());
+        assert_eq!(model.log_prob(b"ab"), model.log_prob(b"ef"));
+    }
+
+    #[test]
+    fn test_corpus_tables() {
+        let corpus = include_bytes!("../data/play_that_funky_music.txt");
+        let freqs = Frequencies::from_corpus(corpus);
+        let total = freqs.letters.iter().sum::<f64>() + freqs.space + freqs.other;
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(freqs.letters[4] > freqs.letters[25]);
+
+        let counts = ngram_counts(b"Abab, ab!", 2);
+        assert_eq!(counts, vec![(b"AB".to_vec(), 3), (b"BA".to_vec(), 2)]);
+
+        // A model trained on the lyrics should prefer them to general English.
+        let lyrics = &b"play that funky music white boy"[..];
+        let model = NgramModel::from_corpus(corpus, 3).unwrap();
+        let scorer = ChiSquaredScorer::new(freqs);
+        assert!(model.log_prob(lyrics) > model.log_prob(b"zqxv jkwp qzzy vbnmx qwrt ypl"));
+        assert!(scorer.score(lyrics) > scorer.score(b"zqxv jkwp qzzy vbnmx qwrt ypl"));
+    }
 }
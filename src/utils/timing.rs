@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+use std::f64;
+use std::time::{Duration, Instant};
+
+use utils::math;
+
+// Scales the median absolute deviation of normally distributed data to its standard
+// deviation.
+const MAD_SCALE: f64 = 1.4826;
+
+/// Run `f` once, returning its result and how long it took by the monotonic clock.
+pub fn time<T, F>(f: F) -> (T, Duration)
+    where F: FnOnce() -> T
+{
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// Time `samples` runs of `f`, after `warmup` runs that aren't timed. The first few runs
+/// of anything are slow while caches and branch predictors fill up, and would otherwise
+/// show up as a difference between whatever happens to be measured first and the rest.
+pub fn measure<F>(warmup: usize, samples: usize, mut f: F) -> Samples
+    where F: FnMut()
+{
+    for _ in 0..warmup {
+        f();
+    }
+    Samples::new((0..samples)
+        .map(|_| {
+            let (_, elapsed) = time(&mut f);
+            elapsed.as_secs_f64() * 1e9
+        })
+        .collect())
+}
+
+/// A set of timing measurements, in nanoseconds when they come from `measure`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Samples {
+    sorted: Vec<f64>,
+}
+
+impl Samples {
+    pub fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        Samples { sorted: values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// The measurements, from fastest to slowest.
+    pub fn values(&self) -> &[f64] {
+        &self.sorted
+    }
+
+    /// The p-th percentile, for p from 0 to 100, interpolating between the measurements
+    /// on either side. Panics if there are no measurements.
+    pub fn percentile(&self, p: f64) -> f64 {
+        assert!(!self.is_empty(), "No measurements.");
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (self.len() - 1) as f64;
+        let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+        let fraction = rank - low as f64;
+        self.sorted[low] * (1.0 - fraction) + self.sorted[high] * fraction
+    }
+
+    pub fn median(&self) -> f64 {
+        self.percentile(50.0)
+    }
+
+    /// The median absolute deviation from the median, scaled to estimate the standard
+    /// deviation. Unlike the standard deviation itself, a few huge outliers barely move it.
+    pub fn mad(&self) -> f64 {
+        let median = self.median();
+        let deviations = self.sorted.iter().map(|x| (x - median).abs()).collect();
+        MAD_SCALE * Samples::new(deviations).median()
+    }
+
+    /// The measurements within `k` MADs of the median. Timings have a long tail of slow
+    /// runs that were interrupted or descheduled, which say nothing about the code being
+    /// timed, and a `k` of 3 or so drops them. If the MAD is zero, only the measurements
+    /// equal to the median are kept.
+    pub fn reject_outliers(&self, k: f64) -> Samples {
+        let (median, limit) = (self.median(), k * self.mad());
+        Samples::new(self.sorted.iter().cloned().filter(|x| (x - median).abs() <= limit).collect())
+    }
+}
+
+/// How two sets of timings compare.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    /// How much slower the second set is than the first, by their medians.
+    pub difference: f64,
+    /// The Mann-Whitney z-score, positive when the second set tends to be slower.
+    pub z: f64,
+    /// The probability of a z-score at least this far from zero if both sets came from the
+    /// same distribution.
+    pub p_value: f64,
+}
+
+impl Comparison {
+    /// Whether the sets differ at the given significance level, such as 0.01.
+    pub fn is_significant(&self, alpha: f64) -> bool {
+        self.p_value < alpha
+    }
+}
+
+/// Compare two sets of timings with the Mann-Whitney U test, which asks whether a timing
+/// from one set tends to be larger than one from the other. It only looks at the order of
+/// the timings, not their values, so it's unaffected by the long tail that throws off
+/// t-tests, and doesn't need outliers to be rejected first. The p-value comes from the
+/// normal approximation, corrected for ties, which needs about ten timings in each set.
+pub fn compare(a: &Samples, b: &Samples) -> Comparison {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+
+    // Rank everything together, giving tied values the average of their ranks.
+    let mut all = a.values().iter().map(|&x| (x, false))
+        .chain(b.values().iter().map(|&x| (x, true)))
+        .collect::<Vec<_>>();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal));
+    let (mut rank_sum, mut ties) = (0.0, 0.0);
+    let mut i = 0;
+    while i < all.len() {
+        let j = (i..all.len()).find(|&j| all[j].0 != all[i].0).unwrap_or(all.len());
+        let count = (j - i) as f64;
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += rank * all[i..j].iter().filter(|&&(_, second)| second).count() as f64;
+        ties += count * count * count - count;
+        i = j;
+    }
+
+    let u = rank_sum - n2 * (n2 + 1.0) / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    let z = if variance > 0.0 { (u - n1 * n2 / 2.0) / variance.sqrt() } else { 0.0 };
+    Comparison {
+        difference: b.median() - a.median(),
+        z: z,
+        p_value: math::erfc(z.abs() / f64::consts::SQRT_2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::random::{RandomSource, SeededRng};
+
+    #[test]
+    fn test_statistics() {
+        let samples = Samples::new(vec![9.0, 1.0, 3.0, 2.0, 1000.0, 5.0, 4.0, 7.0, 6.0, 8.0]);
+        assert_eq!(samples.median(), 5.5);
+        assert_eq!(samples.percentile(0.0), 1.0);
+        assert_eq!(samples.percentile(100.0), 1000.0);
+        assert_eq!(samples.mad(), 2.5 * MAD_SCALE);
+        assert_eq!(samples.reject_outliers(3.0).values(),
+                   &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+
+        let mut calls = 0;
+        assert_eq!(measure(3, 5, || calls += 1).len(), 5);
+        assert_eq!(calls, 8);
+    }
+
+    #[test]
+    fn test_compare() {
+        // Noisy timings with the occasional huge outlier, as from a real machine.
+        let mut rng = SeededRng::new(0);
+        let mut timings = |base: f64| {
+            Samples::new((0..200)
+                .map(|_| {
+                    let outlier = if rng.range(0, 50) == 0 { 1e6 } else { 0.0 };
+                    base + rng.range(0, 1000) as f64 + outlier
+                })
+                .collect())
+        };
+        let fast = timings(10_000.0);
+        let same = timings(10_000.0);
+        let slow = timings(10_200.0);
+
+        assert!(!compare(&fast, &same).is_significant(0.01));
+        let comparison = compare(&fast, &slow);
+        assert!(comparison.is_significant(0.01));
+        assert!(comparison.z > 0.0 && comparison.difference > 0.0);
+        assert!(compare(&slow, &fast).z < 0.0);
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use utils::bytes;
+use utils::attacks::BruteForce;
+use utils::prng::lcg::Lcg;
+use utils::prng::mt19937::Mt19937;
+
+/// A way of deriving a password reset token from the Unix time it's issued at. All of them
+/// give 32 hex digits that look random, and none of them are: anyone who knows roughly when
+/// a token was issued can enumerate the candidates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// Seed MT19937 with the time and take four outputs, as in challenge 24.
+    Mt19937,
+    /// Seed an LCG with the MMIX parameters with the time and take two outputs.
+    Lcg,
+    /// Just format the time, padded to look like the others.
+    Time,
+}
+
+pub const SCHEMES: [Scheme; 3] = [Scheme::Mt19937, Scheme::Lcg, Scheme::Time];
+
+impl Scheme {
+    /// The token this scheme issues at the given time.
+    pub fn token_at(&self, timestamp: u64) -> String {
+        match *self {
+            Scheme::Mt19937 => {
+                let mut mt = Mt19937::new(timestamp as u32);
+                let words = (0..4).map(|_| mt.next_u32()).collect::<Vec<_>>();
+                bytes::hex_from_bytes(&bytes::pack_u32_be(&words))
+            }
+            Scheme::Lcg => {
+                let mut lcg = Lcg::mmix(timestamp);
+                format!("{:016x}{:016x}", lcg.next_state(), lcg.next_state())
+            }
+            Scheme::Time => format!("{:032x}", timestamp),
+        }
+    }
+
+    /// The token this scheme issues right now.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn token(&self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.token_at(now.as_secs())
+    }
+}
+
+/// The scheme and time that produced a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Origin {
+    pub scheme: Scheme,
+    pub timestamp: u64,
+}
+
+/// Find the scheme and time that produced a token, trying every second in [start, end].
+/// Returns None if no scheme produces it in that window.
+pub fn identify(token: &str, start: u64, end: u64) -> Option<Origin> {
+    let token = token.trim().to_lowercase();
+    let origins = (start..end + 1).flat_map(|timestamp| {
+        SCHEMES.iter().map(move |&scheme| {
+            Origin {
+                scheme: scheme,
+                timestamp: timestamp,
+            }
+        })
+    });
+    BruteForce::new("token origin")
+        .run(origins, |origin| origin.scheme.token_at(origin.timestamp) == token)
+        .found()
+}
+
+/// Every token the scheme could have issued in [start, end], oldest first. Against a reset
+/// endpoint that accepts any unexpired token, these are all the guesses an attacker needs.
+pub fn predict(scheme: Scheme, start: u64, end: u64) -> Vec<String> {
+    (start..end + 1).map(|timestamp| scheme.token_at(timestamp)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify() {
+        let issued = 1_500_000_000;
+        for &scheme in &SCHEMES {
+            let token = scheme.token_at(issued);
+            assert_eq!(token.len(), 32);
+            let origin = identify(&token, issued - 600, issued + 600).unwrap();
+            assert_eq!(origin,
+                       Origin {
+                           scheme: scheme,
+                           timestamp: issued,
+                       });
+
+            let candidates = predict(scheme, issued - 60, issued);
+            assert_eq!(candidates.len(), 61);
+            assert_eq!(candidates.last(), Some(&token));
+        }
+
+        assert_eq!(identify(&Scheme::Lcg.token_at(issued), issued + 1, issued + 600), None);
+        assert_eq!(identify("0123456789abcdef0123456789abcdef", issued - 10, issued), None);
+    }
+}
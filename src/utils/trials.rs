@@ -0,0 +1,84 @@
+use std::panic::{self, AssertUnwindSafe};
+
+// The z-score for a two-sided 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// The outcome of running a non-deterministic experiment many times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trials {
+    pub runs: usize,
+    pub successes: usize,
+}
+
+impl Trials {
+    /// The fraction of runs that succeeded.
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.runs as f64
+    }
+
+    /// The 95% Wilson score interval for the true success rate. Unlike the normal
+    /// approximation, it stays within [0, 1] and is still meaningful when every run
+    /// succeeds, which is the usual case here.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        if self.runs == 0 {
+            return (0.0, 1.0);
+        }
+        let n = self.runs as f64;
+        let p = self.success_rate();
+        let z2 = Z_95 * Z_95;
+        let denominator = 1.0 + z2 / n;
+        let center = (p + z2 / (2.0 * n)) / denominator;
+        let half_width = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+        ((center - half_width).max(0.0), (center + half_width).min(1.0))
+    }
+}
+
+/// Run a randomized experiment the given number of times, counting how often it succeeds.
+/// A run that panics counts as a failure, so an attack that gives up on an unlucky oracle
+/// doesn't abort the whole batch. The panic message is still printed by the panic hook.
+pub fn run<F>(runs: usize, trial: F) -> Trials
+    where F: Fn() -> bool
+{
+    let mut successes = 0;
+    for i in 0..runs {
+        match panic::catch_unwind(AssertUnwindSafe(&trial)) {
+            Ok(true) => successes += 1,
+            Ok(false) => debug!("Trial {} of {} failed", i + 1, runs),
+            Err(_) => debug!("Trial {} of {} panicked", i + 1, runs),
+        }
+    }
+    Trials {
+        runs: runs,
+        successes: successes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_confidence_interval() {
+        let (low, high) = Trials { runs: 100, successes: 50 }.confidence_interval();
+        assert!((low - 0.4038).abs() < 1e-4);
+        assert!((high - 0.5962).abs() < 1e-4);
+
+        let (low, high) = Trials { runs: 100, successes: 100 }.confidence_interval();
+        assert!((low - 0.9630).abs() < 1e-4);
+        assert_eq!(high, 1.0);
+    }
+
+    #[test]
+    fn test_run() {
+        let counter = Cell::new(0);
+        let trials = run(10, || {
+            counter.set(counter.get() + 1);
+            counter.get() % 2 == 0
+        });
+        assert_eq!(trials, Trials { runs: 10, successes: 5 });
+    }
+}
@@ -0,0 +1,358 @@
+//! A toy web app that keeps its sessions in encrypted cookies, as a single target for the
+//! attacks on block cipher modes. The mode, nonces and plaintext policy are configurable,
+//! and each choice leaves the app open to a different attack:
+//!
+//! - ECB lets blocks be cut from one cookie and pasted into another (challenge 13).
+//! - CBC lets bits flipped in one block edit the next (challenge 16), and the difference
+//!   between the errors for bad padding and bad cookies is a padding oracle (challenge 17).
+//! - CTR lets bits be flipped in place, and with a fixed nonce every cookie shares a
+//!   keystream, so knowing one plaintext decrypts the rest.
+//! - GCM stops tampering, unless nonces are reused, when the keystream is shared just as
+//!   in CTR.
+//!
+//! A cookie is "user=<name>;uid=<n>;role=user", with '%', ';' and '=' in the name
+//! percent-encoded, and is sent as the IV or nonce followed by the ciphertext and tag.
+
+use std::fmt;
+
+use utils::aead::{Aead, Gcm};
+use utils::crypto::{self, Ctr, PaddingError};
+use utils::nonce::NonceSequence;
+use utils::oracles::{CookieError, PlaintextPolicy};
+#[cfg(not(target_arch = "wasm32"))]
+use utils::random::OsSource;
+use utils::random::RandomSource;
+
+/// The block cipher mode the app encrypts its cookies with, always over AES-128.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Ecb,
+    Cbc,
+    Ctr,
+    Gcm,
+}
+
+impl Mode {
+    // How many bytes of IV or nonce, and of tag, a cookie carries around its ciphertext.
+    fn overhead(&self) -> (usize, usize) {
+        match *self {
+            Mode::Ecb => (0, 0),
+            Mode::Cbc => (16, 0),
+            Mode::Ctr => (8, 0),
+            Mode::Gcm => (12, 16),
+        }
+    }
+}
+
+/// Why the app refused a cookie.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The cookie is too short to hold an IV or nonce and tag, or the ciphertext isn't a
+    /// whole number of blocks.
+    Malformed,
+    /// The ciphertext decrypted to something without valid PKCS#7 padding.
+    Padding,
+    /// The GCM tag didn't match.
+    Authentication,
+    /// The plaintext decrypted, but the app's `PlaintextPolicy` rejected it.
+    Rejected(Vec<u8>),
+    /// The plaintext was accepted, but isn't a session: it has no user or uid.
+    Invalid,
+}
+
+impl From<CookieError> for SessionError {
+    fn from(err: CookieError) -> Self {
+        match err {
+            CookieError::Padding(PaddingError::Length) => SessionError::Malformed,
+            CookieError::Padding(PaddingError::Padding) => SessionError::Padding,
+            CookieError::Rejected(plaintext) => SessionError::Rejected(plaintext),
+        }
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SessionError::Malformed => write!(f, "Malformed cookie."),
+            SessionError::Padding => write!(f, "Invalid PKCS#7 padding."),
+            SessionError::Authentication => write!(f, "Invalid tag."),
+            SessionError::Rejected(_) => write!(f, "Invalid characters in cookie."),
+            SessionError::Invalid => write!(f, "Not a session cookie."),
+        }
+    }
+}
+
+/// The fields of a valid session cookie, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub fields: Vec<(String, String)>,
+}
+
+impl Session {
+    // Split a cookie into its fields, ignoring anything without an '='.
+    fn parse(cookie: &str) -> Result<Self, SessionError> {
+        let fields = cookie.split(';')
+            .filter_map(|field| {
+                let mut pair = field.splitn(2, '=');
+                match (pair.next(), pair.next()) {
+                    (Some(key), Some(value)) => Some((key.to_string(), value.to_string())),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let session = Session { fields: fields };
+        if session.get("user").is_none() || session.get("uid").is_none() {
+            return Err(SessionError::Invalid);
+        }
+        Ok(session)
+    }
+
+    /// The value of the first field with the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|field| field.0 == key).map(|field| field.1.as_str())
+    }
+
+    /// Whether any field says "role=admin". Like the server in challenge 16, this looks for
+    /// the field anywhere, which is what lets an injected one count.
+    pub fn is_admin(&self) -> bool {
+        self.fields.iter().any(|field| field.0 == "role" && field.1 == "admin")
+    }
+}
+
+/// The app: it registers users by issuing them cookies, and checks cookies on each request.
+pub struct App {
+    mode: Mode,
+    key: Vec<u8>,
+    rng: Box<dyn RandomSource>,
+    // None for random nonces drawn from `rng`.
+    nonces: Option<Box<dyn NonceSequence>>,
+    policy: PlaintextPolicy,
+    next_uid: u64,
+}
+
+impl App {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(mode: Mode) -> Self {
+        Self::with_source(mode, OsSource::new())
+    }
+
+    /// Create an app that draws its key, and its IVs and random nonces, from the given
+    /// source. Those IVs and nonces are only as unpredictable as the source is, so outside
+    /// of tests this should be an `OsSource`.
+    pub fn with_source<R>(mode: Mode, mut rng: R) -> Self
+        where R: RandomSource + 'static
+    {
+        App {
+            mode: mode,
+            key: rng.bytes(16),
+            rng: Box::new(rng),
+            nonces: None,
+            policy: PlaintextPolicy::default(),
+            next_uid: 10,
+        }
+    }
+
+    /// Take the nonces for CTR and GCM from the given sequence, such as a `FixedNonce` to
+    /// make every cookie reuse one. CBC IVs are always random: a predictable IV, even one
+    /// that never repeats, lets a chosen plaintext cancel it out, as in the BEAST attack.
+    pub fn with_nonces<N>(mut self, nonces: N) -> Self
+        where N: NonceSequence + 'static
+    {
+        self.nonces = Some(Box::new(nonces));
+        self
+    }
+
+    /// Apply the given policy to decrypted cookies.
+    pub fn with_policy(mut self, policy: PlaintextPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Register a user, returning their session cookie.
+    pub fn register(&mut self, user: &str) -> Vec<u8> {
+        let name = user.replace("%", "%25").replace(";", "%3B").replace("=", "%3D");
+        let cookie = format!("user={};uid={};role=user", name, self.next_uid);
+        self.next_uid += 1;
+        self.encrypt(cookie.as_bytes())
+    }
+
+    // Encrypt a cookie, prefixing the IV or nonce and appending the tag.
+    fn encrypt(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let (nonce_len, _) = self.mode.overhead();
+        let nonce = match (self.mode, self.nonces.as_mut()) {
+            (Mode::Cbc, _) | (_, None) => self.rng.bytes(nonce_len),
+            (_, Some(nonces)) => nonces.next_nonce(nonce_len),
+        };
+        let mut cookie = nonce.clone();
+        match self.mode {
+            Mode::Ecb => cookie.extend(crypto::encrypt_ecb(&self.key, plaintext, true)),
+            Mode::Cbc => cookie.extend(crypto::encrypt_cbc(&self.key, &nonce, plaintext)),
+            Mode::Ctr => {
+                let mut ciphertext = plaintext.to_vec();
+                Ctr::new(&self.key, self.counter_nonce(&nonce)).apply_keystream(&mut ciphertext);
+                cookie.extend(ciphertext);
+            }
+            Mode::Gcm => {
+                let mut ciphertext = plaintext.to_vec();
+                let tag = Gcm::new(&self.key).seal(&nonce, b"", &mut ciphertext);
+                cookie.extend(ciphertext);
+                cookie.extend(tag);
+            }
+        }
+        cookie
+    }
+
+    // The CTR nonce from the 8 bytes at the start of a cookie.
+    fn counter_nonce(&self, nonce: &[u8]) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(nonce);
+        u64::from_le_bytes(bytes)
+    }
+
+    // Decrypt a cookie, without applying the policy.
+    fn decrypt(&self, cookie: &[u8]) -> Result<Vec<u8>, SessionError> {
+        let (nonce_len, tag_len) = self.mode.overhead();
+        if cookie.len() < nonce_len + tag_len {
+            return Err(SessionError::Malformed);
+        }
+        let (nonce, rest) = cookie.split_at(nonce_len);
+        let (ciphertext, tag) = rest.split_at(rest.len() - tag_len);
+        match self.mode {
+            Mode::Ecb => {
+                if ciphertext.is_empty() || ciphertext.len() % 16 != 0 {
+                    return Err(SessionError::Malformed);
+                }
+                let padded = crypto::decrypt_ecb(&self.key, ciphertext, false);
+                crypto::strip_pkcs7(&padded).ok_or(SessionError::Padding)
+            }
+            Mode::Cbc => {
+                crypto::decrypt_cbc_checked(&self.key, nonce, ciphertext)
+                    .map_err(|err| CookieError::Padding(err).into())
+            }
+            Mode::Ctr => {
+                let mut plaintext = ciphertext.to_vec();
+                Ctr::new(&self.key, self.counter_nonce(nonce)).apply_keystream(&mut plaintext);
+                Ok(plaintext)
+            }
+            Mode::Gcm => {
+                let mut plaintext = ciphertext.to_vec();
+                Gcm::new(&self.key)
+                    .open(nonce, b"", &mut plaintext, tag)
+                    .map_err(|_| SessionError::Authentication)?;
+                Ok(plaintext)
+            }
+        }
+    }
+
+    /// Check a cookie, returning the session it holds.
+    pub fn session(&self, cookie: &[u8]) -> Result<Session, SessionError> {
+        let plaintext = self.policy.apply(self.decrypt(cookie)?)?;
+        Session::parse(&plaintext)
+    }
+
+    /// The admin page: whether the cookie belongs to an admin.
+    pub fn is_admin(&self, cookie: &[u8]) -> Result<bool, SessionError> {
+        self.session(cookie).map(|session| session.is_admin())
+    }
+
+    /// The HTTP status the admin page responds with: 200 for an admin, 403 for anyone else
+    /// with a valid cookie, 400 for a cookie that decrypts to something that isn't one, and
+    /// 500 when decryption itself fails. Telling bad padding apart from a bad cookie like
+    /// this is all a padding oracle attack needs.
+    pub fn status(&self, cookie: &[u8]) -> u16 {
+        match self.is_admin(cookie) {
+            Ok(true) => 200,
+            Ok(false) => 403,
+            Err(SessionError::Rejected(_)) | Err(SessionError::Invalid) => 400,
+            Err(_) => 500,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{attacks, bytes};
+    use utils::nonce::FixedNonce;
+    use utils::random::SeededRng;
+
+    fn app(mode: Mode) -> App {
+        App::with_source(mode, SeededRng::new(951))
+    }
+
+    #[test]
+    fn test_sessions() {
+        for &mode in &[Mode::Ecb, Mode::Cbc, Mode::Ctr, Mode::Gcm] {
+            let mut app = app(mode);
+            let cookie = app.register("alice;role=admin");
+            let session = app.session(&cookie).unwrap();
+            assert_eq!(session.get("user"), Some("alice%3Brole%3Dadmin"));
+            assert_eq!(session.get("uid"), Some("10"));
+            assert_eq!(app.status(&cookie), 403);
+            assert_eq!(app.session(&cookie[..cookie.len() - 1]).is_err(), mode != Mode::Ctr);
+            assert_eq!(app.status(&[]), 500);
+        }
+        let mut app = app(Mode::Gcm);
+        let mut cookie = app.register("alice");
+        cookie[20] ^= 1;
+        assert_eq!(app.session(&cookie), Err(SessionError::Authentication));
+    }
+
+    #[test]
+    fn test_ecb_cut_and_paste() {
+        // |user=...........|admin\x0b...\x0b|...;uid=10;role=|user\x0c...\x0c|
+        let mut app = app(Mode::Ecb);
+        let admin = crypto::pad_pkcs7(b"admin", 16).unwrap();
+        let (mut name, _) = attacks::align_input(5, 16, b'.', &admin);
+        let used = 5 + name.len() + ";uid=10;role=".len();
+        name.extend(vec![b'.'; (16 - used % 16) % 16]);
+        let cookie = app.register(&bytes::to_string(&name));
+        assert!(app.is_admin(&attacks::select_blocks(&cookie, 16, &[0, 2, 1])).unwrap());
+    }
+
+    #[test]
+    fn test_cbc_bitflipping_and_padding_oracle() {
+        // The cookie carries its IV, so flipping bits in it edits the first block of
+        // plaintext without scrambling any other: "user=:role<admin" to "user=;role=admin".
+        let mut app = app(Mode::Cbc);
+        let mut cookie = app.register(":role<admin");
+        cookie[5] ^= 1;
+        cookie[10] ^= 1;
+        assert!(app.is_admin(&cookie).unwrap());
+
+        let cookie = app.register("bob");
+        let (iv, ciphertext) = cookie.split_at(16);
+        let mut oracle = |iv: &[u8], ciphertext: &[u8]| {
+            app.status(&[iv, ciphertext].concat()) != 500
+        };
+        let plaintext = attacks::decrypt_cbc_padding_oracle(iv, ciphertext, &mut oracle);
+        assert_eq!(plaintext.unwrap(), b"user=bob;uid=11;role=user");
+    }
+
+    #[test]
+    fn test_nonce_reuse() {
+        for &mode in &[Mode::Ctr, Mode::Gcm] {
+            let mut app = app(mode).with_nonces(FixedNonce::new(0));
+            let known = b"user=mallory;uid=10;role=user";
+            let mine = app.register("mallory");
+            let theirs = app.register("bob");
+            assert_eq!(mine[..8], theirs[..8]);
+
+            // The shared keystream is the XOR of a known plaintext and its ciphertext.
+            let offset = mode.overhead().0;
+            let keystream = bytes::xor(&mine[offset..], known);
+            let plaintext = bytes::xor(&theirs[offset..offset + 24], &keystream);
+            assert_eq!(plaintext, b"user=bob;uid=11;role=use");
+        }
+
+        // CBC ignores the sequence, and draws a fresh random IV for every cookie.
+        let mut app = app(Mode::Cbc).with_nonces(FixedNonce::new(0));
+        let (mine, theirs) = (app.register("mallory"), app.register("bob"));
+        assert_ne!(mine[..16], theirs[..16]);
+        assert_ne!(mine[..16], [0; 16]);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::io::{self, BufRead};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufReader;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::vec;
+
+// Common English words, one per line, in lower case.
+static ENGLISH_WORDS: &'static str = include_str!("../data/english_words.txt");
+
+// A hundred of the most common passwords in public breach dumps, most common first.
+static COMMON_PASSWORDS: &'static str = include_str!("../data/common_passwords.txt");
+
+/// A list of words, such as the vocabulary a plaintext is checked against, or the
+/// passwords a dictionary attack tries. Lines are trimmed, and blank lines and lines
+/// starting with '#' are skipped, so the usual wordlist files load as they are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+// Trim a line from a wordlist, returning None if it's blank or a comment.
+fn parse_line(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        None
+    } else {
+        Some(line.to_string())
+    }
+}
+
+impl Wordlist {
+    pub fn new<I, W>(lines: I) -> Self
+        where I: IntoIterator<Item = W>,
+              W: AsRef<str>
+    {
+        Wordlist {
+            words: lines.into_iter().filter_map(|line| parse_line(line.as_ref())).collect(),
+        }
+    }
+
+    /// The embedded list of common English words, in lower case.
+    pub fn english() -> Self {
+        Self::new(ENGLISH_WORDS.lines())
+    }
+
+    /// The embedded list of common passwords, small enough to try in full against even a
+    /// slow hash, and a good start for mangling.
+    pub fn passwords() -> Self {
+        Self::new(COMMON_PASSWORDS.lines())
+    }
+
+    /// Load a wordlist file, with one word per line. Lines that aren't valid UTF-8, which
+    /// turn up in lists taken from breaches, are converted lossily rather than rejected.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let words = stream(BufReader::new(File::open(path)?)).collect::<io::Result<Vec<_>>>()?;
+        Ok(Wordlist { words: words })
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Every variant of every word under the given rules, as by `mangle`, without repeats.
+    pub fn mangle(&self, rules: &[Rule]) -> Wordlist {
+        let mut seen = HashSet::new();
+        let words = mangled(self.words.iter().cloned(), rules)
+            .filter(|word| seen.insert(word.clone()))
+            .collect();
+        Wordlist { words: words }
+    }
+}
+
+impl IntoIterator for Wordlist {
+    type Item = String;
+    type IntoIter = vec::IntoIter<String>;
+
+    fn into_iter(self) -> vec::IntoIter<String> {
+        self.words.into_iter()
+    }
+}
+
+/// An iterator over the words of a wordlist as they're read, from `stream`.
+pub struct Words<R> {
+    reader: R,
+}
+
+impl<R: BufRead> Iterator for Words<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if let Some(word) = parse_line(&String::from_utf8_lossy(&line)) {
+                        return Some(Ok(word));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Read a wordlist one word at a time, for lists like rockyou.txt that are too big to hold
+/// in memory, with the same rules for lines as `Wordlist`.
+pub fn stream<R: BufRead>(reader: R) -> Words<R> {
+    Words { reader: reader }
+}
+
+/// A password mangling rule, turning one word into the variants people make of it when
+/// told their password needs more than lower-case letters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// The word in lower case, upper case, and capitalized.
+    ToggleCase,
+    /// The word followed by every number of up to this many digits, including ones with
+    /// leading zeros: 2 gives 0 to 9 and 00 to 99.
+    DigitSuffix(u32),
+}
+
+impl Rule {
+    // The variants of a word under this rule, starting with the word itself.
+    fn apply(&self, word: &str) -> Vec<String> {
+        let mut variants = vec![word.to_string()];
+        match *self {
+            Rule::ToggleCase => {
+                let mut chars = word.chars();
+                let capitalized = chars.next()
+                    .map_or(String::new(), |first| first.to_uppercase().chain(chars).collect());
+                variants.extend(vec![word.to_lowercase(), word.to_uppercase(), capitalized]);
+            }
+            Rule::DigitSuffix(digits) => {
+                for width in 1..digits as usize + 1 {
+                    for n in 0..10u64.pow(width as u32) {
+                        variants.push(format!("{}{:0width$}", word, n, width = width));
+                    }
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        variants.retain(|variant| seen.insert(variant.clone()));
+        variants
+    }
+}
+
+/// Every variant of a word under the given rules, applied one after another, so that
+/// [ToggleCase, DigitSuffix(1)] gives "Dragon7" as well as "dragon" and "DRAGON". The word
+/// itself comes first, and there are no repeats.
+pub fn mangle(word: &str, rules: &[Rule]) -> Vec<String> {
+    let mut variants = vec![word.to_string()];
+    for rule in rules {
+        let mut seen = HashSet::new();
+        variants = variants.iter()
+            .flat_map(|variant| rule.apply(variant))
+            .filter(|variant| seen.insert(variant.clone()))
+            .collect();
+    }
+    variants
+}
+
+/// An iterator over the variants of each word from another iterator, created by `mangled`.
+pub struct Mangled<I> {
+    words: I,
+    rules: Vec<Rule>,
+    variants: vec::IntoIter<String>,
+}
+
+impl<I: Iterator<Item = String>> Iterator for Mangled<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            if let Some(variant) = self.variants.next() {
+                return Some(variant);
+            }
+            let word = self.words.next()?;
+            self.variants = mangle(&word, &self.rules).into_iter();
+        }
+    }
+}
+
+/// Mangle words as they come, such as those read by `stream`, so that a list too big to
+/// hold in memory can still be tried with every variant. Only one word's variants are held
+/// at a time, so unlike `Wordlist::mangle`, variants shared by two words are repeated.
+pub fn mangled<I>(words: I, rules: &[Rule]) -> Mangled<I::IntoIter>
+    where I: IntoIterator<Item = String>
+{
+    Mangled {
+        words: words.into_iter(),
+        rules: rules.to_vec(),
+        variants: Vec::new().into_iter(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wordlist() {
+        let list = Wordlist::new("# comment\nhunter2\n\n  letmein \r\n".lines());
+        assert_eq!(list.words(), &["hunter2".to_string(), "letmein".to_string()]);
+        assert!(Wordlist::english().len() > 1000);
+        assert_eq!(Wordlist::passwords().words()[..2], ["123456", "password"]);
+
+        let streamed = stream(&b"one\n\xfftwo\n#three\nfour"[..]).collect::<io::Result<Vec<_>>>();
+        assert_eq!(streamed.unwrap(), vec!["one", "\u{fffd}two", "four"]);
+        assert!(Wordlist::from_file("no/such/wordlist.txt").is_err());
+    }
+
+    #[test]
+    fn test_mangle() {
+        assert_eq!(mangle("Dragon", &[Rule::ToggleCase]), vec!["Dragon", "dragon", "DRAGON"]);
+        let variants = mangle("pass", &[Rule::DigitSuffix(2)]);
+        assert_eq!(variants.len(), 1 + 10 + 100);
+        assert!(variants.contains(&"pass07".to_string()));
+        assert!(variants.contains(&"pass7".to_string()));
+
+        let mangled = Wordlist::new(vec!["dragon", "Dragon"]).mangle(&[Rule::ToggleCase,
+                                                                         Rule::DigitSuffix(1)]);
+        assert_eq!(mangled.len(), 3 * 11);
+        assert!(mangled.words().contains(&"Dragon7".to_string()));
+
+        let words = stream(&b"dragon\nDragon\n"[..]).map(Result::unwrap);
+        let streamed = super::mangled(words, &[Rule::ToggleCase, Rule::DigitSuffix(1)]);
+        assert_eq!(streamed.collect::<Vec<_>>().len(), 3 * 11 + 3 * 11);
+    }
+}
@@ -0,0 +1,69 @@
+use wasm_bindgen::prelude::*;
+
+use utils::{attacks, bytes, text};
+
+/// Break single-byte XOR on a hex-encoded ciphertext, returning the most likely plaintext.
+#[wasm_bindgen(js_name = crackSingleByteXor)]
+pub fn crack_single_byte_xor(hex: &str) -> Result<String, JsValue> {
+    let ciphertext = bytes::hex_to_bytes(hex.trim()).map_err(JsValue::from_str)?;
+    let scorer = text::TieBreaker::english();
+    let (_, plaintext, _) = attacks::single_byte_brute_force_with(&ciphertext, &scorer);
+    Ok(plaintext)
+}
+
+/// The key and plaintext recovered from a repeating-key XOR ciphertext.
+#[wasm_bindgen]
+pub struct RepeatingKeyXor {
+    key: String,
+    plaintext: String,
+}
+
+#[wasm_bindgen]
+impl RepeatingKeyXor {
+    #[wasm_bindgen(getter)]
+    pub fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn plaintext(&self) -> String {
+        self.plaintext.clone()
+    }
+}
+
+/// Break repeating-key XOR on a base64-encoded ciphertext, which may contain line breaks.
+#[wasm_bindgen(js_name = crackRepeatingKeyXor)]
+pub fn crack_repeating_key_xor(base64: &str) -> Result<RepeatingKeyXor, JsValue> {
+    let ciphertext = bytes::base64_decode(base64, &bytes::BASE64_MIME)
+        .map_err(JsValue::from_str)?;
+
+    // Key size detection compares pairs of chunks for key sizes up to 40 bytes.
+    if ciphertext.len() < 80 {
+        return Err(JsValue::from_str("Ciphertext must be at least 80 bytes long."));
+    }
+
+    let key = attacks::find_repeating_key(&ciphertext);
+    Ok(RepeatingKeyXor {
+        plaintext: bytes::to_string(&bytes::xor(&ciphertext, key.iter().cycle())),
+        key: bytes::to_string(&key),
+    })
+}
+
+/// Given hex-encoded ciphertexts, one per line, return the index of the line most likely
+/// to have been encrypted with a 16-byte block cipher in ECB mode, if any.
+#[wasm_bindgen(js_name = detectEcb)]
+pub fn detect_ecb(lines: &str) -> Result<Option<u32>, JsValue> {
+    let mut result = None;
+    let mut max = 0;
+
+    for (i, line) in lines.lines().enumerate() {
+        let ciphertext = bytes::hex_to_bytes(line.trim()).map_err(JsValue::from_str)?;
+        let score = attacks::detect_ecb(&ciphertext, 16);
+        if score.is_ecb() && score.repeats > max {
+            max = score.repeats;
+            result = Some(i as u32);
+        }
+    }
+
+    Ok(result)
+}